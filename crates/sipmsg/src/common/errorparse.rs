@@ -1,53 +1,165 @@
+use alloc::{format, string::String};
 use core::convert::From;
+use core::fmt;
 use core::str;
 use nom;
 use nom::error::{ErrorKind, ParseError};
 
+/// What part of SIP message is failed to parse
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SipParseErrorKind<'a> {
+    /// Error is not classified. Ex: error of internal nom parser
+    Unknown,
+    /// First bytes of message are not a method or `SIP/`
+    UnknownMessageType,
+    /// Request-Line or Status-Line is malformed
+    BadStartLine,
+    /// Method of Request-Line is not supported
+    BadMethod,
+    /// SIP-Version is malformed. Ex: `SIP/2.300`
+    BadVersion,
+    /// SIP-Version is not `SIP/2.0`. Ex: `SIP/7.0`
+    UnsupportedVersion,
+    /// Status-Code is not 3DIGIT
+    BadStatusCode,
+    /// Request-URI is malformed
+    BadUri,
+    /// Request-URI has scheme other than sip or sips
+    UnsupportedUriScheme,
+    /// Method of CSeq header is not the same as method of Request-Line
+    CSeqMethodMismatch,
+    /// Header name is not a token or colon is absent
+    BadHeaderName,
+    /// Header value is malformed
+    BadHeaderValue { header: &'a str },
+    /// There is no empty line between headers and body
+    MissingHeadersEnd,
+    /// Body is shorter than value of Content-Length header
+    BodyTooShort,
+    /// Number of header fields exceeds `ParseLimits::max_headers`
+    TooManyHeaders,
+    /// Header field exceeds `ParseLimits::max_header_line_len`
+    HeaderTooLong,
+    /// Number of Via entries exceeds `ParseLimits::max_via`
+    TooManyVias,
+    /// Number of values in header field exceeds `ParseLimits::max_values_per_header`
+    TooManyValues { header: &'a str },
+    /// Request-URI exceeds `ParseLimits::max_uri_len`
+    UriTooLong,
+    /// Body exceeds `ParseLimits::max_body_len`
+    BodyTooLarge,
+    /// Content-Type of message is not the type that was requested. Ex: not `application/sdp`
+    UnsupportedMediaType,
+    /// Line of SDP body is malformed. `field` is type of line. Ex: `m`
+    BadSdp { field: &'a str },
+    /// Multipart body has no boundary or its delimiters are malformed
+    BadMultipart,
+    /// Body of event package is malformed. `content_type` is media type of body.
+    /// Ex: `application/pidf+xml`
+    BadBody { content_type: &'a str },
+    /// Body can't be decoded according to Content-Encoding. `coding` is content-coding.
+    /// Ex: `gzip`
+    BadContentEncoding { coding: &'a str },
+}
+
+impl<'a> SipParseErrorKind<'a> {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SipParseErrorKind::Unknown => "Unknown",
+            SipParseErrorKind::UnknownMessageType => "UnknownMessageType",
+            SipParseErrorKind::BadStartLine => "BadStartLine",
+            SipParseErrorKind::BadMethod => "BadMethod",
+            SipParseErrorKind::BadVersion => "BadVersion",
+            SipParseErrorKind::UnsupportedVersion => "UnsupportedVersion",
+            SipParseErrorKind::BadStatusCode => "BadStatusCode",
+            SipParseErrorKind::BadUri => "BadUri",
+            SipParseErrorKind::UnsupportedUriScheme => "UnsupportedUriScheme",
+            SipParseErrorKind::CSeqMethodMismatch => "CSeqMethodMismatch",
+            SipParseErrorKind::BadHeaderName => "BadHeaderName",
+            SipParseErrorKind::BadHeaderValue { .. } => "BadHeaderValue",
+            SipParseErrorKind::MissingHeadersEnd => "MissingHeadersEnd",
+            SipParseErrorKind::BodyTooShort => "BodyTooShort",
+            SipParseErrorKind::TooManyHeaders => "TooManyHeaders",
+            SipParseErrorKind::HeaderTooLong => "HeaderTooLong",
+            SipParseErrorKind::TooManyVias => "TooManyVias",
+            SipParseErrorKind::TooManyValues { .. } => "TooManyValues",
+            SipParseErrorKind::UriTooLong => "UriTooLong",
+            SipParseErrorKind::BodyTooLarge => "BodyTooLarge",
+            SipParseErrorKind::UnsupportedMediaType => "UnsupportedMediaType",
+            SipParseErrorKind::BadSdp { .. } => "BadSdp",
+            SipParseErrorKind::BadMultipart => "BadMultipart",
+            SipParseErrorKind::BadBody { .. } => "BadBody",
+            SipParseErrorKind::BadContentEncoding { .. } => "BadContentEncoding",
+        }
+    }
+
+    /// Returns true if error is caused by exceeded `ParseLimits`
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            SipParseErrorKind::TooManyHeaders
+                | SipParseErrorKind::HeaderTooLong
+                | SipParseErrorKind::TooManyVias
+                | SipParseErrorKind::TooManyValues { .. }
+                | SipParseErrorKind::UriTooLong
+                | SipParseErrorKind::BodyTooLarge
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct SipParseError<'a> {
     pub code: u32,
     pub message: Option<&'a str>,
+    /// What is failed
+    pub kind: SipParseErrorKind<'a>,
+    /// Name of the header that was parsed when error is occurred
+    pub header: Option<&'a str>,
+    /// Byte offset within the message where error is occurred
+    pub offset: Option<usize>,
+    /// Line number within the message. First line is 1
+    pub line: Option<usize>,
+    /// Part of input where error is occurred. Used for offset calculation
+    input: Option<&'a [u8]>,
 }
 
 impl<'a> From<(&'a str, ErrorKind)> for SipParseError<'a> {
     fn from(error: (&'a str, ErrorKind)) -> Self {
-        SipParseError {
-            code: error.1 as u32,
-            message: Some(error.0),
-        }
+        SipParseError::new(error.1 as u32, Some(error.0)).with_input(error.0.as_bytes())
     }
 }
 
 impl<'a> ParseError<&'a str> for SipParseError<'a> {
     fn from_error_kind(error: &'a str, kind: ErrorKind) -> Self {
-        SipParseError {
-            code: kind as u32,
-            message: Some(error),
-        }
+        SipParseError::new(kind as u32, Some(error)).with_input(error.as_bytes())
     }
 
     fn append(error: &'a str, kind: ErrorKind, _other: SipParseError) -> Self {
-        SipParseError {
-            code: kind as u32,
-            message: Some(error),
-        }
+        SipParseError::new(kind as u32, Some(error)).with_input(error.as_bytes())
     }
 }
 
 #[macro_export]
 macro_rules! sip_parse_error {
-    // error with message
+    // error without message
     ($error_code:expr) => {
         Err(nom::Err::Error(SipParseError::new($error_code, None)))
     };
 
-    // error without message
+    // error with message
     ($error_code:expr, $message:expr) => {
         Err(nom::Err::Error(SipParseError::new(
             $error_code,
             Some($message),
         )))
     };
+
+    // error with message and kind
+    ($error_code:expr, $message:expr, $kind:expr) => {
+        Err(nom::Err::Error(
+            SipParseError::new($error_code, Some($message)).with_kind($kind),
+        ))
+    };
 }
 
 impl<'a> SipParseError<'a> {
@@ -55,10 +167,137 @@ impl<'a> SipParseError<'a> {
         SipParseError {
             code: code,
             message: message,
+            kind: SipParseErrorKind::Unknown,
+            header: None,
+            offset: None,
+            line: None,
+            input: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: SipParseErrorKind<'a>) -> SipParseError<'a> {
+        self.kind = kind;
+        self
+    }
+
+    /// Set kind only if it is not classified yet
+    pub fn or_kind(self, kind: SipParseErrorKind<'a>) -> SipParseError<'a> {
+        if self.kind == SipParseErrorKind::Unknown {
+            return self.with_kind(kind);
+        }
+        self
+    }
+
+    /// Set name of header. Does nothing if it already set
+    pub fn or_header(mut self, header: &'a str) -> SipParseError<'a> {
+        if self.header.is_none() {
+            self.header = Some(header);
+        }
+        self
+    }
+
+    /// Set part of input where error is occurred. Does nothing if it already set
+    pub fn or_input(mut self, input: &'a [u8]) -> SipParseError<'a> {
+        if self.input.is_none() {
+            self.input = Some(input);
+        }
+        self
+    }
+
+    fn with_input(mut self, input: &'a [u8]) -> SipParseError<'a> {
+        self.input = Some(input);
+        self
+    }
+
+    /// Calculates `offset` and `line` relatively to the start of `message`.
+    /// `message` should be the same buffer that was passed to parser.
+    pub fn locate(mut self, message: &[u8]) -> SipParseError<'a> {
+        let input = match self.input {
+            Some(input) => input,
+            None => return self,
+        };
+        let message_start = message.as_ptr() as usize;
+        let input_start = input.as_ptr() as usize;
+        if input_start < message_start || input_start > message_start + message.len() {
+            return self;
+        }
+        let offset = input_start - message_start;
+        self.offset = Some(offset);
+        self.line = Some(message[..offset].iter().filter(|c| **c == b'\n').count() + 1);
+        self
+    }
+
+    /// Text that can be used as Reason-Phrase of response with `status_code`.
+    /// Ex: `Malformed Via Header`
+    pub fn reason_phrase(&self) -> String {
+        match self.kind {
+            SipParseErrorKind::Unknown => String::from("Bad Request"),
+            SipParseErrorKind::UnknownMessageType => String::from("Unknown Message Type"),
+            SipParseErrorKind::BadStartLine => String::from("Malformed Start Line"),
+            SipParseErrorKind::BadMethod => String::from("Unsupported Method"),
+            SipParseErrorKind::BadVersion => String::from("Malformed SIP Version"),
+            SipParseErrorKind::UnsupportedVersion => String::from("Version Not Supported"),
+            SipParseErrorKind::BadStatusCode => String::from("Malformed Status Code"),
+            SipParseErrorKind::BadUri => String::from("Malformed Request-URI"),
+            SipParseErrorKind::UnsupportedUriScheme => String::from("Unsupported URI Scheme"),
+            SipParseErrorKind::CSeqMethodMismatch => String::from("CSeq Method Mismatch"),
+            SipParseErrorKind::BadHeaderName => String::from("Malformed Header Name"),
+            SipParseErrorKind::BadHeaderValue { header } => format!("Malformed {} Header", header),
+            SipParseErrorKind::MissingHeadersEnd => String::from("Missing End Of Headers"),
+            SipParseErrorKind::BodyTooShort => String::from("Body Shorter Than Content-Length"),
+            SipParseErrorKind::TooManyHeaders
+            | SipParseErrorKind::HeaderTooLong
+            | SipParseErrorKind::TooManyVias
+            | SipParseErrorKind::TooManyValues { .. } => String::from("Message Too Large"),
+            SipParseErrorKind::UriTooLong => String::from("Request-URI Too Long"),
+            SipParseErrorKind::BodyTooLarge => String::from("Request Entity Too Large"),
+            SipParseErrorKind::UnsupportedMediaType => String::from("Unsupported Media Type"),
+            SipParseErrorKind::BadSdp { field } => format!("Malformed SDP {}= Line", field),
+            SipParseErrorKind::BadMultipart => String::from("Malformed Multipart Body"),
+            SipParseErrorKind::BadBody { content_type } => {
+                format!("Malformed {} Body", content_type)
+            }
+            SipParseErrorKind::BadContentEncoding { coding } => {
+                format!("Malformed {} Content Coding", coding)
+            }
+        }
+    }
+
+    /// Status code of response that should be sent on this error.
+    /// 413 if body is too large, 414 if Request-URI is too long,
+    /// 415 if Content-Type is unsupported, 416 if scheme of Request-URI is unsupported,
+    /// 505 if SIP-Version is unsupported,
+    /// 513 if headers are too large and 400 otherwise
+    pub fn status_code(&self) -> u16 {
+        match self.kind {
+            SipParseErrorKind::TooManyHeaders
+            | SipParseErrorKind::HeaderTooLong
+            | SipParseErrorKind::TooManyVias
+            | SipParseErrorKind::TooManyValues { .. } => 513,
+            SipParseErrorKind::UriTooLong => 414,
+            SipParseErrorKind::BodyTooLarge => 413,
+            SipParseErrorKind::UnsupportedMediaType => 415,
+            SipParseErrorKind::UnsupportedUriScheme => 416,
+            SipParseErrorKind::UnsupportedVersion => 505,
+            _ => 400,
         }
     }
 }
 
+impl<'a> fmt::Display for SipParseError<'a> {
+    /// Ex: `BadHeaderValue (header: Via) at line 3, offset 57`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind.as_str())?;
+        if let Some(header) = self.header {
+            write!(f, " (header: {})", header)?;
+        }
+        if let (Some(line), Some(offset)) = (self.line, self.offset) {
+            write!(f, " at line {}, offset {}", line, offset)?;
+        }
+        Ok(())
+    }
+}
+
 impl<'a> ParseError<&'a [u8]> for SipParseError<'a> {
     fn from_error_kind(error: &'a [u8], kind: ErrorKind) -> Self {
         let error_str: &str;
@@ -70,10 +309,7 @@ impl<'a> ParseError<&'a [u8]> for SipParseError<'a> {
                 error_str = "Internal error of parser. Can't cast error string to to utf8";
             }
         }
-        SipParseError {
-            code: kind as u32,
-            message: Some(error_str),
-        }
+        SipParseError::new(kind as u32, Some(error_str)).with_input(error)
     }
 
     fn append(error: &'a [u8], kind: ErrorKind, _other: SipParseError) -> Self {
@@ -86,9 +322,80 @@ impl<'a> ParseError<&'a [u8]> for SipParseError<'a> {
                 error_str = "Internal error of parser. Can't cast error string to to utf8";
             }
         }
-        SipParseError {
-            code: kind as u32,
-            message: Some(error_str),
-        }
+        SipParseError::new(kind as u32, Some(error_str)).with_input(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_test() {
+        let message = b"INVITE sip:a@b SIP/2.0\r\nVia: bad\r\n\r\n";
+        let err = SipParseError::new(1, None)
+            .or_input(&message[24..])
+            .locate(message);
+        assert_eq!(err.offset, Some(24));
+        assert_eq!(err.line, Some(2));
+
+        let other_buffer = b"Via: bad";
+        let err = SipParseError::new(1, None)
+            .or_input(other_buffer)
+            .locate(message);
+        assert_eq!(err.offset, None);
+        assert_eq!(err.line, None);
+    }
+
+    #[test]
+    fn reason_phrase_test() {
+        let err = SipParseError::new(1, None)
+            .or_kind(SipParseErrorKind::BadHeaderValue { header: "Via" })
+            .or_kind(SipParseErrorKind::BadStartLine);
+        assert_eq!(err.reason_phrase(), "Malformed Via Header");
+        assert_eq!(
+            SipParseError::new(1, None)
+                .with_kind(SipParseErrorKind::BodyTooShort)
+                .reason_phrase(),
+            "Body Shorter Than Content-Length"
+        );
+    }
+
+    #[test]
+    fn status_code_test() {
+        let err = |kind| SipParseError::new(1, None).with_kind(kind);
+        assert_eq!(err(SipParseErrorKind::BadStartLine).status_code(), 400);
+        assert_eq!(err(SipParseErrorKind::BodyTooLarge).status_code(), 413);
+        assert_eq!(err(SipParseErrorKind::UriTooLong).status_code(), 414);
+        assert_eq!(
+            err(SipParseErrorKind::UnsupportedMediaType).status_code(),
+            415
+        );
+        assert_eq!(
+            err(SipParseErrorKind::UnsupportedUriScheme).status_code(),
+            416
+        );
+        assert_eq!(
+            err(SipParseErrorKind::UnsupportedVersion).status_code(),
+            505
+        );
+        assert_eq!(err(SipParseErrorKind::TooManyVias).status_code(), 513);
+        assert_eq!(
+            err(SipParseErrorKind::TooManyValues { header: "Route" }).reason_phrase(),
+            "Message Too Large"
+        );
+        assert_eq!(
+            err(SipParseErrorKind::BadSdp { field: "m" }).reason_phrase(),
+            "Malformed SDP m= Line"
+        );
+        assert_eq!(
+            err(SipParseErrorKind::BadBody {
+                content_type: "application/pidf+xml"
+            })
+            .reason_phrase(),
+            "Malformed application/pidf+xml Body"
+        );
+        assert!(SipParseErrorKind::HeaderTooLong.is_limit_exceeded());
+        assert!(!SipParseErrorKind::BadHeaderName.is_limit_exceeded());
     }
 }
@@ -0,0 +1,102 @@
+//! Line folding of header values
+//! [rfc3261 section-7.3.1](https://tools.ietf.org/html/rfc3261#section-7.3.1)
+//!
+//! Header fields can be extended over multiple lines by preceding each
+//! extra line with at least one SP or horizontal tab. All linear white space,
+//! including folding, has the same semantics as SP.
+use crate::common::bnfcore::{is_crlf, is_wsp};
+use alloc::{borrow::Cow, string::String};
+
+/// Returns true if value contains CRLF followed by WSP
+pub fn is_folded(value: &[u8]) -> bool {
+    let mut idx = 0;
+    while idx + 2 < value.len() {
+        if is_crlf(&value[idx..]) && is_wsp(value[idx + 2]) {
+            return true;
+        }
+        idx += 1;
+    }
+    false
+}
+
+/// Replaces every folded LWS (`*WSP CRLF 1*WSP`) by single SP.
+/// Whitespaces without line break are left as is.
+/// Returns borrowed value if there is nothing to unfold.
+/// ```rust
+/// use sipmsg::common::folding::unfold_lws;
+/// assert_eq!(unfold_lws("0009\r\n INVITE"), "0009 INVITE");
+/// assert_eq!(unfold_lws("SIP  /   2.0\r\n /UDP"), "SIP  /   2.0 /UDP");
+/// assert_eq!(unfold_lws("value  \r\n\t \tnext"), "value next");
+/// assert_eq!(unfold_lws("not  folded"), "not  folded");
+/// ```
+pub fn unfold_lws(value: &str) -> Cow<'_, str> {
+    let bytes = value.as_bytes();
+    if !is_folded(bytes) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut copied = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if !is_wsp(bytes[idx]) && !is_crlf(&bytes[idx..]) {
+            idx += 1;
+            continue;
+        }
+        let lws_start = idx;
+        let mut folded = false;
+        loop {
+            if idx < bytes.len() && is_wsp(bytes[idx]) {
+                idx += 1;
+            } else if is_crlf(&bytes[idx..]) && idx + 2 < bytes.len() && is_wsp(bytes[idx + 2]) {
+                folded = true;
+                idx += 2;
+            } else {
+                break;
+            }
+        }
+        if !folded {
+            if idx == lws_start {
+                idx += 1; // CRLF without WSP after. Leave it as is
+            }
+            continue;
+        }
+        result.push_str(&value[copied..lws_start]);
+        result.push(' ');
+        copied = idx;
+    }
+    result.push_str(&value[copied..]);
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfold_lws_test() {
+        assert_eq!(unfold_lws(""), "");
+        assert_eq!(unfold_lws("value"), "value");
+        assert_eq!(unfold_lws("value\r\n"), "value\r\n");
+        assert_eq!(unfold_lws("0009\r\n INVITE"), "0009 INVITE");
+        assert_eq!(
+            unfold_lws("SIP  /   2.0\r\n /UDP\r\n 192.0.2.2"),
+            "SIP  /   2.0 /UDP 192.0.2.2"
+        );
+        assert_eq!(
+            unfold_lws("newfangled value\r\n continued newfangled value"),
+            "newfangled value continued newfangled value"
+        );
+        assert_eq!(unfold_lws("a \r\n \r\n\tb"), "a b");
+        assert_eq!(unfold_lws("\"quoted\r\n  text\""), "\"quoted text\"");
+        assert!(matches!(unfold_lws("no folding"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn is_folded_test() {
+        assert!(is_folded(b"a\r\n b"));
+        assert!(!is_folded(b"a\r\nb"));
+        assert!(!is_folded(b"a\r\n"));
+        assert!(!is_folded(b"a b"));
+    }
+}
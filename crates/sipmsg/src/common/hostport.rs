@@ -17,11 +17,84 @@ fn host_char_allowed(c: u8) -> bool {
     is_alphanum(c) || c == b'-' || c == b'.'
 }
 
+// IPv4address    =  1*3DIGIT "." 1*3DIGIT "." 1*3DIGIT "." 1*3DIGIT
+pub fn is_ipv4_address(addr: &[u8]) -> bool {
+    let mut octets = 0;
+    for octet in addr.split(|c| *c == b'.') {
+        octets += 1;
+        if octet.is_empty() || octet.len() > 3 || !octet.iter().all(|c| is_digit(*c)) {
+            return false;
+        }
+        match str::from_utf8(octet).map(|octet| octet.parse::<u8>()) {
+            Ok(Ok(_)) => {}
+            _ => return false,
+        }
+    }
+    octets == 4
+}
+
+/// Returns number of 16-bit groups in part of IPv6 address
+/// that is separated by "::". IPv4address is counted as two groups
+fn count_ipv6_groups(part: &[u8], may_end_with_ipv4: bool) -> Option<usize> {
+    if part.is_empty() {
+        return Some(0);
+    }
+    let mut groups = 0;
+    let mut pieces = part.split(|c| *c == b':').peekable();
+    while let Some(piece) = pieces.next() {
+        let is_last = pieces.peek().is_none();
+        if is_last && may_end_with_ipv4 && piece.contains(&b'.') {
+            if !is_ipv4_address(piece) {
+                return None;
+            }
+            groups += 2;
+        } else if !piece.is_empty() && piece.len() <= 4 && piece.iter().all(|c| is_hexdig(*c)) {
+            groups += 1;
+        } else {
+            return None;
+        }
+    }
+    Some(groups)
+}
+
+// IPv6address    =  hexpart [ ":" IPv4address ]
+// hexpart        =  hexseq / hexseq "::" [ hexseq ] / "::" [ hexseq ]
+// hexseq         =  hex4 *( ":" hex4)
+// hex4           =  1*4HEXDIG
+/// Validates IPv6address. There may be only one "::" in address.
+/// [rfc5118 section-4.8](https://tools.ietf.org/html/rfc5118#section-4.8)
+pub fn is_ipv6_address(addr: &[u8]) -> bool {
+    let compressed_pos = addr.windows(2).position(|w| w == b"::");
+    let groups = match compressed_pos {
+        Some(pos) => {
+            let (head, tail) = (&addr[..pos], &addr[pos + 2..]);
+            match (
+                count_ipv6_groups(head, false),
+                count_ipv6_groups(tail, true),
+            ) {
+                (Some(head_groups), Some(tail_groups)) => head_groups + tail_groups,
+                _ => return false,
+            }
+        }
+        None => match count_ipv6_groups(addr, true) {
+            Some(groups) => groups,
+            None => return false,
+        },
+    };
+    match compressed_pos {
+        Some(_) => groups <= 7,
+        None => groups == 8,
+    }
+}
+
 impl<'a> HostPort<'a> {
     pub fn take_ipv6_host(input: &'a [u8]) -> nom::IResult<&[u8], &[u8], SipParseError> {
         let (input, _) = take(1usize)(input)?; // skip '['
         let (input, ipv6_host) = take_until("]")(input)?;
         let (input, _) = take(1usize)(input)?; // skip ']'
+        if !is_ipv6_address(ipv6_host) {
+            return sip_parse_error!(4, "Invalid IPv6 address");
+        }
         Ok((input, ipv6_host))
     }
 
@@ -112,6 +185,28 @@ mod tests {
         host_port_test_case("[2001:db8::10]:8080", "2001:db8::10", Some(8080), "");
     }
 
+    #[test]
+    fn ip_address_validation() {
+        assert!(is_ipv4_address(b"192.0.2.1"));
+        assert!(!is_ipv4_address(b"192.0.2"));
+        assert!(!is_ipv4_address(b"192.0.2.256"));
+
+        assert!(is_ipv6_address(b"2001:db8::10"));
+        assert!(is_ipv6_address(b"2001:db8::10:5070"));
+        assert!(is_ipv6_address(b"::ffff:192.0.2.2"));
+        assert!(is_ipv6_address(b"2001:db8::192.0.2.1"));
+        assert!(is_ipv6_address(b"2001:db8:0:0:0:0:0:1"));
+        assert!(is_ipv6_address(b"::"));
+        assert!(!is_ipv6_address(b"2001:db8:::192.0.2.1"));
+        assert!(!is_ipv6_address(b"2001:db8::1::2"));
+        assert!(!is_ipv6_address(b"2001:db8:0:0:0:0:1"));
+        assert!(!is_ipv6_address(b"2001:db8:0:0:0:0:0:0:1"));
+        assert!(!is_ipv6_address(b"2001:db8::12345"));
+        assert!(!is_ipv6_address(b"192.0.2.1::"));
+
+        assert!(HostPort::parse(b"[2001:db8:::192.0.2.1]").is_err());
+    }
+
     #[test]
     fn host_parse_with_rest() {
         host_port_test_case(
@@ -1,6 +1,7 @@
 #[macro_use]
 pub mod errorparse;
 
+pub mod folding;
 pub mod hostport;
 pub mod nom_wrappers;
 
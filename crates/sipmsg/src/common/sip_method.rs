@@ -15,6 +15,8 @@ pub enum SipMethod {
     REGISTER,
     SUBSCRIBE,
     UPDATE,
+    /// extension-method. Name of method can be taken by `RequestLine::method_str`
+    Extension,
 }
 
 impl SipMethod {
@@ -34,6 +36,7 @@ impl SipMethod {
             &SipMethod::REGISTER => "REGISTER",
             &SipMethod::SUBSCRIBE => "SUBSCRIBE",
             &SipMethod::UPDATE => "UPDATE",
+            &SipMethod::Extension => "extension-method",
         }
     }
 
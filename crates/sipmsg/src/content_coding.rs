@@ -0,0 +1,471 @@
+//! Content codings of message-body and negotiation of Accept-Encoding.
+//! [rfc3261 section-20.2](https://tools.ietf.org/html/rfc3261#section-20.2),
+//! [rfc3261 section-20.12](https://tools.ietf.org/html/rfc3261#section-20.12)
+//!
+//! Decoding and encoding of `gzip` and `deflate` bodies are enabled by `compression` feature.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::content_coding::{AcceptEncoding, ContentCoding};
+//! use sipmsg::SipRequest;
+//!
+//! let invite = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+//! CSeq: 1 INVITE\r\n\
+//! Accept-Encoding: deflate;q=0.5, gzip, *;q=0\r\n\r\n"
+//!     .as_bytes();
+//! let (_, request) = SipRequest::parse(invite).unwrap();
+//! let accept = AcceptEncoding::from_headers(&request.headers);
+//! assert_eq!(accept.qvalue("gzip"), 1000);
+//! assert_eq!(accept.qvalue("identity"), 0);
+//! assert_eq!(
+//!     accept.preferred(&[ContentCoding::Deflate, ContentCoding::Gzip]),
+//!     Some(ContentCoding::Gzip)
+//! );
+//! ```
+
+use crate::{
+    common::errorparse::{SipParseError, SipParseErrorKind},
+    multipart::unsupported_media_type,
+    negotiation::header_qvalue,
+    SipHeaders, SipRFCHeader,
+};
+use alloc::vec::Vec;
+use core::cell::OnceCell;
+
+#[cfg(feature = "compression")]
+use alloc::borrow::Cow;
+
+/// Limit of body decoded by `body_as_*` methods of message.
+/// `decoded_body` method of message takes its own limit
+pub const MAX_DECODED_BODY_LEN: usize = 1024 * 1024;
+
+/// content-coding of Content-Encoding and Accept-Encoding headers
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ContentCoding {
+    /// Body is not encoded
+    Identity,
+    /// [rfc1952](https://tools.ietf.org/html/rfc1952). `x-gzip` is the same coding
+    Gzip,
+    /// zlib format of [rfc1950](https://tools.ietf.org/html/rfc1950)
+    Deflate,
+}
+
+impl ContentCoding {
+    /// Case insensitive. Returns `None` if coding is not supported. Ex: `compress`, `br`
+    pub fn parse(coding: &str) -> Option<ContentCoding> {
+        if coding.eq_ignore_ascii_case("identity") {
+            Some(ContentCoding::Identity)
+        } else if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentCoding::Gzip)
+        } else if coding.eq_ignore_ascii_case("deflate") {
+            Some(ContentCoding::Deflate)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentCoding::Identity => "identity",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    fn error<'a>(&self, message: &'a str, input: &'a [u8]) -> nom::Err<SipParseError<'a>> {
+        nom::Err::Error(
+            SipParseError::new(1, Some(message))
+                .with_kind(SipParseErrorKind::BadContentEncoding {
+                    coding: self.as_str(),
+                })
+                .or_input(input),
+        )
+    }
+}
+
+/// One entry of Accept-Encoding. `q` is qvalue multiplied by 1000
+struct EncodingRange<'a> {
+    coding: &'a str,
+    q: u16,
+}
+
+/// Accept-Encoding header values of message.
+/// If header is absent only `identity` is acceptable, as RFC 3261 requires
+pub struct AcceptEncoding<'a> {
+    ranges: Vec<EncodingRange<'a>>,
+}
+
+impl<'a> AcceptEncoding<'a> {
+    /// Values with malformed qvalue are ignored
+    pub fn from_headers(headers: &SipHeaders<'a>) -> AcceptEncoding<'a> {
+        let mut ranges = Vec::new();
+        let hdrs = match headers.get_rfc(SipRFCHeader::AcceptEncoding) {
+            Some(hdrs) => hdrs,
+            None => {
+                ranges.push(EncodingRange {
+                    coding: "identity",
+                    q: 1000,
+                });
+                return AcceptEncoding { ranges };
+            }
+        };
+        for hdr in hdrs.iter().filter(|hdr| !hdr.value.vstr.is_empty()) {
+            let q = match header_qvalue(hdr) {
+                Some(q) => q,
+                None => continue,
+            };
+            ranges.push(EncodingRange {
+                coding: hdr.value.vstr,
+                q,
+            });
+        }
+        AcceptEncoding { ranges }
+    }
+
+    /// qvalue of coding multiplied by 1000. 0 means that coding is not acceptable.
+    /// `identity` is acceptable unless it is excluded explicitly or by `*;q=0`
+    pub fn qvalue(&self, coding: &str) -> u16 {
+        let find = |name: &str| {
+            self.ranges
+                .iter()
+                .find(|range| range.coding.eq_ignore_ascii_case(name))
+                .map(|range| range.q)
+        };
+        if let Some(q) = find(coding) {
+            return q;
+        }
+        if coding.eq_ignore_ascii_case("x-gzip") {
+            if let Some(q) = find("gzip") {
+                return q;
+            }
+        }
+        match find("*") {
+            Some(q) => q,
+            None if coding.eq_ignore_ascii_case("identity") => 1,
+            None => 0,
+        }
+    }
+
+    pub fn is_acceptable(&self, coding: ContentCoding) -> bool {
+        self.qvalue(coding.as_str()) > 0
+    }
+
+    /// Acceptable coding of `supported` with the highest qvalue.
+    /// The first one of `supported` wins if qvalues are equal
+    pub fn preferred(&self, supported: &[ContentCoding]) -> Option<ContentCoding> {
+        let mut preferred: Option<(ContentCoding, u16)> = None;
+        for coding in supported {
+            let q = self.qvalue(coding.as_str());
+            if q > 0 && preferred.map_or(true, |(_, best_q)| q > best_q) {
+                preferred = Some((*coding, q));
+            }
+        }
+        preferred.map(|(coding, _)| coding)
+    }
+}
+
+#[cfg(feature = "compression")]
+impl ContentCoding {
+    /// Decodes body. `max_len` limits length of decoded body.
+    /// Returns `BadContentEncoding` error if body is corrupted or `max_len` is exceeded
+    pub fn decode<'a>(
+        &self,
+        body: &'a [u8],
+        max_len: Option<usize>,
+    ) -> Result<Vec<u8>, nom::Err<SipParseError<'a>>> {
+        self.inflate(body, max_len)
+            .map_err(|message| self.error(message, body))
+    }
+
+    fn inflate(&self, body: &[u8], max_len: Option<usize>) -> Result<Vec<u8>, &'static str> {
+        use miniz_oxide::inflate::{
+            decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit,
+        };
+
+        let max_len = max_len.unwrap_or(usize::MAX);
+        match self {
+            ContentCoding::Identity if body.len() > max_len => Err("Body is too large"),
+            ContentCoding::Identity => Ok(body.to_vec()),
+            ContentCoding::Gzip => {
+                let (deflated, crc, size) = gzip_members(body).ok_or("Malformed gzip header")?;
+                let decoded = decompress_to_vec_with_limit(deflated, max_len)
+                    .map_err(|_| "Malformed deflate stream")?;
+                if crc32(&decoded) != crc || decoded.len() as u32 != size {
+                    return Err("Checksum of gzip body does not match");
+                }
+                Ok(decoded)
+            }
+            // Some implementations send raw deflate stream without zlib wrapper
+            ContentCoding::Deflate => decompress_to_vec_zlib_with_limit(body, max_len)
+                .or_else(|_| decompress_to_vec_with_limit(body, max_len))
+                .map_err(|_| "Malformed deflate stream"),
+        }
+    }
+
+    pub fn encode(&self, body: &[u8]) -> Vec<u8> {
+        use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+
+        match self {
+            ContentCoding::Identity => body.to_vec(),
+            ContentCoding::Gzip => {
+                let mut encoded = Vec::with_capacity(body.len() / 2 + 18);
+                // ID1 ID2 CM=deflate FLG MTIME(4) XFL OS=unknown
+                encoded.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+                encoded.extend_from_slice(&compress_to_vec(body, COMPRESSION_LEVEL));
+                encoded.extend_from_slice(&crc32(body).to_le_bytes());
+                encoded.extend_from_slice(&(body.len() as u32).to_le_bytes());
+                encoded
+            }
+            ContentCoding::Deflate => compress_to_vec_zlib(body, COMPRESSION_LEVEL),
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Deflate stream, CRC32 and ISIZE of single-member gzip body
+#[cfg(feature = "compression")]
+fn gzip_members(body: &[u8]) -> Option<(&[u8], u32, u32)> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if body.len() < 18 || body[..3] != [0x1f, 0x8b, 8] {
+        return None;
+    }
+    let flags = body[3];
+    let (stream, trailer) = body.split_at(body.len() - 8);
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let xlen = u16::from_le_bytes([*stream.get(pos)?, *stream.get(pos + 1)?]);
+        pos += 2 + xlen as usize;
+    }
+    for flag in &[FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            pos += stream.get(pos..)?.iter().position(|c| *c == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    Some((stream.get(pos..)?, crc, size))
+}
+
+/// CRC-32 of gzip trailer
+#[cfg(feature = "compression")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Decodes message-body according to Content-Encoding.
+/// Codings are applied in the order they are listed, so they are decoded in reverse order.
+/// Returns `UnsupportedMediaType` error if coding is not supported
+#[cfg(feature = "compression")]
+pub(crate) fn decoded_body<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+    max_len: Option<usize>,
+) -> Result<Cow<'a, [u8]>, nom::Err<SipParseError<'a>>> {
+    let body = body.unwrap_or(&[]);
+    let hdrs = match headers.get_rfc(SipRFCHeader::ContentEncoding) {
+        Some(hdrs) => hdrs,
+        None => return Ok(Cow::Borrowed(body)),
+    };
+    let mut decoded = Cow::Borrowed(body);
+    for hdr in hdrs.iter().rev() {
+        let coding = match ContentCoding::parse(hdr.value.vstr) {
+            Some(ContentCoding::Identity) => continue,
+            Some(coding) => coding,
+            None => return Err(unsupported_media_type()),
+        };
+        // location of errors is the whole message-body
+        decoded = Cow::Owned(
+            coding
+                .inflate(&decoded, max_len)
+                .map_err(|message| coding.error(message, body))?,
+        );
+    }
+    Ok(decoded)
+}
+
+/// Message-body without Content-Encoding. Decoded body is kept in `decoded` of message,
+/// so it is decoded once and borrowed as long as the message.
+/// Without `compression` feature encoded body is rejected with `BadContentEncoding` error
+pub(crate) fn content_body<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+    decoded: &'a OnceCell<Vec<u8>>,
+) -> Result<Option<&'a [u8]>, nom::Err<SipParseError<'a>>> {
+    if let Some(content) = decoded.get() {
+        return Ok(Some(content));
+    }
+    match decode_content(headers, body)? {
+        Some(content) => Ok(Some(decoded.get_or_init(|| content))),
+        None => Ok(body),
+    }
+}
+
+/// Returns `None` if body is not encoded
+#[cfg(feature = "compression")]
+fn decode_content<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<Option<Vec<u8>>, nom::Err<SipParseError<'a>>> {
+    match decoded_body(headers, body, Some(MAX_DECODED_BODY_LEN))? {
+        Cow::Borrowed(_) => Ok(None),
+        Cow::Owned(content) => Ok(Some(content)),
+    }
+}
+
+/// Returns `None` if body is not encoded
+#[cfg(not(feature = "compression"))]
+fn decode_content<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<Option<Vec<u8>>, nom::Err<SipParseError<'a>>> {
+    let hdrs = match headers.get_rfc(SipRFCHeader::ContentEncoding) {
+        Some(hdrs) => hdrs,
+        None => return Ok(None),
+    };
+    for hdr in hdrs {
+        match ContentCoding::parse(hdr.value.vstr) {
+            Some(ContentCoding::Identity) => {}
+            Some(coding) => {
+                return Err(coding.error(
+                    "Decoding of body requires compression feature",
+                    body.unwrap_or(&[]),
+                ))
+            }
+            None => return Err(unsupported_media_type()),
+        }
+    }
+    Ok(None)
+}
+
+/// Encodes outgoing body with the coding preferred by peer.
+/// `peer_headers` are headers of message received from peer. Ex: request for response body.
+/// Returns `Identity` if peer does not accept `gzip` or `deflate`.
+/// Value of coding should be added to Content-Encoding of outgoing message
+/// ```rust
+/// use sipmsg::content_coding::{encode_body_for, ContentCoding};
+/// use sipmsg::SipRequest;
+///
+/// let (_, request) = SipRequest::parse(
+///     b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCSeq: 1 OPTIONS\r\nAccept-Encoding: gzip\r\n\r\n",
+/// )
+/// .unwrap();
+/// let (coding, body) = encode_body_for(&request.headers, b"v=0\r\n");
+/// assert_eq!(coding, ContentCoding::Gzip);
+/// assert_eq!(coding.decode(&body, None).unwrap(), b"v=0\r\n");
+/// ```
+#[cfg(feature = "compression")]
+pub fn encode_body_for<'b>(
+    peer_headers: &SipHeaders<'_>,
+    body: &'b [u8],
+) -> (ContentCoding, Cow<'b, [u8]>) {
+    let supported = [ContentCoding::Gzip, ContentCoding::Deflate];
+    match AcceptEncoding::from_headers(peer_headers).preferred(&supported) {
+        Some(coding) => (coding, Cow::Owned(coding.encode(body))),
+        None => (ContentCoding::Identity, Cow::Borrowed(body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(input: &str) -> SipHeaders<'_> {
+        SipHeaders::parse(input.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn accept_encoding_test() {
+        let accept = AcceptEncoding::from_headers(&headers("Call-ID: 1\r\n\r\n"));
+        assert!(accept.is_acceptable(ContentCoding::Identity));
+        assert!(!accept.is_acceptable(ContentCoding::Gzip));
+
+        let accept = AcceptEncoding::from_headers(&headers("Accept-Encoding: \r\n\r\n"));
+        assert!(accept.is_acceptable(ContentCoding::Identity));
+        assert_eq!(accept.preferred(&[ContentCoding::Gzip]), None);
+
+        let hdrs = headers("Accept-Encoding: gzip;q=0.5, DEFLATE;q=0.8\r\n\r\n");
+        let accept = AcceptEncoding::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("x-gzip"), 500);
+        assert_eq!(
+            accept.preferred(&[ContentCoding::Gzip, ContentCoding::Deflate]),
+            Some(ContentCoding::Deflate)
+        );
+
+        let hdrs = headers("Accept-Encoding: gzip;q=1.0, identity; q=0.5, *;q=0\r\n\r\n");
+        let accept = AcceptEncoding::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("identity"), 500);
+        assert!(!accept.is_acceptable(ContentCoding::Deflate));
+
+        let hdrs = headers("Accept-Encoding: *;q=0.1, br;q=1.0, gzip;q=1.5\r\n\r\n");
+        let accept = AcceptEncoding::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("gzip"), 100);
+        assert_eq!(
+            accept.preferred(&[ContentCoding::Gzip, ContentCoding::Deflate]),
+            Some(ContentCoding::Gzip)
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn encode_decode_test() {
+        let body = "v=0\r\no=alice 2890844526 2890844526 IN IP4 host.atlanta.example.com\r\n\
+                    s=\r\nc=IN IP4 host.atlanta.example.com\r\nt=0 0\r\n"
+            .repeat(4);
+        for coding in &[
+            ContentCoding::Identity,
+            ContentCoding::Gzip,
+            ContentCoding::Deflate,
+        ] {
+            let encoded = coding.encode(body.as_bytes());
+            assert_eq!(coding.decode(&encoded, None).unwrap(), body.as_bytes());
+            assert!(coding.decode(&encoded, Some(10)).is_err());
+        }
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        // gzip -n of "hello\n" with file name
+        let gzip = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, b'h', b'i', 0x00, 0xcb,
+            0x48, 0xcd, 0xc9, 0xc9, 0xe7, 0x02, 0x00, 0x20, 0x30, 0x3a, 0x36, 0x06, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(ContentCoding::Gzip.decode(&gzip, None).unwrap(), b"hello\n");
+        let mut corrupted = gzip;
+        corrupted[21] ^= 1;
+        match ContentCoding::Gzip.decode(&corrupted, None) {
+            Err(nom::Err::Error(e)) => {
+                assert_eq!(
+                    e.kind,
+                    SipParseErrorKind::BadContentEncoding { coding: "gzip" }
+                )
+            }
+            _ => panic!("Checksum should not match"),
+        }
+        assert!(ContentCoding::Gzip.decode(b"hello\n", None).is_err());
+
+        let raw_deflate = miniz_oxide::deflate::compress_to_vec(b"hello\n", 6);
+        assert_eq!(
+            ContentCoding::Deflate.decode(&raw_deflate, None).unwrap(),
+            b"hello\n"
+        );
+    }
+}
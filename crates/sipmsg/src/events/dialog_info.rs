@@ -0,0 +1,344 @@
+use crate::{
+    common::errorparse::SipParseError,
+    events::{
+        body_error, element_text, elements, parse_xml, DocumentState, Escaped, XML_DECLARATION,
+    },
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// Direction of dialog from the point of view of observed user agent
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DialogDirection {
+    /// User agent sent initial request
+    Initiator,
+    /// User agent received initial request
+    Recipient,
+}
+
+impl DialogDirection {
+    fn parse(value: &str) -> Option<DialogDirection> {
+        match value {
+            "initiator" => Some(DialogDirection::Initiator),
+            "recipient" => Some(DialogDirection::Recipient),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DialogDirection::Initiator => "initiator",
+            DialogDirection::Recipient => "recipient",
+        }
+    }
+}
+
+/// [rfc4235 section-3.7.1](https://tools.ietf.org/html/rfc4235#section-3.7.1)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DialogState {
+    Trying,
+    Proceeding,
+    Early,
+    Confirmed,
+    Terminated,
+}
+
+impl DialogState {
+    fn parse(value: &str) -> Option<DialogState> {
+        match value {
+            "trying" => Some(DialogState::Trying),
+            "proceeding" => Some(DialogState::Proceeding),
+            "early" => Some(DialogState::Early),
+            "confirmed" => Some(DialogState::Confirmed),
+            "terminated" => Some(DialogState::Terminated),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DialogState::Trying => "trying",
+            DialogState::Proceeding => "proceeding",
+            DialogState::Early => "early",
+            DialogState::Confirmed => "confirmed",
+            DialogState::Terminated => "terminated",
+        }
+    }
+}
+
+/// `<local>` or `<remote>` participant of dialog
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Participant {
+    /// Ex: `sip:alice@example.com`
+    pub identity: Option<String>,
+    /// `display` attribute of identity
+    pub display: Option<String>,
+    /// `uri` attribute of target. Ex: Contact of participant
+    pub target: Option<String>,
+}
+
+impl Participant {
+    fn parse(node: roxmltree::Node<'_, '_>) -> Participant {
+        let identity = elements(node, "identity").next();
+        Participant {
+            identity: identity.map(|identity| identity.text().unwrap_or("").trim().to_string()),
+            display: identity
+                .and_then(|identity| identity.attribute("display"))
+                .map(|display| display.to_string()),
+            target: elements(node, "target")
+                .next()
+                .and_then(|target| target.attribute("uri"))
+                .map(|uri| uri.to_string()),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        write!(f, "<{}>\r\n", name)?;
+        if let Some(identity) = &self.identity {
+            match &self.display {
+                Some(display) => write!(
+                    f,
+                    "<identity display=\"{}\">{}</identity>\r\n",
+                    Escaped(display),
+                    Escaped(identity)
+                )?,
+                None => write!(f, "<identity>{}</identity>\r\n", Escaped(identity))?,
+            }
+        }
+        if let Some(target) = &self.target {
+            write!(f, "<target uri=\"{}\"/>\r\n", Escaped(target))?;
+        }
+        write!(f, "</{}>\r\n", name)
+    }
+}
+
+/// `<dialog>` element
+#[derive(Clone, PartialEq, Debug)]
+pub struct Dialog {
+    pub id: String,
+    pub call_id: Option<String>,
+    pub local_tag: Option<String>,
+    pub remote_tag: Option<String>,
+    pub direction: Option<DialogDirection>,
+    pub state: DialogState,
+    /// `event` attribute of state. Ex: `rejected`, `remote-bye`
+    pub event: Option<String>,
+    /// `code` attribute of state. Status code of response that changed the state
+    pub code: Option<u16>,
+    /// Seconds since dialog is confirmed
+    pub duration: Option<u64>,
+    pub local: Option<Participant>,
+    pub remote: Option<Participant>,
+}
+
+impl Dialog {
+    pub fn new(id: &str, state: DialogState) -> Dialog {
+        Dialog {
+            id: id.to_string(),
+            call_id: None,
+            local_tag: None,
+            remote_tag: None,
+            direction: None,
+            state,
+            event: None,
+            code: None,
+            duration: None,
+            local: None,
+            remote: None,
+        }
+    }
+}
+
+/// Dialog state information
+/// [rfc4235](https://tools.ietf.org/html/rfc4235)
+#[derive(Clone, PartialEq, Debug)]
+pub struct DialogInfo {
+    /// Incremented for each NOTIFY of subscription
+    pub version: u32,
+    pub state: DocumentState,
+    /// Ex: `sip:alice@example.com`
+    pub entity: String,
+    pub dialogs: Vec<Dialog>,
+}
+
+impl DialogInfo {
+    pub const CONTENT_TYPE: &'static str = "application/dialog-info+xml";
+
+    pub fn new(entity: &str, version: u32, state: DocumentState) -> DialogInfo {
+        DialogInfo {
+            version,
+            state,
+            entity: entity.to_string(),
+            dialogs: Vec::new(),
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<DialogInfo, nom::Err<SipParseError<'_>>> {
+        let error = |message| body_error(DialogInfo::CONTENT_TYPE, message, input);
+        let document = parse_xml(DialogInfo::CONTENT_TYPE, "dialog-info", input)?;
+        let root = document.root_element();
+        let version = match root.attribute("version").map(|v| v.parse()) {
+            Some(Ok(version)) => version,
+            _ => return Err(error("dialog-info has no valid version")),
+        };
+        let state = match root.attribute("state").and_then(DocumentState::parse) {
+            Some(state) => state,
+            None => return Err(error("dialog-info has no valid state")),
+        };
+        let entity = match root.attribute("entity") {
+            Some(entity) => entity,
+            None => return Err(error("dialog-info has no entity")),
+        };
+        let mut dialog_info = DialogInfo::new(entity, version, state);
+        for node in elements(root, "dialog") {
+            let id = match node.attribute("id") {
+                Some(id) => id,
+                None => return Err(error("dialog has no id")),
+            };
+            let state_node = match elements(node, "state").next() {
+                Some(state_node) => state_node,
+                None => return Err(error("dialog has no state")),
+            };
+            let state = match DialogState::parse(state_node.text().unwrap_or("").trim()) {
+                Some(state) => state,
+                None => return Err(error("Unknown dialog state")),
+            };
+            let direction = match node.attribute("direction") {
+                Some(direction) => match DialogDirection::parse(direction) {
+                    Some(direction) => Some(direction),
+                    None => return Err(error("Unknown dialog direction")),
+                },
+                None => None,
+            };
+            let attribute = |name| node.attribute(name).map(|value| value.to_string());
+            dialog_info.dialogs.push(Dialog {
+                id: id.to_string(),
+                call_id: attribute("call-id"),
+                local_tag: attribute("local-tag"),
+                remote_tag: attribute("remote-tag"),
+                direction,
+                state,
+                event: state_node.attribute("event").map(|event| event.to_string()),
+                code: state_node
+                    .attribute("code")
+                    .and_then(|code| code.parse().ok()),
+                duration: element_text(node, "duration").and_then(|d| d.parse().ok()),
+                local: elements(node, "local").next().map(Participant::parse),
+                remote: elements(node, "remote").next().map(Participant::parse),
+            });
+        }
+        Ok(dialog_info)
+    }
+}
+
+impl fmt::Display for DialogInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(XML_DECLARATION)?;
+        write!(
+            f,
+            "<dialog-info xmlns=\"urn:ietf:params:xml:ns:dialog-info\" \
+             version=\"{}\" state=\"{}\" entity=\"{}\">\r\n",
+            self.version,
+            self.state.as_str(),
+            Escaped(&self.entity)
+        )?;
+        for dialog in &self.dialogs {
+            write!(f, "<dialog id=\"{}\"", Escaped(&dialog.id))?;
+            if let Some(call_id) = &dialog.call_id {
+                write!(f, " call-id=\"{}\"", Escaped(call_id))?;
+            }
+            if let Some(local_tag) = &dialog.local_tag {
+                write!(f, " local-tag=\"{}\"", Escaped(local_tag))?;
+            }
+            if let Some(remote_tag) = &dialog.remote_tag {
+                write!(f, " remote-tag=\"{}\"", Escaped(remote_tag))?;
+            }
+            if let Some(direction) = dialog.direction {
+                write!(f, " direction=\"{}\"", direction.as_str())?;
+            }
+            f.write_str(">\r\n<state")?;
+            if let Some(event) = &dialog.event {
+                write!(f, " event=\"{}\"", Escaped(event))?;
+            }
+            if let Some(code) = dialog.code {
+                write!(f, " code=\"{}\"", code)?;
+            }
+            write!(f, ">{}</state>\r\n", dialog.state.as_str())?;
+            if let Some(duration) = dialog.duration {
+                write!(f, "<duration>{}</duration>\r\n", duration)?;
+            }
+            if let Some(local) = &dialog.local {
+                local.write(f, "local")?;
+            }
+            if let Some(remote) = &dialog.remote {
+                remote.write(f, "remote")?;
+            }
+            f.write_str("</dialog>\r\n")?;
+        }
+        f.write_str("</dialog-info>\r\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        // rfc4235 section-5.2
+        let body = b"<?xml version=\"1.0\"?>
+            <dialog-info xmlns=\"urn:ietf:params:xml:ns:dialog-info\"
+                version=\"1\" state=\"full\" entity=\"sip:alice@example.com\">
+              <dialog id=\"as7d900as8\" call-id=\"a84b4c76e66710\"
+                  local-tag=\"1928301774\" direction=\"initiator\">
+                <state event=\"rejected\" code=\"486\">terminated</state>
+                <local>
+                  <identity display=\"Alice\">sip:alice@example.com</identity>
+                  <target uri=\"sip:alice@pc33.example.com\"/>
+                </local>
+              </dialog>
+            </dialog-info>";
+        let dialog_info = DialogInfo::parse(body).unwrap();
+        assert_eq!(dialog_info.version, 1);
+        assert_eq!(dialog_info.state, DocumentState::Full);
+        assert_eq!(dialog_info.entity, "sip:alice@example.com");
+        let dialog = &dialog_info.dialogs[0];
+        assert_eq!(dialog.id, "as7d900as8");
+        assert_eq!(dialog.call_id.as_deref(), Some("a84b4c76e66710"));
+        assert_eq!(dialog.remote_tag, None);
+        assert_eq!(dialog.direction, Some(DialogDirection::Initiator));
+        assert_eq!(dialog.state, DialogState::Terminated);
+        assert_eq!(dialog.event.as_deref(), Some("rejected"));
+        assert_eq!(dialog.code, Some(486));
+        let local = dialog.local.as_ref().unwrap();
+        assert_eq!(local.display.as_deref(), Some("Alice"));
+        assert_eq!(local.target.as_deref(), Some("sip:alice@pc33.example.com"));
+        assert_eq!(dialog.remote, None);
+
+        let generated = dialog_info.to_string();
+        assert_eq!(
+            DialogInfo::parse(generated.as_bytes()).unwrap(),
+            dialog_info
+        );
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        let body = |dialog: &str| {
+            alloc::format!(
+                "<dialog-info version=\"0\" state=\"partial\" entity=\"sip:a@b\">{}</dialog-info>",
+                dialog
+            )
+        };
+        assert!(DialogInfo::parse(body("").as_bytes()).is_ok());
+        assert!(DialogInfo::parse(b"<dialog-info state=\"full\" entity=\"sip:a@b\"/>").is_err());
+        assert!(DialogInfo::parse(body("<dialog id=\"1\"/>").as_bytes()).is_err());
+        assert!(DialogInfo::parse(
+            body("<dialog id=\"1\"><state>ringing</state></dialog>").as_bytes()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Bodies of event packages used in SUBSCRIBE/NOTIFY and PUBLISH.
+//! Enabled by `std` feature.
+//!
+//! | Content-Type                           | Type             | RFC                                                 |
+//! |----------------------------------------|------------------|-----------------------------------------------------|
+//! | `application/pidf+xml`                 | `Presence`       | [rfc3863](https://tools.ietf.org/html/rfc3863)      |
+//! | `application/dialog-info+xml`          | `DialogInfo`     | [rfc4235](https://tools.ietf.org/html/rfc4235)      |
+//! | `application/reginfo+xml`              | `RegInfo`        | [rfc3680](https://tools.ietf.org/html/rfc3680)      |
+//! | `application/simple-message-summary`   | `MessageSummary` | [rfc3842](https://tools.ietf.org/html/rfc3842)      |
+//!
+//! Parsed documents own their data. `Display` of each type generates the document.
+//! XML namespaces are not checked, elements are matched by local name.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::events::{BasicStatus, EventBody};
+//! use sipmsg::SipRequest;
+//!
+//! let publish = "PUBLISH sip:presentity@example.com SIP/2.0\r\n\
+//! CSeq: 1 PUBLISH\r\n\
+//! Event: presence\r\n\
+//! Content-Type: application/pidf+xml\r\n\r\n\
+//! <?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+//! <presence xmlns=\"urn:ietf:params:xml:ns:pidf\" entity=\"pres:presentity@example.com\">\
+//!   <tuple id=\"mobile\"><status><basic>open</basic></status></tuple>\
+//! </presence>"
+//!     .as_bytes();
+//! let (_, request) = SipRequest::parse(publish).unwrap();
+//! match request.event_body().unwrap() {
+//!     EventBody::Presence(presence) => {
+//!         assert_eq!(presence.entity, "pres:presentity@example.com");
+//!         assert_eq!(presence.tuples[0].status, Some(BasicStatus::Open));
+//!     }
+//!     _ => panic!("PIDF expected"),
+//! }
+//! ```
+
+mod dialog_info;
+pub use dialog_info::{Dialog, DialogDirection, DialogInfo, DialogState, Participant};
+
+mod mwi;
+pub use mwi::{MessageCount, MessageSummary};
+
+mod pidf;
+pub use pidf::{BasicStatus, Presence, Tuple};
+
+mod reginfo;
+pub use reginfo::{
+    ContactEvent, ContactState, RegContact, RegInfo, Registration, RegistrationState,
+};
+
+use crate::{
+    common::errorparse::{SipParseError, SipParseErrorKind},
+    multipart::unsupported_media_type,
+    SipHeaders, SipRFCHeader,
+};
+use alloc::string::{String, ToString};
+use core::{fmt, str};
+
+/// Document of event package selected by Content-Type
+#[derive(Clone, PartialEq, Debug)]
+pub enum EventBody {
+    Presence(Presence),
+    DialogInfo(DialogInfo),
+    RegInfo(RegInfo),
+    MessageSummary(MessageSummary),
+}
+
+impl EventBody {
+    /// Parses `body` according to media type of Content-Type without parameters.
+    /// Returns `UnsupportedMediaType` error if media type is not known
+    pub fn parse<'a>(
+        content_type: &str,
+        body: &'a [u8],
+    ) -> Result<EventBody, nom::Err<SipParseError<'a>>> {
+        let content_type = content_type.to_ascii_lowercase();
+        match content_type.as_str() {
+            Presence::CONTENT_TYPE => Ok(EventBody::Presence(Presence::parse(body)?)),
+            DialogInfo::CONTENT_TYPE => Ok(EventBody::DialogInfo(DialogInfo::parse(body)?)),
+            RegInfo::CONTENT_TYPE => Ok(EventBody::RegInfo(RegInfo::parse(body)?)),
+            MessageSummary::CONTENT_TYPE => {
+                Ok(EventBody::MessageSummary(MessageSummary::parse(body)?))
+            }
+            _ => Err(unsupported_media_type()),
+        }
+    }
+
+    /// Value of Content-Type header for generated document
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            EventBody::Presence(_) => Presence::CONTENT_TYPE,
+            EventBody::DialogInfo(_) => DialogInfo::CONTENT_TYPE,
+            EventBody::RegInfo(_) => RegInfo::CONTENT_TYPE,
+            EventBody::MessageSummary(_) => MessageSummary::CONTENT_TYPE,
+        }
+    }
+}
+
+impl fmt::Display for EventBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventBody::Presence(presence) => presence.fmt(f),
+            EventBody::DialogInfo(dialog_info) => dialog_info.fmt(f),
+            EventBody::RegInfo(reginfo) => reginfo.fmt(f),
+            EventBody::MessageSummary(summary) => summary.fmt(f),
+        }
+    }
+}
+
+/// `state` attribute of `dialog-info` and `reginfo` documents
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DocumentState {
+    /// Document has complete state
+    Full,
+    /// Document has only changes since the previous one
+    Partial,
+}
+
+impl DocumentState {
+    fn parse(value: &str) -> Option<DocumentState> {
+        match value {
+            "full" => Some(DocumentState::Full),
+            "partial" => Some(DocumentState::Partial),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentState::Full => "full",
+            DocumentState::Partial => "partial",
+        }
+    }
+}
+
+pub(crate) fn event_body<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<EventBody, nom::Err<SipParseError<'a>>> {
+    match headers.get_rfc_s(SipRFCHeader::ContentType) {
+        Some(hdr) => EventBody::parse(hdr.value.vstr, body.unwrap_or(&[])),
+        None => Err(unsupported_media_type()),
+    }
+}
+
+fn body_error<'a>(
+    content_type: &'a str,
+    message: &'a str,
+    input: &'a [u8],
+) -> nom::Err<SipParseError<'a>> {
+    nom::Err::Error(
+        SipParseError::new(1, Some(message))
+            .with_kind(SipParseErrorKind::BadBody { content_type })
+            .or_input(input),
+    )
+}
+
+/// Parses XML document and checks local name of root element
+fn parse_xml<'a>(
+    content_type: &'a str,
+    root: &str,
+    input: &'a [u8],
+) -> Result<roxmltree::Document<'a>, nom::Err<SipParseError<'a>>> {
+    let text = match str::from_utf8(input) {
+        Ok(text) => text,
+        Err(_) => return Err(body_error(content_type, "Body is not valid UTF-8", input)),
+    };
+    let document = match roxmltree::Document::parse(text) {
+        Ok(document) => document,
+        Err(_) => return Err(body_error(content_type, "Malformed XML", input)),
+    };
+    if document.root_element().tag_name().name() != root {
+        return Err(body_error(content_type, "Unexpected root element", input));
+    }
+    Ok(document)
+}
+
+/// Child elements with local name `name`
+fn elements<'a, 'input: 'a>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+/// Trimmed text of the first child element with local name `name`
+fn element_text(node: roxmltree::Node<'_, '_>, name: &str) -> Option<String> {
+    elements(node, name)
+        .next()
+        .map(|element| element.text().unwrap_or("").trim().to_string())
+}
+
+/// Escapes text and attribute values of generated XML
+struct Escaped<'a>(&'a str);
+
+impl<'a> fmt::Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                _ => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n";
@@ -0,0 +1,206 @@
+use crate::{common::errorparse::SipParseError, events::body_error};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str};
+
+/// Counts of messages of one class. Ex: `Voice-Message: 2/8 (0/2)`
+#[derive(Clone, PartialEq, Debug)]
+pub struct MessageCount {
+    /// Ex: `voice-message`, `fax-message`
+    pub class: String,
+    pub new: u32,
+    pub old: u32,
+    pub new_urgent: Option<u32>,
+    pub old_urgent: Option<u32>,
+}
+
+impl MessageCount {
+    pub fn new(class: &str, new: u32, old: u32) -> MessageCount {
+        MessageCount {
+            class: class.to_string(),
+            new,
+            old,
+            new_urgent: None,
+            old_urgent: None,
+        }
+    }
+
+    /// `msg-summary-line = message-context-class HCOLON newmsgs SLASH oldmsgs
+    ///                     [ LPAREN new-urgentmsgs SLASH old-urgentmsgs RPAREN ]`
+    fn parse(class: &str, value: &str) -> Option<MessageCount> {
+        let (counts, urgent) = match value.find('(') {
+            Some(pos) => (&value[..pos], Some(value[pos + 1..].trim_end())),
+            None => (value, None),
+        };
+        let (new, old) = counts_pair(counts)?;
+        let mut count = MessageCount::new(class, new, old);
+        if let Some(urgent) = urgent {
+            let (new_urgent, old_urgent) = counts_pair(urgent.strip_suffix(')')?)?;
+            count.new_urgent = Some(new_urgent);
+            count.old_urgent = Some(old_urgent);
+        }
+        Some(count)
+    }
+}
+
+fn counts_pair(value: &str) -> Option<(u32, u32)> {
+    let mut counts = value.splitn(2, '/');
+    let new = counts.next()?.trim().parse().ok()?;
+    let old = counts.next()?.trim().parse().ok()?;
+    Some((new, old))
+}
+
+/// Message summary of message waiting indication
+/// [rfc3842](https://tools.ietf.org/html/rfc3842)
+#[derive(Clone, PartialEq, Debug)]
+pub struct MessageSummary {
+    /// `Messages-Waiting: yes`
+    pub messages_waiting: bool,
+    /// Ex: `sip:alice@example.com`
+    pub account: Option<String>,
+    pub counts: Vec<MessageCount>,
+}
+
+impl MessageSummary {
+    pub const CONTENT_TYPE: &'static str = "application/simple-message-summary";
+
+    pub fn new(messages_waiting: bool) -> MessageSummary {
+        MessageSummary {
+            messages_waiting,
+            account: None,
+            counts: Vec::new(),
+        }
+    }
+
+    /// Counts of messages of class. Case insensitive
+    pub fn count(&self, class: &str) -> Option<&MessageCount> {
+        self.counts
+            .iter()
+            .find(|count| count.class.eq_ignore_ascii_case(class))
+    }
+
+    /// Lines that are not known are ignored. Ex: optional message headers
+    pub fn parse(input: &[u8]) -> Result<MessageSummary, nom::Err<SipParseError<'_>>> {
+        let error = |message| body_error(MessageSummary::CONTENT_TYPE, message, input);
+        let text = match str::from_utf8(input) {
+            Ok(text) => text,
+            Err(_) => return Err(error("Body is not valid UTF-8")),
+        };
+        let mut messages_waiting = None;
+        let mut account = None;
+        let mut counts = Vec::new();
+        for line in text.lines() {
+            let (name, value) = match line.find(':') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => continue,
+            };
+            if name.eq_ignore_ascii_case("Messages-Waiting") {
+                messages_waiting = match value.to_ascii_lowercase().as_str() {
+                    "yes" => Some(true),
+                    "no" => Some(false),
+                    _ => return Err(error("Messages-Waiting must be yes or no")),
+                };
+            } else if name.eq_ignore_ascii_case("Message-Account") {
+                account = Some(value.to_string());
+            } else if is_message_class(name) {
+                match MessageCount::parse(&name.to_ascii_lowercase(), value) {
+                    Some(count) => counts.push(count),
+                    None => return Err(error("Malformed message counts")),
+                }
+            }
+        }
+        match messages_waiting {
+            Some(messages_waiting) => Ok(MessageSummary {
+                messages_waiting,
+                account,
+                counts,
+            }),
+            None => Err(error("Messages-Waiting is missing")),
+        }
+    }
+}
+
+/// Message context classes of [rfc3458](https://tools.ietf.org/html/rfc3458)
+fn is_message_class(name: &str) -> bool {
+    [
+        "voice-message",
+        "fax-message",
+        "pager-message",
+        "multimedia-message",
+        "text-message",
+        "none",
+    ]
+    .iter()
+    .any(|class| name.eq_ignore_ascii_case(class))
+}
+
+impl fmt::Display for MessageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Messages-Waiting: {}\r\n",
+            if self.messages_waiting { "yes" } else { "no" }
+        )?;
+        if let Some(account) = &self.account {
+            write!(f, "Message-Account: {}\r\n", account)?;
+        }
+        for count in &self.counts {
+            write!(f, "{}: {}/{}", count.class, count.new, count.old)?;
+            if let (Some(new_urgent), Some(old_urgent)) = (count.new_urgent, count.old_urgent) {
+                write!(f, " ({}/{})", new_urgent, old_urgent)?;
+            }
+            f.write_str("\r\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        // rfc3842 section-6
+        let body = b"Messages-Waiting: yes\r\n\
+                     Message-Account: sip:alice@vmail.example.com\r\n\
+                     Voice-Message: 2/8 (0/2)\r\n\
+                     Fax-Message: 1/0\r\n";
+        let summary = MessageSummary::parse(body).unwrap();
+        assert!(summary.messages_waiting);
+        assert_eq!(
+            summary.account.as_deref(),
+            Some("sip:alice@vmail.example.com")
+        );
+        let voice = summary.count("Voice-Message").unwrap();
+        assert_eq!((voice.new, voice.old), (2, 8));
+        assert_eq!((voice.new_urgent, voice.old_urgent), (Some(0), Some(2)));
+        let fax = summary.count("fax-message").unwrap();
+        assert_eq!((fax.new, fax.old), (1, 0));
+        assert_eq!(fax.new_urgent, None);
+        assert!(summary.count("pager-message").is_none());
+
+        let generated = summary.to_string();
+        assert!(generated.contains("voice-message: 2/8 (0/2)\r\n"));
+        assert_eq!(
+            MessageSummary::parse(generated.as_bytes()).unwrap(),
+            summary
+        );
+
+        let summary = MessageSummary::parse(b"Messages-Waiting: no\n").unwrap();
+        assert!(!summary.messages_waiting);
+        assert!(summary.counts.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert!(MessageSummary::parse(b"").is_err());
+        assert!(MessageSummary::parse(b"Messages-Waiting: maybe\r\n").is_err());
+        assert!(MessageSummary::parse(b"Messages-Waiting: yes\r\nVoice-Message: 2\r\n").is_err());
+        assert!(
+            MessageSummary::parse(b"Messages-Waiting: yes\r\nVoice-Message: 2/8 (0/2\r\n").is_err()
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    common::errorparse::SipParseError,
+    events::{body_error, element_text, elements, parse_xml, Escaped, XML_DECLARATION},
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// `<basic>` status of tuple
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BasicStatus {
+    Open,
+    Closed,
+}
+
+impl BasicStatus {
+    fn parse(value: &str) -> Option<BasicStatus> {
+        match value {
+            "open" => Some(BasicStatus::Open),
+            "closed" => Some(BasicStatus::Closed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BasicStatus::Open => "open",
+            BasicStatus::Closed => "closed",
+        }
+    }
+}
+
+/// `<tuple>` of presence document. Ex: one device of presentity
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tuple {
+    pub id: String,
+    pub status: Option<BasicStatus>,
+    /// Contact address. Ex: `sip:alice@pc33.example.com`
+    pub contact: Option<String>,
+    /// `priority` attribute of contact. Value from 0 to 1
+    pub priority: Option<String>,
+    pub notes: Vec<String>,
+    /// Ex: `2001-10-27T16:49:29Z`
+    pub timestamp: Option<String>,
+}
+
+impl Tuple {
+    pub fn new(id: &str, status: BasicStatus) -> Tuple {
+        Tuple {
+            id: id.to_string(),
+            status: Some(status),
+            contact: None,
+            priority: None,
+            notes: Vec::new(),
+            timestamp: None,
+        }
+    }
+}
+
+/// Presence Information Data Format
+/// [rfc3863](https://tools.ietf.org/html/rfc3863)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Presence {
+    /// Ex: `pres:someone@example.com`
+    pub entity: String,
+    pub tuples: Vec<Tuple>,
+    pub notes: Vec<String>,
+}
+
+impl Presence {
+    pub const CONTENT_TYPE: &'static str = "application/pidf+xml";
+
+    pub fn new(entity: &str) -> Presence {
+        Presence {
+            entity: entity.to_string(),
+            tuples: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<Presence, nom::Err<SipParseError<'_>>> {
+        let error = |message| body_error(Presence::CONTENT_TYPE, message, input);
+        let document = parse_xml(Presence::CONTENT_TYPE, "presence", input)?;
+        let root = document.root_element();
+        let mut presence = match root.attribute("entity") {
+            Some(entity) => Presence::new(entity),
+            None => return Err(error("presence has no entity")),
+        };
+        for node in elements(root, "tuple") {
+            let id = match node.attribute("id") {
+                Some(id) => id,
+                None => return Err(error("tuple has no id")),
+            };
+            let status = match elements(node, "status").next() {
+                Some(status) => status,
+                None => return Err(error("tuple has no status")),
+            };
+            let basic = match element_text(status, "basic") {
+                Some(basic) => match BasicStatus::parse(&basic) {
+                    Some(basic) => Some(basic),
+                    None => return Err(error("Unknown basic status")),
+                },
+                None => None,
+            };
+            let contact = elements(node, "contact").next();
+            presence.tuples.push(Tuple {
+                id: id.to_string(),
+                status: basic,
+                contact: contact.and_then(|c| c.text()).map(|c| c.trim().to_string()),
+                priority: contact
+                    .and_then(|c| c.attribute("priority"))
+                    .map(|p| p.to_string()),
+                notes: notes(node),
+                timestamp: element_text(node, "timestamp"),
+            });
+        }
+        presence.notes = notes(root);
+        Ok(presence)
+    }
+}
+
+fn notes(node: roxmltree::Node<'_, '_>) -> Vec<String> {
+    elements(node, "note")
+        .map(|note| note.text().unwrap_or("").trim().to_string())
+        .collect()
+}
+
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(XML_DECLARATION)?;
+        write!(
+            f,
+            "<presence xmlns=\"urn:ietf:params:xml:ns:pidf\" entity=\"{}\">\r\n",
+            Escaped(&self.entity)
+        )?;
+        for tuple in &self.tuples {
+            write!(f, "<tuple id=\"{}\">\r\n<status>", Escaped(&tuple.id))?;
+            if let Some(status) = tuple.status {
+                write!(f, "<basic>{}</basic>", status.as_str())?;
+            }
+            f.write_str("</status>\r\n")?;
+            if let Some(contact) = &tuple.contact {
+                match &tuple.priority {
+                    Some(priority) => write!(
+                        f,
+                        "<contact priority=\"{}\">{}</contact>\r\n",
+                        Escaped(priority),
+                        Escaped(contact)
+                    )?,
+                    None => write!(f, "<contact>{}</contact>\r\n", Escaped(contact))?,
+                }
+            }
+            for note in &tuple.notes {
+                write!(f, "<note>{}</note>\r\n", Escaped(note))?;
+            }
+            if let Some(timestamp) = &tuple.timestamp {
+                write!(f, "<timestamp>{}</timestamp>\r\n", Escaped(timestamp))?;
+            }
+            f.write_str("</tuple>\r\n")?;
+        }
+        for note in &self.notes {
+            write!(f, "<note>{}</note>\r\n", Escaped(note))?;
+        }
+        f.write_str("</presence>\r\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parse_test() {
+        // rfc3863 section-6
+        let body = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+            <impp:presence xmlns:impp=\"urn:ietf:params:xml:ns:pidf\"
+                entity=\"pres:someone@example.com\">
+              <impp:tuple id=\"sg89ae\">
+                <impp:status>
+                  <impp:basic>open</impp:basic>
+                </impp:status>
+                <impp:contact priority=\"0.8\">tel:+09012345678</impp:contact>
+              </impp:tuple>
+              <impp:note xml:lang=\"en\">Don't Disturb Please!</impp:note>
+            </impp:presence>";
+        let presence = Presence::parse(body).unwrap();
+        assert_eq!(presence.entity, "pres:someone@example.com");
+        assert_eq!(presence.tuples.len(), 1);
+        let tuple = &presence.tuples[0];
+        assert_eq!(tuple.id, "sg89ae");
+        assert_eq!(tuple.status, Some(BasicStatus::Open));
+        assert_eq!(tuple.contact.as_deref(), Some("tel:+09012345678"));
+        assert_eq!(tuple.priority.as_deref(), Some("0.8"));
+        assert_eq!(presence.notes, vec!["Don't Disturb Please!"]);
+
+        let generated = presence.to_string();
+        assert!(generated.contains("<note>Don&apos;t Disturb Please!</note>"));
+        assert_eq!(Presence::parse(generated.as_bytes()).unwrap(), presence);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert!(Presence::parse(b"<presence>").is_err());
+        assert!(Presence::parse(b"<presence/>").is_err());
+        assert!(Presence::parse(b"<dialog-info entity=\"x\"/>").is_err());
+        assert!(Presence::parse(b"<presence entity=\"x\"><tuple/></presence>").is_err());
+        assert!(Presence::parse(
+            b"<presence entity=\"x\"><tuple id=\"1\"><status><basic>away</basic>\
+              </status></tuple></presence>"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{
+    common::errorparse::SipParseError,
+    events::{
+        body_error, element_text, elements, parse_xml, DocumentState, Escaped, XML_DECLARATION,
+    },
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+/// `state` attribute of `<registration>`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RegistrationState {
+    Init,
+    Active,
+    Terminated,
+}
+
+impl RegistrationState {
+    fn parse(value: &str) -> Option<RegistrationState> {
+        match value {
+            "init" => Some(RegistrationState::Init),
+            "active" => Some(RegistrationState::Active),
+            "terminated" => Some(RegistrationState::Terminated),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationState::Init => "init",
+            RegistrationState::Active => "active",
+            RegistrationState::Terminated => "terminated",
+        }
+    }
+}
+
+/// `state` attribute of `<contact>`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ContactState {
+    Active,
+    Terminated,
+}
+
+impl ContactState {
+    fn parse(value: &str) -> Option<ContactState> {
+        match value {
+            "active" => Some(ContactState::Active),
+            "terminated" => Some(ContactState::Terminated),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContactState::Active => "active",
+            ContactState::Terminated => "terminated",
+        }
+    }
+}
+
+/// `event` attribute of `<contact>`. Reason of the last state change
+/// [rfc3680 section-5.3](https://tools.ietf.org/html/rfc3680#section-5.3)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ContactEvent {
+    Registered,
+    Created,
+    Refreshed,
+    Shortened,
+    Expired,
+    Deactivated,
+    Probation,
+    Unregistered,
+    Rejected,
+}
+
+impl ContactEvent {
+    fn parse(value: &str) -> Option<ContactEvent> {
+        match value {
+            "registered" => Some(ContactEvent::Registered),
+            "created" => Some(ContactEvent::Created),
+            "refreshed" => Some(ContactEvent::Refreshed),
+            "shortened" => Some(ContactEvent::Shortened),
+            "expired" => Some(ContactEvent::Expired),
+            "deactivated" => Some(ContactEvent::Deactivated),
+            "probation" => Some(ContactEvent::Probation),
+            "unregistered" => Some(ContactEvent::Unregistered),
+            "rejected" => Some(ContactEvent::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContactEvent::Registered => "registered",
+            ContactEvent::Created => "created",
+            ContactEvent::Refreshed => "refreshed",
+            ContactEvent::Shortened => "shortened",
+            ContactEvent::Expired => "expired",
+            ContactEvent::Deactivated => "deactivated",
+            ContactEvent::Probation => "probation",
+            ContactEvent::Unregistered => "unregistered",
+            ContactEvent::Rejected => "rejected",
+        }
+    }
+}
+
+/// `<contact>` of registration
+#[derive(Clone, PartialEq, Debug)]
+pub struct RegContact {
+    pub id: String,
+    pub state: ContactState,
+    pub event: ContactEvent,
+    /// Seconds until the binding expires
+    pub expires: Option<u32>,
+    /// Ex: `0.8`
+    pub q: Option<String>,
+    /// Ex: `sip:joe@pc34.example.com`
+    pub uri: String,
+    pub display_name: Option<String>,
+}
+
+impl RegContact {
+    pub fn new(id: &str, uri: &str, state: ContactState, event: ContactEvent) -> RegContact {
+        RegContact {
+            id: id.to_string(),
+            state,
+            event,
+            expires: None,
+            q: None,
+            uri: uri.to_string(),
+            display_name: None,
+        }
+    }
+}
+
+/// `<registration>` of address-of-record
+#[derive(Clone, PartialEq, Debug)]
+pub struct Registration {
+    /// Address-of-record. Ex: `sip:joe@example.com`
+    pub aor: String,
+    pub id: String,
+    pub state: RegistrationState,
+    pub contacts: Vec<RegContact>,
+}
+
+impl Registration {
+    pub fn new(aor: &str, id: &str, state: RegistrationState) -> Registration {
+        Registration {
+            aor: aor.to_string(),
+            id: id.to_string(),
+            state,
+            contacts: Vec::new(),
+        }
+    }
+}
+
+/// Registration state information
+/// [rfc3680](https://tools.ietf.org/html/rfc3680)
+#[derive(Clone, PartialEq, Debug)]
+pub struct RegInfo {
+    /// Incremented for each NOTIFY of subscription
+    pub version: u32,
+    pub state: DocumentState,
+    pub registrations: Vec<Registration>,
+}
+
+impl RegInfo {
+    pub const CONTENT_TYPE: &'static str = "application/reginfo+xml";
+
+    pub fn new(version: u32, state: DocumentState) -> RegInfo {
+        RegInfo {
+            version,
+            state,
+            registrations: Vec::new(),
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<RegInfo, nom::Err<SipParseError<'_>>> {
+        let error = |message| body_error(RegInfo::CONTENT_TYPE, message, input);
+        let document = parse_xml(RegInfo::CONTENT_TYPE, "reginfo", input)?;
+        let root = document.root_element();
+        let version = match root.attribute("version").map(|v| v.parse()) {
+            Some(Ok(version)) => version,
+            _ => return Err(error("reginfo has no valid version")),
+        };
+        let state = match root.attribute("state").and_then(DocumentState::parse) {
+            Some(state) => state,
+            None => return Err(error("reginfo has no valid state")),
+        };
+        let mut reginfo = RegInfo::new(version, state);
+        for node in elements(root, "registration") {
+            let (aor, id) = match (node.attribute("aor"), node.attribute("id")) {
+                (Some(aor), Some(id)) => (aor, id),
+                _ => return Err(error("registration has no aor or id")),
+            };
+            let state = match node.attribute("state").and_then(RegistrationState::parse) {
+                Some(state) => state,
+                None => return Err(error("registration has no valid state")),
+            };
+            let mut registration = Registration::new(aor, id, state);
+            for contact in elements(node, "contact") {
+                let id = match contact.attribute("id") {
+                    Some(id) => id,
+                    None => return Err(error("contact has no id")),
+                };
+                let state = match contact.attribute("state").and_then(ContactState::parse) {
+                    Some(state) => state,
+                    None => return Err(error("contact has no valid state")),
+                };
+                let event = match contact.attribute("event").and_then(ContactEvent::parse) {
+                    Some(event) => event,
+                    None => return Err(error("contact has no valid event")),
+                };
+                let uri = match element_text(contact, "uri") {
+                    Some(uri) => uri,
+                    None => return Err(error("contact has no uri")),
+                };
+                let mut reg_contact = RegContact::new(id, &uri, state, event);
+                reg_contact.expires = contact.attribute("expires").and_then(|e| e.parse().ok());
+                reg_contact.q = contact.attribute("q").map(|q| q.to_string());
+                reg_contact.display_name = element_text(contact, "display-name");
+                registration.contacts.push(reg_contact);
+            }
+            reginfo.registrations.push(registration);
+        }
+        Ok(reginfo)
+    }
+}
+
+impl fmt::Display for RegInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(XML_DECLARATION)?;
+        write!(
+            f,
+            "<reginfo xmlns=\"urn:ietf:params:xml:ns:reginfo\" version=\"{}\" state=\"{}\">\r\n",
+            self.version,
+            self.state.as_str()
+        )?;
+        for registration in &self.registrations {
+            write!(
+                f,
+                "<registration aor=\"{}\" id=\"{}\" state=\"{}\">\r\n",
+                Escaped(&registration.aor),
+                Escaped(&registration.id),
+                registration.state.as_str()
+            )?;
+            for contact in &registration.contacts {
+                write!(
+                    f,
+                    "<contact id=\"{}\" state=\"{}\" event=\"{}\"",
+                    Escaped(&contact.id),
+                    contact.state.as_str(),
+                    contact.event.as_str()
+                )?;
+                if let Some(expires) = contact.expires {
+                    write!(f, " expires=\"{}\"", expires)?;
+                }
+                if let Some(q) = &contact.q {
+                    write!(f, " q=\"{}\"", Escaped(q))?;
+                }
+                write!(f, ">\r\n<uri>{}</uri>\r\n", Escaped(&contact.uri))?;
+                if let Some(display_name) = &contact.display_name {
+                    write!(
+                        f,
+                        "<display-name>{}</display-name>\r\n",
+                        Escaped(display_name)
+                    )?;
+                }
+                f.write_str("</contact>\r\n")?;
+            }
+            f.write_str("</registration>\r\n")?;
+        }
+        f.write_str("</reginfo>\r\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        // rfc3680 section-6
+        let body = b"<?xml version=\"1.0\"?>
+            <reginfo xmlns=\"urn:ietf:params:xml:ns:reginfo\"
+                version=\"0\" state=\"full\">
+              <registration aor=\"sip:user@example.com\" id=\"as9\"
+                  state=\"active\">
+                <contact id=\"76\" state=\"active\" event=\"registered\"
+                    duration-registered=\"7322\" q=\"0.8\">
+                  <uri>sip:user@pc887.example.com</uri>
+                </contact>
+                <contact id=\"77\" state=\"terminated\" event=\"expired\"
+                    expires=\"0\">
+                  <uri>sip:user@university.edu</uri>
+                  <display-name>Joe</display-name>
+                </contact>
+              </registration>
+            </reginfo>";
+        let reginfo = RegInfo::parse(body).unwrap();
+        assert_eq!(reginfo.version, 0);
+        assert_eq!(reginfo.state, DocumentState::Full);
+        let registration = &reginfo.registrations[0];
+        assert_eq!(registration.aor, "sip:user@example.com");
+        assert_eq!(registration.state, RegistrationState::Active);
+        assert_eq!(registration.contacts.len(), 2);
+        let contact = &registration.contacts[0];
+        assert_eq!(contact.event, ContactEvent::Registered);
+        assert_eq!(contact.q.as_deref(), Some("0.8"));
+        assert_eq!(contact.uri, "sip:user@pc887.example.com");
+        let contact = &registration.contacts[1];
+        assert_eq!(contact.state, ContactState::Terminated);
+        assert_eq!(contact.event, ContactEvent::Expired);
+        assert_eq!(contact.expires, Some(0));
+        assert_eq!(contact.display_name.as_deref(), Some("Joe"));
+
+        let generated = reginfo.to_string();
+        assert_eq!(RegInfo::parse(generated.as_bytes()).unwrap(), reginfo);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert!(RegInfo::parse(b"<reginfo version=\"0\"/>").is_err());
+        assert!(RegInfo::parse(
+            b"<reginfo version=\"0\" state=\"full\">\
+              <registration aor=\"sip:a@b\" id=\"1\" state=\"pending\"/></reginfo>"
+        )
+        .is_err());
+        assert!(RegInfo::parse(
+            b"<reginfo version=\"0\" state=\"full\">\
+              <registration aor=\"sip:a@b\" id=\"1\" state=\"init\">\
+              <contact id=\"1\" state=\"active\" event=\"created\"/></registration></reginfo>"
+        )
+        .is_err());
+    }
+}
@@ -39,6 +39,10 @@ impl<'a> GenericParam<'a> {
             (input, param_val)
         } else if input[0] == b'[' {
             HostPort::take_ipv6_host(input)?
+        } else if param_name.eq_ignore_ascii_case("received") {
+            // via-received = "received" EQUAL (IPv4address / IPv6address)
+            // IPv6address is not enclosed in "[]" here. See rfc5118 section-4.5
+            take_while(|c| is_token_char(c) || c == b':')(input)?
         } else {
             take_while(is_token_char)(input)?
         };
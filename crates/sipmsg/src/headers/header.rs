@@ -1,14 +1,25 @@
 use crate::{
-    common::{bnfcore::*, errorparse::SipParseError, nom_wrappers::from_utf8_nom, take_sws_token},
+    common::{
+        bnfcore::*,
+        errorparse::{SipParseError, SipParseErrorKind},
+        folding::unfold_lws,
+        nom_wrappers::from_utf8_nom,
+        take_sws_token,
+    },
     headers::{
         parsers::ExtensionParser,
         traits::{HeaderValueParserFn, SipHeaderParser},
         GenericParams, SipRFCHeader, SipUri,
     },
+    options::{exceeds, limit_error, ParseLimits},
+};
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    string::String,
 };
-use alloc::collections::{BTreeMap, VecDeque};
 use core::str;
-use nom::{bytes::complete::take_while1, character::complete};
+use nom::{bytes::complete::take_while1, character::complete, sequence::tuple};
 use unicase::Ascii;
 
 // All possible types of value
@@ -139,6 +150,12 @@ impl<'a> HeaderValue<'a> {
         self.vtags.as_ref()
     }
 
+    /// Value where folded LWS is replaced by single SP.
+    /// Ex: `0009\r\n INVITE` -> `0009 INVITE`
+    pub fn normalized(&self) -> Cow<'a, str> {
+        unfold_lws(self.vstr)
+    }
+
     pub fn sip_uri(&self) -> Option<&SipUri<'a>> {
         self.sip_uri.as_ref()
     }
@@ -176,6 +193,15 @@ impl<'a> Header<'a> {
         self.parameters.as_ref()
     }
 
+    /// Value with parameters where folded LWS is replaced by single SP.
+    /// `raw_value_param` keeps original bytes.
+    pub fn normalized_value_param(&self) -> Cow<'a, str> {
+        match String::from_utf8_lossy(self.raw_value_param) {
+            Cow::Borrowed(s) => unfold_lws(s),
+            Cow::Owned(s) => Cow::Owned(unfold_lws(&s).into_owned()),
+        }
+    }
+
     pub fn find_parser(header_name: &'a str) -> (Option<SipRFCHeader>, HeaderValueParserFn) {
         match SipRFCHeader::from_str(&header_name) {
             Some(rfc_header) => (Some(rfc_header), rfc_header.get_parser()),
@@ -184,11 +210,16 @@ impl<'a> Header<'a> {
     }
 
     pub fn take_name(source_input: &'a [u8]) -> nom::IResult<&[u8], &'a str, SipParseError> {
-        let (input, header_name) = take_while1(is_token_char)(source_input)?;
-        let (input, _) = take_sws_token::colon(input)?;
+        let mut name_and_colon = tuple((take_while1(is_token_char), take_sws_token::colon));
+        let (input, (header_name, _)) = name_and_colon(source_input).map_err(|e| {
+            e.map(|e| {
+                e.with_kind(SipParseErrorKind::BadHeaderName)
+                    .or_input(source_input)
+            })
+        })?;
         match str::from_utf8(header_name) {
             Ok(hdr_str) => Ok((input, hdr_str)),
-            Err(_) => sip_parse_error!(1, "Bad header name"),
+            Err(_) => sip_parse_error!(1, "Bad header name", SipParseErrorKind::BadHeaderName),
         }
     }
 
@@ -234,13 +265,44 @@ impl<'a> Header<'a> {
     pub fn parse(
         input: &'a [u8],
     ) -> nom::IResult<&[u8], (Option<SipRFCHeader>, VecDeque<Header<'a>>), SipParseError> {
+        Header::parse_limited(input, &ParseLimits::default(), 0)
+    }
+
+    /// Parses header field and aborts as soon as number of its values exceeds
+    /// `max_values_per_header` or number of Via entries with `via_count` of previous
+    /// Via fields exceeds `max_via`
+    pub(crate) fn parse_limited(
+        source_input: &'a [u8],
+        limits: &ParseLimits,
+        via_count: usize,
+    ) -> nom::IResult<&'a [u8], (Option<SipRFCHeader>, VecDeque<Header<'a>>), SipParseError<'a>>
+    {
         let mut headers = VecDeque::new();
-        let (input, header_name) = Header::take_name(input)?;
+        let (input, header_name) = Header::take_name(source_input)?;
         let (rfc_type, value_parser) = Header::find_parser(header_name);
+        let value_error = |e: SipParseError<'a>| {
+            e.or_kind(SipParseErrorKind::BadHeaderValue {
+                header: header_name,
+            })
+            .or_header(header_name)
+            .or_input(input)
+        };
         let mut inp = input;
         loop {
-            let (input, (value, params)) = Header::take_value(inp, value_parser)?;
+            let (input, (value, params)) =
+                Header::take_value(inp, value_parser).map_err(|e| e.map(value_error))?;
             headers.push_back(Header::new(header_name, value, params, &inp[..inp.len() - input.len()]));
+            if exceeds(limits.max_values_per_header, headers.len()) {
+                let kind = SipParseErrorKind::TooManyValues {
+                    header: header_name,
+                };
+                return Err(limit_error(kind, source_input));
+            }
+            if rfc_type == Some(SipRFCHeader::Via)
+                && exceeds(limits.max_via, via_count + headers.len())
+            {
+                return Err(limit_error(SipParseErrorKind::TooManyVias, source_input));
+            }
             if input[0] == b',' {
                 let (input, _) = take_sws_token::comma(input)?;
                 inp = input;
@@ -1,18 +1,55 @@
 use crate::{
-    common::{bnfcore::is_crlf, errorparse::SipParseError},
-    headers::{SipHeader, SipRFCHeader},
+    common::{
+        bnfcore::{is_crlf, is_wsp},
+        errorparse::{SipParseError, SipParseErrorKind},
+    },
+    headers::{
+        parsers::digit_header::delta_seconds, SipDate, SipHeader, SipRFCHeader, SipRetryAfter,
+        SipTimestamp,
+    },
+    options::{exceeds, limit_error, ParseLimits, ParseOptions},
 };
-use alloc::collections::{
-    btree_map::{BTreeMap, Keys},
-    VecDeque,
+use alloc::{
+    collections::{
+        btree_map::{BTreeMap, Keys},
+        VecDeque,
+    },
+    vec::Vec,
 };
 use core::str;
 use nom::bytes::complete::tag;
 use unicase::Ascii;
 
+/// Header line that was skipped in lenient mode
+#[derive(Debug)]
+pub struct InvalidHeader<'a> {
+    /// Header name. `None` if name is malformed too
+    pub name: Option<&'a str>,
+    /// Whole header line without trailing CRLF
+    pub raw: &'a [u8],
+    /// Why header is invalid
+    pub error: SipParseError<'a>,
+}
+
 pub struct Headers<'a> {
     rfc_headers: BTreeMap<SipRFCHeader, VecDeque<SipHeader<'a>>>,
     ext_headers: Option<BTreeMap<Ascii<&'a str>, VecDeque<SipHeader<'a>>>>,
+    invalid_headers: Option<Vec<InvalidHeader<'a>>>,
+}
+
+fn value_error<'a>(
+    hdr: SipRFCHeader,
+    msg: &'static str,
+    value: &SipHeader<'a>,
+) -> nom::Err<SipParseError<'a>> {
+    nom::Err::Error(
+        SipParseError::new(1, Some(msg))
+            .with_kind(SipParseErrorKind::BadHeaderValue {
+                header: hdr.as_str(),
+            })
+            .or_header(hdr.as_str())
+            .or_input(value.raw_value_param),
+    )
 }
 
 impl<'a> Headers<'a> {
@@ -59,6 +96,95 @@ impl<'a> Headers<'a> {
         }
     }
 
+    /// UTC time of Date header. `None` if header is absent or present more than once
+    pub fn date(&self) -> Option<SipDate> {
+        let hdr = self.get_rfc_s(SipRFCHeader::Date)?;
+        SipDate::parse(hdr.value.vstr.as_bytes())
+            .ok()
+            .map(|(_, date)| date)
+    }
+
+    /// Expires header in seconds.
+    /// `Ok(None)` if header is absent, error if value overflows u32 or header is repeated
+    pub fn expires(&self) -> Result<Option<u32>, nom::Err<SipParseError<'a>>> {
+        self.delta_seconds(SipRFCHeader::Expires)
+    }
+
+    /// Min-Expires header in seconds. Errors as in `expires`
+    pub fn min_expires(&self) -> Result<Option<u32>, nom::Err<SipParseError<'a>>> {
+        self.delta_seconds(SipRFCHeader::MinExpires)
+    }
+
+    /// Max-Forwards header. Errors as in `expires`
+    pub fn max_forwards(&self) -> Result<Option<u32>, nom::Err<SipParseError<'a>>> {
+        self.delta_seconds(SipRFCHeader::MaxForwards)
+    }
+
+    /// Content-Length header. Errors as in `expires`, but value is limited by usize
+    pub fn content_length(&self) -> Result<Option<usize>, nom::Err<SipParseError<'a>>> {
+        match self.single_rfc(SipRFCHeader::ContentLength)? {
+            Some(hdr) => match hdr.value.vstr.parse::<usize>() {
+                Ok(length) => Ok(Some(length)),
+                Err(_) => Err(value_error(
+                    SipRFCHeader::ContentLength,
+                    "Invalid Content-Length",
+                    hdr,
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Timestamp header with time and optional delay. Errors as in `expires`
+    pub fn timestamp(&self) -> Result<Option<SipTimestamp>, nom::Err<SipParseError<'a>>> {
+        match self.single_rfc(SipRFCHeader::Timestamp)? {
+            Some(hdr) => match SipTimestamp::from_value(&hdr.value) {
+                Some(timestamp) => Ok(Some(timestamp)),
+                None => Err(value_error(
+                    SipRFCHeader::Timestamp,
+                    "Invalid Timestamp value",
+                    hdr,
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Retry-After header with `duration` parameter and comment. Errors as in `expires`
+    pub fn retry_after(&self) -> Result<Option<SipRetryAfter<'a>>, nom::Err<SipParseError<'a>>> {
+        match self.single_rfc(SipRFCHeader::RetryAfter)? {
+            Some(hdr) => match SipRetryAfter::from_header(hdr) {
+                Ok(retry_after) => Ok(Some(retry_after)),
+                Err(msg) => Err(value_error(SipRFCHeader::RetryAfter, msg, hdr)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn delta_seconds(&self, hdr: SipRFCHeader) -> Result<Option<u32>, nom::Err<SipParseError<'a>>> {
+        match self.single_rfc(hdr)? {
+            Some(value) => match delta_seconds(value.value.vstr.as_bytes()) {
+                Some(seconds) => Ok(Some(seconds)),
+                None => Err(value_error(hdr, "Invalid delta-seconds value", value)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Header that must be present at most once
+    fn single_rfc(
+        &self,
+        hdr: SipRFCHeader,
+    ) -> Result<Option<&SipHeader<'a>>, nom::Err<SipParseError<'a>>> {
+        match self.rfc_headers.get(&hdr) {
+            Some(hdrs) if hdrs.len() > 1 => {
+                Err(value_error(hdr, "Multiple header values", &hdrs[1]))
+            }
+            Some(hdrs) => Ok(hdrs.front()),
+            None => Ok(None),
+        }
+    }
+
     /// Returns length of unique headers
     // TODO rename to unique_len and add total_len
     pub fn len(&self) -> usize {
@@ -68,9 +194,10 @@ impl<'a> Headers<'a> {
         }
     }
 
-    fn new() -> Headers<'a> {
+    pub(crate) fn new() -> Headers<'a> {
         Headers {
             ext_headers: None,
+            invalid_headers: None,
             rfc_headers: BTreeMap::<SipRFCHeader, VecDeque<SipHeader<'a>>>::new(),
         }
     }
@@ -128,21 +255,141 @@ impl<'a> Headers<'a> {
         Some(self.ext_headers.as_ref().unwrap().keys())
     }
 
+    /// Malformed header lines that were skipped in lenient mode
+    pub fn invalid_headers(&self) -> &[InvalidHeader<'a>] {
+        match &self.invalid_headers {
+            Some(hdrs) => hdrs.as_slice(),
+            None => &[],
+        }
+    }
+
+    /// Calculates position of errors of invalid headers relatively to the start of `message`
+    pub(crate) fn locate_invalid_headers(&mut self, message: &[u8]) {
+        if let Some(hdrs) = self.invalid_headers.take() {
+            self.invalid_headers = Some(
+                hdrs.into_iter()
+                    .map(|hdr| InvalidHeader {
+                        error: hdr.error.locate(message),
+                        ..hdr
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    fn via_count(&self) -> usize {
+        match self.get_rfc(SipRFCHeader::Via) {
+            Some(vias) => vias.len(),
+            None => 0,
+        }
+    }
+
+    fn add_invalid_header(&mut self, header: InvalidHeader<'a>) {
+        self.invalid_headers
+            .get_or_insert_with(Vec::new)
+            .push(header);
+    }
+
+    /// Parse one header field with trailing CRLF
+    fn take_header(
+        &self,
+        input: &'a [u8],
+        limits: &ParseLimits,
+    ) -> nom::IResult<&'a [u8], (Option<SipRFCHeader>, VecDeque<SipHeader<'a>>), SipParseError<'a>>
+    {
+        let (input, (rfc_type, vec_headers)) =
+            SipHeader::parse_limited(input, limits, self.via_count())?;
+        let header_name = vec_headers[0].name.into_inner();
+        let (input, _) = tag::<_, _, SipParseError>("\r\n")(input).map_err(|e| {
+            e.map(|e| {
+                e.with_kind(SipParseErrorKind::BadHeaderValue {
+                    header: header_name,
+                })
+                .or_header(header_name)
+            })
+        })?;
+        Ok((input, (rfc_type, vec_headers)))
+    }
+
     pub fn parse(input: &'a [u8]) -> nom::IResult<&[u8], Headers<'a>, SipParseError> {
+        Headers::parse_ext(input, &ParseOptions::default())
+    }
+
+    pub fn parse_ext(
+        input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Headers<'a>, SipParseError<'a>> {
+        Headers::parse_fields(input, options, false)
+    }
+
+    /// Parses headers that may end with the end of input instead of empty line.
+    /// Used for message fragments. Ex: `message/sipfrag` body
+    pub(crate) fn parse_fragment(
+        input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Headers<'a>, SipParseError<'a>> {
+        Headers::parse_fields(input, options, true)
+    }
+
+    fn parse_fields(
+        input: &'a [u8],
+        options: &ParseOptions,
+        is_fragment: bool,
+    ) -> nom::IResult<&'a [u8], Headers<'a>, SipParseError<'a>> {
+        let limits = &options.limits;
         let mut headers_result = Headers::new();
+        let mut headers_count = 0;
         let mut inp2 = input;
         loop {
-            let (input, (rfc_type, vec_headers)) = SipHeader::parse(inp2)?;
-            match rfc_type {
-                Some(hdr_type) => {
-                    headers_result.add_rfc_header(hdr_type, vec_headers);
+            if inp2.is_empty() && is_fragment {
+                break;
+            }
+            if inp2.is_empty() {
+                return Err(nom::Err::Error(
+                    SipParseError::new(1, Some("There is no empty line after headers"))
+                        .with_kind(SipParseErrorKind::MissingHeadersEnd)
+                        .or_input(inp2),
+                ));
+            }
+            headers_count += 1;
+            if exceeds(limits.max_headers, headers_count) {
+                return Err(limit_error(SipParseErrorKind::TooManyHeaders, inp2));
+            }
+            if limits.max_header_line_len.is_some()
+                && exceeds(limits.max_header_line_len, take_header_line(inp2).1.len())
+            {
+                return Err(limit_error(SipParseErrorKind::HeaderTooLong, inp2));
+            }
+            // limits of values are checked while values are split
+            match headers_result.take_header(inp2, limits) {
+                Ok((input, (rfc_type, vec_headers))) => {
+                    match rfc_type {
+                        Some(hdr_type) => {
+                            headers_result.add_rfc_header(hdr_type, vec_headers);
+                        }
+                        None => {
+                            headers_result.add_extension_header(vec_headers);
+                        }
+                    }
+                    inp2 = input; // skip crlf of header field
                 }
-                None => {
-                    headers_result.add_extension_header(vec_headers);
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e))
+                    if options.is_lenient() && !e.kind.is_limit_exceeded() =>
+                {
+                    let (input, raw) = take_header_line(inp2);
+                    let name = match SipHeader::take_name(inp2) {
+                        Ok((_, name)) => Some(name),
+                        Err(_) => None,
+                    };
+                    headers_result.add_invalid_header(InvalidHeader {
+                        name,
+                        raw,
+                        error: e,
+                    });
+                    inp2 = input;
                 }
+                Err(e) => return Err(e),
             }
-            let (input, _) = tag("\r\n")(input)?; // move to header parse
-            inp2 = input; // skip crlf of header field
             if is_crlf(inp2) {
                 // end of headers and start of body part
                 break;
@@ -152,6 +399,19 @@ impl<'a> Headers<'a> {
     }
 }
 
+/// Returns header line including folded lines in second argument
+/// and input after CRLF of header line in first argument
+fn take_header_line(input: &[u8]) -> (&[u8], &[u8]) {
+    let mut idx = 0;
+    while idx < input.len() {
+        if is_crlf(&input[idx..]) && (idx + 2 >= input.len() || !is_wsp(input[idx + 2])) {
+            return (&input[idx + 2..], &input[..idx]);
+        }
+        idx += 1;
+    }
+    (&input[input.len()..], input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,4 +469,13 @@ mod tests {
             Err(_) => panic!(),
         }
     }
+
+    #[test]
+    fn take_header_line_test() {
+        assert_eq!(
+            take_header_line(b"Via: bad\r\n value\r\nTo: a\r\n"),
+            (&b"To: a\r\n"[..], &b"Via: bad\r\n value"[..])
+        );
+        assert_eq!(take_header_line(b"Via: bad"), (&b""[..], &b"Via: bad"[..]));
+    }
 }
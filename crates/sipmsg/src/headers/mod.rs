@@ -1,5 +1,6 @@
 mod headers;
 pub use headers::Headers as SipHeaders;
+pub use headers::InvalidHeader as SipInvalidHeader;
 
 mod header;
 pub use header::Header as SipHeader;
@@ -19,4 +20,5 @@ pub use sipuri::SipUri;
 
 mod name_addr;
 mod parsers;
+pub use parsers::{SipDate, SipRetryAfter, SipTimestamp};
 mod auth_params;
\ No newline at end of file
@@ -1,18 +1,22 @@
 use crate::{
-    common::{bnfcore::is_token_char, errorparse::SipParseError, take_sws_token, nom_wrappers},
+    common::{
+        bnfcore::{is_token_char, is_wsp},
+        errorparse::SipParseError,
+        nom_wrappers, take_sws_token,
+    },
     headers::{
         header::{HeaderTagType, HeaderTags},
+        sipuri::uri_scheme,
     },
 };
 
-use nom::{
-    bytes::complete::{take_while1},
-    character::complete,
-    sequence::tuple,
-};
+use nom::{bytes::complete::take_while1, sequence::tuple};
 
 use crate::SipUri;
 
+// name-addr      =  [ display-name ] LAQUOT addr-spec RAQUOT
+// addr-spec      =  SIP-URI / SIPS-URI / absoluteURI
+// display-name   =  *(token LWS)/ quoted-string
 #[derive(PartialEq, Debug)]
 pub enum NameAddrValueType {
     QuotedDisplayName,
@@ -30,28 +34,48 @@ fn predict_value_type(input: &[u8]) -> NameAddrValueType {
         return NameAddrValueType::AquoutedSipURI;
     }
 
-    if &input[..3] != b"sip" {
-        return NameAddrValueType::TokenDisplayName;
+    // token can't contain ':', so this is start of addr-spec without display name
+    if uri_scheme(input).is_some() {
+        return NameAddrValueType::SipURI;
     }
 
-    if input[3] == b':' || &input[3..5] == b"s:" {
-        return NameAddrValueType::SipURI; // this is start of URI, display name isn't present
+    NameAddrValueType::TokenDisplayName
+}
+
+fn is_sip_uri(input: &[u8]) -> bool {
+    match uri_scheme(input) {
+        Some(scheme) => scheme == b"sip" || scheme == b"sips",
+        None => false,
     }
+}
 
-    return NameAddrValueType::TokenDisplayName;
+/// Takes `*(token LWS)`. Returned display name doesn't include trailing LWS
+fn take_tokens_display_name(source_input: &[u8]) -> nom::IResult<&[u8], &[u8], SipParseError<'_>> {
+    let (mut input, _) = take_while1(is_token_char)(source_input)?;
+    let mut display_name_len = source_input.len() - input.len();
+    loop {
+        let (rest, wsps) = nom_wrappers::take_sws(input)?;
+        if rest.is_empty() || !is_token_char(rest[0]) {
+            return Ok((rest, &source_input[..display_name_len]));
+        }
+        if wsps.is_empty() {
+            return sip_parse_error!(1, "Tokens of display-name must be separated by LWS");
+        }
+        let (rest, _) = take_while1(is_token_char)(rest)?;
+        display_name_len = source_input.len() - rest.len();
+        input = rest;
+    }
 }
 
 fn take_display_name(
     source_input: &[u8],
     display_name_type: NameAddrValueType,
-) -> nom::IResult<&[u8], &[u8], SipParseError> {
+) -> nom::IResult<&[u8], &[u8], SipParseError<'_>> {
     if display_name_type == NameAddrValueType::QuotedDisplayName {
         let (input, (_, display_name, _)) = nom_wrappers::take_quoted_string(source_input)?;
         return Ok((input, display_name));
     } else if display_name_type == NameAddrValueType::TokenDisplayName {
-        let (input, display_name) = take_while1(is_token_char)(source_input)?;
-        let (input, _) = complete::space0(input)?;
-        return Ok((input, display_name));
+        return take_tokens_display_name(source_input);
     }
     sip_parse_error!(
         666,
@@ -59,17 +83,35 @@ fn take_display_name(
     )
 }
 
+/// absoluteURI. It can't contain whitespaces.
+/// If it is not enclosed in <> it also can't contain ',', ';' and '?'
+fn take_absolute_uri(
+    input: &[u8],
+    is_quoted_uri: bool,
+) -> nom::IResult<&[u8], &[u8], SipParseError<'_>> {
+    if uri_scheme(input).is_none() {
+        return sip_parse_error!(4, "Invalid scheme of absolute uri");
+    }
+    take_while1(|c| {
+        !is_wsp(c)
+            && c != b'\r'
+            && c != b'\n'
+            && c != b'>'
+            && (is_quoted_uri || (c != b',' && c != b';' && c != b'?'))
+    })(input)
+}
+
 pub fn take<'a>(
     source_input: &'a [u8],
 ) -> nom::IResult<&'a [u8], (&[u8], HeaderTags<'a>, Option<SipUri>), SipParseError<'a>> {
-    if source_input.len() < 5 {
-        return sip_parse_error!(2, "name-addr header value is too short");
+    if source_input.is_empty() {
+        return sip_parse_error!(2, "name-addr header value is empty");
     }
     let mut tags = HeaderTags::new();
     let next_value_type = predict_value_type(source_input);
-    let input = if next_value_type == NameAddrValueType::QuotedDisplayName
-        || next_value_type == NameAddrValueType::TokenDisplayName
-    {
+    let has_display_name = next_value_type == NameAddrValueType::QuotedDisplayName
+        || next_value_type == NameAddrValueType::TokenDisplayName;
+    let input = if has_display_name {
         let (input, display_name) = take_display_name(source_input, next_value_type)?;
         tags.insert(HeaderTagType::DisplayName, display_name);
         input
@@ -81,24 +123,21 @@ pub fn take<'a>(
         return sip_parse_error!(3, "Contact header value is invalid");
     }
 
-    let (input, is_quoted_uri) = if input[0] == b'<' {
+    let (input, is_quoted_uri) = if input[0] == b'<' || has_display_name {
         let (input, _) = take_sws_token::laquot(input)?;
         (input, true)
     } else {
         (input, false)
     };
 
-    if source_input.len() < 5 {
-        return sip_parse_error!(2, "Contact header value is too short");
-    }
-
-    let is_sip_uri = &input[..4] == b"sip:" || &input[..5] == b"sips:";
-    if !is_sip_uri && !is_quoted_uri {
-        return sip_parse_error!(4, "Absolute uri in contact header without <> not supported");
-    }
-
-    if is_sip_uri {
+    if is_sip_uri(input) {
         let (input, sipuri) = SipUri::parse_ext(input, is_quoted_uri)?;
+        if !is_quoted_uri && sipuri.headers().is_some() {
+            return sip_parse_error!(
+                4,
+                "URI with headers must be enclosed in <> in name-addr header"
+            );
+        }
         let mut count_wsps_after_raquout = 0;
         let input = if is_quoted_uri {
             let (input, wsps_after) = take_sws_token::raquot(input)?;
@@ -118,7 +157,20 @@ pub fn take<'a>(
     }
 
     // this is absolute uri
-    let uri_taker = take_while1(|c| c != b'>');
+    if !is_quoted_uri {
+        let (input, uri) = take_absolute_uri(input, false)?;
+        tags.insert(HeaderTagType::AbsoluteURI, uri);
+        return Ok((
+            input,
+            (
+                &source_input[..source_input.len() - input.len()],
+                tags,
+                None,
+            ),
+        ));
+    }
+
+    let uri_taker = |input| take_absolute_uri(input, true);
     let (input, (uri, spaces_after_raquot)) = tuple((uri_taker, take_sws_token::raquot))(input)?;
     tags.insert(HeaderTagType::AbsoluteURI, uri);
 
@@ -131,3 +183,50 @@ pub fn take<'a>(
         ),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_name_test() {
+        let (input, (vstr, tags, _)) =
+            take("token1~` token2'+_ token3*%!.- <sip:mundane@example.com>;tag=1".as_bytes())
+                .unwrap();
+        assert_eq!(
+            tags[&HeaderTagType::DisplayName],
+            b"token1~` token2'+_ token3*%!.-"
+        );
+        assert_eq!(
+            vstr,
+            b"token1~` token2'+_ token3*%!.- <sip:mundane@example.com>"
+        );
+        assert_eq!(input, b";tag=1");
+
+        let (_, (_, tags, uri)) = take("caller<sip:caller@example.com>".as_bytes()).unwrap();
+        assert_eq!(tags[&HeaderTagType::DisplayName], b"caller");
+        assert_eq!(uri.unwrap().hostport.host, "example.com");
+
+        assert!(take("Bell, Alexander <sip:a.g.bell@example.com>".as_bytes()).is_err());
+        assert!(take("\"Mr. J. User <sip:j.user@example.com>\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn addr_spec_test() {
+        let (input, (vstr, tags, uri)) = take("isbn:2983792873;tag=1\r\n".as_bytes()).unwrap();
+        assert_eq!(tags[&HeaderTagType::AbsoluteURI], b"isbn:2983792873");
+        assert_eq!(vstr, b"isbn:2983792873");
+        assert_eq!(uri, None);
+        assert_eq!(input, b";tag=1\r\n");
+
+        assert!(take("< sip:t.watson@example.org >".as_bytes()).is_err());
+        assert!(take("<sip:t.watson@example.org >".as_bytes()).is_err());
+        assert!(take("< http://www.example.com>".as_bytes()).is_err());
+        assert!(
+            take("sip:user@example.com?Route=%3Csip:sip.example.com%3E\r\n".as_bytes()).is_err()
+        );
+        assert!(
+            take("<sip:user@example.com?Route=%3Csip:sip.example.com%3E>\r\n".as_bytes()).is_ok()
+        );
+    }
+}
@@ -8,15 +8,36 @@ use crate::headers::{
     traits::SipHeaderParser,
 };
 
+use core::str;
 use nom::bytes::complete::take_while1;
 
 /// CSeq  =  "CSeq" HCOLON 1*DIGIT LWS Method
+/// The sequence number value MUST be expressible as a 32-bit unsigned integer
+/// and MUST be less than 2**31.
 pub struct CSeq;
 
+/// Maximum value of sequence number of CSeq
+pub const MAX_CSEQ_NUMBER: u32 = (1 << 31) - 1;
+
+impl CSeq {
+    fn is_valid_number(number: &[u8]) -> bool {
+        match str::from_utf8(number) {
+            Ok(number) => match number.parse::<u32>() {
+                Ok(number) => number <= MAX_CSEQ_NUMBER,
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
+}
+
 impl SipHeaderParser for CSeq {
     fn take_value(source_input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
         let mut tags = HeaderTags::new();
         let (input, number) = take_while1(is_digit)(source_input)?;
+        if !CSeq::is_valid_number(number) {
+            return sip_parse_error!(1, "CSeq number must be less than 2**31");
+        }
         let (input, _) = take_sws(input)?;
         let (input, method) = take_while1(is_token_char)(input)?;
         tags.insert(HeaderTagType::Number, number);
@@ -49,5 +70,9 @@ mod test {
             val.tags().unwrap()[&HeaderTagType::Method],
             "INVITE".as_bytes()
         );
+
+        assert!(CSeq::take_value("2147483647 INVITE\r\n".as_bytes()).is_ok());
+        assert!(CSeq::take_value("2147483648 INVITE\r\n".as_bytes()).is_err());
+        assert!(CSeq::take_value("36893488147419103232 REGISTER\r\n".as_bytes()).is_err());
     }
 }
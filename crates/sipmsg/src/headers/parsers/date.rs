@@ -3,6 +3,7 @@ use crate::headers::{
     header::{HeaderValue, HeaderValueType},
     traits::SipHeaderParser,
 };
+use core::fmt;
 
 use nom::{
     bytes::complete::{tag, take},
@@ -23,61 +24,77 @@ use nom::{
 //                  / "May" / "Jun" / "Jul" / "Aug"
 //                  / "Sep" / "Oct" / "Nov" / "Dec"
 
-/// Be careful. It is not a full validation of date.
+/// Value is validated by `SipDate::parse`
 pub struct Date;
 
-impl Date {
-    fn is_wkday(value_name: &[u8]) -> bool {
-        match value_name {
-            b"Mon" => return true,
-            b"Tue" => true,
-            b"Wed" => true,
-            b"Thu" => true,
-            b"Fri" => true,
-            b"Sat" => true,
-            b"Sun" => true,
-            _ => return false,
-        }
-    }
+const WKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const SECONDS_PER_DAY: u64 = 86400;
 
-    fn is_month(value_name: &[u8]) -> bool {
-        match value_name {
-            b"Jan" => return true,
-            b"Feb" => true,
-            b"Mar" => true,
-            b"Apr" => true,
-            b"May" => true,
-            b"Jun" => true,
-            b"Jul" => true,
-            b"Aug" => true,
-            b"Sep" => true,
-            b"Oct" => true,
-            b"Nov" => true,
-            b"Dec" => true,
-            _ => return false,
-        }
+// Date: Sat, 13 Nov 2010 23:29:00 GMT
+
+impl SipHeaderParser for Date {
+    fn take_value(source_input: &[u8]) -> nom::IResult<&[u8], HeaderValue<'_>, SipParseError<'_>> {
+        let (input, _) = SipDate::parse(source_input)?;
+        let (_, hdr_val) = HeaderValue::new(
+            &source_input[..source_input.len() - input.len()],
+            HeaderValueType::DateString,
+            None,
+            None,
+        )?;
+        Ok((input, hdr_val))
     }
 }
 
-// Date: Sat, 13 Nov 2010 23:29:00 GMT
+/// UTC time of SIP-date. Seconds since 1970-01-01 00:00:00 UTC.
+/// `Display` formats rfc1123-date for outgoing messages
+/// ```rust
+/// use sipmsg::SipDate;
+///
+/// let (_, date) = SipDate::parse(b"Sat, 13 Nov 2010 23:29:00 GMT").unwrap();
+/// assert_eq!(date.timestamp(), 1289690940);
+/// assert_eq!(
+///     SipDate::from_timestamp(1289690940).to_string(),
+///     "Sat, 13 Nov 2010 23:29:00 GMT"
+/// );
+/// assert!(SipDate::parse(b"Fri, 13 Nov 2010 23:29:00 GMT").is_err());
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SipDate(u64);
 
-impl SipHeaderParser for Date {
-    fn take_value(source_input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
-        let (input, wday) = take(3usize)(source_input)?;
-        if !Date::is_wkday(wday) {
-            return sip_parse_error!(1, "Invalid wday value in Date header");
-        }
+impl SipDate {
+    pub fn from_timestamp(timestamp: u64) -> SipDate {
+        SipDate(timestamp)
+    }
+
+    /// Seconds since 1970-01-01 00:00:00 UTC
+    pub fn timestamp(&self) -> u64 {
+        self.0
+    }
+
+    /// Parses rfc1123-date. Weekday must match the date, day must exist in month
+    /// (leap years are considered), time must be in range 00:00:00 - 23:59:59.
+    /// Dates before 1970 are rejected
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], SipDate, SipParseError<'_>> {
+        let (input, wkday) = take(3usize)(input)?;
+        let wkday = match WKDAYS.iter().position(|name| name.as_bytes() == wkday) {
+            Some(wkday) => wkday as u64,
+            None => return sip_parse_error!(1, "Invalid wday value in Date header"),
+        };
         let (input, _) = tag(", ")(input)?;
         let (input, day) = digit1(input)?;
-        if day.len() > 2 {
+        if day.len() != 2 {
             return sip_parse_error!(1, "Invalid day value in Date header");
         }
         let (input, _) = char(' ')(input)?;
 
         let (input, month) = take(3usize)(input)?;
-        if !Date::is_month(month) {
-            return sip_parse_error!(2, "Invalid month value in Date header");
-        }
+        let month = match MONTHS.iter().position(|name| name.as_bytes() == month) {
+            Some(month) => month as u64 + 1,
+            None => return sip_parse_error!(2, "Invalid month value in Date header"),
+        };
         let (input, _) = char(' ')(input)?;
         let (input, year) = digit1(input)?;
         if year.len() != 4 {
@@ -85,24 +102,107 @@ impl SipHeaderParser for Date {
             return sip_parse_error!(3, "Invalid year value in Date header");
         }
         let (input, _) = char(' ')(input)?;
-        let (input, (_hours, _, _mins, _, _seconds)) =
+        let (input, (hours, _, minutes, _, seconds)) =
             tuple((digit1, char(':'), digit1, char(':'), digit1))(input)?;
+        if hours.len() != 2 || minutes.len() != 2 || seconds.len() != 2 {
+            return sip_parse_error!(4, "Invalid time value in Date header");
+        }
 
         let (input, _) = char(' ')(input)?;
         let (input, _) = tag("GMT")(input)?;
-        let (_, hdr_val) = HeaderValue::new(
-            &source_input[..source_input.len() - input.len()],
-            HeaderValueType::DateString,
-            None,
-            None,
-        )?;
-        Ok((input, hdr_val))
+
+        let (day, year) = (to_number(day), to_number(year));
+        if year < 1970 {
+            return sip_parse_error!(3, "Date before 1970 is not supported");
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return sip_parse_error!(1, "Invalid day value in Date header");
+        }
+        let (hours, minutes, seconds) = (to_number(hours), to_number(minutes), to_number(seconds));
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return sip_parse_error!(4, "Invalid time value in Date header");
+        }
+        let days = days_from_civil(year, month, day);
+        if (days + 4) % 7 != wkday {
+            return sip_parse_error!(1, "Weekday does not match date in Date header");
+        }
+        Ok((
+            input,
+            SipDate(days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds),
+        ))
     }
 }
 
+/// rfc1123-date. Ex: `Sat, 13 Nov 2010 23:29:00 GMT`
+impl fmt::Display for SipDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.0 / SECONDS_PER_DAY;
+        let seconds = self.0 % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            WKDAYS[((days + 4) % 7) as usize],
+            day,
+            MONTHS[(month - 1) as usize],
+            year,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+/// Digits are checked by parser
+fn to_number(digits: &[u8]) -> u64 {
+    digits
+        .iter()
+        .fold(0, |number, digit| number * 10 + (digit - b'0') as u64)
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01. Years are counted from March, so leap day is the last day of year.
+/// [chrono-Compatible Low-Level Date Algorithms](http://howardhinnant.github.io/date_algorithms.html)
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Year, month (1-12) and day (1-31) of days since 1970-01-01
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::ToString;
 
     #[test]
     fn test_date_value() {
@@ -110,5 +210,62 @@ mod test {
             Date::take_value("Sat, 13 Nov 2010 23:29:00 GMT \r\n".as_bytes()).unwrap();
         assert_eq!(val.vstr, "Sat, 13 Nov 2010 23:29:00 GMT");
         assert_eq!(input, b" \r\n");
+
+        assert!(Date::take_value("Sat, 13 Nov 2010 24:00:00 GMT\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn sip_date_test() {
+        let parse = |date: &str| {
+            SipDate::parse(date.as_bytes())
+                .ok()
+                .map(|(_, date)| date.timestamp())
+        };
+        assert_eq!(parse("Thu, 01 Jan 1970 00:00:00 GMT").unwrap(), 0);
+        assert_eq!(parse("Thu, 21 Feb 2002 13:02:03 GMT").unwrap(), 1014296523);
+        assert_eq!(parse("Tue, 29 Feb 2000 23:59:59 GMT").unwrap(), 951868799);
+        assert_eq!(parse("Thu, 29 Feb 2024 12:00:00 GMT").unwrap(), 1709208000);
+        assert_eq!(parse("Wed, 02 Jun 1982 00:00:00 GMT").unwrap(), 391824000);
+
+        // date1 has exactly two digits of day
+        assert!(parse("Wed, 2 Jun 1982 00:00:00 GMT").is_none());
+        assert!(parse("Wed, 002 Jun 1982 00:00:00 GMT").is_none());
+        // not leap years
+        assert!(parse("Thu, 29 Feb 2001 00:00:00 GMT").is_none());
+        assert!(parse("Thu, 29 Feb 2100 00:00:00 GMT").is_none());
+        assert!(parse("Sat, 31 Apr 2010 00:00:00 GMT").is_none());
+        assert!(parse("Sat, 00 Nov 2010 23:29:00 GMT").is_none());
+        assert!(parse("Sat, 13 Nov 2010 23:60:00 GMT").is_none());
+        assert!(parse("Sat, 13 Nov 2010 23:29:60 GMT").is_none());
+        assert!(parse("Sat, 13 Nov 2010 3:29:00 GMT").is_none());
+        assert!(parse("Mon, 13 Nov 2010 23:29:00 GMT").is_none());
+        assert!(parse("Mon, 01 Jan 1968 00:00:00 GMT").is_none());
+        assert!(parse("Sat, 13 Nov 2010 23:29:00 UTC").is_none());
+    }
+
+    #[test]
+    fn sip_date_format_test() {
+        for timestamp in &[
+            0,
+            951868799,
+            951868800,
+            1289690940,
+            4102444800,
+            253402300799,
+        ] {
+            let date = SipDate::from_timestamp(*timestamp).to_string();
+            assert_eq!(
+                SipDate::parse(date.as_bytes()).unwrap().1.timestamp(),
+                *timestamp
+            );
+        }
+        assert_eq!(
+            SipDate::from_timestamp(951868800).to_string(),
+            "Wed, 01 Mar 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            SipDate::from_timestamp(253402300799).to_string(),
+            "Fri, 31 Dec 9999 23:59:59 GMT"
+        );
     }
 }
@@ -2,6 +2,7 @@ use crate::{
     common::{bnfcore::is_digit, errorparse::SipParseError},
     headers::header::{HeaderValue, HeaderValueType},
 };
+use core::str;
 use nom::bytes::complete::take_while1;
 
 pub fn take(input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
@@ -9,3 +10,26 @@ pub fn take(input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
     let (_, hdr_val) = HeaderValue::new(res_val, HeaderValueType::Digit, None, None)?;
     Ok((inp, hdr_val))
 }
+
+/// delta-seconds = 1*DIGIT. `None` if value is not a number or overflows u32
+pub(crate) fn delta_seconds(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || !digits.iter().all(|digit| is_digit(*digit)) {
+        return None;
+    }
+    str::from_utf8(digits).ok()?.parse::<u32>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_seconds_test() {
+        assert_eq!(delta_seconds(b"0"), Some(0));
+        assert_eq!(delta_seconds(b"3600"), Some(3600));
+        assert_eq!(delta_seconds(b"4294967295"), Some(u32::MAX));
+        assert_eq!(delta_seconds(b"4294967296"), None);
+        assert_eq!(delta_seconds(b"+10"), None);
+        assert_eq!(delta_seconds(b""), None);
+    }
+}
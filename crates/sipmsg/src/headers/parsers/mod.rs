@@ -19,17 +19,17 @@ pub use contact::Contact;
 mod cseq;
 pub use cseq::CSeq;
 mod date;
-pub use date::Date;
+pub use date::{Date, SipDate};
 mod from;
 pub use from::From;
 mod retry_after;
-pub use retry_after::RetryAfter;
+pub use retry_after::{RetryAfter, SipRetryAfter};
 mod user_agent;
 pub use user_agent::UserAgent;
 pub mod mime_version;
 pub use mime_version::MimeVersion;
 pub mod timestamp;
-pub use timestamp::Timestamp;
+pub use timestamp::{SipTimestamp, Timestamp};
 pub mod via;
 pub use via::Via;
 pub mod warning;
@@ -3,14 +3,63 @@ use crate::{
         bnfcore::is_digit, errorparse::SipParseError, nom_wrappers::take_sws, take_sws_token,
     },
     headers::{
-        header::{HeaderTagType, HeaderTags, HeaderValue, HeaderValueType},
+        header::{Header, HeaderTagType, HeaderTags, HeaderValue, HeaderValueType},
+        parsers::digit_header::delta_seconds,
         traits::SipHeaderParser,
     },
 };
+use core::str;
 use nom::bytes::complete::{take_until, take_while1};
 
 pub struct RetryAfter;
 
+/// Typed value of Retry-After header
+/// ```rust
+/// use sipmsg::*;
+///
+/// let (_, hdrs) =
+///     SipHeaders::parse(b"Retry-After: 18000 (in a meeting) ;duration=3600\r\n\r\n").unwrap();
+/// let retry_after = hdrs.retry_after().unwrap().unwrap();
+/// assert_eq!(retry_after.seconds, 18000);
+/// assert_eq!(retry_after.duration, Some(3600));
+/// assert_eq!(retry_after.comment, Some("in a meeting"));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SipRetryAfter<'a> {
+    /// delta-seconds
+    pub seconds: u32,
+    /// `duration` parameter in seconds
+    pub duration: Option<u32>,
+    pub comment: Option<&'a str>,
+}
+
+impl<'a> SipRetryAfter<'a> {
+    /// Error if seconds or duration is not delta-seconds in range of u32
+    pub(crate) fn from_header(hdr: &Header<'a>) -> Result<SipRetryAfter<'a>, &'static str> {
+        let tags = hdr.value.tags().ok_or("Invalid Retry-After value")?;
+        let seconds = tags
+            .get(&HeaderTagType::Seconds)
+            .and_then(|seconds| delta_seconds(seconds))
+            .ok_or("Invalid Retry-After seconds")?;
+        let duration = match hdr.params().and_then(|params| params.get("duration")) {
+            Some(duration) => Some(
+                duration
+                    .and_then(|duration| delta_seconds(duration.as_bytes()))
+                    .ok_or("Invalid Retry-After duration")?,
+            ),
+            None => None,
+        };
+        let comment = tags
+            .get(&HeaderTagType::Comment)
+            .and_then(|comment| str::from_utf8(comment).ok());
+        Ok(SipRetryAfter {
+            seconds,
+            duration,
+            comment,
+        })
+    }
+}
+
 impl SipHeaderParser for RetryAfter {
     fn take_value(source_input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
         let (input, seconds) = take_while1(is_digit)(source_input)?;
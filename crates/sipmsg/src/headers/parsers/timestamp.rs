@@ -9,10 +9,47 @@ use crate::{
         traits::SipHeaderParser,
     },
 };
+use core::str;
 use nom::bytes::complete::take_while1;
 
 pub struct Timestamp;
 
+/// Typed value of Timestamp header
+/// ```rust
+/// use sipmsg::*;
+///
+/// let (_, hdrs) = SipHeaders::parse(b"Timestamp: 54.25 0.5\r\n\r\n").unwrap();
+/// let timestamp = hdrs.timestamp().unwrap().unwrap();
+/// assert_eq!(timestamp.time, 54.25);
+/// assert_eq!(timestamp.delay, Some(0.5));
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SipTimestamp {
+    pub time: f64,
+    pub delay: Option<f64>,
+}
+
+impl SipTimestamp {
+    /// `None` if value is not a Timestamp or number does not fit in f64
+    pub(crate) fn from_value(value: &HeaderValue) -> Option<SipTimestamp> {
+        let tags = value.tags()?;
+        let time = to_f64(tags.get(&HeaderTagType::TimveVal)?)?;
+        let delay = match tags.get(&HeaderTagType::Delay) {
+            Some(delay) => Some(to_f64(delay)?),
+            None => None,
+        };
+        Some(SipTimestamp { time, delay })
+    }
+}
+
+fn to_f64(digits: &[u8]) -> Option<f64> {
+    str::from_utf8(digits)
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+}
+
 impl SipHeaderParser for Timestamp {
     fn take_value(source_input: &[u8]) -> nom::IResult<&[u8], HeaderValue, SipParseError> {
         let (input, _int_part_time) = take_while1(is_digit)(source_input)?;
@@ -59,6 +96,7 @@ impl SipHeaderParser for Timestamp {
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::format;
 
     #[test]
     fn test_timestamp_value() {
@@ -74,5 +112,16 @@ mod test {
         assert_eq!(val.vstr, "12.34 0.5");
         assert_eq!(val.tags().unwrap()[&HeaderTagType::TimveVal], b"12.34");
         assert_eq!(val.tags().unwrap()[&HeaderTagType::Delay], b"0.5");
+        assert_eq!(
+            SipTimestamp::from_value(&val),
+            Some(SipTimestamp {
+                time: 12.34,
+                delay: Some(0.5)
+            })
+        );
+
+        let huge = format!("{}\r\n", "9".repeat(400));
+        let (_, val) = Timestamp::take_value(huge.as_bytes()).unwrap();
+        assert_eq!(SipTimestamp::from_value(&val), None);
     }
 }
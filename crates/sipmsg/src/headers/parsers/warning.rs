@@ -25,7 +25,9 @@ impl SipHeaderParser for Warning {
             return sip_parse_error!(1, "Invalid warning code");
         }
         let (input, _) = space1(input)?;
-        let (input, warn_agent) = take_while1(is_token_char)(input)?;
+        // hostport or pseudonym
+        let (input, warn_agent) =
+            take_while1(|c| is_token_char(c) || c == b':' || c == b'[' || c == b']')(input)?;
         let (input, _) = space1(input)?;
         let (input, (_, warn_text, _)) = take_quoted_string(input)?;
 
@@ -76,5 +78,12 @@ mod test {
             val.tags().unwrap()[&HeaderTagType::WarnText],
             "Session parameter 'foo' not understood".as_bytes()
         );
+
+        let (_, val) =
+            Warning::take_value("399 [2001:db8::1]:5060 \"Overloaded\"\r\n".as_bytes()).unwrap();
+        assert_eq!(
+            val.tags().unwrap()[&HeaderTagType::WarnAgent],
+            b"[2001:db8::1]:5060"
+        );
     }
 }
@@ -126,7 +126,7 @@ impl SipRFCHeader {
         None
     }
 
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             &SipRFCHeader::Accept => "Accept",
             &SipRFCHeader::AcceptEncoding => "Accept-Encoding",
@@ -1,5 +1,6 @@
 use crate::{
-    common::bnfcore::is_unreserved, common::hostport::HostPort,
+    common::bnfcore::{is_alpha, is_alphanum, is_unreserved},
+    common::hostport::HostPort,
     common::nom_wrappers::from_utf8_nom, common::nom_wrappers::take_while_with_escaped,
     errorparse::SipParseError, headers::GenericParams, userinfo::UserInfo,
 };
@@ -24,6 +25,22 @@ impl RequestUriScheme {
     }
 }
 
+/// Returns scheme if input starts with `scheme ":"`
+/// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+pub fn uri_scheme(input: &[u8]) -> Option<&[u8]> {
+    if input.is_empty() || !is_alpha(input[0]) {
+        return None;
+    }
+    let scheme_len = input
+        .iter()
+        .take_while(|c| is_alphanum(**c) || **c == b'+' || **c == b'-' || **c == b'.')
+        .count();
+    if input.len() > scheme_len && input[scheme_len] == b':' {
+        return Some(&input[..scheme_len]);
+    }
+    None
+}
+
 /// hnv-unreserved  =  "[" / "]" / "/" / "?" / ":" / "+" / "$"
 #[inline]
 fn is_hnv_unreserved_char(c: u8) -> bool {
@@ -169,12 +186,13 @@ impl<'a> SipUri<'a> {
         let (input_after_scheme, _) = take(1usize)(input)?; // skip ':'
         let scheme = RequestUriScheme::from_bytes(uri_scheme)?;
 
-        let (right_with_ampersat, before_ampersat) =
-            take_till(|c| c == b'@' || c == b'\n' || c == b',')(input_after_scheme)?;
-        let is_user_info_present = right_with_ampersat.is_empty()
-            || right_with_ampersat[0] == b'\n'
-            || right_with_ampersat[0] == b',';
-        // If right_with_apersat reach '\n' is empty there is no user info
+        // user and password may contain ',' (rfc4475 section-3.1.1.2) when
+        // uri is enclosed in "<>", otherwise ',' separates header values
+        let (right_with_ampersat, before_ampersat) = take_till(|c| {
+            c == b'@' || c == b'\n' || c == b'>' || (!parse_with_parameters && c == b',')
+        })(input_after_scheme)?;
+        let is_user_info_present = right_with_ampersat.is_empty() || right_with_ampersat[0] != b'@';
+        // If right_with_apersat doesn't start with '@' there is no user info
         let userinfo = if is_user_info_present {
             None
         } else {
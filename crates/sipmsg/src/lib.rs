@@ -26,8 +26,8 @@
 //! CSeq: 986759 INVITE\r\n\r\nbody_stuff"
 //! .as_bytes();
 //!
-//! // First parameter not realized yet.
-//! // It should consist be residue if Content-Length is less then actual body length.
+//! // First parameter is residue after message-body if Content-Length
+//! // is less then actual body length.
 //! let (_, sip_msg) = SipMessage::parse(invite_msg_buf).unwrap();
 //! let request = sip_msg.request().unwrap();
 //! assert_eq!(request.rl.method, SipMethod::INVITE);
@@ -115,6 +115,8 @@ extern crate nom;
 #[macro_use]
 pub mod common;
 pub use common::errorparse;
+pub use common::errorparse::SipParseError;
+pub use common::errorparse::SipParseErrorKind;
 pub use common::sip_method::SipMethod;
 
 mod message;
@@ -123,6 +125,11 @@ pub use message::MessageType as SipMessageType;
 pub use message::SipVersion;
 pub use message::SipMessage;
 
+mod options;
+pub use options::ParseLimits;
+pub use options::ParseMode;
+pub use options::ParseOptions;
+
 mod userinfo;
 
 mod request;
@@ -134,10 +141,27 @@ pub use response::Response as SipResponse;
 pub use response::StatusCode as SipResponseStatusCode;
 pub use response::StatusLine as SipResponseStatusLine;
 
+mod sipfrag;
+pub use sipfrag::SipFrag;
+pub use sipfrag::StartLine as SipFragStartLine;
+
 mod headers;
 pub use headers::sipuri::RequestUriScheme as SipRequestUriScheme;
 pub use headers::*;
 
 mod serializer;
 
+pub mod content_coding;
+
+pub mod negotiation;
+
+pub mod warning;
+
+pub mod multipart;
+
+pub mod sdp;
+
+#[cfg(feature = "std")]
+pub mod events;
+
 pub use unicase::Ascii as SipAscii;
@@ -1,12 +1,154 @@
-use crate::common::errorparse::SipParseError;
-use crate::{SipRequest, SipResponse};
-use nom;
+use crate::common::{
+    bnfcore::is_token_char,
+    errorparse::{SipParseError, SipParseErrorKind},
+};
+use crate::multipart::{is_multipart, unsupported_media_type, Multipart};
+use crate::options::{exceeds, limit_error};
+use crate::sdp::SessionDescription;
+use crate::{ParseOptions, SipFrag, SipHeaders, SipRFCHeader, SipRequest, SipResponse};
+use core::str;
+use nom::{self, bytes::complete::tag};
 
 /// SIP-Version
 /// ex. `SIP/2.0 -> SipVersion(2, 0)`
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct SipVersion(pub u8, pub u8);
 
+impl SipVersion {
+    /// Takes digits of major and minor versions
+    pub fn parse<'a>(
+        major: &'a [u8],
+        minor: &'a [u8],
+    ) -> nom::IResult<&'a [u8], SipVersion, SipParseError<'a>> {
+        let to_u8 = |digits: &'a [u8]| match str::from_utf8(digits) {
+            Ok(digits_str) => digits_str.parse::<u8>().ok(),
+            Err(_) => None,
+        };
+        match (to_u8(major), to_u8(minor)) {
+            (Some(major_ver), Some(minor_ver)) => Ok((minor, SipVersion(major_ver, minor_ver))),
+            _ => Err(nom::Err::Error(
+                SipParseError::new(1, Some("Invalid SIP-Version"))
+                    .with_kind(SipParseErrorKind::BadVersion)
+                    .or_input(major),
+            )),
+        }
+    }
+}
+
+/// Messages with version other than `SIP/2.0` are rejected with `UnsupportedVersion`.
+/// `version_input` is SIP-Version of start line
+pub(crate) fn check_version<'a>(
+    version: SipVersion,
+    version_input: &'a [u8],
+) -> Result<(), nom::Err<SipParseError<'a>>> {
+    if version == SipVersion(2, 0) {
+        return Ok(());
+    }
+    Err(nom::Err::Error(
+        SipParseError::new(1, Some("Only SIP/2.0 is supported"))
+            .with_kind(SipParseErrorKind::UnsupportedVersion)
+            .or_input(version_input),
+    ))
+}
+
+/// Parses message-body as SDP. Content-Type must be `application/sdp` or `multipart/*`
+/// with `application/sdp` part, otherwise `UnsupportedMediaType` error is returned.
+/// Location of `BadSdp` error is relative to the start of message-body
+pub(crate) fn body_as_sdp<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<SessionDescription<'a>, nom::Err<SipParseError<'a>>> {
+    let body = body.unwrap_or(&[]);
+    let sdp = match headers.get_rfc_s(SipRFCHeader::ContentType) {
+        Some(hdr) if hdr.value.vstr.eq_ignore_ascii_case("application/sdp") => body,
+        Some(hdr) if is_multipart(hdr.value.vstr) => {
+            match body_multipart(headers, Some(body))?.find_body("application/sdp") {
+                Ok(Some(sdp)) => sdp,
+                Ok(None) => return Err(unsupported_media_type()),
+                Err(e) => return Err(e.map(|e| e.locate(body))),
+            }
+        }
+        _ => return Err(unsupported_media_type()),
+    };
+    match SessionDescription::parse(sdp) {
+        Ok((_, sdp)) => Ok(sdp),
+        Err(e) => Err(e.map(|e| e.locate(body))),
+    }
+}
+
+/// Parses multipart message-body. Content-Type must be `multipart/*`,
+/// otherwise `UnsupportedMediaType` error is returned.
+/// Location of errors is relative to the start of message-body
+pub(crate) fn body_multipart<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<Multipart<'a>, nom::Err<SipParseError<'a>>> {
+    let body = body.unwrap_or(&[]);
+    Multipart::from_headers(headers, body).map_err(|e| e.map(|e| e.locate(body)))
+}
+
+/// Parses message-body as fragment of SIP message. Content-Type must be `message/sipfrag`,
+/// otherwise `UnsupportedMediaType` error is returned.
+/// Location of errors is relative to the start of message-body
+pub(crate) fn body_as_sipfrag<'a>(
+    headers: &SipHeaders<'a>,
+    body: Option<&'a [u8]>,
+) -> Result<SipFrag<'a>, nom::Err<SipParseError<'a>>> {
+    match headers.get_rfc_s(SipRFCHeader::ContentType) {
+        Some(hdr) if hdr.value.vstr.eq_ignore_ascii_case("message/sipfrag") => {}
+        _ => return Err(unsupported_media_type()),
+    }
+    let (_, frag) = SipFrag::parse(body.unwrap_or(&[]))?;
+    Ok(frag)
+}
+
+/// Marks errors of Request-Line or Status-Line
+pub(crate) fn start_line_error<'a, O>(
+    result: nom::IResult<&'a [u8], O, SipParseError<'a>>,
+) -> nom::IResult<&'a [u8], O, SipParseError<'a>> {
+    result.map_err(|e| e.map(|e| e.or_kind(SipParseErrorKind::BadStartLine)))
+}
+
+/// Takes empty line that separates headers and message-body
+pub(crate) fn take_headers_end(input: &[u8]) -> nom::IResult<&[u8], &[u8], SipParseError<'_>> {
+    tag::<_, _, SipParseError>("\r\n")(input)
+        .map_err(|e| e.map(|e| e.with_kind(SipParseErrorKind::MissingHeadersEnd)))
+}
+
+/// Takes message-body according to Content-Length header.
+/// If Content-Length is absent the rest of input is message-body.
+/// Returns bytes after message-body in first argument.
+pub(crate) fn take_body<'a>(
+    input: &'a [u8],
+    headers: &SipHeaders<'a>,
+    options: &ParseOptions,
+) -> nom::IResult<&'a [u8], &'a [u8], SipParseError<'a>> {
+    let max_body_len = options.limits.max_body_len;
+    let length = match headers.content_length()? {
+        Some(length) => length,
+        None => {
+            if exceeds(max_body_len, input.len()) {
+                return Err(limit_error(SipParseErrorKind::BodyTooLarge, input));
+            }
+            return Ok((&input[input.len()..], input));
+        }
+    };
+    if exceeds(max_body_len, length) {
+        let content_length = headers
+            .get_rfc_s(SipRFCHeader::ContentLength)
+            .map_or(input, |hdr| hdr.raw_value_param);
+        return Err(limit_error(SipParseErrorKind::BodyTooLarge, content_length));
+    }
+    if length > input.len() {
+        return Err(nom::Err::Error(
+            SipParseError::new(1, Some("Body is shorter than Content-Length"))
+                .with_kind(SipParseErrorKind::BodyTooShort)
+                .or_input(input),
+        ));
+    }
+    Ok((&input[length..], &input[..length]))
+}
+
 pub enum SipMessage<'a> {
     Request(SipRequest<'a>),
     Response(SipResponse<'a>),
@@ -30,16 +172,32 @@ impl<'a> SipMessage<'a> {
     }
 
     pub fn parse(raw_message: &'a [u8]) -> nom::IResult<&[u8], SipMessage<'a>, SipParseError> {
+        SipMessage::parse_ext(raw_message, &ParseOptions::default())
+    }
+
+    pub fn parse_ext(
+        raw_message: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], SipMessage<'a>, SipParseError<'a>> {
         match get_message_type(raw_message) {
             MessageType::Request => {
-                let (inp, request) = SipRequest::parse(raw_message)?;
+                let (inp, request) = SipRequest::parse_ext(raw_message, options)?;
                 return Ok((inp, SipMessage::Request(request)));
             }
             MessageType::Response => {
-                let (inp, response) = SipResponse::parse(raw_message)?;
+                let (inp, response) = SipResponse::parse_ext(raw_message, options)?;
                 return Ok((inp, SipMessage::Response(response)));
             }
-            MessageType::Unknown => sip_parse_error!(1, "Message is invalid. Can't predict type of message"),
+            MessageType::Unknown if is_extension_request(raw_message) => {
+                let (inp, request) = SipRequest::parse_ext(raw_message, options)?;
+                Ok((inp, SipMessage::Request(request)))
+            }
+            MessageType::Unknown => Err(nom::Err::Error(
+                SipParseError::new(1, Some("Message is invalid. Can't predict type of message"))
+                    .with_kind(SipParseErrorKind::UnknownMessageType)
+                    .or_input(raw_message)
+                    .locate(raw_message),
+            )),
         }
     }
 }
@@ -68,8 +226,16 @@ const REF: &'static [u8] = &['R' as u8, 'E' as u8, 'F' as u8]; // REFER
 const SUB: &'static [u8] = &['S' as u8, 'U' as u8, 'B' as u8]; // SUBSCRIBE
 const UPD: &'static [u8] = &['U' as u8, 'P' as u8, 'D' as u8]; // UPDATE
 
+/// Returns true if message starts with extension-method followed by SP
+fn is_extension_request(mt: &[u8]) -> bool {
+    let method_len = mt.iter().take_while(|c| is_token_char(**c)).count();
+    method_len > 0 && mt.len() > method_len && mt[method_len] == b' '
+}
+
 /// Fast determinates message type and minimal validate for further transmission to suitable parser.
 /// Does not validate full first line, just first 3 bytes.
+/// Requests with extension-method are reported as `Unknown`,
+/// but `SipMessage::parse` parses them anyway.
 /// ```rust
 /// assert_eq!(
 ///     sipmsg::get_sip_message_type(
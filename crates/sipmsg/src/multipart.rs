@@ -0,0 +1,444 @@
+//! Multipart message-body
+//! [rfc2046 section-5.1](https://tools.ietf.org/html/rfc2046#section-5.1)
+//!
+//! Parts are not copied. Headers and bodies of parts are borrowed from the message-body.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::multipart::MultipartBuilder;
+//! use sipmsg::SipMessage;
+//!
+//! let builder = MultipartBuilder::new("mixed", "unique-boundary-1")
+//!     .part("application/sdp", b"v=0\r\n")
+//!     .part("application/isup", b"\x01\x00\x49")
+//!     .header("Content-Disposition", "signal;handling=optional");
+//! let content_type = builder.content_type();
+//! let body = builder.build();
+//!
+//! let mut message = format!(
+//!     "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+//!      Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKkjshdyff\r\n\
+//!      CSeq: 1 INVITE\r\n\
+//!      Content-Type: {}\r\n\
+//!      Content-Length: {}\r\n\r\n",
+//!     content_type,
+//!     body.len()
+//! )
+//! .into_bytes();
+//! message.extend_from_slice(&body);
+//!
+//! let (_, message) = SipMessage::parse(&message).unwrap();
+//! let multipart = message.request().unwrap().body_multipart().unwrap();
+//! assert_eq!(multipart.subtype, "mixed");
+//! assert_eq!(multipart.parts.len(), 2);
+//! assert_eq!(multipart.parts[0].body, b"v=0\r\n");
+//!
+//! let isup = multipart.part("application/isup").unwrap();
+//! assert_eq!(isup.disposition(), Some("signal"));
+//! assert_eq!(isup.body, b"\x01\x00\x49");
+//! ```
+
+use crate::{
+    common::{
+        bnfcore::{is_crlf, is_token_char},
+        errorparse::{SipParseError, SipParseErrorKind},
+    },
+    SipHeaders, SipRFCHeader,
+};
+use alloc::{format, string::String, vec::Vec};
+
+/// Part of multipart body
+pub struct BodyPart<'a> {
+    /// Headers of part. `None` if part has no headers, then it is `text/plain`
+    pub headers: Option<SipHeaders<'a>>,
+    pub body: &'a [u8],
+}
+
+impl<'a> BodyPart<'a> {
+    /// Media type of Content-Type without parameters. Ex: `application/sdp`
+    pub fn content_type(&self) -> Option<&'a str> {
+        let headers = self.headers.as_ref()?;
+        Some(headers.get_rfc_s(SipRFCHeader::ContentType)?.value.vstr)
+    }
+
+    /// Value of Content-ID header. Ex: `<alice@atlanta.example.com>`
+    pub fn content_id(&self) -> Option<&'a str> {
+        let headers = self.headers.as_ref()?;
+        Some(headers.get_ext_s("Content-ID")?.value.vstr)
+    }
+
+    /// Disposition type of Content-Disposition without parameters. Ex: `render`
+    pub fn disposition(&self) -> Option<&'a str> {
+        let headers = self.headers.as_ref()?;
+        Some(
+            headers
+                .get_rfc_s(SipRFCHeader::ContentDisposition)?
+                .value
+                .vstr,
+        )
+    }
+
+    /// Parses body of nested multipart
+    pub fn multipart(&self) -> Result<Multipart<'a>, nom::Err<SipParseError<'a>>> {
+        match &self.headers {
+            Some(headers) => Multipart::from_headers(headers, self.body),
+            None => Err(unsupported_media_type()),
+        }
+    }
+
+    fn is_multipart(&self) -> bool {
+        self.content_type().is_some_and(is_multipart)
+    }
+
+    fn parse(input: &'a [u8]) -> Result<BodyPart<'a>, nom::Err<SipParseError<'a>>> {
+        if input.is_empty() || is_crlf(input) {
+            return Ok(BodyPart {
+                headers: None,
+                body: &input[input.len().min(2)..],
+            });
+        }
+        let (rest, headers) = SipHeaders::parse(input)?;
+        Ok(BodyPart {
+            headers: Some(headers),
+            body: &rest[2..],
+        })
+    }
+}
+
+/// Multipart body with parsed parts
+pub struct Multipart<'a> {
+    /// Subtype of Content-Type. Ex: `mixed`, `related`
+    pub subtype: &'a str,
+    pub boundary: &'a str,
+    pub parts: Vec<BodyPart<'a>>,
+}
+
+impl<'a> Multipart<'a> {
+    /// Parses `body` using media type and boundary of Content-Type header.
+    /// Returns `UnsupportedMediaType` error if Content-Type is not `multipart/*`
+    pub fn from_headers(
+        headers: &SipHeaders<'a>,
+        body: &'a [u8],
+    ) -> Result<Multipart<'a>, nom::Err<SipParseError<'a>>> {
+        let content_type = match headers.get_rfc_s(SipRFCHeader::ContentType) {
+            Some(hdr) if is_multipart(hdr.value.vstr) => hdr,
+            _ => return Err(unsupported_media_type()),
+        };
+        let boundary = match content_type
+            .params()
+            .and_then(|params| params.get("boundary"))
+        {
+            Some(Some(boundary)) if !boundary.is_empty() => *boundary,
+            _ => {
+                return Err(multipart_error(
+                    "Content-Type has no boundary",
+                    content_type.raw_value_param,
+                ))
+            }
+        };
+        let subtype = &content_type.value.vstr["multipart/".len()..];
+        Multipart::parse(body, subtype, boundary)
+    }
+
+    /// Parses `body` with parts separated by `boundary`.
+    /// Preamble and epilogue are ignored
+    pub fn parse(
+        body: &'a [u8],
+        subtype: &'a str,
+        boundary: &'a str,
+    ) -> Result<Multipart<'a>, nom::Err<SipParseError<'a>>> {
+        let boundary_bytes = boundary.as_bytes();
+        let mut pos = match find_delimiter(body, boundary_bytes, 0) {
+            Some(pos) => pos,
+            None => return Err(multipart_error("Boundary is not found", body)),
+        };
+        let mut parts = Vec::new();
+        loop {
+            let after = pos + 2 + boundary_bytes.len();
+            if body[after..].starts_with(b"--") {
+                if parts.is_empty() {
+                    return Err(multipart_error("Multipart has no parts", &body[pos..]));
+                }
+                break;
+            }
+            let padding = body[after..]
+                .iter()
+                .take_while(|c| **c == b' ' || **c == b'\t')
+                .count();
+            if !is_crlf(&body[after + padding..]) {
+                return Err(multipart_error(
+                    "Boundary must be followed by CRLF",
+                    &body[pos..],
+                ));
+            }
+            let start = after + padding + 2;
+            let next = match find_delimiter(body, boundary_bytes, start) {
+                Some(next) => next,
+                None => {
+                    return Err(multipart_error(
+                        "Close delimiter is not found",
+                        &body[pos..],
+                    ))
+                }
+            };
+            // CRLF before the next boundary belongs to the delimiter
+            let end = if next >= start + 2 { next - 2 } else { start };
+            parts.push(BodyPart::parse(&body[start..end])?);
+            pos = next;
+        }
+        Ok(Multipart {
+            subtype,
+            boundary,
+            parts,
+        })
+    }
+
+    /// First part with media type `content_type`
+    pub fn part(&self, content_type: &str) -> Option<&BodyPart<'a>> {
+        self.parts.iter().find(|part| {
+            part.content_type()
+                .is_some_and(|value| value.eq_ignore_ascii_case(content_type))
+        })
+    }
+
+    /// Part with Content-ID. Angle brackets are optional in `content_id`
+    pub fn part_by_id(&self, content_id: &str) -> Option<&BodyPart<'a>> {
+        let content_id = trim_angle_brackets(content_id);
+        self.parts.iter().find(|part| {
+            part.content_id()
+                .is_some_and(|value| trim_angle_brackets(value) == content_id)
+        })
+    }
+
+    /// Body of the first part with media type `content_type`.
+    /// Nested multiparts are searched too
+    pub fn find_body(
+        &self,
+        content_type: &str,
+    ) -> Result<Option<&'a [u8]>, nom::Err<SipParseError<'a>>> {
+        for part in &self.parts {
+            if part.is_multipart() {
+                if let Some(body) = part.multipart()?.find_body(content_type)? {
+                    return Ok(Some(body));
+                }
+            } else if part
+                .content_type()
+                .is_some_and(|value| value.eq_ignore_ascii_case(content_type))
+            {
+                return Ok(Some(part.body));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Headers and body of part that is added to `MultipartBuilder`
+struct PartContent<'a> {
+    headers: Vec<(&'a str, &'a str)>,
+    body: &'a [u8],
+}
+
+/// Builds multipart body
+pub struct MultipartBuilder<'a> {
+    subtype: &'a str,
+    boundary: &'a str,
+    parts: Vec<PartContent<'a>>,
+}
+
+impl<'a> MultipartBuilder<'a> {
+    /// `boundary` must not occur in bodies of parts
+    pub fn new(subtype: &'a str, boundary: &'a str) -> MultipartBuilder<'a> {
+        MultipartBuilder {
+            subtype,
+            boundary,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Adds part with Content-Type. `body` may be other multipart body
+    pub fn part(mut self, content_type: &'a str, body: &'a [u8]) -> MultipartBuilder<'a> {
+        self.parts.push(PartContent {
+            headers: alloc::vec![("Content-Type", content_type)],
+            body,
+        });
+        self
+    }
+
+    /// Adds header to the last part. Ex: Content-ID, Content-Disposition
+    pub fn header(mut self, name: &'a str, value: &'a str) -> MultipartBuilder<'a> {
+        if let Some(part) = self.parts.last_mut() {
+            part.headers.push((name, value));
+        }
+        self
+    }
+
+    /// Value of Content-Type header of message. Ex: `multipart/mixed;boundary=xyz`
+    pub fn content_type(&self) -> String {
+        if self.boundary.bytes().all(is_token_char) {
+            format!("multipart/{};boundary={}", self.subtype, self.boundary)
+        } else {
+            format!("multipart/{};boundary=\"{}\"", self.subtype, self.boundary)
+        }
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for part in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            for (name, value) in &part.headers {
+                body.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(part.body);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        body
+    }
+}
+
+/// Media type is `multipart/*`
+pub(crate) fn is_multipart(content_type: &str) -> bool {
+    content_type.len() > "multipart/".len()
+        && content_type[.."multipart/".len()].eq_ignore_ascii_case("multipart/")
+}
+
+fn trim_angle_brackets(value: &str) -> &str {
+    value.trim_start_matches('<').trim_end_matches('>')
+}
+
+/// Position of line that starts with `--boundary` beginning from line at `from`
+fn find_delimiter(body: &[u8], boundary: &[u8], from: usize) -> Option<usize> {
+    let mut pos = from;
+    loop {
+        let line = &body[pos..];
+        if line.starts_with(b"--") && line[2..].starts_with(boundary) {
+            match line.get(2 + boundary.len()) {
+                None | Some(b'-') | Some(b' ') | Some(b'\t') | Some(b'\r') => return Some(pos),
+                _ => {}
+            }
+        }
+        pos += line.windows(2).position(|crlf| crlf == b"\r\n")? + 2;
+    }
+}
+
+fn multipart_error<'a>(message: &'a str, input: &'a [u8]) -> nom::Err<SipParseError<'a>> {
+    nom::Err::Error(
+        SipParseError::new(1, Some(message))
+            .with_kind(SipParseErrorKind::BadMultipart)
+            .or_input(input),
+    )
+}
+
+pub(crate) fn unsupported_media_type<'a>() -> nom::Err<SipParseError<'a>> {
+    nom::Err::Error(
+        SipParseError::new(1, Some("Content-Type is not supported"))
+            .with_kind(SipParseErrorKind::UnsupportedMediaType),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let body = b"preamble\r\n\
+                     --boundary1\r\n\
+                     Content-Type: application/sdp\r\n\
+                     \r\n\
+                     v=0\r\n\
+                     \r\n\
+                     --boundary1 \r\n\
+                     \r\n\
+                     plain text\r\n\
+                     --boundary1\r\n\
+                     Content-Type: application/resource-lists+xml\r\n\
+                     Content-ID: <list@example.com>\r\n\
+                     Content-Disposition: recipient-list\r\n\
+                     \r\n\
+                     <resource-lists/>\r\n\
+                     --boundary1--\r\n\
+                     epilogue";
+        let multipart = Multipart::parse(body, "mixed", "boundary1").unwrap();
+        assert_eq!(multipart.parts.len(), 3);
+        assert_eq!(multipart.parts[0].content_type(), Some("application/sdp"));
+        assert_eq!(multipart.parts[0].body, b"v=0\r\n");
+        assert!(multipart.parts[1].headers.is_none());
+        assert_eq!(multipart.parts[1].body, b"plain text");
+        let list = multipart.part_by_id("list@example.com").unwrap();
+        assert_eq!(list.content_id(), Some("<list@example.com>"));
+        assert_eq!(list.disposition(), Some("recipient-list"));
+        assert_eq!(list.body, b"<resource-lists/>");
+        assert!(multipart.part("APPLICATION/SDP").is_some());
+        assert!(multipart.part("text/plain").is_none());
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        let err_kind = |body: &'static [u8]| match Multipart::parse(body, "mixed", "b1") {
+            Err(nom::Err::Error(e)) => e.kind,
+            _ => panic!("Multipart should not be parsed"),
+        };
+        assert_eq!(err_kind(b"no boundary"), SipParseErrorKind::BadMultipart);
+        assert_eq!(
+            err_kind(b"--b1\r\n\r\nbody\r\n"),
+            SipParseErrorKind::BadMultipart
+        );
+        assert_eq!(
+            err_kind(b"--b12\r\n\r\nbody\r\n--b1--"),
+            SipParseErrorKind::BadMultipart
+        );
+        assert_eq!(
+            err_kind(b"--b1 x\r\n\r\nbody\r\n--b1--"),
+            SipParseErrorKind::BadMultipart
+        );
+    }
+
+    #[test]
+    fn nested_test() {
+        let inner = MultipartBuilder::new("related", "inner")
+            .part("application/pidf+xml", b"<presence/>")
+            .header("Content-ID", "<pidf@example.com>");
+        let inner_type = inner.content_type();
+        let inner = inner.build();
+        let outer = MultipartBuilder::new("mixed", "outer:1")
+            .part("text/plain", b"hello")
+            .part(&inner_type, &inner);
+        assert_eq!(outer.content_type(), "multipart/mixed;boundary=\"outer:1\"");
+        let outer = outer.build();
+        assert_eq!(
+            outer,
+            b"--outer:1\r\n\
+              Content-Type: text/plain\r\n\
+              \r\n\
+              hello\r\n\
+              --outer:1\r\n\
+              Content-Type: multipart/related;boundary=inner\r\n\
+              \r\n\
+              --inner\r\n\
+              Content-Type: application/pidf+xml\r\n\
+              Content-ID: <pidf@example.com>\r\n\
+              \r\n\
+              <presence/>\r\n\
+              --inner--\r\n\
+              \r\n\
+              --outer:1--\r\n"
+                .to_vec()
+        );
+
+        let multipart = Multipart::parse(&outer, "mixed", "outer:1").unwrap();
+        let related = multipart.parts[1].multipart().unwrap();
+        assert_eq!(related.subtype, "related");
+        assert_eq!(related.boundary, "inner");
+        assert_eq!(
+            related.part_by_id("<pidf@example.com>").unwrap().body,
+            b"<presence/>"
+        );
+        assert_eq!(
+            multipart.find_body("application/pidf+xml").unwrap(),
+            Some(&b"<presence/>"[..])
+        );
+        assert!(multipart.parts[0].multipart().is_err());
+    }
+}
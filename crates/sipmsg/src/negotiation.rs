@@ -0,0 +1,381 @@
+//! Content negotiation by Accept and Accept-Language headers.
+//! [rfc3261 section-20.1](https://tools.ietf.org/html/rfc3261#section-20.1),
+//! [rfc3261 section-20.3](https://tools.ietf.org/html/rfc3261#section-20.3)
+//!
+//! If negotiation fails `Rejection` has status code of response (406 or 415)
+//! and header that lists what can be used instead.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::negotiation::{check_content_type, select_media_type};
+//! use sipmsg::{SipRequest, SipResponseStatusCode};
+//!
+//! let options = "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+//! CSeq: 63104 OPTIONS\r\n\
+//! Accept: application/*;q=0.5, application/pidf+xml\r\n\r\n"
+//!     .as_bytes();
+//! let (_, request) = SipRequest::parse(options).unwrap();
+//! assert_eq!(
+//!     select_media_type(&request.headers, &["application/sdp", "application/pidf+xml"]),
+//!     Ok("application/pidf+xml")
+//! );
+//!
+//! let rejection = select_media_type(&request.headers, &["text/plain"]).unwrap_err();
+//! assert_eq!(rejection.status_code, SipResponseStatusCode::NotAcceptableResourceContent);
+//! assert_eq!(rejection.to_string(), "Accept: text/plain");
+//!
+//! let rejection = check_content_type(&request.headers, &["application/sdp"]);
+//! assert!(rejection.is_ok()); // request has no body
+//! ```
+
+use crate::{
+    common::bnfcore::is_digit, SipHeader, SipHeaders, SipRFCHeader, SipResponseStatusCode,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt;
+
+/// Negotiation is failed. Response with `status_code` and `header` should be sent
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rejection {
+    /// `NotAcceptableResourceContent` (406) or `UnsupportedMediaType` (415)
+    pub status_code: SipResponseStatusCode,
+    /// `Accept` or `Accept-Language`
+    pub header: SipRFCHeader,
+    /// Value of header. Ex: `application/sdp, text/plain`
+    pub value: String,
+}
+
+impl Rejection {
+    fn new(status_code: SipResponseStatusCode, header: SipRFCHeader, values: &[&str]) -> Rejection {
+        Rejection {
+            status_code,
+            header,
+            value: values.join(", "),
+        }
+    }
+}
+
+/// Header line without CRLF. Ex: `Accept: application/sdp`
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.header.as_str(), self.value)
+    }
+}
+
+/// One entry of Accept or Accept-Language. `q` is qvalue multiplied by 1000
+struct Range<'a> {
+    value: &'a str,
+    q: u16,
+}
+
+/// Values of header with valid qvalues. Empty values are skipped
+fn ranges<'a>(headers: &SipHeaders<'a>, header: SipRFCHeader) -> Option<Vec<Range<'a>>> {
+    let hdrs = headers.get_rfc(header)?;
+    Some(
+        hdrs.iter()
+            .filter(|hdr| !hdr.value.vstr.is_empty())
+            .filter_map(|hdr| {
+                Some(Range {
+                    value: hdr.value.vstr,
+                    q: header_qvalue(hdr)?,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Media type without parameters. Ex: `application/sdp;level=1` -> `application/sdp`
+fn essence(media_type: &str) -> &str {
+    media_type.split(';').next().unwrap_or("").trim()
+}
+
+/// Accept header values of message.
+/// If header is absent only `application/sdp` is acceptable, as RFC 3261 requires.
+/// Empty header means that no body is acceptable
+pub struct Accept<'a> {
+    ranges: Vec<Range<'a>>,
+}
+
+impl<'a> Accept<'a> {
+    pub fn from_headers(headers: &SipHeaders<'a>) -> Accept<'a> {
+        let ranges = ranges(headers, SipRFCHeader::Accept).unwrap_or_else(|| {
+            vec![Range {
+                value: "application/sdp",
+                q: 1000,
+            }]
+        });
+        Accept { ranges }
+    }
+
+    /// qvalue of media type multiplied by 1000. 0 means that media type is not acceptable.
+    /// The most specific range is used: `type/subtype`, then `type/*`, then `*/*`
+    pub fn qvalue(&self, media_type: &str) -> u16 {
+        let media_type = essence(media_type);
+        let m_type = media_type.split('/').next().unwrap_or("");
+        let specificity = |range: &str| {
+            if range.eq_ignore_ascii_case(media_type) {
+                Some(3)
+            } else if range == "*/*" {
+                Some(1)
+            } else {
+                match range.strip_suffix("/*") {
+                    Some(r_type) if r_type.eq_ignore_ascii_case(m_type) => Some(2),
+                    _ => None,
+                }
+            }
+        };
+        self.ranges
+            .iter()
+            .filter_map(|range| Some((specificity(range.value)?, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0, |(_, q)| q)
+    }
+
+    /// Acceptable media type of `available` with the highest qvalue.
+    /// The first one of `available` wins if qvalues are equal
+    pub fn preferred<'m>(&self, available: &[&'m str]) -> Option<&'m str> {
+        preferred(available, |media_type| self.qvalue(media_type))
+    }
+}
+
+/// Accept-Language header values of message.
+/// If header is absent or empty any language is acceptable
+pub struct AcceptLanguage<'a> {
+    ranges: Vec<Range<'a>>,
+}
+
+impl<'a> AcceptLanguage<'a> {
+    pub fn from_headers(headers: &SipHeaders<'a>) -> AcceptLanguage<'a> {
+        AcceptLanguage {
+            ranges: ranges(headers, SipRFCHeader::AcceptLanguage).unwrap_or_default(),
+        }
+    }
+
+    /// qvalue of language tag multiplied by 1000. 0 means that language is not acceptable.
+    /// Range matches tag if it is equal to tag or to prefix of tag followed by `-`.
+    /// The longest matching range is used. Ex: `en` matches `en-gb`
+    pub fn qvalue(&self, language: &str) -> u16 {
+        if self.ranges.is_empty() {
+            return 1000;
+        }
+        let matches = |range: &str| {
+            range == "*"
+                || range.eq_ignore_ascii_case(language)
+                || (language.as_bytes().get(range.len()) == Some(&b'-')
+                    && language
+                        .get(..range.len())
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(range)))
+        };
+        self.ranges
+            .iter()
+            .filter(|range| matches(range.value))
+            .max_by_key(|range| {
+                if range.value == "*" {
+                    0
+                } else {
+                    range.value.len()
+                }
+            })
+            .map_or(0, |range| range.q)
+    }
+
+    /// Acceptable language of `available` with the highest qvalue.
+    /// The first one of `available` wins if qvalues are equal
+    pub fn preferred<'l>(&self, available: &[&'l str]) -> Option<&'l str> {
+        preferred(available, |language| self.qvalue(language))
+    }
+}
+
+fn preferred<'v>(available: &[&'v str], qvalue: impl Fn(&str) -> u16) -> Option<&'v str> {
+    let mut preferred: Option<(&'v str, u16)> = None;
+    for value in available {
+        let q = qvalue(value);
+        if q > 0 && preferred.map_or(true, |(_, best_q)| q > best_q) {
+            preferred = Some((value, q));
+        }
+    }
+    preferred.map(|(value, _)| value)
+}
+
+/// Selects media type of response body from types that can be produced.
+/// Returns 406 rejection with `Accept` of `available` types if request accepts none of them
+pub fn select_media_type<'m>(
+    request_headers: &SipHeaders<'_>,
+    available: &[&'m str],
+) -> Result<&'m str, Rejection> {
+    Accept::from_headers(request_headers)
+        .preferred(available)
+        .ok_or_else(|| {
+            Rejection::new(
+                SipResponseStatusCode::NotAcceptableResourceContent,
+                SipRFCHeader::Accept,
+                available,
+            )
+        })
+}
+
+/// Selects language of response body from languages that can be produced.
+/// Returns 406 rejection with `Accept-Language` of `available` languages
+/// if request accepts none of them
+pub fn select_language<'l>(
+    request_headers: &SipHeaders<'_>,
+    available: &[&'l str],
+) -> Result<&'l str, Rejection> {
+    AcceptLanguage::from_headers(request_headers)
+        .preferred(available)
+        .ok_or_else(|| {
+            Rejection::new(
+                SipResponseStatusCode::NotAcceptableResourceContent,
+                SipRFCHeader::AcceptLanguage,
+                available,
+            )
+        })
+}
+
+/// Checks that Content-Type of request is one of `supported` media types.
+/// Parameters of Content-Type are ignored. Request without Content-Type is accepted.
+/// Returns 415 rejection with `Accept` of `supported` types otherwise
+pub fn check_content_type(
+    request_headers: &SipHeaders<'_>,
+    supported: &[&str],
+) -> Result<(), Rejection> {
+    let content_type = match request_headers.get_rfc_s(SipRFCHeader::ContentType) {
+        Some(hdr) => essence(hdr.value.vstr),
+        None => return Ok(()),
+    };
+    if supported
+        .iter()
+        .any(|media_type| essence(media_type).eq_ignore_ascii_case(content_type))
+    {
+        return Ok(());
+    }
+    Err(Rejection::new(
+        SipResponseStatusCode::UnsupportedMediaType,
+        SipRFCHeader::Accept,
+        supported,
+    ))
+}
+
+/// qvalue of `q` parameter multiplied by 1000. 1000 if parameter is absent.
+/// `None` if qvalue is malformed
+pub(crate) fn header_qvalue(hdr: &SipHeader<'_>) -> Option<u16> {
+    match hdr.params().and_then(|params| params.get("q")) {
+        Some(Some(q)) => parse_qvalue(q),
+        Some(None) => None,
+        None => Some(1000),
+    }
+}
+
+/// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
+fn parse_qvalue(qvalue: &str) -> Option<u16> {
+    let bytes = qvalue.as_bytes();
+    let (int, frac) = match bytes.iter().position(|c| *c == b'.') {
+        Some(pos) => (&bytes[..pos], &bytes[pos + 1..]),
+        None => (bytes, &b""[..]),
+    };
+    if frac.len() > 3 || !frac.iter().all(|c| is_digit(*c)) {
+        return None;
+    }
+    let mut q = 0;
+    for i in 0..3 {
+        q = q * 10 + frac.get(i).map_or(0, |c| (c - b'0') as u16);
+    }
+    match int {
+        b"0" => Some(q),
+        b"1" if q == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn headers(input: &str) -> SipHeaders<'_> {
+        SipHeaders::parse(input.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn qvalue_test() {
+        assert_eq!(parse_qvalue("1"), Some(1000));
+        assert_eq!(parse_qvalue("1.000"), Some(1000));
+        assert_eq!(parse_qvalue("0.5"), Some(500));
+        assert_eq!(parse_qvalue("0.125"), Some(125));
+        assert_eq!(parse_qvalue("0"), Some(0));
+        assert_eq!(parse_qvalue("1.5"), None);
+        assert_eq!(parse_qvalue("0.1234"), None);
+        assert_eq!(parse_qvalue("2"), None);
+        assert_eq!(parse_qvalue(""), None);
+    }
+
+    #[test]
+    fn accept_test() {
+        let accept = Accept::from_headers(&headers("Call-ID: 1\r\n\r\n"));
+        assert_eq!(accept.qvalue("application/sdp"), 1000);
+        assert_eq!(accept.qvalue("text/plain"), 0);
+
+        let hdrs =
+            headers("Accept: text/*;q=0.3, text/html;q=0.7, */*;q=0.1, text/plain;q=0\r\n\r\n");
+        let accept = Accept::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("TEXT/HTML"), 700);
+        assert_eq!(accept.qvalue("text/xml"), 300);
+        assert_eq!(accept.qvalue("text/plain;charset=utf-8"), 0);
+        assert_eq!(accept.qvalue("application/sdp"), 100);
+        assert_eq!(
+            accept.preferred(&["application/sdp", "text/xml", "text/html"]),
+            Some("text/html")
+        );
+        assert_eq!(accept.preferred(&["text/plain"]), None);
+
+        let hdrs = headers("Accept: application/sdp, application/x-private\r\n\r\n");
+        let accept = Accept::from_headers(&hdrs);
+        assert_eq!(
+            accept.preferred(&["application/x-private", "application/sdp"]),
+            Some("application/x-private")
+        );
+    }
+
+    #[test]
+    fn accept_language_test() {
+        let accept = AcceptLanguage::from_headers(&headers("Call-ID: 1\r\n\r\n"));
+        assert_eq!(accept.preferred(&["fr", "en"]), Some("fr"));
+
+        let hdrs = headers("Accept-Language: da, en-gb;q=0.8, en;q=0.7\r\n\r\n");
+        let accept = AcceptLanguage::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("da"), 1000);
+        assert_eq!(accept.qvalue("en-GB"), 800);
+        assert_eq!(accept.qvalue("en-us"), 700);
+        assert_eq!(accept.qvalue("eng"), 0);
+        assert_eq!(accept.preferred(&["en-us", "en-gb", "fr"]), Some("en-gb"));
+
+        let hdrs = headers("Accept-Language: *;q=0.5, fr;q=0\r\n\r\n");
+        let accept = AcceptLanguage::from_headers(&hdrs);
+        assert_eq!(accept.qvalue("de"), 500);
+        assert_eq!(accept.qvalue("fr-ca"), 0);
+    }
+
+    #[test]
+    fn rejection_test() {
+        let hdrs = headers("Accept: application/sdp\r\nContent-Type: text/html\r\n\r\n");
+        let rejection = select_media_type(&hdrs, &["text/plain", "text/html"]).unwrap_err();
+        assert_eq!(
+            rejection.status_code,
+            SipResponseStatusCode::NotAcceptableResourceContent
+        );
+        assert_eq!(rejection.to_string(), "Accept: text/plain, text/html");
+
+        let rejection = check_content_type(&hdrs, &["application/sdp"]).unwrap_err();
+        assert_eq!(
+            rejection.status_code,
+            SipResponseStatusCode::UnsupportedMediaType
+        );
+        assert_eq!(rejection.to_string(), "Accept: application/sdp");
+        assert!(check_content_type(&hdrs, &["Text/HTML;charset=utf-8"]).is_ok());
+
+        let hdrs = headers("Accept-Language: fr\r\n\r\n");
+        let rejection = select_language(&hdrs, &["en", "de"]).unwrap_err();
+        assert_eq!(rejection.header, SipRFCHeader::AcceptLanguage);
+        assert_eq!(rejection.to_string(), "Accept-Language: en, de");
+    }
+}
@@ -0,0 +1,100 @@
+use crate::common::errorparse::{SipParseError, SipParseErrorKind};
+
+/// How parser reacts on malformed header
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ParseMode {
+    /// Any malformed header fails parsing of whole message
+    Strict,
+    /// Malformed header lines are saved as `InvalidHeader` and parsing continues.
+    /// It allows to answer 400 because Via, Call-ID and CSeq are not lost.
+    Lenient,
+}
+
+/// Limits for parsing of untrusted input. `None` means unlimited.
+/// Parsing is aborted as soon as any limit is exceeded, in lenient mode too.
+/// Use `SipParseError::status_code` to get status code of response (413/414/513).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ParseLimits {
+    /// Maximum number of header fields (lines) in message
+    pub max_headers: Option<usize>,
+    /// Maximum length of header field including folded lines, without trailing CRLF
+    pub max_header_line_len: Option<usize>,
+    /// Maximum number of Via entries in all Via headers
+    pub max_via: Option<usize>,
+    /// Maximum number of comma-separated values in one header field
+    pub max_values_per_header: Option<usize>,
+    /// Maximum length of Request-URI
+    pub max_uri_len: Option<usize>,
+    /// Maximum length of message-body
+    pub max_body_len: Option<usize>,
+}
+
+/// Options of message parsing. Default is strict mode without limits
+/// ```rust
+/// use sipmsg::{ParseLimits, ParseMode, ParseOptions};
+/// assert_eq!(ParseOptions::default().mode, ParseMode::Strict);
+/// assert_eq!(ParseOptions::lenient().mode, ParseMode::Lenient);
+///
+/// let options = ParseOptions::strict().with_limits(ParseLimits {
+///     max_headers: Some(64),
+///     max_uri_len: Some(256),
+///     ..Default::default()
+/// });
+/// assert_eq!(options.limits.max_headers, Some(64));
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    pub limits: ParseLimits,
+}
+
+impl ParseOptions {
+    pub fn strict() -> ParseOptions {
+        ParseOptions {
+            mode: ParseMode::Strict,
+            limits: ParseLimits::default(),
+        }
+    }
+
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {
+            mode: ParseMode::Lenient,
+            limits: ParseLimits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: ParseLimits) -> ParseOptions {
+        self.limits = limits;
+        self
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.mode == ParseMode::Lenient
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions::strict()
+    }
+}
+
+/// Returns true if `value` is greater than `limit`
+pub(crate) fn exceeds(limit: Option<usize>, value: usize) -> bool {
+    match limit {
+        Some(limit) => value > limit,
+        None => false,
+    }
+}
+
+/// Error that aborts parsing because of exceeded limit
+pub(crate) fn limit_error<'a>(
+    kind: SipParseErrorKind<'a>,
+    input: &'a [u8],
+) -> nom::Err<SipParseError<'a>> {
+    nom::Err::Failure(
+        SipParseError::new(1, Some("Parse limit is exceeded"))
+            .with_kind(kind)
+            .or_input(input),
+    )
+}
@@ -1,12 +1,28 @@
-use crate::common::{errorparse::SipParseError, sip_method::*};
-use crate::{headers::*, message::*};
+use crate::common::{
+    bnfcore::is_token_char,
+    errorparse::{SipParseError, SipParseErrorKind},
+    sip_method::*,
+};
+use crate::{
+    content_coding::content_body,
+    headers::{sipuri::uri_scheme, *},
+    message::{
+        body_as_sdp, body_as_sipfrag, body_multipart, check_version, start_line_error, take_body,
+        take_headers_end, SipVersion,
+    },
+    multipart::Multipart,
+    options::{exceeds, limit_error, ParseOptions},
+    sdp::SessionDescription,
+    SipFrag,
+};
 use nom::{
     bytes::complete::{tag, take_while1},
-    character::{complete, is_alphabetic},
+    character::complete,
     sequence::tuple,
 };
 
-use core::{str, u8};
+use alloc::vec::Vec;
+use core::{cell::OnceCell, str, u8};
 
 /// [rfc3261 section-7.1](https://tools.ietf.org/html/rfc3261#section-7.1)
 pub struct Request<'a> {
@@ -16,6 +32,8 @@ pub struct Request<'a> {
     pub headers: SipHeaders<'a>,
     /// The body of message
     pub body: Option<&'a [u8]>,
+    /// Body decoded according to Content-Encoding
+    decoded: OnceCell<Vec<u8>>,
 }
 
 impl<'a> Request<'a> {
@@ -24,16 +42,115 @@ impl<'a> Request<'a> {
             rl: rl,
             headers: headers,
             body: body,
+            decoded: OnceCell::new(),
         }
     }
 
-    pub fn parse(buf_input: &'a [u8]) -> nom::IResult<&[u8], Request, SipParseError> {
-        let (input, rl) = RequestLine::parse(buf_input)?;
+    /// Parses body as SDP if Content-Type is `application/sdp`.
+    /// Body is decoded according to Content-Encoding first.
+    /// Returns `UnsupportedMediaType` error otherwise
+    pub fn body_as_sdp(&self) -> Result<SessionDescription<'_>, nom::Err<SipParseError<'_>>> {
+        body_as_sdp(&self.headers, self.content_body()?)
+    }
+
+    /// Parses body as fragment of SIP message if Content-Type is `message/sipfrag`.
+    /// Ex: progress of transfer in NOTIFY for REFER.
+    /// Returns `UnsupportedMediaType` error otherwise
+    pub fn body_as_sipfrag(&self) -> Result<SipFrag<'_>, nom::Err<SipParseError<'_>>> {
+        body_as_sipfrag(&self.headers, self.content_body()?)
+    }
+
+    /// Parses body as multipart if Content-Type is `multipart/*`.
+    /// Returns `UnsupportedMediaType` error otherwise
+    pub fn body_multipart(&self) -> Result<Multipart<'_>, nom::Err<SipParseError<'_>>> {
+        body_multipart(&self.headers, self.content_body()?)
+    }
+
+    /// Decodes body according to Content-Encoding (`gzip`, `deflate` or `identity`).
+    /// `max_len` limits length of decoded body. Ex: `ParseLimits::max_body_len`.
+    /// Returns `UnsupportedMediaType` error if coding is not supported
+    #[cfg(feature = "compression")]
+    pub fn decoded_body(
+        &self,
+        max_len: Option<usize>,
+    ) -> Result<alloc::borrow::Cow<'a, [u8]>, nom::Err<SipParseError<'a>>> {
+        crate::content_coding::decoded_body(&self.headers, self.body, max_len)
+    }
 
-        let (input, headers) = SipHeaders::parse(input)?;
-        // TODO check header Content-Length and fix buf_input return
-        let (body, _) = tag("\r\n")(input)?;
-        Ok((buf_input, Request::new(rl, headers, Some(body))))
+    /// Body decoded according to Content-Encoding for `body_as_*` methods.
+    /// Length of decoded body is limited by `MAX_DECODED_BODY_LEN`
+    fn content_body(&self) -> Result<Option<&[u8]>, nom::Err<SipParseError<'_>>> {
+        content_body(&self.headers, self.body, &self.decoded)
+    }
+
+    /// Parses body of event package selected by Content-Type.
+    /// Ex: PIDF of PUBLISH or dialog-info of NOTIFY.
+    /// Returns `UnsupportedMediaType` error if media type is not known
+    #[cfg(feature = "std")]
+    pub fn event_body(&self) -> Result<crate::events::EventBody, nom::Err<SipParseError<'_>>> {
+        crate::events::event_body(&self.headers, self.content_body()?)
+    }
+
+    pub fn parse(buf_input: &'a [u8]) -> nom::IResult<&'a [u8], Request<'a>, SipParseError<'a>> {
+        Request::parse_ext(buf_input, &ParseOptions::default())
+    }
+
+    pub fn parse_ext(
+        buf_input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Request<'a>, SipParseError<'a>> {
+        match Request::parse_message(buf_input, options) {
+            Ok((input, mut msg)) => {
+                msg.headers.locate_invalid_headers(buf_input);
+                Ok((input, msg))
+            }
+            Err(e) => Err(e.map(|e| e.locate(buf_input))),
+        }
+    }
+
+    fn parse_message(
+        buf_input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Request<'a>, SipParseError<'a>> {
+        let (input, rl) = RequestLine::parse_ext(buf_input, options)?;
+        let version_pos = rl
+            .raw
+            .iter()
+            .rposition(|c| *c == b' ')
+            .map_or(0, |pos| pos + 1);
+        check_version(rl.sip_version, &rl.raw[version_pos..])?;
+
+        let (input, headers) = SipHeaders::parse_ext(input, options)?;
+        if !options.is_lenient() {
+            Request::check_cseq_method(&rl, &headers)?;
+        }
+        let (input, _) = take_headers_end(input)?;
+        let (input, body) = take_body(input, &headers, options)?;
+        Ok((input, Request::new(rl, headers, Some(body))))
+    }
+
+    /// Method of CSeq must be the same as method of Request-Line
+    fn check_cseq_method(
+        rl: &RequestLine<'a>,
+        headers: &SipHeaders<'a>,
+    ) -> Result<(), nom::Err<SipParseError<'a>>> {
+        let cseq = match headers.get_rfc_s(SipRFCHeader::CSeq) {
+            Some(cseq) => cseq,
+            None => return Ok(()),
+        };
+        let cseq_method = cseq
+            .value
+            .tags()
+            .and_then(|tags| tags.get(&SipHeaderTagType::Method));
+        match cseq_method {
+            Some(method) if *method != rl.method_str().as_bytes() => Err(nom::Err::Error(
+                SipParseError::new(1, Some("CSeq method does not match Request-Line"))
+                    .with_kind(SipParseErrorKind::CSeqMethodMismatch)
+                    .or_header(SipRFCHeader::CSeq.as_str())
+                    .or_input(cseq.raw_value_param),
+            )),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -50,32 +167,61 @@ pub struct RequestLine<'a> {
 impl<'a> RequestLine<'a> {
     fn parse_method(method: &[u8]) -> Option<SipMethod> {
         match str::from_utf8(method) {
-            Ok(s) => SipMethod::from_str(s),
+            Ok(s) => Some(SipMethod::from_str(s).unwrap_or(SipMethod::Extension)),
             Err(_) => None,
         }
     }
+
+    /// Method as it is written in Request-Line.
+    /// Useful for `SipMethod::Extension`
+    pub fn method_str(&self) -> &'a str {
+        let method_len = self.raw.iter().take_while(|c| **c != b' ').count();
+        str::from_utf8(&self.raw[..method_len]).unwrap_or("")
+    }
+
+    /// Request-URI = SIP-URI / SIPS-URI / absoluteURI.
+    /// Returns error `UnsupportedUriScheme` if uri is absoluteURI
+    fn uri_error(uri: &'a [u8], error: SipParseError<'a>) -> SipParseError<'a> {
+        if let Some(scheme) = uri_scheme(uri) {
+            if !scheme.eq_ignore_ascii_case(b"sip") && !scheme.eq_ignore_ascii_case(b"sips") {
+                return SipParseError::new(1, Some("Scheme of Request-URI is not supported"))
+                    .with_kind(SipParseErrorKind::UnsupportedUriScheme)
+                    .or_input(uri);
+            }
+        }
+        error.with_kind(SipParseErrorKind::BadUri).or_input(uri)
+    }
     pub fn parse(source_input: &[u8]) -> nom::IResult<&[u8], RequestLine, SipParseError> {
-        let method = take_while1(is_alphabetic);
+        RequestLine::parse_ext(source_input, &ParseOptions::default())
+    }
+
+    pub fn parse_ext(
+        source_input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], RequestLine<'a>, SipParseError<'a>> {
+        let method = take_while1(is_token_char);
         let uri = take_while1(|c| c != b' ' as u8);
         let (input, (method, _, uri, _, _, major_version, _, minor_version, _)) =
-            tuple((
+            start_line_error(tuple((
                 method,
-                complete::space1,
+                complete::char(' '),
                 uri,
-                complete::space1,
+                complete::char(' '),
                 tag("SIP/"),
                 complete::digit1,
                 complete::char('.'),
                 complete::digit1,
                 complete::crlf,
-            ))(source_input)?;
+            ))(source_input))?;
+
+        if exceeds(options.limits.max_uri_len, uri.len()) {
+            return Err(limit_error(SipParseErrorKind::UriTooLong, uri));
+        }
 
-        let (_, sip_uri) = SipUri::parse(uri)?;
+        let (_, sip_uri) =
+            SipUri::parse(uri).map_err(|e| e.map(|e| RequestLine::uri_error(uri, e)))?;
 
-        let sip_version = SipVersion(
-            u8::from_str_radix(str::from_utf8(major_version).unwrap(), 10).unwrap(),
-            u8::from_str_radix(str::from_utf8(minor_version).unwrap(), 10).unwrap(),
-        );
+        let (_, sip_version) = SipVersion::parse(major_version, minor_version)?;
 
         match RequestLine::parse_method(method) {
             Some(m) => Ok((
@@ -87,7 +233,13 @@ impl<'a> RequestLine<'a> {
                     raw: &source_input[..source_input.len() - input.len()],
                 },
             )),
-            None => return sip_parse_error!(1, "Error cast from_utf8"),
+            None => {
+                return Err(nom::Err::Error(
+                    SipParseError::new(1, Some("Unsupported method"))
+                        .with_kind(SipParseErrorKind::BadMethod)
+                        .or_input(method),
+                ))
+            }
         }
     }
 }
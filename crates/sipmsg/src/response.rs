@@ -1,10 +1,22 @@
-use crate::common::{errorparse::SipParseError, nom_wrappers::from_utf8_nom};
+use crate::common::{
+    bnfcore::is_digit,
+    errorparse::{SipParseError, SipParseErrorKind},
+    nom_wrappers::from_utf8_nom,
+};
+use crate::content_coding::content_body;
 use crate::headers::*;
-use crate::message::SipVersion;
+use crate::message::{
+    body_as_sdp, body_multipart, check_version, start_line_error, take_body, take_headers_end,
+    SipVersion,
+};
+use crate::multipart::Multipart;
+use crate::options::ParseOptions;
+use crate::sdp::SessionDescription;
 
-use core::str;
+use alloc::vec::Vec;
+use core::{cell::OnceCell, str};
 use nom::{
-    bytes::complete::{tag, take, take_until},
+    bytes::complete::{tag, take, take_until, take_while1},
     character::complete,
     sequence::tuple,
 };
@@ -17,6 +29,8 @@ pub struct Response<'a> {
     pub headers: SipHeaders<'a>,
     /// Body
     pub body: Option<&'a [u8]>,
+    /// Body decoded according to Content-Encoding
+    decoded: OnceCell<Vec<u8>>,
 }
 
 /// Ex: `SIP/2.0 401 Unauthorized`
@@ -31,22 +45,26 @@ pub struct StatusLine<'a> {
 impl<'a> StatusLine<'a> {
     pub fn parse(source_input: &'a [u8]) -> nom::IResult<&[u8], StatusLine<'a>, SipParseError> {
         let (input, (_, major_version, _, minor_version, _, status_code, _, reason_phrase, _)) =
-            tuple((
+            start_line_error(tuple((
                 tag("SIP/"),
                 complete::digit1,
                 complete::char('.'),
                 complete::digit1,
-                complete::space1,
-                take(3usize),
-                complete::space1,
+                complete::char(' '),
+                take_while1(|c| c != b' ' && c != b'\r'),
+                complete::char(' '),
                 take_until("\r\n"),
                 take(2usize), // skip /r/n
-            ))(source_input)?;
+            ))(source_input))?;
 
-        let sip_version = SipVersion(
-            u8::from_str_radix(str::from_utf8(major_version).unwrap(), 10).unwrap(),
-            u8::from_str_radix(str::from_utf8(minor_version).unwrap(), 10).unwrap(),
-        );
+        let (_, sip_version) = SipVersion::parse(major_version, minor_version)?;
+        if status_code.len() != 3 || !status_code.iter().all(|c| is_digit(*c)) {
+            return Err(nom::Err::Error(
+                SipParseError::new(1, Some("Status-Code is not 3DIGIT"))
+                    .with_kind(SipParseErrorKind::BadStatusCode)
+                    .or_input(status_code),
+            ));
+        }
 
         let status_code = StatusCode::from_bytes_str(status_code);
         let (_, reason_phrase_str) = from_utf8_nom(reason_phrase)?;
@@ -69,17 +87,69 @@ impl<'a> Response<'a> {
             sl: sl,
             headers: headers,
             body: body,
+            decoded: OnceCell::new(),
         }
     }
 
+    /// Parses body as SDP if Content-Type is `application/sdp`.
+    /// Body is decoded according to Content-Encoding first.
+    /// Returns `UnsupportedMediaType` error otherwise
+    pub fn body_as_sdp(&self) -> Result<SessionDescription<'_>, nom::Err<SipParseError<'_>>> {
+        body_as_sdp(&self.headers, self.content_body()?)
+    }
+
+    /// Parses body as multipart if Content-Type is `multipart/*`.
+    /// Returns `UnsupportedMediaType` error otherwise
+    pub fn body_multipart(&self) -> Result<Multipart<'_>, nom::Err<SipParseError<'_>>> {
+        body_multipart(&self.headers, self.content_body()?)
+    }
+
+    /// Decodes body according to Content-Encoding (`gzip`, `deflate` or `identity`).
+    /// `max_len` limits length of decoded body. Ex: `ParseLimits::max_body_len`.
+    /// Returns `UnsupportedMediaType` error if coding is not supported
+    #[cfg(feature = "compression")]
+    pub fn decoded_body(
+        &self,
+        max_len: Option<usize>,
+    ) -> Result<alloc::borrow::Cow<'a, [u8]>, nom::Err<SipParseError<'a>>> {
+        crate::content_coding::decoded_body(&self.headers, self.body, max_len)
+    }
+
+    /// Body decoded according to Content-Encoding for `body_as_*` methods.
+    /// Length of decoded body is limited by `MAX_DECODED_BODY_LEN`
+    fn content_body(&self) -> Result<Option<&[u8]>, nom::Err<SipParseError<'_>>> {
+        content_body(&self.headers, self.body, &self.decoded)
+    }
+
     pub fn parse(buf_input: &'a [u8]) -> nom::IResult<&[u8], Response<'a>, SipParseError> {
+        Response::parse_ext(buf_input, &ParseOptions::default())
+    }
+
+    pub fn parse_ext(
+        buf_input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Response<'a>, SipParseError<'a>> {
+        match Response::parse_message(buf_input, options) {
+            Ok((input, mut msg)) => {
+                msg.headers.locate_invalid_headers(buf_input);
+                Ok((input, msg))
+            }
+            Err(e) => Err(e.map(|e| e.locate(buf_input))),
+        }
+    }
+
+    fn parse_message(
+        buf_input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], Response<'a>, SipParseError<'a>> {
         let (input, rl) = StatusLine::parse(buf_input)?;
+        check_version(rl.sip_version, rl.raw)?;
 
-        let (input, headers) = SipHeaders::parse(input)?;
-        // TODO check header Content-Length and fix buf_input return
-        let (body, _) = tag("\r\n")(input)?;
+        let (input, headers) = SipHeaders::parse_ext(input, options)?;
+        let (input, _) = take_headers_end(input)?;
+        let (input, body) = take_body(input, &headers, options)?;
 
-        Ok((buf_input, Response::new(rl, headers, Some(body))))
+        Ok((input, Response::new(rl, headers, Some(body))))
     }
 }
 
@@ -0,0 +1,522 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// `a=rtpmap:<payload type> <encoding name>/<clock rate>[/<encoding parameters>]`
+/// [rfc8866 section-6.6](https://tools.ietf.org/html/rfc8866#section-6.6)
+#[derive(Clone, PartialEq, Debug)]
+pub struct RtpMap<'a> {
+    pub payload_type: u8,
+    pub encoding_name: &'a str,
+    pub clock_rate: u32,
+    /// Number of audio channels. Ex: `2` in `opus/48000/2`
+    pub encoding_params: Option<&'a str>,
+}
+
+impl<'a> RtpMap<'a> {
+    pub fn new(payload_type: u8, encoding_name: &'a str, clock_rate: u32) -> RtpMap<'a> {
+        RtpMap {
+            payload_type,
+            encoding_name,
+            clock_rate,
+            encoding_params: None,
+        }
+    }
+
+    fn parse(value: &'a str) -> Option<RtpMap<'a>> {
+        let (payload_type, encoding) = split_once(value, ' ')?;
+        let mut encoding = encoding.split('/');
+        let encoding_name = encoding.next().filter(|name| !name.is_empty())?;
+        let clock_rate = encoding.next()?.parse().ok()?;
+        let encoding_params = encoding.next();
+        if encoding.next().is_some() {
+            return None;
+        }
+        Some(RtpMap {
+            payload_type: payload_type.parse().ok()?,
+            encoding_name,
+            clock_rate,
+            encoding_params,
+        })
+    }
+}
+
+/// `a=fmtp:<format> <format specific parameters>`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fmtp<'a> {
+    pub format: &'a str,
+    pub params: &'a str,
+}
+
+impl<'a> Fmtp<'a> {
+    /// Parameters separated by `;`. Ex: `profile-level-id=42e01f;packetization-mode=1`
+    pub fn params(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> {
+        self.params
+            .split(';')
+            .map(|param| param.trim())
+            .filter(|param| !param.is_empty())
+            .map(|param| match split_once(param, '=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (param, None),
+            })
+    }
+
+    /// Value of parameter with `name`. Names are case-insensitive
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params()
+            .find(|(param_name, _)| param_name.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value)
+    }
+}
+
+/// Direction of media stream. `sendrecv` is default.
+/// [rfc8866 section-6.7](https://tools.ietf.org/html/rfc8866#section-6.7)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Direction {
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl Direction {
+    pub fn parse(s: &str) -> Option<Direction> {
+        match s {
+            "sendrecv" => Some(Direction::SendRecv),
+            "sendonly" => Some(Direction::SendOnly),
+            "recvonly" => Some(Direction::RecvOnly),
+            "inactive" => Some(Direction::Inactive),
+            _ => None,
+        }
+    }
+
+    /// Direction by whether media is sent and received
+    pub fn from_flags(sending: bool, receiving: bool) -> Direction {
+        match (sending, receiving) {
+            (true, true) => Direction::SendRecv,
+            (true, false) => Direction::SendOnly,
+            (false, true) => Direction::RecvOnly,
+            (false, false) => Direction::Inactive,
+        }
+    }
+
+    pub fn is_sending(self) -> bool {
+        self == Direction::SendRecv || self == Direction::SendOnly
+    }
+
+    pub fn is_receiving(self) -> bool {
+        self == Direction::SendRecv || self == Direction::RecvOnly
+    }
+
+    /// Direction seen from the other side. Ex: `sendonly` becomes `recvonly`
+    pub fn reverse(self) -> Direction {
+        Direction::from_flags(self.is_receiving(), self.is_sending())
+    }
+
+    /// Media is sent or received only if both directions allow it
+    pub fn intersect(self, other: Direction) -> Direction {
+        Direction::from_flags(
+            self.is_sending() && other.is_sending(),
+            self.is_receiving() && other.is_receiving(),
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::SendRecv => "sendrecv",
+            Direction::SendOnly => "sendonly",
+            Direction::RecvOnly => "recvonly",
+            Direction::Inactive => "inactive",
+        }
+    }
+}
+
+/// `a=candidate:` ICE candidate
+/// [rfc8839 section-5.1](https://tools.ietf.org/html/rfc8839#section-5.1)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Candidate<'a> {
+    pub foundation: &'a str,
+    pub component: u16,
+    pub transport: &'a str,
+    pub priority: u32,
+    pub address: &'a str,
+    pub port: u16,
+    /// `host`, `srflx`, `prflx` or `relay`
+    pub typ: &'a str,
+    pub related_address: Option<&'a str>,
+    pub related_port: Option<u16>,
+    /// Name and value of extension attributes. Ex: `generation 0`
+    pub extensions: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Candidate<'a> {
+    fn parse(value: &'a str) -> Option<Candidate<'a>> {
+        let mut parts = value.split(' ');
+        let mut next = || parts.next().filter(|part| !part.is_empty());
+        let foundation = next()?;
+        let component = next()?.parse().ok()?;
+        let transport = next()?;
+        let priority = next()?.parse().ok()?;
+        let address = next()?;
+        let port = next()?.parse().ok()?;
+        if next()? != "typ" {
+            return None;
+        }
+        let mut candidate = Candidate {
+            foundation,
+            component,
+            transport,
+            priority,
+            address,
+            port,
+            typ: next()?,
+            related_address: None,
+            related_port: None,
+            extensions: Vec::new(),
+        };
+        while let Some(name) = next() {
+            let value = next()?;
+            match name {
+                "raddr" => candidate.related_address = Some(value),
+                "rport" => candidate.related_port = Some(value.parse().ok()?),
+                _ => candidate.extensions.push((name, value)),
+            }
+        }
+        Some(candidate)
+    }
+}
+
+/// `a=fingerprint:<hash function> <fingerprint>`
+/// [rfc8122 section-5](https://tools.ietf.org/html/rfc8122#section-5)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fingerprint<'a> {
+    /// Ex: `sha-256`
+    pub hash_function: &'a str,
+    /// Uppercase hex bytes separated by colons
+    pub fingerprint: &'a str,
+}
+
+/// `a=setup:` role of endpoint in TCP or DTLS connection
+/// [rfc4145 section-4](https://tools.ietf.org/html/rfc4145#section-4)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Setup {
+    Active,
+    Passive,
+    ActPass,
+    HoldConn,
+}
+
+impl Setup {
+    pub fn parse(s: &str) -> Option<Setup> {
+        match s {
+            "active" => Some(Setup::Active),
+            "passive" => Some(Setup::Passive),
+            "actpass" => Some(Setup::ActPass),
+            "holdconn" => Some(Setup::HoldConn),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Setup::Active => "active",
+            Setup::Passive => "passive",
+            Setup::ActPass => "actpass",
+            Setup::HoldConn => "holdconn",
+        }
+    }
+}
+
+/// `a=group:<semantics> *(SP <identification-tag>)`
+/// [rfc5888 section-5](https://tools.ietf.org/html/rfc5888#section-5)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Group<'a> {
+    /// Ex: `BUNDLE`, `LS`, `FID`
+    pub semantics: &'a str,
+    /// Values of `a=mid` of grouped media
+    pub mids: Vec<&'a str>,
+}
+
+/// `a=<attribute-name>[:<attribute-value>]`.
+/// Attributes that are not known are kept in `Attribute::Other`
+#[derive(Clone, PartialEq, Debug)]
+pub enum Attribute<'a> {
+    RtpMap(RtpMap<'a>),
+    Fmtp(Fmtp<'a>),
+    Direction(Direction),
+    /// Length of time in milliseconds represented by the media in a packet
+    Ptime(u32),
+    Candidate(Candidate<'a>),
+    Fingerprint(Fingerprint<'a>),
+    Setup(Setup),
+    Mid(&'a str),
+    Group(Group<'a>),
+    Other {
+        name: &'a str,
+        value: Option<&'a str>,
+    },
+}
+
+impl<'a> Attribute<'a> {
+    /// Parses value of `a=` line. Returns `None` if value of known attribute is malformed
+    pub fn parse(input: &'a str) -> Option<Attribute<'a>> {
+        let (name, value) = match split_once(input, ':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (input, None),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        if let Some(direction) = Direction::parse(name) {
+            return match value {
+                None => Some(Attribute::Direction(direction)),
+                Some(_) => None,
+            };
+        }
+        let attribute = match (name, value) {
+            ("rtpmap", Some(value)) => Attribute::RtpMap(RtpMap::parse(value)?),
+            ("fmtp", Some(value)) => {
+                let (format, params) = split_once(value, ' ')?;
+                Attribute::Fmtp(Fmtp { format, params })
+            }
+            ("ptime", Some(value)) => Attribute::Ptime(value.parse().ok()?),
+            ("candidate", Some(value)) => Attribute::Candidate(Candidate::parse(value)?),
+            ("fingerprint", Some(value)) => {
+                let (hash_function, fingerprint) = split_once(value, ' ')?;
+                Attribute::Fingerprint(Fingerprint {
+                    hash_function,
+                    fingerprint,
+                })
+            }
+            ("setup", Some(value)) => Attribute::Setup(Setup::parse(value)?),
+            ("mid", Some(value)) if !value.is_empty() => Attribute::Mid(value),
+            ("group", Some(value)) => {
+                let mut tags = value.split(' ');
+                let semantics = tags.next().filter(|semantics| !semantics.is_empty())?;
+                Attribute::Group(Group {
+                    semantics,
+                    mids: tags.collect(),
+                })
+            }
+            ("rtpmap", None)
+            | ("fmtp", None)
+            | ("ptime", None)
+            | ("candidate", None)
+            | ("fingerprint", None)
+            | ("setup", None)
+            | ("mid", _)
+            | ("group", None) => return None,
+            (name, value) => Attribute::Other { name, value },
+        };
+        Some(attribute)
+    }
+
+    /// Ex: `rtpmap`
+    pub fn name(&self) -> &'a str {
+        match self {
+            Attribute::RtpMap(_) => "rtpmap",
+            Attribute::Fmtp(_) => "fmtp",
+            Attribute::Direction(direction) => direction.as_str(),
+            Attribute::Ptime(_) => "ptime",
+            Attribute::Candidate(_) => "candidate",
+            Attribute::Fingerprint(_) => "fingerprint",
+            Attribute::Setup(_) => "setup",
+            Attribute::Mid(_) => "mid",
+            Attribute::Group(_) => "group",
+            Attribute::Other { name, .. } => name,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Attribute<'a> {
+    /// Value of `a=` line. Ex: `rtpmap:0 PCMU/8000`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Attribute::RtpMap(rtpmap) => {
+                write!(
+                    f,
+                    "rtpmap:{} {}/{}",
+                    rtpmap.payload_type, rtpmap.encoding_name, rtpmap.clock_rate
+                )?;
+                if let Some(params) = rtpmap.encoding_params {
+                    write!(f, "/{}", params)?;
+                }
+                Ok(())
+            }
+            Attribute::Fmtp(fmtp) => write!(f, "fmtp:{} {}", fmtp.format, fmtp.params),
+            Attribute::Direction(direction) => f.write_str(direction.as_str()),
+            Attribute::Ptime(ptime) => write!(f, "ptime:{}", ptime),
+            Attribute::Candidate(c) => {
+                write!(
+                    f,
+                    "candidate:{} {} {} {} {} {} typ {}",
+                    c.foundation, c.component, c.transport, c.priority, c.address, c.port, c.typ
+                )?;
+                if let Some(address) = c.related_address {
+                    write!(f, " raddr {}", address)?;
+                }
+                if let Some(port) = c.related_port {
+                    write!(f, " rport {}", port)?;
+                }
+                for (name, value) in &c.extensions {
+                    write!(f, " {} {}", name, value)?;
+                }
+                Ok(())
+            }
+            Attribute::Fingerprint(fp) => {
+                write!(f, "fingerprint:{} {}", fp.hash_function, fp.fingerprint)
+            }
+            Attribute::Setup(setup) => write!(f, "setup:{}", setup.as_str()),
+            Attribute::Mid(mid) => write!(f, "mid:{}", mid),
+            Attribute::Group(group) => {
+                write!(f, "group:{}", group.semantics)?;
+                for mid in &group.mids {
+                    write!(f, " {}", mid)?;
+                }
+                Ok(())
+            }
+            Attribute::Other { name, value: None } => f.write_str(name),
+            Attribute::Other {
+                name,
+                value: Some(value),
+            } => write!(f, "{}:{}", name, value),
+        }
+    }
+}
+
+fn split_once(s: &str, delimiter: char) -> Option<(&str, &str)> {
+    let pos = s.find(delimiter)?;
+    Some((&s[..pos], &s[pos + delimiter.len_utf8()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn rtpmap_test() {
+        let attr = Attribute::parse("rtpmap:111 opus/48000/2").unwrap();
+        assert_eq!(
+            attr,
+            Attribute::RtpMap(RtpMap {
+                payload_type: 111,
+                encoding_name: "opus",
+                clock_rate: 48000,
+                encoding_params: Some("2"),
+            })
+        );
+        assert_eq!(attr.to_string(), "rtpmap:111 opus/48000/2");
+        assert_eq!(
+            Attribute::parse("rtpmap:0 PCMU/8000").unwrap().to_string(),
+            "rtpmap:0 PCMU/8000"
+        );
+        assert!(Attribute::parse("rtpmap:0 PCMU").is_none());
+        assert!(Attribute::parse("rtpmap:256 PCMU/8000").is_none());
+        assert!(Attribute::parse("rtpmap:0 PCMU/8000/1/2").is_none());
+    }
+
+    #[test]
+    fn fmtp_test() {
+        let attr =
+            Attribute::parse("fmtp:97 profile-level-id=42e01f; packetization-mode=1").unwrap();
+        match attr {
+            Attribute::Fmtp(fmtp) => {
+                assert_eq!(fmtp.format, "97");
+                assert_eq!(fmtp.param("packetization-mode"), Some("1"));
+                assert_eq!(fmtp.param("PROFILE-LEVEL-ID"), Some("42e01f"));
+                assert_eq!(fmtp.param("level-asymmetry-allowed"), None);
+            }
+            _ => panic!("fmtp expected"),
+        }
+        let attr = Attribute::parse("fmtp:101 0-15").unwrap();
+        match &attr {
+            Attribute::Fmtp(fmtp) => {
+                assert_eq!(fmtp.params().collect::<Vec<_>>(), vec![("0-15", None)])
+            }
+            _ => panic!("fmtp expected"),
+        }
+        assert_eq!(attr.to_string(), "fmtp:101 0-15");
+    }
+
+    #[test]
+    fn candidate_test() {
+        let value = "candidate:842163049 1 udp 1677729535 203.0.113.141 46154 typ srflx \
+                     raddr 10.0.1.1 rport 8998 generation 0";
+        let attr = Attribute::parse(value).unwrap();
+        match &attr {
+            Attribute::Candidate(c) => {
+                assert_eq!(c.foundation, "842163049");
+                assert_eq!(c.component, 1);
+                assert_eq!(c.priority, 1677729535);
+                assert_eq!(c.address, "203.0.113.141");
+                assert_eq!(c.port, 46154);
+                assert_eq!(c.typ, "srflx");
+                assert_eq!(c.related_address, Some("10.0.1.1"));
+                assert_eq!(c.related_port, Some(8998));
+                assert_eq!(c.extensions, vec![("generation", "0")]);
+            }
+            _ => panic!("candidate expected"),
+        }
+        assert_eq!(attr.to_string(), value);
+        assert!(Attribute::parse("candidate:1 1 udp 1 192.0.2.1 5000 host").is_none());
+        assert!(Attribute::parse("candidate:1 1 udp 1 192.0.2.1 5000 typ host raddr").is_none());
+    }
+
+    #[test]
+    fn direction_test() {
+        assert_eq!(Direction::SendOnly.reverse(), Direction::RecvOnly);
+        assert_eq!(Direction::Inactive.reverse(), Direction::Inactive);
+        assert_eq!(
+            Direction::SendRecv.intersect(Direction::RecvOnly),
+            Direction::RecvOnly
+        );
+        assert_eq!(
+            Direction::SendOnly.intersect(Direction::RecvOnly),
+            Direction::Inactive
+        );
+        assert_eq!(Direction::from_flags(true, true), Direction::SendRecv);
+    }
+
+    #[test]
+    fn other_attributes_test() {
+        assert_eq!(
+            Attribute::parse("sendonly"),
+            Some(Attribute::Direction(Direction::SendOnly))
+        );
+        assert_eq!(Attribute::parse("ptime:20"), Some(Attribute::Ptime(20)));
+        assert_eq!(
+            Attribute::parse("setup:actpass"),
+            Some(Attribute::Setup(Setup::ActPass))
+        );
+        assert_eq!(Attribute::parse("mid:audio"), Some(Attribute::Mid("audio")));
+        let group = Attribute::parse("group:BUNDLE audio video").unwrap();
+        assert_eq!(
+            group,
+            Attribute::Group(Group {
+                semantics: "BUNDLE",
+                mids: vec!["audio", "video"],
+            })
+        );
+        assert_eq!(group.to_string(), "group:BUNDLE audio video");
+        let fingerprint = Attribute::parse("fingerprint:sha-256 4A:AD:B9").unwrap();
+        assert_eq!(fingerprint.name(), "fingerprint");
+        assert_eq!(fingerprint.to_string(), "fingerprint:sha-256 4A:AD:B9");
+        assert_eq!(
+            Attribute::parse("rtcp-mux"),
+            Some(Attribute::Other {
+                name: "rtcp-mux",
+                value: None
+            })
+        );
+        assert_eq!(
+            Attribute::parse("tool:x:y").unwrap().to_string(),
+            "tool:x:y"
+        );
+
+        assert!(Attribute::parse("sendonly:1").is_none());
+        assert!(Attribute::parse("ptime:abc").is_none());
+        assert!(Attribute::parse("setup:unknown").is_none());
+        assert!(Attribute::parse("mid:").is_none());
+        assert!(Attribute::parse(":value").is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::sdp::{
+    Attribute, Bandwidth, Connection, Direction, Fmtp, MediaDescription, Origin, RtpMap,
+    SessionDescription, Timing,
+};
+
+/// Builds `SessionDescription`. `s=-` and `t=0 0` are used by default
+/// ```rust
+/// use sipmsg::sdp::{Connection, Direction, MediaBuilder, Origin, RtpMap, SessionBuilder};
+///
+/// let origin = Origin {
+///     username: "-",
+///     session_id: 20518,
+///     session_version: 0,
+///     nettype: "IN",
+///     addrtype: "IP4",
+///     unicast_address: "203.0.113.1",
+/// };
+/// let sdp = SessionBuilder::new(origin)
+///     .connection(Connection::new("203.0.113.1"))
+///     .media(
+///         MediaBuilder::new("audio", 54400, "RTP/AVP")
+///             .codec("0", RtpMap::new(0, "PCMU", 8000))
+///             .direction(Direction::SendRecv)
+///             .build(),
+///     )
+///     .build();
+///
+/// assert_eq!(
+///     sdp.to_string(),
+///     "v=0\r\n\
+///      o=- 20518 0 IN IP4 203.0.113.1\r\n\
+///      s=-\r\n\
+///      c=IN IP4 203.0.113.1\r\n\
+///      t=0 0\r\n\
+///      m=audio 54400 RTP/AVP 0\r\n\
+///      a=rtpmap:0 PCMU/8000\r\n\
+///      a=sendrecv\r\n"
+/// );
+/// ```
+pub struct SessionBuilder<'a> {
+    sdp: SessionDescription<'a>,
+}
+
+impl<'a> SessionBuilder<'a> {
+    pub fn new(origin: Origin<'a>) -> SessionBuilder<'a> {
+        let mut sdp = SessionDescription::new(origin, "-");
+        // `t=0 0` is added by `build` if there is no `timing`
+        sdp.timings.clear();
+        SessionBuilder { sdp }
+    }
+
+    pub fn session_name(mut self, session_name: &'a str) -> SessionBuilder<'a> {
+        self.sdp.session_name = session_name;
+        self
+    }
+
+    pub fn information(mut self, information: &'a str) -> SessionBuilder<'a> {
+        self.sdp.information = Some(information);
+        self
+    }
+
+    pub fn uri(mut self, uri: &'a str) -> SessionBuilder<'a> {
+        self.sdp.uri = Some(uri);
+        self
+    }
+
+    pub fn email(mut self, email: &'a str) -> SessionBuilder<'a> {
+        self.sdp.emails.push(email);
+        self
+    }
+
+    pub fn phone(mut self, phone: &'a str) -> SessionBuilder<'a> {
+        self.sdp.phones.push(phone);
+        self
+    }
+
+    pub fn connection(mut self, connection: Connection<'a>) -> SessionBuilder<'a> {
+        self.sdp.connection = Some(connection);
+        self
+    }
+
+    pub fn bandwidth(mut self, bwtype: &'a str, bandwidth: u64) -> SessionBuilder<'a> {
+        self.sdp.bandwidths.push(Bandwidth { bwtype, bandwidth });
+        self
+    }
+
+    /// Adds `t=` line. `t=0 0` is used if it is not called
+    pub fn timing(mut self, timing: Timing<'a>) -> SessionBuilder<'a> {
+        self.sdp.timings.push(timing);
+        self
+    }
+
+    pub fn attribute(mut self, attribute: Attribute<'a>) -> SessionBuilder<'a> {
+        self.sdp.attributes.push(attribute);
+        self
+    }
+
+    pub fn media(mut self, media: MediaDescription<'a>) -> SessionBuilder<'a> {
+        self.sdp.media.push(media);
+        self
+    }
+
+    pub fn build(mut self) -> SessionDescription<'a> {
+        if self.sdp.timings.is_empty() {
+            self.sdp.timings.push(Timing::permanent());
+        }
+        self.sdp
+    }
+}
+
+/// Builds `MediaDescription`
+pub struct MediaBuilder<'a> {
+    media: MediaDescription<'a>,
+}
+
+impl<'a> MediaBuilder<'a> {
+    pub fn new(media: &'a str, port: u16, protocol: &'a str) -> MediaBuilder<'a> {
+        MediaBuilder {
+            media: MediaDescription::new(media, port, protocol),
+        }
+    }
+
+    pub fn port_count(mut self, port_count: u16) -> MediaBuilder<'a> {
+        self.media.port_count = Some(port_count);
+        self
+    }
+
+    /// Adds format to `m=` line without `a=rtpmap`. Ex: static payload type
+    pub fn format(mut self, format: &'a str) -> MediaBuilder<'a> {
+        self.media.formats.push(format);
+        self
+    }
+
+    /// Adds format to `m=` line and its `a=rtpmap`
+    pub fn codec(mut self, format: &'a str, rtpmap: RtpMap<'a>) -> MediaBuilder<'a> {
+        self.media.formats.push(format);
+        self.media.attributes.push(Attribute::RtpMap(rtpmap));
+        self
+    }
+
+    pub fn fmtp(mut self, format: &'a str, params: &'a str) -> MediaBuilder<'a> {
+        self.media
+            .attributes
+            .push(Attribute::Fmtp(Fmtp { format, params }));
+        self
+    }
+
+    pub fn information(mut self, information: &'a str) -> MediaBuilder<'a> {
+        self.media.information = Some(information);
+        self
+    }
+
+    pub fn connection(mut self, connection: Connection<'a>) -> MediaBuilder<'a> {
+        self.media.connections.push(connection);
+        self
+    }
+
+    pub fn bandwidth(mut self, bwtype: &'a str, bandwidth: u64) -> MediaBuilder<'a> {
+        self.media.bandwidths.push(Bandwidth { bwtype, bandwidth });
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> MediaBuilder<'a> {
+        self.media.attributes.push(Attribute::Direction(direction));
+        self
+    }
+
+    pub fn ptime(mut self, ptime: u32) -> MediaBuilder<'a> {
+        self.media.attributes.push(Attribute::Ptime(ptime));
+        self
+    }
+
+    pub fn mid(mut self, mid: &'a str) -> MediaBuilder<'a> {
+        self.media.attributes.push(Attribute::Mid(mid));
+        self
+    }
+
+    pub fn attribute(mut self, attribute: Attribute<'a>) -> MediaBuilder<'a> {
+        self.media.attributes.push(attribute);
+        self
+    }
+
+    pub fn build(self) -> MediaDescription<'a> {
+        self.media
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::Setup;
+    use alloc::string::ToString;
+
+    #[test]
+    fn build_and_parse_test() {
+        let origin = Origin {
+            username: "alice",
+            session_id: 1,
+            session_version: 2,
+            nettype: "IN",
+            addrtype: "IP6",
+            unicast_address: "2001:db8::1",
+        };
+        let sdp = SessionBuilder::new(origin)
+            .session_name("Talk")
+            .bandwidth("CT", 384)
+            .attribute(Attribute::Other {
+                name: "ice-lite",
+                value: None,
+            })
+            .media(
+                MediaBuilder::new("audio", 9, "UDP/TLS/RTP/SAVPF")
+                    .connection(Connection::new("2001:db8::1"))
+                    .codec(
+                        "111",
+                        RtpMap {
+                            encoding_params: Some("2"),
+                            ..RtpMap::new(111, "opus", 48000)
+                        },
+                    )
+                    .fmtp("111", "minptime=10;useinbandfec=1")
+                    .format("0")
+                    .mid("0")
+                    .ptime(20)
+                    .attribute(Attribute::Setup(Setup::ActPass))
+                    .direction(Direction::RecvOnly)
+                    .build(),
+            )
+            .media(
+                MediaBuilder::new("video", 0, "RTP/AVP")
+                    .format("31")
+                    .build(),
+            )
+            .build();
+        let serialized = sdp.to_string();
+        assert_eq!(
+            serialized,
+            "v=0\r\n\
+             o=alice 1 2 IN IP6 2001:db8::1\r\n\
+             s=Talk\r\n\
+             b=CT:384\r\n\
+             t=0 0\r\n\
+             a=ice-lite\r\n\
+             m=audio 9 UDP/TLS/RTP/SAVPF 111 0\r\n\
+             c=IN IP6 2001:db8::1\r\n\
+             a=rtpmap:111 opus/48000/2\r\n\
+             a=fmtp:111 minptime=10;useinbandfec=1\r\n\
+             a=mid:0\r\n\
+             a=ptime:20\r\n\
+             a=setup:actpass\r\n\
+             a=recvonly\r\n\
+             m=video 0 RTP/AVP 31\r\n"
+        );
+        let (_, parsed) = SessionDescription::parse(serialized.as_bytes()).unwrap();
+        assert_eq!(parsed, sdp);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    common::errorparse::SipParseError,
+    sdp::{
+        is_end,
+        session::{parse_attribute, Bandwidth, Connection},
+        take_line, Attribute, Candidate, Direction, Fingerprint, Fmtp, RtpMap, Setup,
+    },
+};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// `m=<media> <port>[/<number of ports>] <proto> <fmt> ...` and following lines
+/// [rfc8866 section-5.14](https://tools.ietf.org/html/rfc8866#section-5.14)
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaDescription<'a> {
+    /// Ex: `audio`, `video`, `application`
+    pub media: &'a str,
+    /// `0` if media stream is rejected or disabled
+    pub port: u16,
+    pub port_count: Option<u16>,
+    /// Ex: `RTP/AVP`, `UDP/TLS/RTP/SAVPF`
+    pub protocol: &'a str,
+    /// Payload types for RTP profiles
+    pub formats: Vec<&'a str>,
+    /// `i=`
+    pub information: Option<&'a str>,
+    /// `c=` of media level
+    pub connections: Vec<Connection<'a>>,
+    /// `b=`
+    pub bandwidths: Vec<Bandwidth<'a>>,
+    /// `k=`. Obsolete
+    pub key: Option<&'a str>,
+    /// `a=` of media level
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> MediaDescription<'a> {
+    /// Media without formats and attributes
+    pub fn new(media: &'a str, port: u16, protocol: &'a str) -> MediaDescription<'a> {
+        MediaDescription {
+            media,
+            port,
+            port_count: None,
+            protocol,
+            formats: Vec::new(),
+            information: None,
+            connections: Vec::new(),
+            bandwidths: Vec::new(),
+            key: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Parses `m=` line and lines of media section up to next `m=` line
+    pub fn parse(
+        input: &'a [u8],
+    ) -> nom::IResult<&'a [u8], MediaDescription<'a>, SipParseError<'a>> {
+        let (mut input, line) = take_line(input)?;
+        if line.field != "m" {
+            return line.error("Media description must start with m= line");
+        }
+        let mut parts = line.value.split(' ');
+        let (media, port, protocol) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(media), Some(port), Some(protocol))
+                if !media.is_empty() && !protocol.is_empty() =>
+            {
+                (media, port, protocol)
+            }
+            _ => return line.error("m= line must have media, port and protocol"),
+        };
+        let mut media = MediaDescription::new(media, 0, protocol);
+        match port.find('/') {
+            Some(pos) => {
+                media.port = line.number(&port[..pos])?;
+                media.port_count = Some(line.number(&port[pos + 1..])?);
+            }
+            None => media.port = line.number(port)?,
+        }
+        for format in parts {
+            if format.is_empty() {
+                return line.error("Formats must be separated by single space");
+            }
+            media.formats.push(format);
+        }
+
+        while !is_end(input) {
+            let (rest, line) = take_line(input)?;
+            if line.field == "m" {
+                break;
+            }
+            input = rest;
+            match line.field {
+                "i" => media.information = Some(line.value),
+                "c" => media.connections.push(Connection::parse(&line)?),
+                "b" => media.bandwidths.push(Bandwidth::parse(&line)?),
+                "k" => media.key = Some(line.value),
+                "a" => media.attributes.push(parse_attribute(&line)?),
+                _ => return line.error("Unexpected line in media description"),
+            }
+        }
+        Ok((input, media))
+    }
+
+    /// Media stream is rejected by answerer or disabled
+    pub fn is_rejected(&self) -> bool {
+        self.port == 0
+    }
+
+    /// First attribute with `name`
+    pub fn attribute(&self, name: &str) -> Option<&Attribute<'a>> {
+        self.attributes.iter().find(|attr| attr.name() == name)
+    }
+
+    pub fn rtpmaps(&self) -> impl Iterator<Item = &RtpMap<'a>> {
+        self.attributes.iter().filter_map(|attr| match attr {
+            Attribute::RtpMap(rtpmap) => Some(rtpmap),
+            _ => None,
+        })
+    }
+
+    /// `a=rtpmap` of `payload_type`
+    pub fn rtpmap(&self, payload_type: u8) -> Option<&RtpMap<'a>> {
+        self.rtpmaps()
+            .find(|rtpmap| rtpmap.payload_type == payload_type)
+    }
+
+    /// `a=fmtp` of `format`
+    pub fn fmtp(&self, format: &str) -> Option<&Fmtp<'a>> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Fmtp(fmtp) if fmtp.format == format => Some(fmtp),
+            _ => None,
+        })
+    }
+
+    /// Media level direction attribute. See `SessionDescription::media_direction`
+    pub fn direction(&self) -> Option<Direction> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Direction(direction) => Some(*direction),
+            _ => None,
+        })
+    }
+
+    pub fn ptime(&self) -> Option<u32> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Ptime(ptime) => Some(*ptime),
+            _ => None,
+        })
+    }
+
+    pub fn candidates(&self) -> impl Iterator<Item = &Candidate<'a>> {
+        self.attributes.iter().filter_map(|attr| match attr {
+            Attribute::Candidate(candidate) => Some(candidate),
+            _ => None,
+        })
+    }
+
+    pub fn fingerprint(&self) -> Option<&Fingerprint<'a>> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Fingerprint(fingerprint) => Some(fingerprint),
+            _ => None,
+        })
+    }
+
+    pub fn setup(&self) -> Option<Setup> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Setup(setup) => Some(*setup),
+            _ => None,
+        })
+    }
+
+    /// `a=mid`. Identification tag of media
+    pub fn mid(&self) -> Option<&'a str> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Mid(mid) => Some(*mid),
+            _ => None,
+        })
+    }
+}
+
+impl<'a> fmt::Display for MediaDescription<'a> {
+    /// `m=` line and lines of media section. Each line ends with CRLF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m={} {}", self.media, self.port)?;
+        if let Some(port_count) = self.port_count {
+            write!(f, "/{}", port_count)?;
+        }
+        write!(f, " {}", self.protocol)?;
+        for format in &self.formats {
+            write!(f, " {}", format)?;
+        }
+        f.write_str("\r\n")?;
+        if let Some(information) = self.information {
+            write!(f, "i={}\r\n", information)?;
+        }
+        for connection in &self.connections {
+            connection.fmt(f)?;
+        }
+        for bandwidth in &self.bandwidths {
+            bandwidth.fmt(f)?;
+        }
+        if let Some(key) = self.key {
+            write!(f, "k={}\r\n", key)?;
+        }
+        for attribute in &self.attributes {
+            write!(f, "a={}\r\n", attribute)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::errorparse::SipParseErrorKind;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn parse_media_test() {
+        let input = "m=audio 49170/2 RTP/AVP 0 97 101\r\n\
+                     i=Voice\r\n\
+                     b=TIAS:64000\r\n\
+                     a=rtpmap:97 iLBC/8000\r\n\
+                     a=fmtp:97 mode=30\r\n\
+                     a=rtpmap:101 telephone-event/8000\r\n\
+                     a=ptime:30\r\n\
+                     a=mid:a1\r\n\
+                     a=setup:active\r\n\
+                     a=fingerprint:sha-1 4A:AD:B9:B1\r\n\
+                     a=candidate:1 1 UDP 2130706431 192.0.2.1 49170 typ host\r\n\
+                     a=inactive\r\n\
+                     m=video 0 RTP/AVP 31\r\n"
+            .as_bytes();
+        let (rest, media) = MediaDescription::parse(input).unwrap();
+        assert_eq!(rest, b"m=video 0 RTP/AVP 31\r\n");
+        assert_eq!(media.media, "audio");
+        assert_eq!(media.port, 49170);
+        assert_eq!(media.port_count, Some(2));
+        assert_eq!(media.protocol, "RTP/AVP");
+        assert_eq!(media.formats, vec!["0", "97", "101"]);
+        assert_eq!(media.information, Some("Voice"));
+        assert_eq!(media.bandwidths[0].bandwidth, 64000);
+        assert_eq!(media.rtpmaps().count(), 2);
+        assert_eq!(media.rtpmap(101).unwrap().encoding_name, "telephone-event");
+        assert_eq!(media.rtpmap(0), None);
+        assert_eq!(media.fmtp("97").unwrap().param("mode"), Some("30"));
+        assert_eq!(media.ptime(), Some(30));
+        assert_eq!(media.mid(), Some("a1"));
+        assert_eq!(media.setup(), Some(Setup::Active));
+        assert_eq!(media.fingerprint().unwrap().hash_function, "sha-1");
+        assert_eq!(media.candidates().next().unwrap().port, 49170);
+        assert_eq!(media.direction(), Some(Direction::Inactive));
+        assert!(!media.is_rejected());
+        assert_eq!(
+            media.to_string().as_bytes(),
+            &input[..input.len() - rest.len()]
+        );
+
+        let (rest, media) = MediaDescription::parse(rest).unwrap();
+        assert!(rest.is_empty());
+        assert!(media.is_rejected());
+        assert_eq!(media.direction(), None);
+    }
+
+    #[test]
+    fn parse_media_errors_test() {
+        let err_kind = |input| match MediaDescription::parse(input) {
+            Err(nom::Err::Error(e)) => e.kind,
+            _ => panic!("Media should not be parsed"),
+        };
+        assert_eq!(
+            err_kind(b"a=sendrecv\r\n"),
+            SipParseErrorKind::BadSdp { field: "a" }
+        );
+        assert_eq!(
+            err_kind(b"m=audio RTP/AVP 0\r\n"),
+            SipParseErrorKind::BadSdp { field: "m" }
+        );
+        assert_eq!(
+            err_kind(b"m=audio 70000 RTP/AVP 0\r\n"),
+            SipParseErrorKind::BadSdp { field: "m" }
+        );
+        assert_eq!(
+            err_kind(b"m=audio 5000 RTP/AVP  0\r\n"),
+            SipParseErrorKind::BadSdp { field: "m" }
+        );
+        assert_eq!(
+            err_kind(b"m=audio 5000 RTP/AVP 0\r\na=rtpmap:0\r\n"),
+            SipParseErrorKind::BadSdp { field: "a" }
+        );
+        assert_eq!(
+            err_kind(b"m=audio 5000 RTP/AVP 0\r\ns=-\r\n"),
+            SipParseErrorKind::BadSdp { field: "s" }
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Session Description Protocol
+//! [rfc8866](https://tools.ietf.org/html/rfc8866)
+//!
+//! Parser doesn't copy input. All text fields are borrowed from the body of message.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::sdp::{Direction, SessionDescription};
+//!
+//! let body = "v=0\r\n\
+//! o=alice 2890844526 2890844526 IN IP4 atlanta.example.com\r\n\
+//! s=-\r\n\
+//! c=IN IP4 192.0.2.101\r\n\
+//! t=0 0\r\n\
+//! m=audio 49172 RTP/AVP 0 8\r\n\
+//! a=rtpmap:0 PCMU/8000\r\n\
+//! a=rtpmap:8 PCMA/8000\r\n\
+//! a=sendonly\r\n"
+//!     .as_bytes();
+//!
+//! let (_, sdp) = SessionDescription::parse(body).unwrap();
+//! assert_eq!(sdp.origin.session_version, 2890844526);
+//! assert_eq!(sdp.connection.as_ref().unwrap().address, "192.0.2.101");
+//!
+//! let audio = &sdp.media[0];
+//! assert_eq!(audio.port, 49172);
+//! assert_eq!(audio.formats, vec!["0", "8"]);
+//! assert_eq!(audio.rtpmap(8).unwrap().encoding_name, "PCMA");
+//! assert_eq!(audio.direction(), Some(Direction::SendOnly));
+//!
+//! // Serializer produces the same SDP
+//! assert_eq!(sdp.to_string().as_bytes(), body);
+//! ```
+
+mod attribute;
+pub use attribute::{Attribute, Candidate, Direction, Fingerprint, Fmtp, Group, RtpMap, Setup};
+
+mod media;
+pub use media::MediaDescription;
+
+mod session;
+pub use session::{Bandwidth, Connection, Origin, SessionDescription, Timing};
+
+mod builder;
+pub use builder::{MediaBuilder, SessionBuilder};
+
+mod offer_answer;
+pub use offer_answer::{Codec, MediaCapability, NegotiationError, NegotiationState, OfferAnswer};
+
+use crate::common::errorparse::{SipParseError, SipParseErrorKind};
+use core::str;
+
+/// Line of SDP: `<type>=<value>`
+pub(crate) struct Line<'a> {
+    /// Type of line. Ex: `m`
+    pub field: &'a str,
+    pub value: &'a str,
+    /// Line without CRLF. Used for error location
+    pub raw: &'a [u8],
+}
+
+impl<'a> Line<'a> {
+    pub fn error<O>(&self, message: &'a str) -> Result<O, nom::Err<SipParseError<'a>>> {
+        Err(sdp_error(self.field, message, self.raw))
+    }
+
+    /// Parses value or part of value as a number
+    pub fn number<T: str::FromStr>(&self, value: &str) -> Result<T, nom::Err<SipParseError<'a>>> {
+        match value.parse::<T>() {
+            Ok(number) => Ok(number),
+            Err(_) => self.error("Invalid number"),
+        }
+    }
+
+    /// Splits value by single spaces. Returns error if number of parts is not `N`
+    pub fn split<const N: usize>(&self) -> Result<[&'a str; N], nom::Err<SipParseError<'a>>> {
+        let mut parts = [""; N];
+        let mut count = 0;
+        for part in self.value.split(' ') {
+            if count == N || part.is_empty() {
+                return self.error("Unexpected number of fields");
+            }
+            parts[count] = part;
+            count += 1;
+        }
+        if count != N {
+            return self.error("Unexpected number of fields");
+        }
+        Ok(parts)
+    }
+}
+
+pub(crate) fn sdp_error<'a>(
+    field: &'a str,
+    message: &'a str,
+    input: &'a [u8],
+) -> nom::Err<SipParseError<'a>> {
+    nom::Err::Error(
+        SipParseError::new(1, Some(message))
+            .with_kind(SipParseErrorKind::BadSdp { field })
+            .or_input(input),
+    )
+}
+
+/// Takes `<type>=<value>` line terminated by CRLF or LF.
+/// The last line may be without line ending
+pub(crate) fn take_line(input: &[u8]) -> nom::IResult<&[u8], Line<'_>, SipParseError<'_>> {
+    let (line, rest) = match input.iter().position(|c| *c == b'\n') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => (input, &input[input.len()..]),
+    };
+    let line = match line.last() {
+        Some(b'\r') => &line[..line.len() - 1],
+        _ => line,
+    };
+    if line.len() < 2 || !line[0].is_ascii_lowercase() || line[1] != b'=' {
+        return Err(sdp_error("", "Line must be in form <type>=<value>", line));
+    }
+    // first byte is checked above, so it is always valid UTF-8
+    let field = str::from_utf8(&line[..1]).unwrap_or("");
+    match str::from_utf8(&line[2..]) {
+        Ok(value) => Ok((
+            rest,
+            Line {
+                field,
+                value,
+                raw: line,
+            },
+        )),
+        Err(_) => Err(sdp_error(field, "Value is not valid UTF-8", line)),
+    }
+}
+
+/// Returns true if the rest of SDP has only empty lines
+pub(crate) fn is_end(input: &[u8]) -> bool {
+    input.iter().all(|c| *c == b'\r' || *c == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_line_test() {
+        let (rest, line) = take_line(b"v=0\r\no=-").unwrap();
+        assert_eq!(line.field, "v");
+        assert_eq!(line.value, "0");
+        assert_eq!(rest, b"o=-");
+
+        let (rest, line) = take_line(b"s=Session\n").unwrap();
+        assert_eq!(line.value, "Session");
+        assert!(rest.is_empty());
+
+        let (rest, line) = take_line(b"s=").unwrap();
+        assert_eq!(line.value, "");
+        assert!(rest.is_empty());
+
+        assert!(take_line(b"V=0\r\n").is_err());
+        assert!(take_line(b"v 0\r\n").is_err());
+        assert!(take_line(b"\r\n").is_err());
+        assert!(take_line(b"s=\xff\r\n").is_err());
+    }
+
+    #[test]
+    fn split_test() {
+        let line = Line {
+            field: "c",
+            value: "IN IP4 192.0.2.1",
+            raw: b"c=IN IP4 192.0.2.1",
+        };
+        assert_eq!(line.split::<3>().ok(), Some(["IN", "IP4", "192.0.2.1"]));
+        assert!(line.split::<2>().is_err());
+        assert!(line.split::<4>().is_err());
+    }
+}
@@ -0,0 +1,599 @@
+use crate::sdp::{
+    Attribute, Connection, Direction, MediaBuilder, MediaDescription, Origin, RtpMap,
+    SessionBuilder, SessionDescription,
+};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+/// Codec that can be offered or accepted by local side
+#[derive(Clone, PartialEq, Debug)]
+pub struct Codec<'a> {
+    /// Payload type used in local offers. Answers use payload type of the offer
+    pub format: &'a str,
+    pub rtpmap: RtpMap<'a>,
+    /// Value of `a=fmtp` without format
+    pub fmtp: Option<&'a str>,
+}
+
+impl<'a> Codec<'a> {
+    /// Codec matches offered format with the same encoding.
+    /// Format without `a=rtpmap` is compared with static payload type
+    fn matches(&self, format: &str, media: &MediaDescription<'_>) -> bool {
+        let offered = match format.parse::<u8>() {
+            Ok(payload_type) => media.rtpmap(payload_type),
+            Err(_) => None,
+        };
+        match offered {
+            Some(rtpmap) => {
+                rtpmap
+                    .encoding_name
+                    .eq_ignore_ascii_case(self.rtpmap.encoding_name)
+                    && rtpmap.clock_rate == self.rtpmap.clock_rate
+                    // number of channels is 1 if it is omitted
+                    && rtpmap.encoding_params.unwrap_or("1")
+                        == self.rtpmap.encoding_params.unwrap_or("1")
+            }
+            None => self.format == format,
+        }
+    }
+}
+
+/// Local capabilities for one media stream
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaCapability<'a> {
+    /// Ex: `audio`
+    pub media: &'a str,
+    /// Local port of media stream
+    pub port: u16,
+    /// Transport protocols that are accepted. The first one is used in offers
+    pub protocols: Vec<&'a str>,
+    /// Codecs in order of preference
+    pub codecs: Vec<Codec<'a>>,
+    /// Direction when call is not on hold
+    pub direction: Direction,
+}
+
+impl<'a> MediaCapability<'a> {
+    /// Capability without codecs. Direction is `sendrecv`
+    pub fn new(media: &'a str, port: u16, protocol: &'a str) -> MediaCapability<'a> {
+        MediaCapability {
+            media,
+            port,
+            protocols: vec![protocol],
+            codecs: Vec::new(),
+            direction: Direction::SendRecv,
+        }
+    }
+
+    /// Adds transport protocol that is accepted in offers of remote side
+    pub fn protocol(mut self, protocol: &'a str) -> MediaCapability<'a> {
+        self.protocols.push(protocol);
+        self
+    }
+
+    pub fn codec(mut self, format: &'a str, rtpmap: RtpMap<'a>) -> MediaCapability<'a> {
+        self.codecs.push(Codec {
+            format,
+            rtpmap,
+            fmtp: None,
+        });
+        self
+    }
+
+    /// Sets `a=fmtp` parameters of codec that was added with `format`
+    pub fn fmtp(mut self, format: &'a str, params: &'a str) -> MediaCapability<'a> {
+        if let Some(codec) = self.codecs.iter_mut().find(|codec| codec.format == format) {
+            codec.fmtp = Some(params);
+        }
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> MediaCapability<'a> {
+        self.direction = direction;
+        self
+    }
+
+    fn accepts(&self, media: &MediaDescription<'_>) -> bool {
+        self.media == media.media
+            && self
+                .protocols
+                .iter()
+                .any(|protocol| protocol.eq_ignore_ascii_case(media.protocol))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NegotiationState {
+    /// No offer is pending
+    Stable,
+    /// Local offer is sent and answer is expected
+    LocalOffer,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NegotiationError {
+    /// New offer can't be made or accepted until answer for local offer is received
+    OfferPending,
+    /// Answer is received but no offer was made
+    NoOffer,
+    /// Media streams of answer don't correspond to the offer
+    MediaMismatch,
+    /// None of offered media streams is acceptable
+    NotAcceptable,
+}
+
+impl NegotiationError {
+    /// Status code of response that rejects the request with offer
+    pub fn status_code(&self) -> u16 {
+        match self {
+            NegotiationError::OfferPending => 491,
+            NegotiationError::NoOffer | NegotiationError::MediaMismatch => 400,
+            NegotiationError::NotAcceptable => 488,
+        }
+    }
+}
+
+/// Offer/answer model of SDP
+/// [rfc3264](https://tools.ietf.org/html/rfc3264)
+///
+/// Keeps the last local session description. Re-offers are made from it, so
+/// order of `m=` lines and payload types stay the same during the session.
+/// Version of `o=` line is incremented every time local description changes.
+/// ```rust
+/// use sipmsg::sdp::{Direction, MediaCapability, OfferAnswer, Origin, RtpMap, SessionDescription};
+///
+/// let origin = Origin {
+///     username: "-",
+///     session_id: 4242,
+///     session_version: 1,
+///     nettype: "IN",
+///     addrtype: "IP4",
+///     unicast_address: "192.0.2.10",
+/// };
+/// let audio = MediaCapability::new("audio", 30000, "RTP/AVP")
+///     .codec("0", RtpMap::new(0, "PCMU", 8000))
+///     .codec("101", RtpMap::new(101, "telephone-event", 8000));
+/// let mut negotiator = OfferAnswer::new(origin, vec![audio]);
+///
+/// let offer = "v=0\r\n\
+/// o=bob 1 1 IN IP4 192.0.2.20\r\n\
+/// s=-\r\n\
+/// c=IN IP4 192.0.2.20\r\n\
+/// t=0 0\r\n\
+/// m=audio 40000 RTP/AVP 8 0 96\r\n\
+/// a=rtpmap:96 telephone-event/8000\r\n\
+/// a=sendonly\r\n";
+/// let (_, offer) = SessionDescription::parse(offer.as_bytes()).unwrap();
+///
+/// let answer = negotiator.receive_offer(&offer).unwrap();
+/// assert_eq!(
+///     answer,
+///     "v=0\r\n\
+///      o=- 4242 1 IN IP4 192.0.2.10\r\n\
+///      s=-\r\n\
+///      c=IN IP4 192.0.2.10\r\n\
+///      t=0 0\r\n\
+///      m=audio 30000 RTP/AVP 0 96\r\n\
+///      a=rtpmap:0 PCMU/8000\r\n\
+///      a=rtpmap:96 telephone-event/8000\r\n\
+///      a=recvonly\r\n"
+/// );
+/// ```
+pub struct OfferAnswer<'a> {
+    origin: Origin<'a>,
+    capabilities: Vec<MediaCapability<'a>>,
+    state: NegotiationState,
+    on_hold: bool,
+    /// Serialized local description that was sent last
+    local: Option<String>,
+}
+
+impl<'a> OfferAnswer<'a> {
+    /// `origin` is used in all local descriptions. Its address is used in `c=` line
+    pub fn new(origin: Origin<'a>, capabilities: Vec<MediaCapability<'a>>) -> OfferAnswer<'a> {
+        OfferAnswer {
+            origin,
+            capabilities,
+            state: NegotiationState::Stable,
+            on_hold: false,
+            local: None,
+        }
+    }
+
+    pub fn state(&self) -> NegotiationState {
+        self.state
+    }
+
+    pub fn is_on_hold(&self) -> bool {
+        self.on_hold
+    }
+
+    /// Next offer puts media streams on hold. Local side stops receiving media:
+    /// `sendrecv` becomes `sendonly` and `recvonly` becomes `inactive`
+    pub fn hold(&mut self) {
+        self.on_hold = true;
+    }
+
+    /// Next offer restores directions of local capabilities
+    pub fn resume(&mut self) {
+        self.on_hold = false;
+    }
+
+    /// Last local offer or answer
+    pub fn local_description(&self) -> Option<SessionDescription<'_>> {
+        let local = self.local.as_ref()?;
+        match SessionDescription::parse(local.as_bytes()) {
+            Ok((_, sdp)) => Some(sdp),
+            Err(_) => None,
+        }
+    }
+
+    /// Makes offer and returns it serialized.
+    /// The first offer has `m=` line for each capability, re-offers keep `m=` lines
+    /// of the last local description and update their directions
+    pub fn create_offer(&mut self) -> Result<&str, NegotiationError> {
+        if self.state == NegotiationState::LocalOffer {
+            return Err(NegotiationError::OfferPending);
+        }
+        let offer = match self.local_description() {
+            Some(previous) => {
+                let mut offer = previous.clone();
+                let capabilities = self.assign(&offer.media);
+                for (media, capability) in offer.media.iter_mut().zip(capabilities) {
+                    if let Some(capability) = capability {
+                        set_direction(media, self.local_direction(capability));
+                    }
+                }
+                self.versioned(offer)
+            }
+            None => {
+                let mut builder = self.session();
+                for capability in &self.capabilities {
+                    let protocol = capability.protocols.first().copied().unwrap_or("RTP/AVP");
+                    let mut media = MediaBuilder::new(capability.media, capability.port, protocol);
+                    for codec in &capability.codecs {
+                        media = media.codec(codec.format, codec.rtpmap.clone());
+                        if let Some(fmtp) = codec.fmtp {
+                            media = media.fmtp(codec.format, fmtp);
+                        }
+                    }
+                    builder =
+                        builder.media(media.direction(self.local_direction(capability)).build());
+                }
+                self.versioned(builder.build())
+            }
+        };
+        self.state = NegotiationState::LocalOffer;
+        Ok(self.local.insert(offer))
+    }
+
+    /// Completes exchange started by `create_offer`.
+    /// Exchange is completed even if answer is not valid
+    pub fn receive_answer(
+        &mut self,
+        answer: &SessionDescription<'_>,
+    ) -> Result<(), NegotiationError> {
+        if self.state != NegotiationState::LocalOffer {
+            return Err(NegotiationError::NoOffer);
+        }
+        self.state = NegotiationState::Stable;
+        let offer = match self.local_description() {
+            Some(offer) => offer,
+            None => return Err(NegotiationError::NoOffer),
+        };
+        if offer.media.len() != answer.media.len() {
+            return Err(NegotiationError::MediaMismatch);
+        }
+        for (offered, answered) in offer.media.iter().zip(&answer.media) {
+            if offered.media != answered.media {
+                return Err(NegotiationError::MediaMismatch);
+            }
+            if !answered.is_rejected()
+                && !answered
+                    .formats
+                    .iter()
+                    .any(|format| offered.formats.contains(format))
+            {
+                return Err(NegotiationError::MediaMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes answer to remote offer and returns it serialized.
+    /// Answer has `m=` line for each offered one. Media stream is rejected with port 0
+    /// if there is no capability for it or no common codec.
+    /// Returns `NotAcceptable` error if all offered streams are rejected
+    pub fn receive_offer(
+        &mut self,
+        offer: &SessionDescription<'_>,
+    ) -> Result<&str, NegotiationError> {
+        if self.state == NegotiationState::LocalOffer {
+            return Err(NegotiationError::OfferPending);
+        }
+        let capabilities = self.assign(&offer.media);
+        let mut builder = self.session();
+        let mut accepted = false;
+        for (index, (offered, capability)) in offer.media.iter().zip(capabilities).enumerate() {
+            let media = match capability {
+                Some(capability) => self.answer_media(offer, index, capability),
+                None => None,
+            };
+            let media = match media {
+                Some(media) => {
+                    accepted = true;
+                    media
+                }
+                None => {
+                    let mut rejected = MediaDescription::new(offered.media, 0, offered.protocol);
+                    rejected.formats = offered.formats.clone();
+                    rejected
+                }
+            };
+            builder = builder.media(media);
+        }
+        if !accepted && !offer.media.is_empty() {
+            return Err(NegotiationError::NotAcceptable);
+        }
+        let answer = self.versioned(builder.build());
+        Ok(self.local.insert(answer))
+    }
+
+    /// Accepted media stream or `None` if there are no common codecs
+    fn answer_media<'b>(
+        &self,
+        offer: &SessionDescription<'b>,
+        index: usize,
+        capability: &MediaCapability<'b>,
+    ) -> Option<MediaDescription<'b>> {
+        let offered = &offer.media[index];
+        let mut media = MediaBuilder::new(offered.media, capability.port, offered.protocol);
+        let mut found = false;
+        for codec in &capability.codecs {
+            let format = match offered
+                .formats
+                .iter()
+                .find(|format| codec.matches(format, offered))
+            {
+                Some(format) => *format,
+                None => continue,
+            };
+            let rtpmap = RtpMap {
+                payload_type: format.parse().unwrap_or(codec.rtpmap.payload_type),
+                ..codec.rtpmap.clone()
+            };
+            media = media.codec(format, rtpmap);
+            if let Some(fmtp) = codec.fmtp {
+                media = media.fmtp(format, fmtp);
+            }
+            found = true;
+        }
+        if !found {
+            return None;
+        }
+        let mut direction = offer.media_direction(index).unwrap_or(Direction::SendRecv);
+        // rfc2543 style hold: remote side doesn't want to receive media
+        if matches!(offer.media_connection(index), Some(connection) if connection.is_unspecified())
+        {
+            direction = Direction::from_flags(direction.is_sending(), false);
+        }
+        let direction = direction
+            .reverse()
+            .intersect(self.local_direction(capability));
+        Some(media.direction(direction).build())
+    }
+
+    /// Capability for each media stream. Capability is used at most once.
+    /// Rejected streams have no capability
+    fn assign<'s>(
+        &'s self,
+        media: &[MediaDescription<'_>],
+    ) -> Vec<Option<&'s MediaCapability<'a>>> {
+        let mut used = vec![false; self.capabilities.len()];
+        media
+            .iter()
+            .map(|media| {
+                if media.is_rejected() {
+                    return None;
+                }
+                let index = (0..self.capabilities.len())
+                    .find(|index| !used[*index] && self.capabilities[*index].accepts(media))?;
+                used[index] = true;
+                Some(&self.capabilities[index])
+            })
+            .collect()
+    }
+
+    fn local_direction(&self, capability: &MediaCapability<'_>) -> Direction {
+        if self.on_hold {
+            Direction::from_flags(capability.direction.is_sending(), false)
+        } else {
+            capability.direction
+        }
+    }
+
+    fn session(&self) -> SessionBuilder<'a> {
+        SessionBuilder::new(self.origin.clone())
+            .connection(Connection::new(self.origin.unicast_address))
+    }
+
+    /// Serializes local description. Version is incremented if it differs from the last one
+    fn versioned(&self, mut sdp: SessionDescription<'_>) -> String {
+        let previous = match self.local_description() {
+            Some(previous) => previous,
+            None => return sdp.to_string(),
+        };
+        sdp.origin.session_version = previous.origin.session_version;
+        let serialized = sdp.to_string();
+        if Some(&serialized) == self.local.as_ref() {
+            return serialized;
+        }
+        sdp.origin.session_version += 1;
+        sdp.to_string()
+    }
+}
+
+/// Replaces direction attribute of media
+fn set_direction(media: &mut MediaDescription<'_>, direction: Direction) {
+    media
+        .attributes
+        .retain(|attr| !matches!(attr, Attribute::Direction(_)));
+    media.attributes.push(Attribute::Direction(direction));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> Origin<'static> {
+        Origin {
+            username: "-",
+            session_id: 7,
+            session_version: 1,
+            nettype: "IN",
+            addrtype: "IP4",
+            unicast_address: "192.0.2.1",
+        }
+    }
+
+    fn negotiator() -> OfferAnswer<'static> {
+        let audio = MediaCapability::new("audio", 20000, "RTP/AVP")
+            .protocol("RTP/SAVP")
+            .codec("111", RtpMap::new(111, "opus", 48000))
+            .fmtp("111", "useinbandfec=1")
+            .codec("0", RtpMap::new(0, "PCMU", 8000));
+        OfferAnswer::new(origin(), vec![audio])
+    }
+
+    fn parse(sdp: &str) -> SessionDescription<'_> {
+        SessionDescription::parse(sdp.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn offer_hold_resume_test() {
+        let mut negotiator = negotiator();
+        let offer = negotiator.create_offer().unwrap().to_string();
+        assert_eq!(
+            offer,
+            "v=0\r\n\
+             o=- 7 1 IN IP4 192.0.2.1\r\n\
+             s=-\r\n\
+             c=IN IP4 192.0.2.1\r\n\
+             t=0 0\r\n\
+             m=audio 20000 RTP/AVP 111 0\r\n\
+             a=rtpmap:111 opus/48000\r\n\
+             a=fmtp:111 useinbandfec=1\r\n\
+             a=rtpmap:0 PCMU/8000\r\n\
+             a=sendrecv\r\n"
+        );
+        assert_eq!(negotiator.state(), NegotiationState::LocalOffer);
+        assert_eq!(
+            negotiator.create_offer(),
+            Err(NegotiationError::OfferPending)
+        );
+
+        let answer = parse(
+            "v=0\r\no=- 1 1 IN IP4 192.0.2.2\r\ns=-\r\nc=IN IP4 192.0.2.2\r\nt=0 0\r\n\
+             m=audio 30000 RTP/AVP 0\r\n",
+        );
+        assert_eq!(negotiator.receive_answer(&answer), Ok(()));
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+        assert_eq!(
+            negotiator.receive_answer(&answer),
+            Err(NegotiationError::NoOffer)
+        );
+
+        // session refresh doesn't change version
+        let refresh = negotiator.create_offer().unwrap().to_string();
+        assert_eq!(refresh, offer);
+        negotiator.receive_answer(&answer).unwrap();
+
+        negotiator.hold();
+        let held = parse(negotiator.create_offer().unwrap());
+        assert_eq!(held.origin.session_version, 2);
+        assert_eq!(held.media[0].formats, vec!["111", "0"]);
+        assert_eq!(held.media_direction(0), Some(Direction::SendOnly));
+        negotiator.receive_answer(&answer).unwrap();
+
+        negotiator.resume();
+        let resumed = negotiator.create_offer().unwrap();
+        let resumed = parse(resumed);
+        assert_eq!(resumed.origin.session_version, 3);
+        assert_eq!(resumed.media_direction(0), Some(Direction::SendRecv));
+    }
+
+    #[test]
+    fn answer_test() {
+        let mut negotiator = negotiator();
+        let offer = parse(
+            "v=0\r\no=bob 1 1 IN IP4 192.0.2.2\r\ns=-\r\nc=IN IP4 192.0.2.2\r\nt=0 0\r\n\
+             m=video 30002 RTP/AVP 31\r\n\
+             m=audio 30000 RTP/SAVP 0 98\r\n\
+             a=rtpmap:98 OPUS/48000/1\r\n\
+             m=audio 0 RTP/AVP 0\r\n\
+             m=audio 30004 RTP/AVP 0\r\n",
+        );
+        let answer = parse(negotiator.receive_offer(&offer).unwrap());
+        assert_eq!(answer.origin.session_version, 1);
+        assert_eq!(answer.media.len(), 4);
+        assert!(answer.media[0].is_rejected());
+        assert_eq!(answer.media[0].formats, vec!["31"]);
+        let audio = &answer.media[1];
+        assert_eq!(audio.port, 20000);
+        assert_eq!(audio.protocol, "RTP/SAVP");
+        assert_eq!(audio.formats, vec!["98", "0"]);
+        assert_eq!(audio.rtpmap(98).unwrap().encoding_name, "opus");
+        assert_eq!(audio.fmtp("98").unwrap().params, "useinbandfec=1");
+        assert_eq!(audio.direction(), Some(Direction::SendRecv));
+        // port 0 is rejected, capability is already used by the second stream
+        assert!(answer.media[2].is_rejected());
+        assert!(answer.media[3].is_rejected());
+
+        // remote hold in rfc2543 style
+        let offer = parse(
+            "v=0\r\no=bob 1 2 IN IP4 192.0.2.2\r\ns=-\r\nc=IN IP4 0.0.0.0\r\nt=0 0\r\n\
+             m=video 0 RTP/AVP 31\r\n\
+             m=audio 30000 RTP/SAVP 0\r\n\
+             m=audio 0 RTP/AVP 0\r\n\
+             m=audio 0 RTP/AVP 0\r\n",
+        );
+        let answer = parse(negotiator.receive_offer(&offer).unwrap());
+        assert_eq!(answer.origin.session_version, 2);
+        assert_eq!(answer.media_direction(1), Some(Direction::RecvOnly));
+
+        let offer = parse(
+            "v=0\r\no=bob 1 1 IN IP4 192.0.2.2\r\ns=-\r\nt=0 0\r\n\
+             m=audio 30000 RTP/AVP 8\r\n",
+        );
+        let err = negotiator.receive_offer(&offer).unwrap_err();
+        assert_eq!(err, NegotiationError::NotAcceptable);
+        assert_eq!(err.status_code(), 488);
+    }
+
+    #[test]
+    fn glare_and_mismatch_test() {
+        let mut negotiator = negotiator();
+        negotiator.create_offer().unwrap();
+        let remote = parse(
+            "v=0\r\no=bob 1 1 IN IP4 192.0.2.2\r\ns=-\r\nt=0 0\r\n\
+             m=audio 30000 RTP/AVP 0\r\n",
+        );
+        assert_eq!(
+            negotiator.receive_offer(&remote).unwrap_err().status_code(),
+            491
+        );
+
+        let answer = parse(
+            "v=0\r\no=bob 1 1 IN IP4 192.0.2.2\r\ns=-\r\nt=0 0\r\n\
+             m=audio 30000 RTP/AVP 8\r\n",
+        );
+        assert_eq!(
+            negotiator.receive_answer(&answer),
+            Err(NegotiationError::MediaMismatch)
+        );
+        assert_eq!(negotiator.state(), NegotiationState::Stable);
+    }
+}
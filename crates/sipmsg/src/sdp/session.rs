@@ -0,0 +1,495 @@
+use crate::{
+    common::errorparse::SipParseError,
+    sdp::{
+        is_end, sdp_error, take_line, Attribute, Direction, Fingerprint, Group, Line,
+        MediaDescription,
+    },
+};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// `o=<username> <sess-id> <sess-version> <nettype> <addrtype> <unicast-address>`
+/// [rfc8866 section-5.2](https://tools.ietf.org/html/rfc8866#section-5.2)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Origin<'a> {
+    /// `-` if user is not known
+    pub username: &'a str,
+    pub session_id: u64,
+    /// Must be increased when session description is modified
+    pub session_version: u64,
+    pub nettype: &'a str,
+    pub addrtype: &'a str,
+    pub unicast_address: &'a str,
+}
+
+impl<'a> Origin<'a> {
+    fn parse(line: &Line<'a>) -> Result<Origin<'a>, nom::Err<SipParseError<'a>>> {
+        let [username, session_id, session_version, nettype, addrtype, unicast_address] =
+            line.split::<6>()?;
+        Ok(Origin {
+            username,
+            session_id: line.number(session_id)?,
+            session_version: line.number(session_version)?,
+            nettype,
+            addrtype,
+            unicast_address,
+        })
+    }
+}
+
+/// `c=<nettype> <addrtype> <connection-address>`
+/// [rfc8866 section-5.7](https://tools.ietf.org/html/rfc8866#section-5.7)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Connection<'a> {
+    /// `IN`
+    pub nettype: &'a str,
+    /// `IP4` or `IP6`
+    pub addrtype: &'a str,
+    /// Address with optional TTL and number of addresses. Ex: `233.252.0.1/127/3`
+    pub address: &'a str,
+}
+
+impl<'a> Connection<'a> {
+    /// Connection `IN IP4 <address>` or `IN IP6 <address>` depending on address
+    pub fn new(address: &'a str) -> Connection<'a> {
+        Connection {
+            nettype: "IN",
+            addrtype: if address.contains(':') { "IP6" } else { "IP4" },
+            address,
+        }
+    }
+
+    pub(crate) fn parse(line: &Line<'a>) -> Result<Connection<'a>, nom::Err<SipParseError<'a>>> {
+        let [nettype, addrtype, address] = line.split::<3>()?;
+        Ok(Connection {
+            nettype,
+            addrtype,
+            address,
+        })
+    }
+
+    /// Address without TTL and number of addresses
+    pub fn host(&self) -> &'a str {
+        self.address.split('/').next().unwrap_or(self.address)
+    }
+
+    /// `0.0.0.0` is used by RFC 2543 endpoints to put session on hold
+    pub fn is_unspecified(&self) -> bool {
+        self.host() == "0.0.0.0" || self.host() == "::"
+    }
+}
+
+/// `b=<bwtype>:<bandwidth>`
+/// [rfc8866 section-5.8](https://tools.ietf.org/html/rfc8866#section-5.8)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bandwidth<'a> {
+    /// Ex: `AS`, `CT`, `TIAS`
+    pub bwtype: &'a str,
+    /// Kilobits per second for `AS` and `CT`
+    pub bandwidth: u64,
+}
+
+impl<'a> Bandwidth<'a> {
+    pub(crate) fn parse(line: &Line<'a>) -> Result<Bandwidth<'a>, nom::Err<SipParseError<'a>>> {
+        let mut parts = line.value.splitn(2, ':');
+        let bwtype = parts.next().unwrap_or("");
+        match parts.next() {
+            Some(bandwidth) if !bwtype.is_empty() => Ok(Bandwidth {
+                bwtype,
+                bandwidth: line.number(bandwidth)?,
+            }),
+            _ => line.error("Bandwidth must be in form <bwtype>:<bandwidth>"),
+        }
+    }
+}
+
+/// `t=<start-time> <stop-time>` and following `r=` lines
+/// [rfc8866 section-5.9](https://tools.ietf.org/html/rfc8866#section-5.9)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Timing<'a> {
+    /// NTP time. `0` means unbounded session
+    pub start: u64,
+    pub stop: u64,
+    /// Values of `r=` lines
+    pub repeats: Vec<&'a str>,
+}
+
+impl<'a> Timing<'a> {
+    /// `t=0 0`
+    pub fn permanent() -> Timing<'a> {
+        Timing {
+            start: 0,
+            stop: 0,
+            repeats: Vec::new(),
+        }
+    }
+
+    fn parse(line: &Line<'a>) -> Result<Timing<'a>, nom::Err<SipParseError<'a>>> {
+        let [start, stop] = line.split::<2>()?;
+        Ok(Timing {
+            start: line.number(start)?,
+            stop: line.number(stop)?,
+            repeats: Vec::new(),
+        })
+    }
+}
+
+/// SDP session description
+/// [rfc8866 section-5](https://tools.ietf.org/html/rfc8866#section-5)
+#[derive(Clone, PartialEq, Debug)]
+pub struct SessionDescription<'a> {
+    /// `v=`. Always `0`
+    pub version: u8,
+    /// `o=`
+    pub origin: Origin<'a>,
+    /// `s=`. `-` if session has no name
+    pub session_name: &'a str,
+    /// `i=`
+    pub information: Option<&'a str>,
+    /// `u=`
+    pub uri: Option<&'a str>,
+    /// `e=`
+    pub emails: Vec<&'a str>,
+    /// `p=`
+    pub phones: Vec<&'a str>,
+    /// `c=` of session level. Used by media without own `c=`
+    pub connection: Option<Connection<'a>>,
+    /// `b=`
+    pub bandwidths: Vec<Bandwidth<'a>>,
+    /// `t=` with `r=`. There is at least one timing
+    pub timings: Vec<Timing<'a>>,
+    /// `z=`
+    pub time_zones: Option<&'a str>,
+    /// `k=`. Obsolete
+    pub key: Option<&'a str>,
+    /// `a=` of session level
+    pub attributes: Vec<Attribute<'a>>,
+    /// `m=` sections
+    pub media: Vec<MediaDescription<'a>>,
+}
+
+impl<'a> SessionDescription<'a> {
+    pub fn parse(
+        input: &'a [u8],
+    ) -> nom::IResult<&'a [u8], SessionDescription<'a>, SipParseError<'a>> {
+        let (input, line) = take_line(input)?;
+        if line.field != "v" {
+            return line.error("SDP must start with v= line");
+        }
+        if line.value != "0" {
+            return line.error("Only version 0 is supported");
+        }
+        let (input, line) = take_line(input)?;
+        if line.field != "o" {
+            return line.error("v= line must be followed by o= line");
+        }
+        let origin = Origin::parse(&line)?;
+        let (mut input, line) = take_line(input)?;
+        if line.field != "s" {
+            return line.error("o= line must be followed by s= line");
+        }
+        let mut sdp = SessionDescription::new(origin, line.value);
+        // only t= lines of body
+        sdp.timings.clear();
+
+        while !is_end(input) {
+            let (rest, line) = take_line(input)?;
+            if line.field == "m" {
+                break;
+            }
+            input = rest;
+            match line.field {
+                "i" => sdp.information = Some(line.value),
+                "u" => sdp.uri = Some(line.value),
+                "e" => sdp.emails.push(line.value),
+                "p" => sdp.phones.push(line.value),
+                "c" => sdp.connection = Some(Connection::parse(&line)?),
+                "b" => sdp.bandwidths.push(Bandwidth::parse(&line)?),
+                "t" => sdp.timings.push(Timing::parse(&line)?),
+                "r" => match sdp.timings.last_mut() {
+                    Some(timing) => timing.repeats.push(line.value),
+                    None => return line.error("r= line must follow t= line"),
+                },
+                "z" => sdp.time_zones = Some(line.value),
+                "k" => sdp.key = Some(line.value),
+                "a" => sdp.attributes.push(parse_attribute(&line)?),
+                _ => return line.error("Unexpected line in session description"),
+            }
+        }
+        if sdp.timings.is_empty() {
+            return Err(sdp_error(
+                "t",
+                "Session description must have t= line",
+                input,
+            ));
+        }
+
+        while !is_end(input) {
+            let (rest, media) = MediaDescription::parse(input)?;
+            sdp.media.push(media);
+            input = rest;
+        }
+        Ok((&input[input.len()..], sdp))
+    }
+
+    /// Session description without media. Timing is `t=0 0`
+    pub fn new(origin: Origin<'a>, session_name: &'a str) -> SessionDescription<'a> {
+        SessionDescription {
+            version: 0,
+            origin,
+            session_name,
+            information: None,
+            uri: None,
+            emails: Vec::new(),
+            phones: Vec::new(),
+            connection: None,
+            bandwidths: Vec::new(),
+            timings: alloc::vec![Timing::permanent()],
+            time_zones: None,
+            key: None,
+            attributes: Vec::new(),
+            media: Vec::new(),
+        }
+    }
+
+    /// First session level attribute with `name`
+    pub fn attribute(&self, name: &str) -> Option<&Attribute<'a>> {
+        self.attributes.iter().find(|attr| attr.name() == name)
+    }
+
+    /// Session level direction attribute. Media level attribute overrides it
+    pub fn direction(&self) -> Option<Direction> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Direction(direction) => Some(*direction),
+            _ => None,
+        })
+    }
+
+    /// Session level fingerprint
+    pub fn fingerprint(&self) -> Option<&Fingerprint<'a>> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Fingerprint(fingerprint) => Some(fingerprint),
+            _ => None,
+        })
+    }
+
+    /// `a=group` attributes
+    pub fn groups(&self) -> impl Iterator<Item = &Group<'a>> {
+        self.attributes.iter().filter_map(|attr| match attr {
+            Attribute::Group(group) => Some(group),
+            _ => None,
+        })
+    }
+
+    /// Direction of media with `index` taking session level attribute into account.
+    /// `sendrecv` if direction is not specified
+    pub fn media_direction(&self, index: usize) -> Option<Direction> {
+        let media = self.media.get(index)?;
+        Some(
+            media
+                .direction()
+                .or_else(|| self.direction())
+                .unwrap_or(Direction::SendRecv),
+        )
+    }
+
+    /// Connection of media with `index`. Session level `c=` is used if media has no own one
+    pub fn media_connection(&self, index: usize) -> Option<&Connection<'a>> {
+        let media = self.media.get(index)?;
+        media.connections.first().or(self.connection.as_ref())
+    }
+}
+
+pub(crate) fn parse_attribute<'a>(
+    line: &Line<'a>,
+) -> Result<Attribute<'a>, nom::Err<SipParseError<'a>>> {
+    match Attribute::parse(line.value) {
+        Some(attr) => Ok(attr),
+        None => line.error("Malformed attribute"),
+    }
+}
+
+impl<'a> fmt::Display for Connection<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "c={} {} {}\r\n",
+            self.nettype, self.addrtype, self.address
+        )
+    }
+}
+
+impl<'a> fmt::Display for Bandwidth<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b={}:{}\r\n", self.bwtype, self.bandwidth)
+    }
+}
+
+impl<'a> fmt::Display for SessionDescription<'a> {
+    /// SDP lines in order defined by rfc8866. Each line ends with CRLF
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let o = &self.origin;
+        write!(f, "v={}\r\n", self.version)?;
+        write!(
+            f,
+            "o={} {} {} {} {} {}\r\n",
+            o.username, o.session_id, o.session_version, o.nettype, o.addrtype, o.unicast_address
+        )?;
+        write!(f, "s={}\r\n", self.session_name)?;
+        if let Some(information) = self.information {
+            write!(f, "i={}\r\n", information)?;
+        }
+        if let Some(uri) = self.uri {
+            write!(f, "u={}\r\n", uri)?;
+        }
+        for email in &self.emails {
+            write!(f, "e={}\r\n", email)?;
+        }
+        for phone in &self.phones {
+            write!(f, "p={}\r\n", phone)?;
+        }
+        if let Some(connection) = &self.connection {
+            connection.fmt(f)?;
+        }
+        for bandwidth in &self.bandwidths {
+            bandwidth.fmt(f)?;
+        }
+        for timing in &self.timings {
+            write!(f, "t={} {}\r\n", timing.start, timing.stop)?;
+            for repeat in &timing.repeats {
+                write!(f, "r={}\r\n", repeat)?;
+            }
+        }
+        if let Some(time_zones) = self.time_zones {
+            write!(f, "z={}\r\n", time_zones)?;
+        }
+        if let Some(key) = self.key {
+            write!(f, "k={}\r\n", key)?;
+        }
+        for attribute in &self.attributes {
+            write!(f, "a={}\r\n", attribute)?;
+        }
+        for media in &self.media {
+            media.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::errorparse::SipParseErrorKind;
+    use alloc::{string::ToString, vec};
+
+    fn parse_error(input: &[u8]) -> SipParseError<'_> {
+        match SessionDescription::parse(input) {
+            Ok(_) => panic!("SDP should not be parsed"),
+            Err(nom::Err::Error(e)) => e.locate(input),
+            Err(_) => panic!("Unexpected error type"),
+        }
+    }
+
+    #[test]
+    fn parse_session_test() {
+        let body = "v=0\r\n\
+                    o=jdoe 3724394400 3724394405 IN IP4 198.51.100.1\r\n\
+                    s=Call to John Smith\r\n\
+                    i=SDP Offer #1\r\n\
+                    u=http://www.jdoe.example.com/home.html\r\n\
+                    e=Jane Doe <jane@jdoe.example.com>\r\n\
+                    p=+1 617 555-6011\r\n\
+                    c=IN IP4 198.51.100.1\r\n\
+                    b=AS:128\r\n\
+                    t=0 0\r\n\
+                    r=7d 1h 0 25h\r\n\
+                    a=group:BUNDLE 1 2\r\n\
+                    a=recvonly\r\n\
+                    m=audio 49170 RTP/AVP 0\r\n\
+                    m=video 51372 RTP/AVP 99\r\n\
+                    c=IN IP6 2001:db8::2\r\n\
+                    a=rtpmap:99 h263-1998/90000\r\n\
+                    a=sendrecv\r\n"
+            .as_bytes();
+        let (rest, sdp) = SessionDescription::parse(body).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(sdp.origin.username, "jdoe");
+        assert_eq!(sdp.origin.session_id, 3724394400);
+        assert_eq!(sdp.origin.session_version, 3724394405);
+        assert_eq!(sdp.origin.unicast_address, "198.51.100.1");
+        assert_eq!(sdp.session_name, "Call to John Smith");
+        assert_eq!(sdp.information, Some("SDP Offer #1"));
+        assert_eq!(sdp.emails, vec!["Jane Doe <jane@jdoe.example.com>"]);
+        assert_eq!(sdp.phones, vec!["+1 617 555-6011"]);
+        assert_eq!(sdp.bandwidths[0].bwtype, "AS");
+        assert_eq!(sdp.bandwidths[0].bandwidth, 128);
+        assert_eq!(sdp.timings[0].repeats, vec!["7d 1h 0 25h"]);
+        assert_eq!(sdp.groups().next().unwrap().mids, vec!["1", "2"]);
+        assert_eq!(sdp.media.len(), 2);
+        assert_eq!(sdp.media_direction(0), Some(Direction::RecvOnly));
+        assert_eq!(sdp.media_direction(1), Some(Direction::SendRecv));
+        assert_eq!(sdp.media_direction(2), None);
+        assert_eq!(sdp.media_connection(0).unwrap().address, "198.51.100.1");
+        assert_eq!(sdp.media_connection(1).unwrap().address, "2001:db8::2");
+        assert_eq!(sdp.to_string().as_bytes(), body);
+    }
+
+    #[test]
+    fn parse_lf_test() {
+        let (rest, sdp) =
+            SessionDescription::parse(b"v=0\no=- 1 1 IN IP4 0.0.0.0\ns=-\nt=0 0\n\r\n").unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(sdp.timings, vec![Timing::permanent()]);
+        assert!(sdp.media.is_empty());
+    }
+
+    #[test]
+    fn new_session_test() {
+        let origin = Origin {
+            username: "-",
+            session_id: 1,
+            session_version: 1,
+            nettype: "IN",
+            addrtype: "IP4",
+            unicast_address: "192.0.2.1",
+        };
+        let sdp = SessionDescription::new(origin, "-");
+        let body = sdp.to_string();
+        assert_eq!(body, "v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nt=0 0\r\n");
+        let (_, parsed) = SessionDescription::parse(body.as_bytes()).unwrap();
+        assert_eq!(parsed, sdp);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        let err = parse_error(b"o=- 1 1 IN IP4 0.0.0.0\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "o" });
+
+        let err = parse_error(b"v=1\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "v" });
+
+        let err = parse_error(b"v=0\r\no=- x 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "o" });
+        assert_eq!(err.line, Some(2));
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "t" });
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nr=1 2\r\nt=0 0\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "r" });
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\nx=1\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "x" });
+        assert_eq!(err.line, Some(5));
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\n\r\na=x\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "" });
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nc=IN IP4\r\nt=0 0\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "c" });
+
+        let err = parse_error(b"v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nb=128\r\nt=0 0\r\n");
+        assert_eq!(err.kind, SipParseErrorKind::BadSdp { field: "b" });
+        assert_eq!(err.reason_phrase(), "Malformed SDP b= Line");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    common::{
+        bnfcore::{is_crlf, is_token_char},
+        errorparse::SipParseError,
+    },
+    options::ParseOptions,
+    SipHeaders, SipRequestLine, SipResponseStatusCode, SipResponseStatusLine,
+};
+
+/// Start line of `message/sipfrag`
+pub enum StartLine<'a> {
+    Request(SipRequestLine<'a>),
+    Status(SipResponseStatusLine<'a>),
+}
+
+/// Fragment of SIP message. Ex: body of NOTIFY for REFER
+/// [rfc3420](https://tools.ietf.org/html/rfc3420)
+///
+/// `sipfrag = [ start-line ] *message-header [ CRLF [ message-body ] ]`
+/// ```rust
+/// use sipmsg::{SipFrag, SipResponseStatusCode, SipRFCHeader};
+///
+/// let (_, frag) = SipFrag::parse(b"SIP/2.0 180 Ringing\r\nCSeq: 1 INVITE\r\n").unwrap();
+/// assert_eq!(frag.status_code(), Some(SipResponseStatusCode::Ringing));
+/// assert!(!frag.is_final());
+/// assert_eq!(
+///     frag.headers.get_rfc_s(SipRFCHeader::CSeq).unwrap().value.vstr,
+///     "1 INVITE"
+/// );
+/// assert_eq!(frag.body, None);
+/// ```
+pub struct SipFrag<'a> {
+    pub start_line: Option<StartLine<'a>>,
+    /// Headers of fragment. Empty if fragment has no headers
+    pub headers: SipHeaders<'a>,
+    /// Bytes after empty line. `None` if there is no empty line
+    pub body: Option<&'a [u8]>,
+}
+
+impl<'a> SipFrag<'a> {
+    pub fn parse(input: &'a [u8]) -> nom::IResult<&'a [u8], SipFrag<'a>, SipParseError<'a>> {
+        SipFrag::parse_ext(input, &ParseOptions::default())
+    }
+
+    /// Start line must end with CRLF. Empty line after headers is optional
+    pub fn parse_ext(
+        input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], SipFrag<'a>, SipParseError<'a>> {
+        SipFrag::parse_fragment(input, options).map_err(|e| e.map(|e| e.locate(input)))
+    }
+
+    fn parse_fragment(
+        input: &'a [u8],
+        options: &ParseOptions,
+    ) -> nom::IResult<&'a [u8], SipFrag<'a>, SipParseError<'a>> {
+        let (input, start_line) = if input.starts_with(b"SIP/") {
+            let (input, sl) = SipResponseStatusLine::parse(input)?;
+            (input, Some(StartLine::Status(sl)))
+        } else if is_request_line(input) {
+            let (input, rl) = SipRequestLine::parse_ext(input, options)?;
+            (input, Some(StartLine::Request(rl)))
+        } else {
+            (input, None)
+        };
+
+        let (input, headers) = if input.is_empty() || is_crlf(input) {
+            (input, SipHeaders::new())
+        } else {
+            SipHeaders::parse_fragment(input, options)?
+        };
+
+        let body = if is_crlf(input) {
+            Some(&input[2..])
+        } else {
+            None
+        };
+        Ok((
+            &input[input.len()..],
+            SipFrag {
+                start_line,
+                headers,
+                body,
+            },
+        ))
+    }
+
+    pub fn status_line(&self) -> Option<&SipResponseStatusLine<'a>> {
+        match &self.start_line {
+            Some(StartLine::Status(sl)) => Some(sl),
+            _ => None,
+        }
+    }
+
+    pub fn request_line(&self) -> Option<&SipRequestLine<'a>> {
+        match &self.start_line {
+            Some(StartLine::Request(rl)) => Some(rl),
+            _ => None,
+        }
+    }
+
+    /// Status code of fragment with Status-Line. Ex: progress of transfer
+    pub fn status_code(&self) -> Option<SipResponseStatusCode> {
+        Some(self.status_line()?.status_code)
+    }
+
+    /// Fragment has final response (2xx-6xx). Subscription of REFER may be terminated
+    pub fn is_final(&self) -> bool {
+        let sl = match self.status_line() {
+            Some(sl) => sl,
+            None => return false,
+        };
+        // the first digit of Status-Code follows SIP-Version
+        match sl.raw.iter().position(|c| *c == b' ') {
+            Some(pos) => sl.raw.get(pos + 1).is_some_and(|digit| *digit >= b'2'),
+            None => false,
+        }
+    }
+}
+
+/// Request-Line starts with method followed by SP.
+/// Header starts with name followed by optional whitespaces and colon
+fn is_request_line(input: &[u8]) -> bool {
+    let token_len = input.iter().take_while(|c| is_token_char(**c)).count();
+    let rest = &input[token_len..];
+    token_len > 0
+        && rest.first() == Some(&b' ')
+        && rest.iter().find(|c| **c != b' ' && **c != b'\t') != Some(&b':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SipMethod, SipParseErrorKind};
+
+    #[test]
+    fn parse_status_test() {
+        let (rest, frag) = SipFrag::parse(b"SIP/2.0 100 Trying\r\n").unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(frag.status_code(), Some(SipResponseStatusCode::Trying));
+        assert_eq!(frag.headers.len(), 0);
+        assert_eq!(frag.body, None);
+
+        let (_, frag) =
+            SipFrag::parse(b"SIP/2.0 603 Declined\r\nCall-ID: 1@a.example.com\r\n\r\nbody")
+                .unwrap();
+        assert_eq!(frag.status_code(), Some(SipResponseStatusCode::Decline));
+        assert_eq!(frag.status_line().unwrap().reason_phrase, "Declined");
+        assert!(frag.is_final());
+        assert_eq!(frag.headers.len(), 1);
+        assert_eq!(frag.body, Some(&b"body"[..]));
+
+        let (_, frag) = SipFrag::parse(b"SIP/2.0 200 OK\r\n\r\n").unwrap();
+        assert!(frag.is_final());
+        assert_eq!(frag.body, Some(&b""[..]));
+    }
+
+    #[test]
+    fn parse_request_and_headers_test() {
+        let (_, frag) = SipFrag::parse(
+            b"REGISTER sip:atlanta.example.com SIP/2.0\r\n\
+              Via : SIP/2.0/UDP pc33.atlanta.example.com;branch=z9hG4bK776asdhds\r\n",
+        )
+        .unwrap();
+        assert_eq!(frag.request_line().unwrap().method, SipMethod::REGISTER);
+        assert_eq!(frag.status_code(), None);
+        assert!(!frag.is_final());
+        assert_eq!(frag.headers.len(), 1);
+
+        let (_, frag) = SipFrag::parse(b"Via : SIP/2.0/UDP pc33.atlanta.example.com\r\n").unwrap();
+        assert!(frag.start_line.is_none());
+        assert_eq!(frag.headers.len(), 1);
+
+        let (_, frag) = SipFrag::parse(b"").unwrap();
+        assert!(frag.start_line.is_none());
+        assert_eq!(frag.body, None);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        match SipFrag::parse(b"SIP/2.0 1800 Ringing\r\n") {
+            Err(nom::Err::Error(e)) => assert_eq!(e.kind, SipParseErrorKind::BadStatusCode),
+            _ => panic!("Fragment should not be parsed"),
+        }
+        match SipFrag::parse(b"SIP/2.0 180 Ringing\r\nCSeq 1 INVITE\r\n") {
+            Err(nom::Err::Error(e)) => {
+                assert_eq!(e.kind, SipParseErrorKind::BadHeaderName);
+                assert_eq!(e.line, Some(2));
+            }
+            _ => panic!("Fragment should not be parsed"),
+        }
+    }
+}
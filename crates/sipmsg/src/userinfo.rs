@@ -29,11 +29,11 @@ impl<'a> UserInfo<'a> {
     }
 
     pub fn from_bytes(input: &'a [u8]) -> Result<UserInfo, nom::Err<SipParseError>> {
-        if input.len() <= 1 {
+        if input.is_empty() {
             return sip_parse_error!(1);
         }
 
-        if !is_userinfo_char(input[0]) {
+        if !is_userinfo_char(input[0]) && input[0] != b'%' {
             return sip_parse_error!(2);
         }
 
@@ -108,6 +108,8 @@ mod tests {
         test_case_from_bytes("+1-212-555-1212:1234@", "+1-212-555-1212", Some("1234"));
         test_case_from_bytes("a:b@", "a", Some("b"));
         test_case_from_bytes("a@", "a", None);
+        test_case_from_bytes("%75se%72@", "%75se%72", None);
+        test_case_from_bytes("%00", "%00", None);
 
         parse_should_fail("alice:@");
         parse_should_fail(":@");
@@ -0,0 +1,343 @@
+//! Warning header values and warn-code registry.
+//! [rfc3261 section-20.43](https://tools.ietf.org/html/rfc3261#section-20.43)
+//!
+//! `Warning` formats header line with quoted and escaped warn-text,
+//! `add_warnings` appends such lines to response that is forwarded by proxy.
+//!
+//! ## Example
+//! ```rust
+//! use sipmsg::warning::{add_warnings, warnings, WarnCode, Warning};
+//! use sipmsg::SipResponse;
+//!
+//! let response = "SIP/2.0 488 Not Acceptable Here\r\n\
+//! CSeq: 1 INVITE\r\n\
+//! Warning: 305 biloxi.example.com \"Incompatible media format\"\r\n\
+//! Content-Length: 0\r\n\r\n"
+//!     .as_bytes();
+//!
+//! let warning = Warning::miscellaneous("edge.example.com", "Callee is \"busy\"").unwrap();
+//! assert_eq!(
+//!     warning.to_string(),
+//!     "Warning: 399 edge.example.com \"Callee is \\\"busy\\\"\""
+//! );
+//!
+//! let forwarded = add_warnings(response, &[warning]).unwrap();
+//! let (_, response) = SipResponse::parse(&forwarded).unwrap();
+//! let received = warnings(&response.headers);
+//! assert_eq!(received[0].code, WarnCode::IncompatibleMediaFormat);
+//! assert_eq!(received[1].code, WarnCode::Miscellaneous);
+//! assert_eq!(received[1].text, "Callee is \"busy\"");
+//! ```
+
+use crate::{
+    common::{
+        bnfcore::{is_digit, is_token_char},
+        errorparse::SipParseError,
+        hostport::is_ipv6_address,
+    },
+    SipHeader, SipHeaderTagType, SipHeaders, SipRFCHeader, SipResponse,
+};
+use alloc::{borrow::Cow, format, string::String, vec::Vec};
+use core::{fmt, str};
+
+/// warn-code registered by rfc3261. Other codes of 3xx range are kept as `Other`
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum WarnCode {
+    /// 300 Incompatible network protocol
+    IncompatibleNetworkProtocol,
+    /// 301 Incompatible network address formats
+    IncompatibleNetworkAddressFormats,
+    /// 302 Incompatible transport protocol
+    IncompatibleTransportProtocol,
+    /// 303 Incompatible bandwidth units
+    IncompatibleBandwidthUnits,
+    /// 304 Media type not available
+    MediaTypeNotAvailable,
+    /// 305 Incompatible media format
+    IncompatibleMediaFormat,
+    /// 306 Attribute not understood
+    AttributeNotUnderstood,
+    /// 307 Session description parameter not understood
+    SessionDescriptionParameterNotUnderstood,
+    /// 330 Multicast not available
+    MulticastNotAvailable,
+    /// 331 Unicast not available
+    UnicastNotAvailable,
+    /// 370 Insufficient bandwidth
+    InsufficientBandwidth,
+    /// 399 Miscellaneous warning
+    Miscellaneous,
+    /// Not registered code in range 300-399
+    Other(u16),
+}
+
+impl WarnCode {
+    /// `None` if code is out of range 300-399
+    pub fn from_code(code: u16) -> Option<WarnCode> {
+        if !(300..=399).contains(&code) {
+            return None;
+        }
+        let warn_code = match code {
+            300 => WarnCode::IncompatibleNetworkProtocol,
+            301 => WarnCode::IncompatibleNetworkAddressFormats,
+            302 => WarnCode::IncompatibleTransportProtocol,
+            303 => WarnCode::IncompatibleBandwidthUnits,
+            304 => WarnCode::MediaTypeNotAvailable,
+            305 => WarnCode::IncompatibleMediaFormat,
+            306 => WarnCode::AttributeNotUnderstood,
+            307 => WarnCode::SessionDescriptionParameterNotUnderstood,
+            330 => WarnCode::MulticastNotAvailable,
+            331 => WarnCode::UnicastNotAvailable,
+            370 => WarnCode::InsufficientBandwidth,
+            399 => WarnCode::Miscellaneous,
+            _ => WarnCode::Other(code),
+        };
+        Some(warn_code)
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            WarnCode::IncompatibleNetworkProtocol => 300,
+            WarnCode::IncompatibleNetworkAddressFormats => 301,
+            WarnCode::IncompatibleTransportProtocol => 302,
+            WarnCode::IncompatibleBandwidthUnits => 303,
+            WarnCode::MediaTypeNotAvailable => 304,
+            WarnCode::IncompatibleMediaFormat => 305,
+            WarnCode::AttributeNotUnderstood => 306,
+            WarnCode::SessionDescriptionParameterNotUnderstood => 307,
+            WarnCode::MulticastNotAvailable => 330,
+            WarnCode::UnicastNotAvailable => 331,
+            WarnCode::InsufficientBandwidth => 370,
+            WarnCode::Miscellaneous => 399,
+            WarnCode::Other(code) => *code,
+        }
+    }
+
+    /// Description from rfc3261. Empty for `Other`
+    pub fn description(&self) -> &'static str {
+        match self {
+            WarnCode::IncompatibleNetworkProtocol => "Incompatible network protocol",
+            WarnCode::IncompatibleNetworkAddressFormats => "Incompatible network address formats",
+            WarnCode::IncompatibleTransportProtocol => "Incompatible transport protocol",
+            WarnCode::IncompatibleBandwidthUnits => "Incompatible bandwidth units",
+            WarnCode::MediaTypeNotAvailable => "Media type not available",
+            WarnCode::IncompatibleMediaFormat => "Incompatible media format",
+            WarnCode::AttributeNotUnderstood => "Attribute not understood",
+            WarnCode::SessionDescriptionParameterNotUnderstood => {
+                "Session description parameter not understood"
+            }
+            WarnCode::MulticastNotAvailable => "Multicast not available",
+            WarnCode::UnicastNotAvailable => "Unicast not available",
+            WarnCode::InsufficientBandwidth => "Insufficient bandwidth",
+            WarnCode::Miscellaneous => "Miscellaneous warning",
+            WarnCode::Other(_) => "",
+        }
+    }
+}
+
+/// One warning-value of Warning header
+#[derive(Clone, PartialEq, Debug)]
+pub struct Warning<'a> {
+    pub code: WarnCode,
+    /// hostport or pseudonym of element that adds warning
+    pub agent: &'a str,
+    /// warn-text without quotes and escaping
+    pub text: Cow<'a, str>,
+}
+
+impl<'a> Warning<'a> {
+    /// `None` if agent is not a hostport or pseudonym. Ex: empty or contains spaces
+    pub fn new(code: WarnCode, agent: &'a str, text: &'a str) -> Option<Warning<'a>> {
+        if !is_warn_agent(agent) {
+            return None;
+        }
+        Some(Warning {
+            code,
+            agent,
+            text: Cow::Borrowed(text),
+        })
+    }
+
+    /// 399 warning with arbitrary text
+    pub fn miscellaneous(agent: &'a str, text: &'a str) -> Option<Warning<'a>> {
+        Warning::new(WarnCode::Miscellaneous, agent, text)
+    }
+
+    /// Warning with description of code as text. Ex: `370 agent "Insufficient bandwidth"`
+    pub fn with_description(code: WarnCode, agent: &'a str) -> Option<Warning<'a>> {
+        Warning::new(code, agent, code.description())
+    }
+
+    /// `None` if header is not a valid Warning
+    pub fn from_header(hdr: &SipHeader<'a>) -> Option<Warning<'a>> {
+        let tags = hdr.value.tags()?;
+        let code = str::from_utf8(tags.get(&SipHeaderTagType::WarnCode)?)
+            .ok()?
+            .parse::<u16>()
+            .ok()?;
+        Some(Warning {
+            code: WarnCode::from_code(code)?,
+            agent: str::from_utf8(tags.get(&SipHeaderTagType::WarnAgent)?).ok()?,
+            text: unescape(str::from_utf8(tags.get(&SipHeaderTagType::WarnText)?).ok()?),
+        })
+    }
+
+    /// warning-value without header name. Ex: `399 agent "text"`
+    pub fn value(&self) -> String {
+        format!("{}", Value(self))
+    }
+}
+
+/// Header line without CRLF. Ex: `Warning: 399 agent "text"`
+impl<'a> fmt::Display for Warning<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", SipRFCHeader::Warning.as_str(), Value(self))
+    }
+}
+
+struct Value<'a, 'b>(&'b Warning<'a>);
+
+impl<'a, 'b> fmt::Display for Value<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03} {} \"", self.0.code.code(), self.0.agent)?;
+        for c in self.0.text.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                // CR and LF can't be escaped in quoted-string
+                '\r' | '\n' => f.write_str(" ")?,
+                // other control characters are allowed only as quoted-pair
+                c if c.is_ascii_control() && c != '\t' => write!(f, "\\{}", c)?,
+                _ => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// warn-agent = hostport / pseudonym, pseudonym = token
+fn is_warn_agent(agent: &str) -> bool {
+    let agent = agent.as_bytes();
+    let (host, port) = match agent.iter().rposition(|c| *c == b']' || *c == b':') {
+        Some(pos) if agent[pos] == b':' => (&agent[..pos], Some(&agent[pos + 1..])),
+        _ => (agent, None),
+    };
+    let is_host = match host.strip_prefix(b"[") {
+        Some(ipv6) => ipv6.strip_suffix(b"]").is_some_and(is_ipv6_address),
+        None => !host.is_empty() && host.iter().all(|c| is_token_char(*c)),
+    };
+    let is_port = match port {
+        Some(port) => {
+            port.iter().all(|c| is_digit(*c))
+                && str::from_utf8(port).is_ok_and(|port| port.parse::<u16>().is_ok())
+        }
+        None => true,
+    };
+    is_host && is_port
+}
+
+/// Removes backslashes of quoted-pairs
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Valid warning-values of Warning headers in order of appearance
+pub fn warnings<'a>(headers: &SipHeaders<'a>) -> Vec<Warning<'a>> {
+    match headers.get_rfc(SipRFCHeader::Warning) {
+        Some(hdrs) => hdrs.iter().filter_map(Warning::from_header).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Appends Warning header lines after the last header of response.
+/// Response must be valid, message-body is copied as is
+pub fn add_warnings<'a>(
+    response: &'a [u8],
+    warnings: &[Warning],
+) -> Result<Vec<u8>, nom::Err<SipParseError<'a>>> {
+    let (input, parsed) = SipResponse::parse(response)?;
+    let tail_len = input.len() + parsed.body.map_or(0, |body| body.len());
+    // "\r\n" that separates headers and message-body
+    let headers_end = response.len() - tail_len - 2;
+
+    let mut result = Vec::with_capacity(response.len() + warnings.len() * 64);
+    result.extend_from_slice(&response[..headers_end]);
+    for warning in warnings {
+        result.extend_from_slice(format!("{}\r\n", warning).as_bytes());
+    }
+    result.extend_from_slice(&response[headers_end..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn warn_code_test() {
+        for code in 300..400 {
+            assert_eq!(WarnCode::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(
+            WarnCode::from_code(307),
+            Some(WarnCode::SessionDescriptionParameterNotUnderstood)
+        );
+        assert_eq!(WarnCode::from_code(350), Some(WarnCode::Other(350)));
+        assert_eq!(WarnCode::from_code(299), None);
+        assert_eq!(WarnCode::from_code(400), None);
+    }
+
+    #[test]
+    fn warning_format_test() {
+        assert_eq!(
+            Warning::with_description(WarnCode::InsufficientBandwidth, "devnull")
+                .unwrap()
+                .to_string(),
+            "Warning: 370 devnull \"Insufficient bandwidth\""
+        );
+        let warning = Warning::miscellaneous("10.0.0.1:5060", "a\\b \"c\"\r\nd\u{7}\te").unwrap();
+        assert_eq!(
+            warning.value(),
+            "399 10.0.0.1:5060 \"a\\\\b \\\"c\\\"  d\\\u{7}\te\""
+        );
+        assert_eq!(unescape("a\\\\b \\\"c\\\""), "a\\b \"c\"");
+        assert_eq!(unescape("plain"), Cow::Borrowed("plain"));
+    }
+
+    #[test]
+    fn warn_agent_test() {
+        for agent in &[
+            "devnull",
+            "biloxi.example.com",
+            "10.0.0.1:5060",
+            "[2001:db8::1]",
+            "[2001:db8::1]:5060",
+        ] {
+            assert!(Warning::miscellaneous(agent, "text").is_some(), "{}", agent);
+        }
+        for agent in &[
+            "",
+            "edge example.com",
+            "host:",
+            "host:65536",
+            "[2001:db8::1",
+            "2001:db8::1",
+            "[example.com]",
+            "\"agent\"",
+        ] {
+            assert!(Warning::miscellaneous(agent, "text").is_none(), "{}", agent);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "compression")]
+
+use sipmsg::content_coding::*;
+use sipmsg::*;
+
+const SDP: &str = "v=0\r\n\
+o=bob 2808844564 2808844564 IN IP4 host.biloxi.example.com\r\n\
+s=\r\n\
+c=IN IP4 host.biloxi.example.com\r\n\
+t=0 0\r\n\
+m=audio 49172 RTP/AVP 0\r\n\
+a=rtpmap:0 PCMU/8000\r\n";
+
+#[test]
+fn response_compressed_for_request() {
+    let invite_buf = "INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 1 INVITE\r\n\
+Accept-Encoding: deflate;q=0.2, gzip;q=0.9\r\n\r\n"
+        .as_bytes();
+    let (_, invite) = SipRequest::parse(invite_buf).unwrap();
+    let (coding, body) = encode_body_for(&invite.headers, SDP.as_bytes());
+    assert_eq!(coding, ContentCoding::Gzip);
+
+    let mut response_buf = format!(
+        "SIP/2.0 200 OK\r\n\
+CSeq: 1 INVITE\r\n\
+Content-Type: application/sdp\r\n\
+Content-Encoding: {}\r\n\
+Content-Length: {}\r\n\r\n",
+        coding.as_str(),
+        body.len()
+    )
+    .into_bytes();
+    response_buf.extend_from_slice(&body);
+    let (_, response) = SipResponse::parse(&response_buf).unwrap();
+    assert_eq!(response.decoded_body(None).unwrap(), SDP.as_bytes());
+    assert!(response.decoded_body(Some(16)).is_err());
+}
+
+#[test]
+fn body_without_encoding() {
+    let (_, request) = SipRequest::parse(
+        "MESSAGE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 2 MESSAGE\r\n\
+Content-Encoding: identity\r\n\r\n\
+Hello"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(request.decoded_body(None).unwrap(), &b"Hello"[..]);
+
+    // peer did not advertise Accept-Encoding
+    let (coding, body) = encode_body_for(&request.headers, b"Hello");
+    assert_eq!(coding, ContentCoding::Identity);
+    assert_eq!(body, &b"Hello"[..]);
+}
+
+#[test]
+fn decoded_body_errors() {
+    let (_, request) = SipRequest::parse(
+        "MESSAGE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 3 MESSAGE\r\n\
+Content-Encoding: br\r\n\r\n\
+Hello"
+            .as_bytes(),
+    )
+    .unwrap();
+    match request.decoded_body(None) {
+        Err(nom::Err::Error(e)) => assert_eq!(e.status_code(), 415),
+        _ => panic!("br is not supported"),
+    }
+
+    let (_, request) = SipRequest::parse(
+        "MESSAGE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 4 MESSAGE\r\n\
+e: deflate\r\n\r\n\
+Hello"
+            .as_bytes(),
+    )
+    .unwrap();
+    match request.decoded_body(None) {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(
+                e.kind,
+                SipParseErrorKind::BadContentEncoding { coding: "deflate" }
+            );
+            assert_eq!(e.status_code(), 400);
+        }
+        _ => panic!("Body is not deflated"),
+    }
+}
+
+#[test]
+fn gzip_sdp_invite() {
+    let body = ContentCoding::Gzip.encode(SDP.as_bytes());
+    let mut invite_buf = format!(
+        "INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 1 INVITE\r\n\
+Content-Type: application/sdp\r\n\
+Content-Encoding: gzip\r\n\
+Content-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    invite_buf.extend_from_slice(&body);
+    let (_, invite) = SipRequest::parse(&invite_buf).unwrap();
+    let sdp = invite.body_as_sdp().unwrap();
+    assert_eq!(sdp.origin.username, "bob");
+    assert_eq!(sdp.media.len(), 1);
+    // decoded body is kept by request
+    assert_eq!(invite.body_as_sdp().unwrap().media[0].port, 49172);
+
+    let mut corrupted = invite_buf.clone();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 1;
+    let (_, invite) = SipRequest::parse(&corrupted).unwrap();
+    match invite.body_as_sdp() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(
+                e.kind,
+                SipParseErrorKind::BadContentEncoding { coding: "gzip" }
+            )
+        }
+        _ => panic!("Size of gzip body does not match"),
+    }
+}
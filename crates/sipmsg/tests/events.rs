@@ -0,0 +1,85 @@
+#![cfg(feature = "std")]
+
+use sipmsg::events::*;
+use sipmsg::*;
+
+#[test]
+fn notify_dialog_info() {
+    let notify_buf = "NOTIFY sip:bob@pc34.example.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP server.example.com;branch=z9hG4bK776asdhds\r\n\
+To: <sip:bob@example.com>;tag=2342\r\n\
+From: <sip:alice@example.com>;tag=1928\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 3 NOTIFY\r\n\
+Event: dialog\r\n\
+Subscription-State: active;expires=3600\r\n\
+Content-Type: Application/Dialog-Info+XML\r\n\r\n\
+<?xml version=\"1.0\"?>\r\n\
+<dialog-info xmlns=\"urn:ietf:params:xml:ns:dialog-info\" version=\"2\" \
+state=\"partial\" entity=\"sip:alice@example.com\">\r\n\
+<dialog id=\"as7d900as8\" direction=\"recipient\"><state>early</state></dialog>\r\n\
+</dialog-info>\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    let dialog_info = match request.event_body().unwrap() {
+        EventBody::DialogInfo(dialog_info) => dialog_info,
+        _ => panic!("dialog-info expected"),
+    };
+    assert_eq!(dialog_info.version, 2);
+    assert_eq!(dialog_info.state, DocumentState::Partial);
+    assert_eq!(dialog_info.dialogs[0].state, DialogState::Early);
+    assert_eq!(
+        dialog_info.dialogs[0].direction,
+        Some(DialogDirection::Recipient)
+    );
+}
+
+#[test]
+fn notify_message_summary() {
+    let notify_buf = "NOTIFY sip:alice@pc33.example.com SIP/2.0\r\n\
+CSeq: 20 NOTIFY\r\n\
+Event: message-summary\r\n\
+Content-Type: application/simple-message-summary\r\n\r\n\
+Messages-Waiting: yes\r\n\
+Voice-Message: 4/8 (1/2)\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    let body = request.event_body().unwrap();
+    assert_eq!(body.content_type(), MessageSummary::CONTENT_TYPE);
+    assert_eq!(
+        body.to_string(),
+        "Messages-Waiting: yes\r\nvoice-message: 4/8 (1/2)\r\n"
+    );
+}
+
+#[test]
+fn event_body_errors() {
+    let notify_buf = "NOTIFY sip:alice@pc33.example.com SIP/2.0\r\n\
+CSeq: 21 NOTIFY\r\n\
+Content-Type: application/reginfo+xml\r\n\r\n\
+<reginfo version=\"1\" state=\"full\"><registration/></reginfo>"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    match request.event_body() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(
+                e.kind,
+                SipParseErrorKind::BadBody {
+                    content_type: RegInfo::CONTENT_TYPE
+                }
+            );
+            assert_eq!(e.status_code(), 400);
+        }
+        _ => panic!("reginfo should not be parsed"),
+    }
+
+    let notify_buf = "NOTIFY sip:alice@pc33.example.com SIP/2.0\r\n\
+CSeq: 22 NOTIFY\r\n\
+Content-Type: application/sdp\r\n\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    match request.event_body() {
+        Err(nom::Err::Error(e)) => assert_eq!(e.status_code(), 415),
+        _ => panic!("SDP is not event body"),
+    }
+}
@@ -314,3 +314,21 @@ fn content_type_header() {
     assert_eq!(hdrs[0].value.vstr, "application/sdp");
     assert_eq!(input.len(), 2)
 }
+
+#[test]
+fn folded_header_value() {
+    let (input, (_, hdrs)) =
+        SipHeader::parse("Subject: Lunch\r\n  tomorrow\r\n\t at noon\r\n".as_bytes()).unwrap();
+    assert_eq!(input, "\r\n".as_bytes());
+    assert_eq!(hdrs[0].value.vstr, "Lunch\r\n  tomorrow\r\n\t at noon");
+    assert_eq!(hdrs[0].value.normalized(), "Lunch tomorrow at noon");
+    assert_eq!(
+        hdrs[0].raw_value_param,
+        "Lunch\r\n  tomorrow\r\n\t at noon".as_bytes()
+    );
+
+    let (_, (_, hdrs)) =
+        SipHeader::parse("X-Folded: first\r\n second;param=\r\n value\r\n".as_bytes()).unwrap();
+    assert_eq!(hdrs[0].value.normalized(), "first second;param= value");
+    assert_eq!(hdrs[0].normalized_value_param(), "first second;param= value");
+}
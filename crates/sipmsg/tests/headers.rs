@@ -194,6 +194,7 @@ fn parse_headers() {
 
     let date_hdr = &hdrs.get_rfc_s(SipRFCHeader::Date).unwrap();
     assert_eq!(date_hdr.value.vstr, "Sat, 15 Oct 2005 04:44:56 GMT");
+    assert_eq!(hdrs.date(), Some(SipDate::from_timestamp(1129351496)));
 
     let error_info = &hdrs.get_rfc_s(SipRFCHeader::ErrorInfo).unwrap();
     assert_eq!(
@@ -207,6 +208,9 @@ fn parse_headers() {
 
     let expires_hdr = &hdrs.get_rfc_s(SipRFCHeader::Expires).unwrap();
     assert_eq!(expires_hdr.value.vstr, "5");
+    assert_eq!(hdrs.expires().unwrap(), Some(5));
+    assert_eq!(hdrs.max_forwards().unwrap(), Some(70));
+    assert_eq!(hdrs.content_length().unwrap(), Some(8));
 
     let in_reply_hdrs = &hdrs.get_rfc(SipRFCHeader::InReplyTo).unwrap();
     assert_eq!(in_reply_hdrs[0].value.vstr, "70710@saturn.bell-tel.com");
@@ -440,6 +444,14 @@ fn parse_headers() {
         retry_after_hdr.value.tags().unwrap()[&SipHeaderTagType::Seconds],
         "18000".as_bytes()
     );
+    assert_eq!(
+        hdrs.retry_after().unwrap(),
+        Some(SipRetryAfter {
+            seconds: 18000,
+            duration: Some(3600),
+            comment: Some("I'm in a meeting")
+        })
+    );
 
     assert_eq!(
         retry_after_hdr.params().unwrap().get(&"duration"),
@@ -481,9 +493,17 @@ fn parse_headers() {
 
     let min_exp_hdr = &hdrs.get_rfc_s(SipRFCHeader::MinExpires).unwrap();
     assert_eq!(min_exp_hdr.value.vstr, "60");
+    assert_eq!(hdrs.min_expires().unwrap(), Some(60));
 
     let timestamp_hdr = &hdrs.get_rfc_s(SipRFCHeader::Timestamp).unwrap();
     assert_eq!(timestamp_hdr.value.vstr, "54");
+    assert_eq!(
+        hdrs.timestamp().unwrap(),
+        Some(SipTimestamp {
+            time: 54.0,
+            delay: None
+        })
+    );
     assert_eq!(
         timestamp_hdr.value.tags().unwrap()[&SipHeaderTagType::TimveVal],
         b"54"
@@ -555,3 +575,27 @@ fn parse_headers() {
 
     assert_eq!(input, "\r\nsomebody".as_bytes());
 }
+
+#[test]
+fn typed_values_overflow() {
+    let (_, hdrs) = SipHeaders::parse(
+        "Expires: 4294967296\r\n\
+         Min-Expires: 4294967295\r\n\
+         Retry-After: 120;duration=99999999999\r\n\r\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    match hdrs.expires() {
+        Err(nom::Err::Error(e)) => assert_eq!(
+            e.kind,
+            SipParseErrorKind::BadHeaderValue { header: "Expires" }
+        ),
+        _ => panic!("Expires overflows u32"),
+    }
+    assert_eq!(hdrs.min_expires().unwrap(), Some(u32::MAX));
+    assert!(hdrs.retry_after().is_err());
+    assert_eq!(hdrs.timestamp().unwrap(), None);
+
+    let (_, hdrs) = SipHeaders::parse("Expires: 10\r\nExpires: 20\r\n\r\n".as_bytes()).unwrap();
+    assert!(hdrs.expires().is_err());
+}
@@ -46,3 +46,277 @@ fn get_message_type() {
         SipMessageType::Unknown
     );
 }
+
+fn parse_error(raw_message: &[u8]) -> SipParseError<'_> {
+    match SipMessage::parse(raw_message) {
+        Ok(_) => panic!("Message should not be parsed"),
+        Err(nom::Err::Error(e)) => e,
+        Err(_) => panic!("Unexpected error type"),
+    }
+}
+
+#[test]
+fn parse_message_errors() {
+    let err = parse_error(
+        "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+         Max-Forwards: 70\r\n\
+         Via: SIP/2.0/UDP\r\n\
+         CSeq: 1 INVITE\r\n\r\n"
+            .as_bytes(),
+    );
+    assert_eq!(
+        err.kind,
+        SipParseErrorKind::BadHeaderValue { header: "Via" }
+    );
+    assert_eq!(err.header, Some("Via"));
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.reason_phrase(), "Malformed Via Header");
+
+    let err = parse_error("INVITE sip:bob@biloxi.com SIP/2.0 \r\n\r\n".as_bytes());
+    assert_eq!(err.kind, SipParseErrorKind::BadStartLine);
+    assert_eq!(err.line, Some(1));
+
+    let err = parse_error("INVITE sip:bob@biloxi.com SIP/2.300\r\n\r\n".as_bytes());
+    assert_eq!(err.kind, SipParseErrorKind::BadVersion);
+
+    let err = parse_error("INVITE bob@biloxi.com SIP/2.0\r\n\r\n".as_bytes());
+    assert_eq!(err.kind, SipParseErrorKind::BadUri);
+    assert_eq!(err.offset, Some(7));
+
+    let err = parse_error("SIP/2.0 2OO OK\r\n\r\n".as_bytes());
+    assert_eq!(err.kind, SipParseErrorKind::BadStatusCode);
+
+    let err = parse_error("\x16\x03\x01 sip:bob@biloxi.com SIP/2.0\r\n\r\n".as_bytes());
+    assert_eq!(err.kind, SipParseErrorKind::UnknownMessageType);
+    assert_eq!(err.offset, Some(0));
+
+    let err = parse_error(
+        "SIP/2.0 200 OK\r\n\
+         Call-ID: a84b4c76e66710\r\n\
+         Bad Header: value\r\n\r\n"
+            .as_bytes(),
+    );
+    assert_eq!(err.kind, SipParseErrorKind::BadHeaderName);
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.offset, Some(45));
+
+    let err = parse_error(
+        "SIP/2.0 200 OK\r\n\
+         Call-ID: a84b4c76e66710\r\n"
+            .as_bytes(),
+    );
+    assert_eq!(err.kind, SipParseErrorKind::MissingHeadersEnd);
+
+    let err = parse_error(
+        "SIP/2.0 200 OK\r\n\
+         Content-Length: 10\r\n\r\nbody"
+            .as_bytes(),
+    );
+    assert_eq!(err.kind, SipParseErrorKind::BodyTooShort);
+    assert_eq!(err.line, Some(4));
+    assert_eq!(format!("{}", err), "BodyTooShort at line 4, offset 38");
+}
+
+#[test]
+fn parse_message_with_residue() {
+    let (rest, msg) = SipMessage::parse(
+        "SIP/2.0 200 OK\r\n\
+         Content-Length: 4\r\n\r\nbodySIP/2.0 180 Ringing\r\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(msg.response().unwrap().body.unwrap(), b"body");
+    assert_eq!(rest, b"SIP/2.0 180 Ringing\r\n");
+}
+
+#[test]
+fn parse_message_lenient() {
+    let raw_message = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 7O\r\n\
+Bad Header\r\n\
+\x20folded\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Content-Length: 4\r\n\r\nbody"
+        .as_bytes();
+
+    assert!(SipMessage::parse(raw_message).is_err());
+    assert!(SipMessage::parse_ext(raw_message, &ParseOptions::strict()).is_err());
+
+    let (rest, msg) = SipMessage::parse_ext(raw_message, &ParseOptions::lenient()).unwrap();
+    assert_eq!(rest.len(), 0);
+    let req = msg.request().unwrap();
+    assert_eq!(req.body.unwrap(), b"body");
+    assert_eq!(
+        req.headers
+            .get_rfc_s(SipRFCHeader::CallID)
+            .unwrap()
+            .value
+            .vstr,
+        "a84b4c76e66710@pc33.atlanta.com"
+    );
+    assert_eq!(
+        req.headers
+            .get_rfc_s(SipRFCHeader::CSeq)
+            .unwrap()
+            .value
+            .vstr,
+        "314159 INVITE"
+    );
+    assert!(req.headers.get_rfc_s(SipRFCHeader::Via).is_some());
+    assert!(req.headers.get_rfc_s(SipRFCHeader::MaxForwards).is_none());
+
+    let invalid = req.headers.invalid_headers();
+    assert_eq!(invalid.len(), 2);
+    assert_eq!(invalid[0].name, Some("Max-Forwards"));
+    assert_eq!(invalid[0].raw, b"Max-Forwards: 7O");
+    assert_eq!(invalid[0].error.line, Some(3));
+    assert_eq!(invalid[1].name, None);
+    assert_eq!(invalid[1].raw, b"Bad Header\r\n folded");
+    assert_eq!(invalid[1].error.kind, SipParseErrorKind::BadHeaderName);
+    assert_eq!(invalid[1].error.line, Some(4));
+
+    // Body length is still checked in lenient mode
+    let raw_message = "SIP/2.0 200 OK\r\n\
+Bad Header\r\n\
+Content-Length: 10\r\n\r\nbody"
+        .as_bytes();
+    match SipMessage::parse_ext(raw_message, &ParseOptions::lenient()) {
+        Err(nom::Err::Error(e)) => assert_eq!(e.kind, SipParseErrorKind::BodyTooShort),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_message_limits() {
+    let raw_message = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Via: SIP/2.0/UDP 192.0.2.1, SIP/2.0/UDP 192.0.2.2\r\n\
+Route: <sip:a.example.com>, <sip:b.example.com>, <sip:c.example.com>\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Content-Length: 4\r\n\r\nbody"
+        .as_bytes();
+
+    let limit_error = |limits: ParseLimits| match SipMessage::parse_ext(
+        raw_message,
+        &ParseOptions::lenient().with_limits(limits),
+    ) {
+        Err(nom::Err::Failure(e)) => e,
+        _ => panic!(),
+    };
+
+    assert!(SipMessage::parse_ext(
+        raw_message,
+        &ParseOptions::strict().with_limits(ParseLimits {
+            max_headers: Some(6),
+            max_header_line_len: Some(68),
+            max_via: Some(3),
+            max_values_per_header: Some(3),
+            max_uri_len: Some(18),
+            max_body_len: Some(4),
+        })
+    )
+    .is_ok());
+
+    let err = limit_error(ParseLimits {
+        max_headers: Some(5),
+        ..Default::default()
+    });
+    assert_eq!(err.kind, SipParseErrorKind::TooManyHeaders);
+    assert_eq!(err.line, Some(7));
+    assert_eq!(err.status_code(), 513);
+
+    let err = limit_error(ParseLimits {
+        max_header_line_len: Some(67),
+        ..Default::default()
+    });
+    assert_eq!(err.kind, SipParseErrorKind::HeaderTooLong);
+    assert_eq!(err.line, Some(4));
+
+    let err = limit_error(ParseLimits {
+        max_via: Some(2),
+        ..Default::default()
+    });
+    assert_eq!(err.kind, SipParseErrorKind::TooManyVias);
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.status_code(), 513);
+
+    let err = limit_error(ParseLimits {
+        max_values_per_header: Some(2),
+        ..Default::default()
+    });
+    assert_eq!(
+        err.kind,
+        SipParseErrorKind::TooManyValues { header: "Route" }
+    );
+    assert_eq!(err.status_code(), 513);
+
+    let err = limit_error(ParseLimits {
+        max_uri_len: Some(17),
+        ..Default::default()
+    });
+    assert_eq!(err.kind, SipParseErrorKind::UriTooLong);
+    assert_eq!(err.offset, Some(7));
+    assert_eq!(err.status_code(), 414);
+    assert_eq!(err.reason_phrase(), "Request-URI Too Long");
+
+    let err = limit_error(ParseLimits {
+        max_body_len: Some(3),
+        ..Default::default()
+    });
+    assert_eq!(err.kind, SipParseErrorKind::BodyTooLarge);
+    assert_eq!(err.status_code(), 413);
+
+    // Without Content-Length whole rest of datagram is a body
+    match SipMessage::parse_ext(
+        "SIP/2.0 200 OK\r\nCall-ID: a\r\n\r\nbody".as_bytes(),
+        &ParseOptions::strict().with_limits(ParseLimits {
+            max_body_len: Some(3),
+            ..Default::default()
+        }),
+    ) {
+        Err(nom::Err::Failure(e)) => assert_eq!(e.kind, SipParseErrorKind::BodyTooLarge),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_oversized_value_list() {
+    // values after the limit are never parsed, so malformed tail doesn't matter
+    let routes = vec!["<sip:a.example.com>"; 10000].join(", ");
+    let vias = vec!["SIP/2.0/UDP 192.0.2.1"; 10000].join(", ");
+    let message = |header: &str, values: &str| {
+        format!(
+            "OPTIONS sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+{}: {}, <<<\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 OPTIONS\r\n\r\n",
+            header, values
+        )
+    };
+    let options = ParseOptions::lenient().with_limits(ParseLimits {
+        max_via: Some(8),
+        max_values_per_header: Some(16),
+        ..Default::default()
+    });
+
+    let raw_message = message("Route", &routes);
+    match SipMessage::parse_ext(raw_message.as_bytes(), &options) {
+        Err(nom::Err::Failure(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::TooManyValues { header: "Route" });
+            assert_eq!(e.line, Some(3));
+        }
+        _ => panic!(),
+    }
+    let raw_message = message("Via", &vias);
+    match SipMessage::parse_ext(raw_message.as_bytes(), &options) {
+        Err(nom::Err::Failure(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::TooManyVias);
+            assert_eq!(e.line, Some(3));
+        }
+        _ => panic!(),
+    }
+}
@@ -0,0 +1,95 @@
+use sipmsg::multipart::*;
+use sipmsg::*;
+
+fn message_with_body(start_line: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = format!(
+        "{}\r\n\
+         Via: SIP/2.0/UDP pc33.atlanta.example.com;branch=z9hG4bK776asdhds\r\n\
+         Call-ID: a84b4c76e66710@pc33.atlanta.example.com\r\n\
+         CSeq: 314159 INVITE\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\r\n",
+        start_line,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+#[test]
+fn request_body_multipart() {
+    let body = b"--unique-boundary-1\r\n\
+Content-Type: application/sdp\r\n\
+\r\n\
+v=0\r\n\
+o=- 1 1 IN IP4 192.0.2.1\r\n\
+s=-\r\n\
+c=IN IP4 192.0.2.1\r\n\
+t=0 0\r\n\
+m=audio 49170 RTP/AVP 0\r\n\
+\r\n\
+--unique-boundary-1\r\n\
+Content-Type: application/ISUP;version=nxv3;base=etsi121\r\n\
+Content-Disposition: signal;handling=optional\r\n\
+\r\n\
+\x01\x00\x49\x00\x00\x03\x02\x00\x07\r\n\
+--unique-boundary-1--\r\n";
+    let message = message_with_body(
+        "INVITE sip:bob@biloxi.example.com SIP/2.0",
+        "multipart/mixed;boundary=unique-boundary-1",
+        body,
+    );
+    let (_, request) = SipRequest::parse(&message).unwrap();
+    let multipart = request.body_multipart().unwrap();
+    assert_eq!(multipart.subtype, "mixed");
+    assert_eq!(multipart.boundary, "unique-boundary-1");
+    assert_eq!(multipart.parts.len(), 2);
+    let isup = multipart.part("application/isup").unwrap();
+    assert_eq!(isup.disposition(), Some("signal"));
+    assert_eq!(isup.body, b"\x01\x00\x49\x00\x00\x03\x02\x00\x07");
+
+    let sdp = request.body_as_sdp().unwrap();
+    assert_eq!(sdp.media[0].port, 49170);
+}
+
+#[test]
+fn response_body_multipart_errors() {
+    let message = message_with_body("SIP/2.0 200 OK", "multipart/mixed", b"--b1--\r\n");
+    let (_, response) = SipResponse::parse(&message).unwrap();
+    match response.body_multipart() {
+        Err(nom::Err::Error(e)) => assert_eq!(e.kind, SipParseErrorKind::BadMultipart),
+        _ => panic!("Multipart should not be parsed"),
+    }
+
+    let body = b"--b1\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b1--\r\n";
+    let message = message_with_body("SIP/2.0 200 OK", "multipart/mixed;boundary=b1", body);
+    let (_, response) = SipResponse::parse(&message).unwrap();
+    match response.body_as_sdp() {
+        Err(nom::Err::Error(e)) => assert_eq!(e.status_code(), 415),
+        _ => panic!("SDP should not be found"),
+    }
+
+    let message = message_with_body("SIP/2.0 200 OK", "text/plain", b"hello");
+    let (_, response) = SipResponse::parse(&message).unwrap();
+    match response.body_multipart() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::UnsupportedMediaType)
+        }
+        _ => panic!("Multipart should not be parsed"),
+    }
+}
+
+#[test]
+fn build_related() {
+    let builder = MultipartBuilder::new("related", "boundary42")
+        .part("application/pidf+xml", b"<presence/>")
+        .header("Content-ID", "<pidf@example.com>");
+    let body = builder.build();
+    let multipart = Multipart::parse(&body, "related", "boundary42").unwrap();
+    assert_eq!(
+        multipart.part_by_id("pidf@example.com").unwrap().body,
+        b"<presence/>"
+    );
+}
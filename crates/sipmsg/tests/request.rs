@@ -233,7 +233,7 @@ fn get_method_type() {
 
 #[test]
 fn get_method_type_fail() {
-    match SipRequestLine::parse("OPTI2ONS sip:user@example.com SIP/2.0\r\n".as_bytes()) {
+    match SipRequestLine::parse("OPTI@ONS sip:user@example.com SIP/2.0\r\n".as_bytes()) {
         Ok((_, _)) => panic!(),
         Err(_e) => (),
     }
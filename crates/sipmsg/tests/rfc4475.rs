@@ -31,14 +31,14 @@ fn parse_request() {
           newvalue ;\r\n \
           secondparam ; q = 0.33\r\n\
     \r\n\
-    v=0\
-    o=mhandley 29739 7272939 IN IP4 192.0.2.3\
-    s=-\
-    c=IN IP4 192.0.2.4\
-    t=0 0\
-    m=audio 49217 RTP/AVP 0 12\
-    m=video 3227 RTP/AVP 31\
-    a=rtpmap:31 LPC"
+    v=0\r\n\
+    o=mhandley 29739 7272939 IN IP4 192.0.2.3\r\n\
+    s=-\r\n\
+    c=IN IP4 192.0.2.4\r\n\
+    t=0 0\r\n\
+    m=audio 49217 RTP/AVP 0 12\r\n\
+    m=video 3227 RTP/AVP 31\r\n\
+    a=rtpmap:31 LPC\r\n"
         .as_bytes();
 
     let res = SipRequest::parse(invite_msg_buf);
@@ -111,6 +111,8 @@ fn parse_request() {
 
     let cseq_header = &headers.get_rfc_s(SipRFCHeader::CSeq).unwrap();
     assert_eq!(cseq_header.value.vstr, "0009\r\n INVITE");
+    assert_eq!(cseq_header.value.normalized(), "0009 INVITE");
+    assert_eq!(cseq_header.raw_value_param, b"0009\r\n INVITE");
     assert_eq!(cseq_header.params(), None);
     assert_eq!(
         cseq_header.value.tags().unwrap()[&SipHeaderTagType::Number],
@@ -124,6 +126,7 @@ fn parse_request() {
     let via_hdrs = headers.get_rfc(SipRFCHeader::Via).unwrap();
     let first_via = &via_hdrs[0];
     assert_eq!(first_via.value.vstr, "SIP  /   2.0\r\n /UDP\r\n 192.0.2.2");
+    assert_eq!(first_via.value.normalized(), "SIP  /   2.0 /UDP 192.0.2.2");
     assert_eq!(
         first_via.params().unwrap().get(&"branch"),
         Some(&Some("390skdjuw"))
@@ -172,6 +175,10 @@ fn parse_request() {
         new_fangled_header.value.vstr,
         "newfangled value\r\n continued newfangled value"
     );
+    assert_eq!(
+        new_fangled_header.value.normalized(),
+        "newfangled value continued newfangled value"
+    );
 
     let unknown_header_with_unusual_value =
         &headers.get_ext_s("unknownHeaderwithunusualValue").unwrap();
@@ -203,6 +210,10 @@ fn parse_request() {
         contact.value.tags().unwrap()[&SipHeaderTagType::DisplayName],
         b"Quoted string \\\"\\\""
     );
+    assert_eq!(
+        contact.normalized_value_param(),
+        "\"Quoted string \\\"\\\"\" <sip:jdrosen@example.com> ; newparam = newvalue ; secondparam ; q = 0.33"
+    );
     let contact_params = contact.params().unwrap();
     assert_eq!(contact_params.get(&"newparam"), Some(&Some("newvalue")));
     assert_eq!(contact_params.get(&"secondparam"), Some(&None));
@@ -216,14 +227,389 @@ fn parse_request() {
     /*********************************************************/
     assert_eq!(
         parsed_req.body.unwrap(),
-        "v=0\
-    o=mhandley 29739 7272939 IN IP4 192.0.2.3\
-    s=-\
-    c=IN IP4 192.0.2.4\
-    t=0 0\
-    m=audio 49217 RTP/AVP 0 12\
-    m=video 3227 RTP/AVP 31\
-    a=rtpmap:31 LPC"
+        "v=0\r\n\
+    o=mhandley 29739 7272939 IN IP4 192.0.2.3\r\n\
+    s=-\r\n\
+    c=IN IP4 192.0.2.4\r\n\
+    t=0 0\r\n\
+    m=audio 49217 RTP/AVP 0 12\r\n\
+    m=video 3227 RTP/AVP 31\r\n\
+    a=rtpmap:31 LPC\r\n"
             .as_bytes()
     );
 }
+
+fn parse_valid(raw_message: &[u8]) -> SipMessage<'_> {
+    match SipMessage::parse(raw_message) {
+        Ok((rest, msg)) => {
+            assert!(rest.is_empty(), "Message should be parsed completely");
+            msg
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => panic!("{}", e),
+        Err(_) => panic!("Unexpected error type"),
+    }
+}
+
+fn parse_invalid(raw_message: &[u8]) -> SipParseError<'_> {
+    match SipMessage::parse(raw_message) {
+        Ok(_) => panic!("Message should not be parsed"),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+        Err(_) => panic!("Unexpected error type"),
+    }
+}
+
+/// rfc4475 section-3.1.1. Valid Messages
+#[test]
+fn valid_messages() {
+    let msg = parse_valid(include_bytes!("rfc4475/wsinv.dat"));
+    assert_eq!(msg.request().unwrap().rl.method, SipMethod::INVITE);
+
+    let msg = parse_valid(include_bytes!("rfc4475/intmeth.dat"));
+    let req = msg.request().unwrap();
+    assert_eq!(req.rl.method, SipMethod::Extension);
+    assert_eq!(
+        req.rl.method_str(),
+        "!interesting-Method0123456789_*+`.%indeed'~"
+    );
+    let user_info = req.rl.uri.user_info().unwrap();
+    assert_eq!(
+        user_info.value,
+        "1_unusual.URI~(to-be!sure)&isn't+it$/crazy?,/;;*"
+    );
+    assert_eq!(
+        user_info.password,
+        Some("&it+has=1,weird!*pas$wo~d_too.(doesn't-it)")
+    );
+    assert_eq!(req.rl.uri.hostport.host, "example.com");
+    let from = req.headers.get_rfc_s(SipRFCHeader::From).unwrap();
+    assert_eq!(
+        from.value.tags().unwrap()[&SipHeaderTagType::DisplayName],
+        b"token1~` token2'+_ token3*%!.-"
+    );
+    assert_eq!(
+        from.params().unwrap().get("tag"),
+        Some(&Some("_token~1'+`*%!-."))
+    );
+    let cseq = req.headers.get_rfc_s(SipRFCHeader::CSeq).unwrap();
+    assert_eq!(
+        cseq.value.tags().unwrap()[&SipHeaderTagType::Method],
+        req.rl.method_str().as_bytes()
+    );
+    assert!(req.headers.get_ext_s("extensionheader-!.%*+_`'~").is_some());
+
+    let msg = parse_valid(include_bytes!("rfc4475/esc01.dat"));
+    let req = msg.request().unwrap();
+    assert_eq!(
+        req.rl.uri.user_info().unwrap().value,
+        "sips%3Auser%40example.com"
+    );
+    let to = req.headers.get_rfc_s(SipRFCHeader::To).unwrap();
+    assert_eq!(
+        to.value.sip_uri().unwrap().user_info().unwrap().value,
+        "%75se%72"
+    );
+
+    let msg = parse_valid(include_bytes!("rfc4475/escnull.dat"));
+    let req = msg.request().unwrap();
+    let contacts = req.headers.get_rfc(SipRFCHeader::Contact).unwrap();
+    assert_eq!(contacts.len(), 2);
+    assert_eq!(
+        contacts[1]
+            .value
+            .sip_uri()
+            .unwrap()
+            .user_info()
+            .unwrap()
+            .value,
+        "%00%00"
+    );
+
+    let msg = parse_valid(include_bytes!("rfc4475/esc02.dat"));
+    assert_eq!(msg.request().unwrap().rl.method, SipMethod::Extension);
+
+    let msg = parse_valid(include_bytes!("rfc4475/lwsdisp.dat"));
+    let from = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::From)
+        .unwrap();
+    assert_eq!(
+        from.value.tags().unwrap()[&SipHeaderTagType::DisplayName],
+        b"caller"
+    );
+
+    let msg = parse_valid(include_bytes!("rfc4475/longreq.dat"));
+    let req = msg.request().unwrap();
+    assert_eq!(req.headers.get_rfc(SipRFCHeader::Via).unwrap().len(), 33);
+    assert_eq!(req.body.unwrap().len(), 150);
+
+    let msg = parse_valid(include_bytes!("rfc4475/semiuri.dat"));
+    let uri = &msg.request().unwrap().rl.uri;
+    assert_eq!(uri.user_info().unwrap().value, "user;par=u%40example.net");
+    assert_eq!(uri.hostport.host, "example.com");
+
+    let msg = parse_valid(include_bytes!("rfc4475/transports.dat"));
+    let vias = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc(SipRFCHeader::Via)
+        .unwrap();
+    let transports: Vec<_> = vias
+        .iter()
+        .map(|via| via.value.tags().unwrap()[&SipHeaderTagType::ProtocolTransport])
+        .collect();
+    assert_eq!(
+        transports,
+        vec![&b"UDP"[..], b"SCTP", b"TLS", b"UNKNOWN", b"TCP"]
+    );
+
+    let msg = parse_valid(include_bytes!("rfc4475/mpart01.dat"));
+    assert!(msg.request().unwrap().body.is_some());
+
+    let msg = parse_valid(include_bytes!("rfc4475/unreason.dat"));
+    let res = msg.response().unwrap();
+    assert_eq!(res.sl.status_code, SipResponseStatusCode::OK);
+    assert!(res.sl.reason_phrase.starts_with("= 2**3 * 5**2 "));
+
+    let msg = parse_valid(include_bytes!("rfc4475/noreason.dat"));
+    let res = msg.response().unwrap();
+    assert_eq!(res.sl.status_code, SipResponseStatusCode::Trying);
+    assert_eq!(res.sl.reason_phrase, "");
+}
+
+/// rfc4475 section-3.1.1.8. Two requests in one datagram.
+/// Only first request is parsed, the rest is returned
+#[test]
+fn double_request() {
+    let buf = include_bytes!("rfc4475/dblreq.dat");
+    let (rest, msg) = SipMessage::parse(buf).ok().unwrap();
+    assert_eq!(msg.request().unwrap().rl.method, SipMethod::REGISTER);
+    assert!(rest.starts_with(b"INVITE sip:joe@example.com SIP/2.0\r\n"));
+
+    let (rest, msg) = SipMessage::parse(rest).ok().unwrap();
+    assert_eq!(msg.request().unwrap().rl.method, SipMethod::INVITE);
+    assert!(rest.is_empty());
+}
+
+/// rfc4475 section-3.1.2. Invalid Messages
+#[test]
+fn invalid_messages() {
+    let cases: Vec<(&[u8], SipParseErrorKind)> = vec![
+        (
+            include_bytes!("rfc4475/badinv01.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "Via" },
+        ),
+        (
+            include_bytes!("rfc4475/clerr.dat"),
+            SipParseErrorKind::BodyTooShort,
+        ),
+        (
+            include_bytes!("rfc4475/scalar02.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "CSeq" },
+        ),
+        (
+            include_bytes!("rfc4475/scalarlg.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "CSeq" },
+        ),
+        (
+            include_bytes!("rfc4475/quotbal.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "To" },
+        ),
+        (
+            include_bytes!("rfc4475/ltgtruri.dat"),
+            SipParseErrorKind::BadUri,
+        ),
+        (
+            include_bytes!("rfc4475/lwsruri.dat"),
+            SipParseErrorKind::BadStartLine,
+        ),
+        (
+            include_bytes!("rfc4475/lwsstart.dat"),
+            SipParseErrorKind::BadStartLine,
+        ),
+        (
+            include_bytes!("rfc4475/trws.dat"),
+            SipParseErrorKind::BadStartLine,
+        ),
+        (
+            include_bytes!("rfc4475/baddate.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "Date" },
+        ),
+        (
+            include_bytes!("rfc4475/regbadct.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "Contact" },
+        ),
+        (
+            include_bytes!("rfc4475/badaspec.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "To" },
+        ),
+        (
+            include_bytes!("rfc4475/baddn.dat"),
+            SipParseErrorKind::BadHeaderValue { header: "From" },
+        ),
+        (
+            include_bytes!("rfc4475/badvers.dat"),
+            SipParseErrorKind::UnsupportedVersion,
+        ),
+        (
+            include_bytes!("rfc4475/mismatch01.dat"),
+            SipParseErrorKind::CSeqMethodMismatch,
+        ),
+        (
+            include_bytes!("rfc4475/mismatch02.dat"),
+            SipParseErrorKind::CSeqMethodMismatch,
+        ),
+        (
+            include_bytes!("rfc4475/bigcode.dat"),
+            SipParseErrorKind::BadStatusCode,
+        ),
+        (
+            include_bytes!("rfc4475/ncl.dat"),
+            SipParseErrorKind::BadHeaderValue {
+                header: "Content-Length",
+            },
+        ),
+        (
+            include_bytes!("rfc4475/mcl01.dat"),
+            SipParseErrorKind::BadHeaderValue {
+                header: "Content-Length",
+            },
+        ),
+        (
+            include_bytes!("rfc4475/unkscm.dat"),
+            SipParseErrorKind::UnsupportedUriScheme,
+        ),
+        (
+            include_bytes!("rfc4475/novelsc.dat"),
+            SipParseErrorKind::UnsupportedUriScheme,
+        ),
+    ];
+    for (raw_message, kind) in cases {
+        assert_eq!(parse_invalid(raw_message).kind, kind);
+    }
+
+    assert_eq!(
+        parse_invalid(include_bytes!("rfc4475/badvers.dat")).status_code(),
+        505
+    );
+    assert_eq!(
+        parse_invalid(include_bytes!("rfc4475/unkscm.dat")).status_code(),
+        416
+    );
+    assert_eq!(
+        parse_invalid(include_bytes!("rfc4475/mismatch01.dat")).status_code(),
+        400
+    );
+
+    // CSeq method is not checked in lenient mode
+    let buf = include_bytes!("rfc4475/mismatch01.dat");
+    assert!(SipMessage::parse_ext(buf, &ParseOptions::lenient()).is_ok());
+}
+
+/// rfc4475 section-3.2 and section-3.3.
+/// These messages are syntactically valid. Semantic checks are
+/// responsibility of transaction and application layers
+#[test]
+fn semantically_invalid_messages() {
+    let msg = parse_valid(include_bytes!("rfc4475/badbranch.dat"));
+    let via = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Via)
+        .unwrap();
+    assert_eq!(via.params().unwrap().get("branch"), Some(&Some("z9hG4bK")));
+
+    let msg = parse_valid(include_bytes!("rfc4475/insuf.dat"));
+    let headers = &msg.request().unwrap().headers;
+    assert!(headers.get_rfc_s(SipRFCHeader::From).is_none());
+    assert!(headers.get_rfc_s(SipRFCHeader::To).is_none());
+    assert!(headers.get_rfc_s(SipRFCHeader::CallID).is_none());
+    assert!(headers.get_rfc_s(SipRFCHeader::MaxForwards).is_none());
+
+    let msg = parse_valid(include_bytes!("rfc4475/multi01.dat"));
+    let headers = &msg.request().unwrap().headers;
+    assert_eq!(headers.get_rfc(SipRFCHeader::CSeq).unwrap().len(), 2);
+    assert_eq!(headers.get_rfc(SipRFCHeader::CallID).unwrap().len(), 2);
+    assert_eq!(headers.get_rfc(SipRFCHeader::To).unwrap().len(), 2);
+    assert_eq!(headers.get_rfc(SipRFCHeader::From).unwrap().len(), 2);
+    assert_eq!(headers.get_rfc(SipRFCHeader::MaxForwards).unwrap().len(), 2);
+
+    // section-3.1.2.11 lists escruri as invalid, but lets an element either reject it
+    // with 400 or ignore the escaped headers. The parser keeps them in the URI,
+    // so the application makes this choice
+    let msg = parse_valid(include_bytes!("rfc4475/escruri.dat"));
+    let uri = &msg.request().unwrap().rl.uri;
+    assert_eq!(
+        uri.headers().unwrap().get("Route"),
+        Some(&"%3Csip:example.com%3E")
+    );
+
+    // section-3.3.4. Unknown URI schemes in header fields
+    for raw_message in &[
+        &include_bytes!("rfc4475/unksm.dat")[..],
+        include_bytes!("rfc4475/unksm2.dat"),
+    ] {
+        let msg = parse_valid(raw_message);
+        let headers = &msg.request().unwrap().headers;
+        for header in &[SipRFCHeader::To, SipRFCHeader::From, SipRFCHeader::Contact] {
+            let value = &headers.get_rfc_s(*header).unwrap().value;
+            assert!(value.sip_uri().is_none());
+            assert!(value
+                .tags()
+                .unwrap()
+                .contains_key(&SipHeaderTagType::AbsoluteURI));
+        }
+    }
+
+    for raw_message in &[
+        &include_bytes!("rfc4475/bext01.dat")[..],
+        include_bytes!("rfc4475/invut.dat"),
+        include_bytes!("rfc4475/regaut01.dat"),
+        include_bytes!("rfc4475/bcast.dat"),
+        include_bytes!("rfc4475/sdp01.dat"),
+        include_bytes!("rfc4475/inv2543.dat"),
+    ] {
+        parse_valid(raw_message);
+    }
+
+    let msg = parse_valid(include_bytes!("rfc4475/zeromf.dat"));
+    let max_forwards = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::MaxForwards);
+    assert_eq!(max_forwards.unwrap().value.vstr, "0");
+
+    let msg = parse_valid(include_bytes!("rfc4475/cparam01.dat"));
+    let contact = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Contact)
+        .unwrap();
+    assert_eq!(contact.params().unwrap().get("unknownparam"), Some(&None));
+    assert_eq!(
+        contact.value.sip_uri().unwrap().user_info().unwrap().value,
+        "+19725552222"
+    );
+
+    let msg = parse_valid(include_bytes!("rfc4475/cparam02.dat"));
+    assert!(msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Contact)
+        .is_some());
+
+    let msg = parse_valid(include_bytes!("rfc4475/regescrt.dat"));
+    let contact = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Contact)
+        .unwrap();
+    assert!(contact.value.sip_uri().unwrap().headers().is_some());
+}
@@ -0,0 +1,122 @@
+use sipmsg::*;
+
+fn parse_valid(raw_message: &[u8]) -> SipMessage<'_> {
+    match SipMessage::parse(raw_message) {
+        Ok((rest, msg)) => {
+            assert!(rest.is_empty(), "Message should be parsed completely");
+            msg
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => panic!("{}", e),
+        Err(_) => panic!("Unexpected error type"),
+    }
+}
+
+fn parse_invalid(raw_message: &[u8]) -> SipParseError<'_> {
+    match SipMessage::parse(raw_message) {
+        Ok(_) => panic!("Message should not be parsed"),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+        Err(_) => panic!("Unexpected error type"),
+    }
+}
+
+#[test]
+fn ipv6_request_uri() {
+    let msg = parse_valid(include_bytes!("rfc5118/ipv6-good.dat"));
+    let req = msg.request().unwrap();
+    assert_eq!(req.rl.uri.hostport.host, "2001:db8::10");
+    let via = req.headers.get_rfc_s(SipRFCHeader::Via).unwrap();
+    assert_eq!(
+        via.value.tags().unwrap()[&SipHeaderTagType::Host],
+        b"2001:db8::9:1"
+    );
+
+    let err = parse_invalid(include_bytes!("rfc5118/ipv6-bad.dat"));
+    assert_eq!(err.kind, SipParseErrorKind::BadUri);
+}
+
+#[test]
+fn ipv6_port() {
+    let msg = parse_valid(include_bytes!("rfc5118/port-ambiguous.dat"));
+    let hostport = &msg.request().unwrap().rl.uri.hostport;
+    assert_eq!(hostport.host, "2001:db8::10:5070");
+    assert_eq!(hostport.port, None);
+
+    let msg = parse_valid(include_bytes!("rfc5118/port-unambiguous.dat"));
+    let hostport = &msg.request().unwrap().rl.uri.hostport;
+    assert_eq!(hostport.host, "2001:db8::10");
+    assert_eq!(hostport.port, Some(5070));
+}
+
+#[test]
+fn via_received_param() {
+    let msg = parse_valid(include_bytes!("rfc5118/via-received-param-with-delim.dat"));
+    let via = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Via)
+        .unwrap();
+    assert_eq!(
+        via.params().unwrap().get("received"),
+        Some(&Some("2001:db8::9:255"))
+    );
+
+    let msg = parse_valid(include_bytes!("rfc5118/via-received-param-no-delim.dat"));
+    let via = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Via)
+        .unwrap();
+    assert_eq!(
+        via.params().unwrap().get("received"),
+        Some(&Some("2001:db8::9:255"))
+    );
+    assert_eq!(
+        via.params().unwrap().get("branch"),
+        Some(&Some("z9hG4bKas3"))
+    );
+}
+
+#[test]
+fn mixed_ip_versions() {
+    let msg = parse_valid(include_bytes!("rfc5118/mult-ip-in-header.dat"));
+    let vias = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc(SipRFCHeader::Via)
+        .unwrap();
+    assert_eq!(vias.len(), 3);
+    assert_eq!(
+        vias[2].params().unwrap().get("received"),
+        Some(&Some("192.0.2.200"))
+    );
+
+    let msg = parse_valid(include_bytes!("rfc5118/mult-ip-in-sdp.dat"));
+    assert!(msg.request().unwrap().body.is_some());
+
+    let msg = parse_valid(include_bytes!("rfc5118/ipv4-mapped-ipv6.dat"));
+    let contact = msg
+        .request()
+        .unwrap()
+        .headers
+        .get_rfc_s(SipRFCHeader::Contact)
+        .unwrap();
+    assert_eq!(
+        contact.value.sip_uri().unwrap().hostport.host,
+        "::ffff:192.0.2.2"
+    );
+}
+
+#[test]
+fn ipv6_abnf() {
+    let err = parse_invalid(include_bytes!("rfc5118/ipv6-bug-abnf-3-colons.dat"));
+    assert_eq!(err.kind, SipParseErrorKind::BadUri);
+
+    let msg = parse_valid(include_bytes!("rfc5118/ipv6-correct-abnf-2-colons.dat"));
+    assert_eq!(
+        msg.request().unwrap().rl.uri.hostport.host,
+        "2001:db8::192.0.2.1"
+    );
+}
@@ -0,0 +1,91 @@
+use sipmsg::sdp::*;
+use sipmsg::*;
+
+#[test]
+fn request_body_as_sdp() {
+    let invite_msg_buf = "INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+Via: SIP/2.0/TCP client.atlanta.example.com:5060;branch=z9hG4bK74bf9\r\n\
+Max-Forwards: 70\r\n\
+From: Alice <sip:alice@atlanta.example.com>;tag=9fxced76sl\r\n\
+To: Bob <sip:bob@biloxi.example.com>\r\n\
+Call-ID: 3848276298220188511@atlanta.example.com\r\n\
+CSeq: 1 INVITE\r\n\
+Contact: <sip:alice@client.atlanta.example.com;transport=tcp>\r\n\
+Content-Type: application/sdp\r\n\
+Content-Length: 151\r\n\
+\r\n\
+v=0\r\n\
+o=alice 2890844526 2890844526 IN IP4 client.atlanta.example.com\r\n\
+s=-\r\n\
+c=IN IP4 192.0.2.101\r\n\
+t=0 0\r\n\
+m=audio 49172 RTP/AVP 0\r\n\
+a=rtpmap:0 PCMU/8000\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(invite_msg_buf).unwrap();
+    let sdp = request.body_as_sdp().unwrap();
+    assert_eq!(sdp.origin.username, "alice");
+    assert_eq!(sdp.media_connection(0).unwrap().host(), "192.0.2.101");
+    assert_eq!(sdp.media[0].rtpmap(0).unwrap().encoding_name, "PCMU");
+    assert_eq!(sdp.media_direction(0), Some(Direction::SendRecv));
+    assert_eq!(sdp.to_string().as_bytes(), request.body.unwrap());
+}
+
+#[test]
+fn response_body_as_sdp_errors() {
+    let response_buf = "SIP/2.0 200 OK\r\n\
+Call-ID: a84b4c76e66710\r\n\
+Content-Type: application/sdp\r\n\
+Content-Length: 41\r\n\
+\r\n\
+v=0\r\n\
+o=- 1 1 IN IP4 192.0.2.1\r\n\
+s=-\r\n\
+x=1\r\n"
+        .as_bytes();
+    let (_, response) = SipResponse::parse(response_buf).unwrap();
+    match response.body_as_sdp() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::BadSdp { field: "x" });
+            assert_eq!(e.line, Some(4));
+            assert_eq!(e.offset, Some(36));
+        }
+        _ => panic!("SDP should not be parsed"),
+    }
+
+    let response_buf = "SIP/2.0 200 OK\r\n\
+Call-ID: a84b4c76e66710\r\n\
+Content-Type: application/pidf+xml\r\n\
+Content-Length: 0\r\n\r\n"
+        .as_bytes();
+    let (_, response) = SipResponse::parse(response_buf).unwrap();
+    match response.body_as_sdp() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::UnsupportedMediaType);
+            assert_eq!(e.status_code(), 415);
+        }
+        _ => panic!("SDP should not be parsed"),
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+fn encoded_sdp_without_compression() {
+    let request_buf = "INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+CSeq: 1 INVITE\r\n\
+Content-Type: application/sdp\r\n\
+Content-Encoding: gzip\r\n\
+Content-Length: 4\r\n\r\n\
+v=0\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(request_buf).unwrap();
+    match request.body_as_sdp() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(
+                e.kind,
+                SipParseErrorKind::BadContentEncoding { coding: "gzip" }
+            )
+        }
+        _ => panic!("gzip body should not be parsed without compression feature"),
+    }
+}
@@ -0,0 +1,54 @@
+use sipmsg::*;
+
+#[test]
+fn notify_body_as_sipfrag() {
+    let notify_buf = "NOTIFY sip:a@atlanta.example.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP agenta.atlanta.example.com;branch=z9hG4bK9922ef992-25\r\n\
+To: <sip:a@atlanta.example.com>;tag=1928301774\r\n\
+From: <sip:b@atlanta.example.com>;tag=a6c85cf\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 73 NOTIFY\r\n\
+Event: refer\r\n\
+Subscription-State: active;expires=60\r\n\
+Content-Type: message/sipfrag;version=2.0\r\n\
+Content-Length: 20\r\n\
+\r\n\
+SIP/2.0 100 Trying\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    let frag = request.body_as_sipfrag().unwrap();
+    assert_eq!(frag.status_code(), Some(SipResponseStatusCode::Trying));
+    assert!(!frag.is_final());
+    assert!(request.body_as_sdp().is_err());
+}
+
+#[test]
+fn notify_body_as_sipfrag_errors() {
+    let notify_buf = "NOTIFY sip:a@atlanta.example.com SIP/2.0\r\n\
+CSeq: 74 NOTIFY\r\n\
+Content-Type: message/sipfrag\r\n\
+Content-Length: 36\r\n\
+\r\n\
+SIP/2.0 200 OK\r\n\
+Call-ID a84b4c76e66710\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    match request.body_as_sipfrag() {
+        Err(nom::Err::Error(e)) => {
+            assert_eq!(e.kind, SipParseErrorKind::BadHeaderName);
+            assert_eq!(e.line, Some(2));
+            assert_eq!(e.offset, Some(24));
+        }
+        _ => panic!("Fragment should not be parsed"),
+    }
+
+    let notify_buf = "NOTIFY sip:a@atlanta.example.com SIP/2.0\r\n\
+CSeq: 75 NOTIFY\r\n\
+Content-Length: 0\r\n\r\n"
+        .as_bytes();
+    let (_, request) = SipRequest::parse(notify_buf).unwrap();
+    match request.body_as_sipfrag() {
+        Err(nom::Err::Error(e)) => assert_eq!(e.status_code(), 415),
+        _ => panic!("Fragment should not be parsed"),
+    }
+}
@@ -0,0 +1,67 @@
+//! Source of time for protocol logic.
+//!
+//! State machines never call `Instant::now` themselves, they take `now` as argument.
+//! Drivers read it from `Clock`, so tests can use `ManualClock` and fire timers
+//! without waiting for them.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// Monotonic clock of operating system
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that is moved only by `advance`
+/// ```rust
+/// use sipcore::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_millis(500));
+/// assert_eq!(clock.now() - start, Duration::from_millis(500));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Cell<Instant>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Cell::new(Instant::now()),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
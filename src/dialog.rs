@@ -0,0 +1,467 @@
+//! Dialog layer. [rfc3261 section-12](https://tools.ietf.org/html/rfc3261#section-12)
+//!
+//! `Dialog` is created by UAC from 1xx/2xx response with To tag and by UAS from
+//! response that it sends. It keeps state that is needed for requests within
+//! dialog: route set, remote target, local and remote CSeq. Dialogs are looked up
+//! by `DialogId` of received messages.
+//!
+//! Like transactions, dialog doesn't send anything. `Dialog::request` returns bytes
+//! of request that should be sent by `Engine::send_request` to resolved `next_hop`.
+//! `Engine` keeps dialogs of INVITE transactions that it drives, see `Engine::dialog_mut`.
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::dialog::{Dialog, DialogId, DialogState};
+//! use sipcore::{SipMethod, SipRequest, SipResponse};
+//!
+//! let invite = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+//! To: Bob <sip:bob@biloxi.com>\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 314159 INVITE\r\n\
+//! Contact: <sip:alice@pc33.atlanta.com>\r\n\r\n";
+//! let ok = "SIP/2.0 200 OK\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+//! Record-Route: <sip:p1.example.com;lr>\r\n\
+//! To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 314159 INVITE\r\n\
+//! Contact: <sip:bob@192.0.2.4>\r\n\r\n";
+//! let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+//! let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+//!
+//! let mut dialog = Dialog::uac(&invite, &ok).unwrap();
+//! assert_eq!(dialog.state(), DialogState::Confirmed);
+//! assert_eq!(DialogId::from_response(&ok).as_ref(), Some(dialog.id()));
+//!
+//! let bye = dialog
+//!     .request(SipMethod::BYE, "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds7", "", b"")
+//!     .unwrap();
+//! assert_eq!(
+//!     bye,
+//!     "BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds7\r\n\
+//! Route: <sip:p1.example.com;lr>\r\n\
+//! Max-Forwards: 70\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 314160 BYE\r\n\
+//! Content-Length: 0\r\n\r\n"
+//!         .as_bytes()
+//! );
+//! assert_eq!(dialog.next_hop(), "sip:p1.example.com;lr");
+//! assert_eq!(dialog.state(), DialogState::Terminated);
+//! ```
+
+use crate::message::{cseq_method, cseq_number, status_code, tag, uri};
+use crate::{
+    SipHeader, SipHeaders, SipMethod, SipRFCHeader, SipRequest, SipRequestUriScheme, SipResponse,
+    SipUri,
+};
+use std::fmt::{self, Write};
+
+/// Identifies dialog. [rfc3261 section-12](https://tools.ietf.org/html/rfc3261#section-12)
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DialogId {
+    pub call_id: String,
+    pub local_tag: String,
+    pub remote_tag: String,
+}
+
+impl DialogId {
+    /// Id of UAS dialog that should receive request. `None` if request has no To tag
+    pub fn from_request(request: &SipRequest) -> Option<DialogId> {
+        let headers = &request.headers;
+        Some(DialogId {
+            call_id: call_id(headers)?.to_string(),
+            local_tag: tag(headers, SipRFCHeader::To)?.to_string(),
+            remote_tag: tag(headers, SipRFCHeader::From)?.to_string(),
+        })
+    }
+
+    /// Id of UAC dialog that should receive response. `None` if response has no To tag
+    pub fn from_response(response: &SipResponse) -> Option<DialogId> {
+        let headers = &response.headers;
+        Some(DialogId {
+            call_id: call_id(headers)?.to_string(),
+            local_tag: tag(headers, SipRFCHeader::From)?.to_string(),
+            remote_tag: tag(headers, SipRFCHeader::To)?.to_string(),
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DialogState {
+    /// Created by provisional response
+    Early,
+    /// Created or confirmed by 2xx response
+    Confirmed,
+    /// BYE is sent or received, or early dialog is ended by non-2xx final response
+    Terminated,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Message can't create dialog, doesn't belong to it or method can't be sent within it
+    InvalidMessage,
+    /// Dialog is terminated
+    InvalidState,
+    /// CSeq of request is lower than remote CSeq. UAS must answer 500 (Server Internal Error)
+    OutOfOrder,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMessage => f.write_str("Invalid message for dialog"),
+            Error::InvalidState => f.write_str("Dialog is terminated"),
+            Error::OutOfOrder => f.write_str("CSeq is lower than remote CSeq"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Dialog {
+    id: DialogId,
+    state: DialogState,
+    uac: bool,
+    /// From or To header of local side with tag
+    local: String,
+    /// From or To header of remote side with tag
+    remote: String,
+    /// Contact URI of local side
+    local_target: Option<String>,
+    /// Contact URI of remote side
+    remote_target: String,
+    /// Record-Route values in order of Route headers
+    route_set: Vec<String>,
+    local_cseq: Option<u32>,
+    remote_cseq: Option<u32>,
+    secure: bool,
+}
+
+impl Dialog {
+    /// Dialog of UAC from 1xx or 2xx `response` to `request`.
+    /// [rfc3261 section-12.1.2](https://tools.ietf.org/html/rfc3261#section-12.1.2)
+    pub fn uac(request: &SipRequest, response: &SipResponse) -> Result<Dialog, Error> {
+        let state = creating_state(response)?;
+        let id = DialogId::from_response(response).ok_or(Error::InvalidMessage)?;
+        if tag(&request.headers, SipRFCHeader::From) != Some(id.local_tag.as_str()) {
+            return Err(Error::InvalidMessage);
+        }
+        Ok(Dialog {
+            id,
+            state,
+            uac: true,
+            local: header_value(&request.headers, SipRFCHeader::From)?,
+            remote: header_value(&response.headers, SipRFCHeader::To)?,
+            local_target: contact(&request.headers).map(str::to_string),
+            remote_target: contact(&response.headers)
+                .ok_or(Error::InvalidMessage)?
+                .to_string(),
+            route_set: record_route(&response.headers).into_iter().rev().collect(),
+            local_cseq: cseq_number(&request.headers),
+            remote_cseq: None,
+            secure: request.rl.uri.scheme == SipRequestUriScheme::SIPS,
+        })
+    }
+
+    /// Dialog of UAS from 1xx or 2xx `response` with To tag that it sends to `request`.
+    /// [rfc3261 section-12.1.1](https://tools.ietf.org/html/rfc3261#section-12.1.1)
+    pub fn uas(request: &SipRequest, response: &SipResponse) -> Result<Dialog, Error> {
+        let state = creating_state(response)?;
+        let id = DialogId::from_response(response).ok_or(Error::InvalidMessage)?;
+        let id = DialogId {
+            call_id: id.call_id,
+            local_tag: id.remote_tag,
+            remote_tag: id.local_tag,
+        };
+        Ok(Dialog {
+            id,
+            state,
+            uac: false,
+            local: header_value(&response.headers, SipRFCHeader::To)?,
+            remote: header_value(&request.headers, SipRFCHeader::From)?,
+            local_target: contact(&response.headers).map(str::to_string),
+            remote_target: contact(&request.headers)
+                .ok_or(Error::InvalidMessage)?
+                .to_string(),
+            route_set: record_route(&request.headers),
+            local_cseq: None,
+            remote_cseq: cseq_number(&request.headers),
+            secure: request.rl.uri.scheme == SipRequestUriScheme::SIPS,
+        })
+    }
+
+    pub fn id(&self) -> &DialogId {
+        &self.id
+    }
+
+    pub fn state(&self) -> DialogState {
+        self.state
+    }
+
+    /// Request-URI and Route of requests must be SIPS
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn remote_target(&self) -> &str {
+        &self.remote_target
+    }
+
+    /// URIs of Record-Route in order of Route headers
+    pub fn route_set(&self) -> &[String] {
+        &self.route_set
+    }
+
+    pub fn local_cseq(&self) -> Option<u32> {
+        self.local_cseq
+    }
+
+    pub fn remote_cseq(&self) -> Option<u32> {
+        self.remote_cseq
+    }
+
+    /// Contact URI that is sent in target refresh requests
+    pub fn set_local_target(&mut self, uri: &str) {
+        self.local_target = Some(uri.to_string());
+    }
+
+    /// URI that should be resolved to send request within dialog: first route
+    /// or remote target. [rfc3261 section-8.1.2](https://tools.ietf.org/html/rfc3261#section-8.1.2)
+    pub fn next_hop(&self) -> &str {
+        self.route_set
+            .first()
+            .map(|route| uri(route))
+            .unwrap_or(&self.remote_target)
+    }
+
+    /// Updates dialog by response to request within dialog or to request that created
+    /// it. For UAS it is response that UAS sends.
+    ///
+    /// 2xx confirms early dialog. UAC takes route set of 2xx and remote target of
+    /// 2xx to INVITE or UPDATE. Non-2xx final response to INVITE terminates early
+    /// dialog, 481 (Call/Transaction Does Not Exist) and 408 (Request Timeout)
+    /// terminate any dialog
+    pub fn on_response(&mut self, response: &SipResponse) -> Result<(), Error> {
+        let id = DialogId::from_response(response).ok_or(Error::InvalidMessage)?;
+        let matches = if self.uac {
+            id == self.id
+        } else {
+            id.call_id == self.id.call_id
+                && id.local_tag == self.id.remote_tag
+                && id.remote_tag == self.id.local_tag
+        };
+        if !matches {
+            return Err(Error::InvalidMessage);
+        }
+        if self.state == DialogState::Terminated {
+            return Err(Error::InvalidState);
+        }
+
+        let code = status_code(response);
+        let method = cseq_method(&response.headers).ok_or(Error::InvalidMessage)?;
+        let is_invite = method.eq_ignore_ascii_case(SipMethod::INVITE.as_str());
+        let target_refresh = is_invite || method.eq_ignore_ascii_case(SipMethod::UPDATE.as_str());
+        match code {
+            200..=299 => {
+                if self.uac && self.state == DialogState::Early && is_invite {
+                    self.route_set = record_route(&response.headers).into_iter().rev().collect();
+                }
+                if self.uac && target_refresh {
+                    if let Some(contact) = contact(&response.headers) {
+                        self.remote_target = contact.to_string();
+                    }
+                }
+                if is_invite {
+                    self.state = DialogState::Confirmed;
+                }
+            }
+            408 | 481 => self.state = DialogState::Terminated,
+            300..=699 if is_invite && self.state == DialogState::Early => {
+                self.state = DialogState::Terminated
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Updates UAS part of dialog by request within it.
+    /// [rfc3261 section-12.2.2](https://tools.ietf.org/html/rfc3261#section-12.2.2)
+    ///
+    /// INVITE and UPDATE replace remote target, BYE terminates dialog
+    pub fn on_request(&mut self, request: &SipRequest) -> Result<(), Error> {
+        if DialogId::from_request(request).as_ref() != Some(&self.id) {
+            return Err(Error::InvalidMessage);
+        }
+        if self.state == DialogState::Terminated {
+            return Err(Error::InvalidState);
+        }
+        let method = request.rl.method;
+        // ACK and CANCEL have CSeq of INVITE
+        if method != SipMethod::ACK && method != SipMethod::CANCEL {
+            let cseq = cseq_number(&request.headers).ok_or(Error::InvalidMessage)?;
+            if matches!(self.remote_cseq, Some(remote) if cseq < remote) {
+                return Err(Error::OutOfOrder);
+            }
+            self.remote_cseq = Some(cseq);
+        }
+        match method {
+            SipMethod::INVITE | SipMethod::UPDATE => {
+                if let Some(contact) = contact(&request.headers) {
+                    self.remote_target = contact.to_string();
+                }
+            }
+            SipMethod::BYE => self.state = DialogState::Terminated,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Request within dialog, ex: BYE, re-INVITE, UPDATE, INFO. Local CSeq is incremented.
+    /// [rfc3261 section-12.2.1.1](https://tools.ietf.org/html/rfc3261#section-12.2.1.1)
+    ///
+    /// `via` is value of Via header with branch. `headers` are added as they are,
+    /// every header ends with CRLF. Contact is added to INVITE and UPDATE if local
+    /// target is known. BYE terminates dialog.
+    /// ACK for 2xx is built by `ack`, CANCEL doesn't belong to dialog
+    pub fn request(
+        &mut self,
+        method: SipMethod,
+        via: &str,
+        headers: &str,
+        body: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if matches!(
+            method,
+            SipMethod::ACK | SipMethod::CANCEL | SipMethod::Extension
+        ) {
+            return Err(Error::InvalidMessage);
+        }
+        if self.state == DialogState::Terminated {
+            return Err(Error::InvalidState);
+        }
+        // UAS chooses initial value like for request outside of dialog
+        let cseq = self.local_cseq.map_or(1, |cseq| cseq + 1);
+        self.local_cseq = Some(cseq);
+        if method == SipMethod::BYE {
+            self.state = DialogState::Terminated;
+        }
+        Ok(self.build(method, cseq, via, headers, body))
+    }
+
+    /// ACK for 2xx response to INVITE with `cseq` number. ACK has route set of dialog
+    /// and own branch. [rfc3261 section-13.2.2.4](https://tools.ietf.org/html/rfc3261#section-13.2.2.4)
+    pub fn ack(&self, cseq: u32, via: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        self.build(SipMethod::ACK, cseq, via, headers, body)
+    }
+
+    fn build(
+        &self,
+        method: SipMethod,
+        cseq: u32,
+        via: &str,
+        headers: &str,
+        body: &[u8],
+    ) -> Vec<u8> {
+        // route set with strict router on top: Request-URI is the first route and
+        // remote target is the last Route
+        let strict = self.route_set.first().filter(|route| !is_loose(route));
+        let (request_uri, routes) = match strict {
+            Some(route) => (uri(route), &self.route_set[1..]),
+            None => (self.remote_target.as_str(), &self.route_set[..]),
+        };
+
+        let mut request = String::with_capacity(512);
+        let _ = write!(request, "{} {} SIP/2.0\r\n", method.as_str(), request_uri);
+        let _ = write!(request, "Via: {}\r\n", via);
+        for route in routes {
+            let _ = write!(request, "Route: {}\r\n", route);
+        }
+        if strict.is_some() {
+            let _ = write!(request, "Route: <{}>\r\n", self.remote_target);
+        }
+        let _ = write!(
+            request,
+            "Max-Forwards: 70\r\nFrom: {}\r\nTo: {}\r\nCall-ID: {}\r\nCSeq: {} {}\r\n",
+            self.local,
+            self.remote,
+            self.id.call_id,
+            cseq,
+            method.as_str()
+        );
+        if let (SipMethod::INVITE | SipMethod::UPDATE, Some(target)) = (method, &self.local_target)
+        {
+            let _ = write!(request, "Contact: <{}>\r\n", target);
+        }
+        request.push_str(headers);
+        let _ = write!(request, "Content-Length: {}\r\n\r\n", body.len());
+        let mut request = request.into_bytes();
+        request.extend_from_slice(body);
+        request
+    }
+}
+
+/// State of dialog that is created by response
+fn creating_state(response: &SipResponse) -> Result<DialogState, Error> {
+    match status_code(response) {
+        101..=199 => Ok(DialogState::Early),
+        200..=299 => Ok(DialogState::Confirmed),
+        _ => Err(Error::InvalidMessage),
+    }
+}
+
+fn call_id<'a>(headers: &SipHeaders<'a>) -> Option<&'a str> {
+    Some(headers.get_rfc_s(SipRFCHeader::CallID)?.value.vstr)
+}
+
+fn header_value(headers: &SipHeaders, header: SipRFCHeader) -> Result<String, Error> {
+    let hdr: &SipHeader = headers.get_rfc_s(header).ok_or(Error::InvalidMessage)?;
+    Ok(hdr.normalized_value_param().into_owned())
+}
+
+/// URI of the first Contact
+fn contact<'a>(headers: &SipHeaders<'a>) -> Option<&'a str> {
+    Some(uri(headers.get_rfc_s(SipRFCHeader::Contact)?.value.vstr))
+}
+
+/// Values of Record-Route headers in order of message
+fn record_route(headers: &SipHeaders) -> Vec<String> {
+    headers
+        .get_rfc(SipRFCHeader::RecordRoute)
+        .map(|routes| {
+            routes
+                .iter()
+                .map(|route| route.normalized_value_param().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Route URI with `lr` parameter. [rfc3261 section-16.12.1.1](https://tools.ietf.org/html/rfc3261#section-16.12.1.1)
+fn is_loose(route: &str) -> bool {
+    match SipUri::parse(uri(route).as_bytes()) {
+        Ok((_, uri)) => uri
+            .params()
+            .is_some_and(|params| params.get("lr").is_some()),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_loose_test() {
+        assert!(is_loose("<sip:p1.example.com;lr>"));
+        assert!(is_loose("<sip:p1.example.com;transport=tcp;lr>;foo=bar"));
+        assert!(!is_loose("<sip:p1.example.com>"));
+    }
+}
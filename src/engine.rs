@@ -0,0 +1,1047 @@
+//! Sans-IO protocol engine.
+//!
+//! `Engine` doesn't own sockets and reads time only from its `Clock`.
+//! Driver feeds it with received messages and timer ticks and takes `Output`s:
+//! datagrams that should be sent and events for application.
+//! Engine also keeps dialogs of INVITE transactions and refreshes registrations.
+//! With `ManualClock` lossy networks and timer expiries can be simulated at full speed.
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::clock::{Clock, ManualClock};
+//! use sipcore::engine::{Engine, Event, Incoming, Output};
+//! use sipcore::transaction::TimerSettings;
+//! use sipcore::transport::TransportKind;
+//! use std::time::Duration;
+//!
+//! let options = "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! To: <sip:carol@chicago.com>\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 63104 OPTIONS\r\n\r\n";
+//!
+//! let clock = ManualClock::new();
+//! let mut engine = Engine::new(&clock, TimerSettings::default());
+//! let carol = "192.0.2.4:5060".parse().unwrap();
+//! let transaction = engine
+//!     .send_request(options.as_bytes().to_vec(), carol, TransportKind::Udp)
+//!     .unwrap()
+//!     .unwrap();
+//! assert!(matches!(engine.poll_output(), Some(Output::Send(_))));
+//!
+//! // request is lost and never answered
+//! clock.advance(Duration::from_secs(32));
+//! engine.handle_timers();
+//! let mut outputs = vec![];
+//! while let Some(output) = engine.poll_output() {
+//!     outputs.push(output);
+//! }
+//! assert_eq!(
+//!     outputs.last(),
+//!     Some(&Output::Event(Event::Timeout { transaction }))
+//! );
+//! ```
+
+use crate::clock::Clock;
+use crate::dialog::{self, Dialog, DialogId, DialogState};
+use crate::message::{cseq_number, response, status_code, tag};
+use crate::registration::{Registration, RegistrationState};
+use crate::transaction::{
+    Action, Error, InviteClientTransaction, InviteServerTransaction, NonInviteClientTransaction,
+    NonInviteServerTransaction, State, TimerSettings, TransactionKey,
+};
+use crate::transport::{network_limits, via, TransportKind};
+use crate::{
+    ParseOptions, SipHeaders, SipMessage, SipMethod, SipRFCHeader, SipRequest,
+    SipRequestUriScheme, SipResponse,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// Message that is received by transport
+pub struct Incoming<'a> {
+    pub message: SipMessage<'a>,
+    /// Bytes of message
+    pub raw: &'a [u8],
+    pub source: SocketAddr,
+    pub transport: TransportKind,
+}
+
+impl<'a> Incoming<'a> {
+    /// Parses message with `network_limits`
+    pub fn parse(
+        raw: &'a [u8],
+        source: SocketAddr,
+        transport: TransportKind,
+    ) -> Result<Incoming<'a>, Error> {
+        let options = ParseOptions::strict().with_limits(network_limits());
+        Incoming::parse_ext(raw, source, transport, &options)
+    }
+
+    /// Message that exceeds limits of `options` is `InvalidMessage`
+    pub fn parse_ext(
+        raw: &'a [u8],
+        source: SocketAddr,
+        transport: TransportKind,
+        options: &ParseOptions,
+    ) -> Result<Incoming<'a>, Error> {
+        let (_, message) =
+            SipMessage::parse_ext(raw, options).map_err(|_| Error::InvalidMessage)?;
+        Ok(Incoming {
+            message,
+            raw,
+            source,
+            transport,
+        })
+    }
+}
+
+/// Message that should be sent by transport
+#[derive(Clone, PartialEq, Debug)]
+pub struct Datagram {
+    pub data: Vec<u8>,
+    pub destination: SocketAddr,
+    pub transport: TransportKind,
+    /// Transaction that should get `Engine::handle_transport_error` if sending fails.
+    /// `None` for ACK of 2xx response
+    pub transaction: Option<TransactionKey>,
+}
+
+/// Events for application
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    /// Received request. Application answers by `Engine::send_response`.
+    /// `transaction` is `None` for ACK of 2xx response
+    Request {
+        transaction: Option<TransactionKey>,
+        message: Vec<u8>,
+        source: SocketAddr,
+        transport: TransportKind,
+    },
+    /// Response for request that was sent by `Engine::send_request`.
+    /// `transaction` is `None` for retransmission of 2xx when transaction is terminated
+    Response {
+        transaction: Option<TransactionKey>,
+        message: Vec<u8>,
+        source: SocketAddr,
+        transport: TransportKind,
+    },
+    /// No final response for request or no ACK for final response
+    Timeout { transaction: TransactionKey },
+    /// Timer C fired after provisional response, request should be cancelled
+    Cancel { transaction: TransactionKey },
+    /// Transport failed to send message of transaction
+    TransportError { transaction: TransactionKey },
+    /// State of registration that is started by `Engine::register` is changed.
+    /// Registration is removed when it is `Unregistered` or `Failed`
+    Registration {
+        call_id: String,
+        state: RegistrationState,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Output {
+    Send(Datagram),
+    Event(Event),
+}
+
+enum Transaction {
+    InviteClient(InviteClientTransaction),
+    NonInviteClient(NonInviteClientTransaction),
+    InviteServer(InviteServerTransaction),
+    NonInviteServer(NonInviteServerTransaction),
+}
+
+impl Transaction {
+    fn next_deadline(&self) -> Option<Instant> {
+        match self {
+            Transaction::InviteClient(t) => t.next_deadline(),
+            Transaction::NonInviteClient(t) => t.next_deadline(),
+            Transaction::InviteServer(t) => t.next_deadline(),
+            Transaction::NonInviteServer(t) => t.next_deadline(),
+        }
+    }
+
+    fn on_timer(&mut self, now: Instant) -> Vec<Action> {
+        match self {
+            Transaction::InviteClient(t) => t.on_timer(now),
+            Transaction::NonInviteClient(t) => t.on_timer(now),
+            Transaction::InviteServer(t) => t.on_timer(now),
+            Transaction::NonInviteServer(t) => t.on_timer(now),
+        }
+    }
+
+    fn on_transport_error(&mut self) -> Vec<Action> {
+        match self {
+            Transaction::InviteClient(t) => t.on_transport_error(),
+            Transaction::NonInviteClient(t) => t.on_transport_error(),
+            Transaction::InviteServer(t) => t.on_transport_error(),
+            Transaction::NonInviteServer(t) => t.on_transport_error(),
+        }
+    }
+}
+
+/// Transaction with address of peer
+struct Entry {
+    transaction: Transaction,
+    peer: SocketAddr,
+    transport: TransportKind,
+    /// INVITE of server transaction, UAS dialog is created from it
+    request: Option<Vec<u8>>,
+    /// Address of top Via where responses of server transaction are sent when
+    /// connection of request is gone
+    fallback: Option<SocketAddr>,
+}
+
+/// Registration with address of registrar
+struct RegistrationEntry {
+    registration: Registration,
+    registrar: SocketAddr,
+    transport: TransportKind,
+}
+
+/// Transaction layer driven by messages and timer ticks
+pub struct Engine<C: Clock> {
+    clock: C,
+    settings: TimerSettings,
+    parse_options: ParseOptions,
+    transactions: HashMap<TransactionKey, Entry>,
+    /// INVITE server transactions that retransmit 2xx, by dialog and CSeq number of 2xx
+    accepted: HashMap<(DialogId, u32), TransactionKey>,
+    /// Dialogs that are created by 1xx and 2xx to INVITE
+    dialogs: HashMap<DialogId, Dialog>,
+    /// Registrations by Call-ID
+    registrations: HashMap<String, RegistrationEntry>,
+    /// Call-ID of registration of REGISTER client transaction
+    registering: HashMap<TransactionKey, String>,
+    outputs: VecDeque<Output>,
+}
+
+impl<C: Clock> Engine<C> {
+    pub fn new(clock: C, settings: TimerSettings) -> Engine<C> {
+        Engine {
+            clock,
+            settings,
+            parse_options: ParseOptions::strict().with_limits(network_limits()),
+            transactions: HashMap::new(),
+            accepted: HashMap::new(),
+            dialogs: HashMap::new(),
+            registrations: HashMap::new(),
+            registering: HashMap::new(),
+            outputs: VecDeque::new(),
+        }
+    }
+
+    /// Options of `parse_incoming`. Default is strict mode with `network_limits`
+    pub fn with_parse_options(mut self, options: ParseOptions) -> Engine<C> {
+        self.parse_options = options;
+        self
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Parses message that is received by transport with options of engine
+    pub fn parse_incoming<'a>(
+        &self,
+        raw: &'a [u8],
+        source: SocketAddr,
+        transport: TransportKind,
+    ) -> Result<Incoming<'a>, Error> {
+        Incoming::parse_ext(raw, source, transport, &self.parse_options)
+    }
+
+    /// Next output of engine
+    pub fn poll_output(&mut self) -> Option<Output> {
+        self.outputs.pop_front()
+    }
+
+    /// Time when `handle_timers` should be called
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let refreshes = self
+            .registrations
+            .values()
+            .filter_map(|entry| entry.registration.next_refresh());
+        self.transactions
+            .values()
+            .filter_map(|entry| entry.transaction.next_deadline())
+            .chain(refreshes)
+            .min()
+    }
+
+    /// Number of transactions that are not terminated
+    pub fn transactions_count(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Dialog of UAC or UAS. Dialog is created by 1xx or 2xx with To tag to INVITE
+    /// and is removed when it is terminated
+    pub fn dialog(&self, id: &DialogId) -> Option<&Dialog> {
+        self.dialogs.get(id)
+    }
+
+    /// Dialog that builds requests within it, ex: BYE. Built request is sent by `send_request`
+    pub fn dialog_mut(&mut self, id: &DialogId) -> Option<&mut Dialog> {
+        self.dialogs.get_mut(id)
+    }
+
+    pub fn dialogs_count(&self) -> usize {
+        self.dialogs.len()
+    }
+
+    /// Registration that is started by `register` and isn't removed yet
+    pub fn registration(&self, call_id: &str) -> Option<&Registration> {
+        self.registrations
+            .get(call_id)
+            .map(|entry| &entry.registration)
+    }
+
+    /// Sends REGISTER of `registration` to `registrar` and refreshes binding until
+    /// `unregister`. Registration is identified by its Call-ID
+    pub fn register(
+        &mut self,
+        mut registration: Registration,
+        registrar: SocketAddr,
+        transport: TransportKind,
+    ) -> Result<TransactionKey, Error> {
+        let call_id = registration.call_id().to_string();
+        let request = registration.request();
+        self.registrations.insert(
+            call_id.clone(),
+            RegistrationEntry {
+                registration,
+                registrar,
+                transport,
+            },
+        );
+        let result = self.send_registration(&call_id, request);
+        if result.is_err() {
+            self.registrations.remove(&call_id);
+        }
+        result
+    }
+
+    /// Sends REGISTER that removes binding of registration
+    pub fn unregister(&mut self, call_id: &str) -> Result<TransactionKey, Error> {
+        let request = match self.registrations.get_mut(call_id) {
+            Some(entry) => entry
+                .registration
+                .unregister()
+                .map_err(|_| Error::InvalidState)?,
+            None => return Err(Error::InvalidState),
+        };
+        self.send_registration(call_id, request)
+    }
+
+    /// Starts client transaction. ACK is sent without transaction and `None` is returned
+    pub fn send_request(
+        &mut self,
+        request: Vec<u8>,
+        destination: SocketAddr,
+        transport: TransportKind,
+    ) -> Result<Option<TransactionKey>, Error> {
+        let now = self.clock.now();
+        let reliable = transport.is_reliable();
+        let (method, dialog) = match SipRequest::parse(&request) {
+            Ok((_, parsed)) if is_sips(&parsed) && !transport.is_secure() => {
+                return Err(Error::InsecureTransport)
+            }
+            Ok((_, parsed)) => (parsed.rl.method, dialog_id(&parsed.headers, true)),
+            Err(_) => return Err(Error::InvalidMessage),
+        };
+        // BYE terminates dialog that it is sent within
+        if let (SipMethod::BYE, Some(dialog)) = (method, dialog) {
+            self.dialogs.remove(&dialog);
+        }
+        let (key, transaction, actions) = match method {
+            SipMethod::ACK => {
+                self.send(request, destination, transport, None);
+                return Ok(None);
+            }
+            SipMethod::INVITE => {
+                let (t, actions) =
+                    InviteClientTransaction::new(request, reliable, self.settings, now)?;
+                (t.key().clone(), Transaction::InviteClient(t), actions)
+            }
+            _ => {
+                let (t, actions) =
+                    NonInviteClientTransaction::new(request, reliable, self.settings, now)?;
+                (t.key().clone(), Transaction::NonInviteClient(t), actions)
+            }
+        };
+        self.transactions.insert(
+            key.clone(),
+            Entry {
+                transaction,
+                peer: destination,
+                transport,
+                request: None,
+                fallback: None,
+            },
+        );
+        self.apply(&key, actions, None);
+        Ok(Some(key))
+    }
+
+    /// Sends response of server transaction that was reported by `Event::Request`
+    pub fn send_response(
+        &mut self,
+        transaction: &TransactionKey,
+        response: Vec<u8>,
+    ) -> Result<(), Error> {
+        let now = self.clock.now();
+        let entry = self
+            .transactions
+            .get_mut(transaction)
+            .ok_or(Error::InvalidState)?;
+        let sent = response.clone();
+        let (actions, accepted) = match &mut entry.transaction {
+            Transaction::InviteServer(t) => {
+                let actions = t.send_response(response, now)?;
+                (actions, t.state() == State::Accepted)
+            }
+            Transaction::NonInviteServer(t) => (t.send_response(response, now)?, false),
+            _ => return Err(Error::InvalidState),
+        };
+        let invite = entry.request.clone();
+        if let Ok((_, response)) = SipResponse::parse(&sent) {
+            if let (true, Some(accepted)) = (accepted, accepted_key(&response)) {
+                self.accepted.insert(accepted, transaction.clone());
+            }
+            self.update_dialog_by_response(&response, invite.as_deref(), false);
+        }
+        self.apply(transaction, actions, None);
+        Ok(())
+    }
+
+    /// Passes received message to its transaction or creates server transaction
+    pub fn handle_incoming(&mut self, incoming: &Incoming) {
+        match &incoming.message {
+            SipMessage::Request(request) => self.handle_request(request, incoming),
+            SipMessage::Response(response) => self.handle_response(response, incoming),
+        }
+    }
+
+    /// Fires timers of transactions that expired
+    pub fn handle_timers(&mut self) {
+        let now = self.clock.now();
+        let expired: Vec<TransactionKey> = self
+            .transactions
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .transaction
+                    .next_deadline()
+                    .is_some_and(|at| at <= now)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            if let Some(entry) = self.transactions.get_mut(&key) {
+                let actions = entry.transaction.on_timer(now);
+                self.apply(&key, actions, None);
+            }
+        }
+
+        let refreshes: Vec<String> = self
+            .registrations
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .registration
+                    .next_refresh()
+                    .is_some_and(|at| at <= now)
+            })
+            .map(|(call_id, _)| call_id.clone())
+            .collect();
+        for call_id in refreshes {
+            let request = match self.registrations.get_mut(&call_id) {
+                Some(entry) => entry.registration.request(),
+                None => continue,
+            };
+            if self.send_registration(&call_id, request).is_err() {
+                self.update_registration(&call_id, Registration::on_failure);
+            }
+        }
+    }
+
+    /// Transport failed to send message of transaction. Server transaction over
+    /// reliable transport resends the last response to address of Via at first
+    pub fn handle_transport_error(&mut self, transaction: &TransactionKey) {
+        if self.use_fallback(transaction) {
+            return;
+        }
+        if let Some(entry) = self.transactions.get_mut(transaction) {
+            let actions = entry.transaction.on_transport_error();
+            self.apply(transaction, actions, None);
+        }
+    }
+
+    /// Connection to peer is closed or failed.
+    /// Every transaction that uses this connection gets transport error,
+    /// server transactions go on with address of Via like in `handle_transport_error`
+    pub fn handle_connection_failure(&mut self, peer: SocketAddr, transport: TransportKind) {
+        let keys: Vec<TransactionKey> = self
+            .transactions
+            .iter()
+            .filter(|(_, entry)| entry.peer == peer && entry.transport == transport)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.handle_transport_error(&key);
+        }
+    }
+
+    /// Server transaction moves from closed connection to address of Via and
+    /// resends the last response.
+    /// [rfc3261 section-18.2.2](https://tools.ietf.org/html/rfc3261#section-18.2.2)
+    fn use_fallback(&mut self, key: &TransactionKey) -> bool {
+        let entry = match self.transactions.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let fallback = match entry.fallback.take() {
+            Some(fallback) => fallback,
+            None => return false,
+        };
+        entry.peer = fallback;
+        let response = match &entry.transaction {
+            Transaction::InviteServer(t) => Some(t.last_response()),
+            Transaction::NonInviteServer(t) => t.last_response(),
+            _ => None,
+        };
+        if let Some(response) = response.map(<[u8]>::to_vec) {
+            let transport = entry.transport;
+            self.send(response, fallback, transport, Some(key.clone()));
+        }
+        true
+    }
+
+    fn handle_request(&mut self, request: &SipRequest, incoming: &Incoming) {
+        let now = self.clock.now();
+        let key = match TransactionKey::server(request) {
+            Some(key) => key,
+            None => return,
+        };
+        if let Some(entry) = self.transactions.get_mut(&key) {
+            let actions = match &mut entry.transaction {
+                Transaction::InviteServer(t) => t.on_request(request, now),
+                Transaction::NonInviteServer(t) => t.on_request(request, now),
+                _ => return,
+            };
+            self.apply(&key, actions, Some(incoming));
+            return;
+        }
+
+        let reliable = incoming.transport.is_reliable();
+        let (transaction, actions) = match request.rl.method {
+            SipMethod::ACK => {
+                // ACK for 2xx stops retransmissions of 2xx and goes directly to application
+                let accepted = DialogId::from_request(request)
+                    .zip(cseq_number(&request.headers))
+                    .and_then(|accepted| self.accepted.get(&accepted))
+                    .cloned();
+                if let Some(accepted) = accepted {
+                    if let Some(Transaction::InviteServer(t)) = self
+                        .transactions
+                        .get_mut(&accepted)
+                        .map(|entry| &mut entry.transaction)
+                    {
+                        let actions = t.on_request(request, now);
+                        self.apply(&accepted, actions, None);
+                    }
+                }
+                self.outputs.push_back(Output::Event(Event::Request {
+                    transaction: None,
+                    message: incoming.raw.to_vec(),
+                    source: incoming.source,
+                    transport: incoming.transport,
+                }));
+                return;
+            }
+            SipMethod::INVITE => {
+                match InviteServerTransaction::new(request, reliable, self.settings) {
+                    Ok((mut t, actions)) => {
+                        // engine is UAS core that retransmits 2xx until ACK
+                        t.set_2xx_retransmission(true);
+                        (Transaction::InviteServer(t), actions)
+                    }
+                    Err(_) => return,
+                }
+            }
+            _ => match NonInviteServerTransaction::new(request, reliable, self.settings) {
+                Ok((t, actions)) => (Transaction::NonInviteServer(t), actions),
+                Err(_) => return,
+            },
+        };
+        // responses over reliable transport are sent back to connection,
+        // Via is used only if connection is gone
+        let destination = via::response_destination(&request.headers, incoming.transport);
+        let (peer, fallback) = if reliable {
+            let fallback = destination.filter(|destination| *destination != incoming.source);
+            (incoming.source, fallback)
+        } else {
+            (destination.unwrap_or(incoming.source), None)
+        };
+        self.transactions.insert(
+            key.clone(),
+            Entry {
+                transaction,
+                peer,
+                transport: incoming.transport,
+                request: Some(incoming.raw.to_vec())
+                    .filter(|_| request.rl.method == SipMethod::INVITE),
+                fallback,
+            },
+        );
+        if is_sips(request) && !incoming.transport.is_secure() {
+            // SIPS must be secured on every hop
+            self.apply(&key, actions, None);
+            let tag = local_tag(&key);
+            let rejection = response(request, 416, "Unsupported URI Scheme", Some(&tag));
+            let _ = self.send_response(&key, rejection);
+            return;
+        }
+        if let Err(dialog::Error::OutOfOrder) = self.update_dialog_by_request(request) {
+            // [rfc3261 section-12.2.2](https://tools.ietf.org/html/rfc3261#section-12.2.2)
+            self.apply(&key, actions, None);
+            let rejection = response(request, 500, "Server Internal Error", None);
+            let _ = self.send_response(&key, rejection);
+            return;
+        }
+        self.apply(&key, actions, Some(incoming));
+    }
+
+    fn handle_response(&mut self, response: &SipResponse, incoming: &Incoming) {
+        let now = self.clock.now();
+        let key = TransactionKey::from_response(response);
+        let entry = key.as_ref().and_then(|key| self.transactions.get_mut(key));
+        let (key, entry) = match (key.clone(), entry) {
+            (Some(key), Some(entry)) => (key, entry),
+            _ => {
+                self.update_dialog_by_response(response, None, true);
+                self.outputs.push_back(Output::Event(Event::Response {
+                    transaction: None,
+                    message: incoming.raw.to_vec(),
+                    source: incoming.source,
+                    transport: incoming.transport,
+                }));
+                return;
+            }
+        };
+        let (actions, invite) = match &mut entry.transaction {
+            Transaction::InviteClient(t) => {
+                let invite = Some(t.request().to_vec())
+                    .filter(|_| (101..=299).contains(&status_code(response)));
+                (t.on_response(response, now), invite)
+            }
+            Transaction::NonInviteClient(t) => (t.on_response(response, now), None),
+            _ => return,
+        };
+        self.update_dialog_by_response(response, invite.as_deref(), true);
+        let registering = self.registering.get(&key).cloned();
+        self.apply(&key, actions, Some(incoming));
+        if let Some(call_id) = registering {
+            self.update_registration(&call_id, |registration| {
+                let _ = registration.on_response(response, now);
+            });
+        }
+    }
+
+    /// Updates UAS dialog by request within it
+    fn update_dialog_by_request(&mut self, request: &SipRequest) -> Result<(), dialog::Error> {
+        let id = match dialog_id(&request.headers, false) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let result = match self.dialogs.get_mut(&id) {
+            Some(dialog) => dialog.on_request(request),
+            None => return Ok(()),
+        };
+        self.remove_terminated(&id);
+        result
+    }
+
+    /// Updates dialog by response that is received by UAC or sent by UAS.
+    /// Dialog is created if it doesn't exist and `invite` is known
+    fn update_dialog_by_response(
+        &mut self,
+        response: &SipResponse,
+        invite: Option<&[u8]>,
+        uac: bool,
+    ) {
+        let id = match dialog_id(&response.headers, uac) {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(dialog) = self.dialogs.get_mut(&id) {
+            let _ = dialog.on_response(response);
+            self.remove_terminated(&id);
+            return;
+        }
+        let invite = match invite.map(SipRequest::parse) {
+            Some(Ok((_, invite))) => invite,
+            _ => return,
+        };
+        let dialog = if uac {
+            Dialog::uac(&invite, response)
+        } else {
+            Dialog::uas(&invite, response)
+        };
+        if let Ok(dialog) = dialog {
+            self.dialogs.insert(id, dialog);
+        }
+    }
+
+    fn remove_terminated(&mut self, id: &DialogId) {
+        if let Some(DialogState::Terminated) = self.dialogs.get(id).map(Dialog::state) {
+            self.dialogs.remove(id);
+        }
+    }
+
+    /// Sends REGISTER of registration by client transaction
+    fn send_registration(
+        &mut self,
+        call_id: &str,
+        request: Vec<u8>,
+    ) -> Result<TransactionKey, Error> {
+        let (registrar, transport) = match self.registrations.get(call_id) {
+            Some(entry) => (entry.registrar, entry.transport),
+            None => return Err(Error::InvalidState),
+        };
+        let key = self
+            .send_request(request, registrar, transport)?
+            .ok_or(Error::InvalidMessage)?;
+        self.registering.insert(key.clone(), call_id.to_string());
+        Ok(key)
+    }
+
+    /// Reports new state of registration, removes it if it is unregistered or failed
+    fn update_registration<F: FnOnce(&mut Registration)>(&mut self, call_id: &str, update: F) {
+        let entry = match self.registrations.get_mut(call_id) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let previous = entry.registration.state();
+        update(&mut entry.registration);
+        let state = entry.registration.state();
+        if matches!(
+            state,
+            RegistrationState::Unregistered | RegistrationState::Failed
+        ) {
+            self.registrations.remove(call_id);
+        }
+        if state != previous {
+            self.outputs.push_back(Output::Event(Event::Registration {
+                call_id: call_id.to_string(),
+                state,
+            }));
+        }
+    }
+
+    /// Converts actions of transaction to outputs
+    fn apply(&mut self, key: &TransactionKey, actions: Vec<Action>, incoming: Option<&Incoming>) {
+        let (peer, transport, is_client) = match self.transactions.get(key) {
+            Some(entry) => (
+                entry.peer,
+                entry.transport,
+                matches!(
+                    entry.transaction,
+                    Transaction::InviteClient(_) | Transaction::NonInviteClient(_)
+                ),
+            ),
+            None => return,
+        };
+        for action in actions {
+            let transaction = key.clone();
+            match action {
+                Action::Send(data) => self.send(data, peer, transport, Some(transaction)),
+                Action::PassToTu => {
+                    let incoming = match incoming {
+                        Some(incoming) => incoming,
+                        None => continue,
+                    };
+                    let message = incoming.raw.to_vec();
+                    let (source, transport) = (incoming.source, incoming.transport);
+                    let event = if is_client {
+                        Event::Response {
+                            transaction: Some(transaction),
+                            message,
+                            source,
+                            transport,
+                        }
+                    } else {
+                        Event::Request {
+                            transaction: Some(transaction),
+                            message,
+                            source,
+                            transport,
+                        }
+                    };
+                    self.outputs.push_back(Output::Event(event));
+                }
+                Action::Timeout => {
+                    self.outputs
+                        .push_back(Output::Event(Event::Timeout { transaction }));
+                    self.registration_failed(key);
+                }
+                Action::Cancel => self
+                    .outputs
+                    .push_back(Output::Event(Event::Cancel { transaction })),
+                Action::TransportError => {
+                    self.outputs
+                        .push_back(Output::Event(Event::TransportError { transaction }));
+                    self.registration_failed(key);
+                }
+                Action::Terminated => {
+                    self.transactions.remove(key);
+                    self.accepted.retain(|_, accepted| accepted != key);
+                    self.registering.remove(key);
+                }
+            }
+        }
+    }
+
+    fn registration_failed(&mut self, key: &TransactionKey) {
+        if let Some(call_id) = self.registering.get(key).cloned() {
+            self.update_registration(&call_id, Registration::on_failure);
+        }
+    }
+
+    fn send(
+        &mut self,
+        data: Vec<u8>,
+        destination: SocketAddr,
+        transport: TransportKind,
+        transaction: Option<TransactionKey>,
+    ) {
+        self.outputs.push_back(Output::Send(Datagram {
+            data,
+            destination,
+            transport,
+            transaction,
+        }));
+    }
+}
+
+/// Dialog of UAS and CSeq number of 2xx response to INVITE
+fn accepted_key(response: &SipResponse) -> Option<(DialogId, u32)> {
+    let id = dialog_id(&response.headers, false)?;
+    Some((id, cseq_number(&response.headers)?))
+}
+
+/// Id of dialog of message. Local tag is in From of requests that are sent and
+/// responses that are received (`local_from`), and in To of other messages
+fn dialog_id(headers: &SipHeaders, local_from: bool) -> Option<DialogId> {
+    let (local, remote) = if local_from {
+        (SipRFCHeader::From, SipRFCHeader::To)
+    } else {
+        (SipRFCHeader::To, SipRFCHeader::From)
+    };
+    Some(DialogId {
+        call_id: headers
+            .get_rfc_s(SipRFCHeader::CallID)?
+            .value
+            .vstr
+            .to_string(),
+        local_tag: tag(headers, local)?.to_string(),
+        remote_tag: tag(headers, remote)?.to_string(),
+    })
+}
+
+fn is_sips(request: &SipRequest) -> bool {
+    request.rl.uri.scheme == SipRequestUriScheme::SIPS
+}
+
+/// To tag of responses that are generated by engine
+fn local_tag(key: &TransactionKey) -> String {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::message::response;
+    use std::time::Duration;
+
+    const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Content-Length: 0\r\n\r\n";
+
+    fn drain<C: Clock>(engine: &mut Engine<C>) -> Vec<Output> {
+        let mut outputs = vec![];
+        while let Some(output) = engine.poll_output() {
+            outputs.push(output);
+        }
+        outputs
+    }
+
+    fn alice() -> SocketAddr {
+        "192.0.2.1:5060".parse().unwrap()
+    }
+
+    #[test]
+    fn server_transaction_test() {
+        let clock = ManualClock::new();
+        let mut engine = Engine::new(&clock, TimerSettings::default());
+        let incoming = Incoming::parse(INVITE.as_bytes(), alice(), TransportKind::Udp).unwrap();
+        engine.handle_incoming(&incoming);
+        let outputs = drain(&mut engine);
+        assert_eq!(outputs.len(), 2);
+        match &outputs[0] {
+            Output::Send(datagram) => {
+                assert!(datagram.data.starts_with(b"SIP/2.0 100 Trying\r\n"));
+                assert_eq!(datagram.destination, alice());
+            }
+            _ => panic!("100 Trying is expected"),
+        }
+        let transaction = match &outputs[1] {
+            Output::Event(Event::Request {
+                transaction: Some(transaction),
+                message,
+                ..
+            }) => {
+                assert_eq!(message, INVITE.as_bytes());
+                transaction.clone()
+            }
+            _ => panic!("INVITE is expected"),
+        };
+
+        // retransmission is absorbed
+        engine.handle_incoming(&incoming);
+        assert_eq!(drain(&mut engine).len(), 1);
+
+        let request = match &incoming.message {
+            SipMessage::Request(request) => request,
+            _ => unreachable!(),
+        };
+        let ok = response(request, 200, "OK", Some("a6c85cf"));
+        engine.send_response(&transaction, ok.clone()).unwrap();
+        assert_eq!(
+            drain(&mut engine),
+            vec![Output::Send(Datagram {
+                data: ok.clone(),
+                destination: alice(),
+                transport: TransportKind::Udp,
+                transaction: Some(transaction.clone())
+            })]
+        );
+        assert_eq!(
+            engine.send_response(&transaction, ok.clone()),
+            Err(Error::InvalidState)
+        );
+
+        // 2xx is retransmitted until ACK, retransmitted INVITE is absorbed
+        clock.advance(Duration::from_millis(500));
+        engine.handle_timers();
+        engine.handle_incoming(&incoming);
+        match &drain(&mut engine)[..] {
+            [Output::Send(datagram)] => assert_eq!(datagram.data, ok),
+            _ => panic!("200 OK is expected"),
+        }
+        let ack = INVITE
+            .replacen("INVITE sip", "ACK sip", 1)
+            .replace("CSeq: 314159 INVITE", "CSeq: 314159 ACK")
+            .replace("z9hG4bK776asdhds", "z9hG4bK776asdhdt")
+            .replace(
+                "Bob <sip:bob@biloxi.com>",
+                "Bob <sip:bob@biloxi.com>;tag=a6c85cf",
+            );
+        let ack = Incoming::parse(ack.as_bytes(), alice(), TransportKind::Udp).unwrap();
+        engine.handle_incoming(&ack);
+        match &drain(&mut engine)[..] {
+            [Output::Event(Event::Request {
+                transaction: None, ..
+            })] => {}
+            _ => panic!("ACK is expected"),
+        }
+        clock.advance(Duration::from_secs(10));
+        engine.handle_timers();
+        assert!(drain(&mut engine).is_empty());
+        assert_eq!(engine.transactions_count(), 1);
+        clock.advance(Duration::from_secs(22));
+        engine.handle_timers();
+        assert_eq!(engine.transactions_count(), 0);
+    }
+
+    #[test]
+    fn sips_requires_secure_transport() {
+        let clock = ManualClock::new();
+        let mut engine = Engine::new(&clock, TimerSettings::default());
+        let sips = INVITE.replacen("sip:bob", "sips:bob", 1);
+        assert_eq!(
+            engine.send_request(sips.clone().into_bytes(), alice(), TransportKind::Tcp),
+            Err(Error::InsecureTransport)
+        );
+        assert!(engine
+            .send_request(sips.clone().into_bytes(), alice(), TransportKind::Tls)
+            .is_ok());
+        drain(&mut engine);
+
+        let incoming = Incoming::parse(sips.as_bytes(), alice(), TransportKind::Tcp).unwrap();
+        engine.handle_incoming(&incoming);
+        // 100 Trying and rejection, INVITE isn't passed to application
+        let outputs = drain(&mut engine);
+        assert_eq!(outputs.len(), 2);
+        match &outputs[1] {
+            Output::Send(datagram) => {
+                assert!(datagram
+                    .data
+                    .starts_with(b"SIP/2.0 416 Unsupported URI Scheme\r\n"))
+            }
+            _ => panic!("416 is expected"),
+        }
+    }
+
+    #[test]
+    fn client_transaction_test() {
+        let clock = ManualClock::new();
+        let mut engine = Engine::new(&clock, TimerSettings::default());
+        let bob = "192.0.2.4:5060".parse().unwrap();
+        let transaction = engine
+            .send_request(INVITE.as_bytes().to_vec(), bob, TransportKind::Udp)
+            .unwrap()
+            .unwrap();
+        assert_eq!(drain(&mut engine).len(), 1);
+        assert_eq!(
+            engine.next_deadline(),
+            Some(clock.now() + Duration::from_millis(500))
+        );
+        clock.advance(Duration::from_millis(500));
+        engine.handle_timers();
+        assert_eq!(drain(&mut engine).len(), 1);
+
+        let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        let incoming = Incoming::parse(&busy, bob, TransportKind::Udp).unwrap();
+        engine.handle_incoming(&incoming);
+        let outputs = drain(&mut engine);
+        assert_eq!(
+            outputs[0],
+            Output::Event(Event::Response {
+                transaction: Some(transaction),
+                message: busy.clone(),
+                source: bob,
+                transport: TransportKind::Udp
+            })
+        );
+        match &outputs[1] {
+            Output::Send(ack) => assert!(ack.data.starts_with(b"ACK sip:bob@biloxi.com")),
+            _ => panic!("ACK is expected"),
+        }
+    }
+}
@@ -1 +1,11 @@
 pub use sipmsg::*;
+
+pub mod clock;
+pub mod dialog;
+pub mod engine;
+pub mod message;
+pub mod network;
+pub mod registration;
+pub mod resolver;
+pub mod transaction;
+pub mod transport;
@@ -0,0 +1,245 @@
+//! Helpers that build messages from received ones.
+//! Header values are copied as they are written in received message.
+
+use crate::{SipHeader, SipHeaderTagType, SipHeaders, SipRFCHeader, SipRequest, SipResponse};
+use std::fmt::Write;
+use std::str;
+
+/// Status-Code as number. Unlike `StatusCode` it keeps codes that are not registered
+pub fn status_code(response: &SipResponse) -> u16 {
+    status_code_of(response.sl.raw).unwrap_or(0)
+}
+
+/// Status-Code of message that starts with Status-Line. Ex: `SIP/2.0 180 Ringing`
+pub(crate) fn status_code_of(message: &[u8]) -> Option<u16> {
+    let code = message.splitn(3, |c| *c == b' ').nth(1)?;
+    if code.len() != 3 {
+        return None;
+    }
+    str::from_utf8(code).ok()?.parse::<u16>().ok()
+}
+
+/// Request-URI as it is written in Request-Line
+pub fn request_uri<'a>(request: &SipRequest<'a>) -> &'a str {
+    let raw = request.rl.raw;
+    let uri = raw.split(|c| *c == b' ').nth(1).unwrap_or(b"");
+    str::from_utf8(uri).unwrap_or("")
+}
+
+/// Sequence number of CSeq header
+pub fn cseq_number(headers: &SipHeaders) -> Option<u32> {
+    let cseq = headers.get_rfc_s(SipRFCHeader::CSeq)?;
+    let number = cseq.value.tags()?.get(&SipHeaderTagType::Number)?;
+    str::from_utf8(number).ok()?.parse::<u32>().ok()
+}
+
+/// Method of CSeq header
+pub fn cseq_method<'a>(headers: &SipHeaders<'a>) -> Option<&'a str> {
+    let cseq = headers.get_rfc_s(SipRFCHeader::CSeq)?;
+    str::from_utf8(cseq.value.tags()?.get(&SipHeaderTagType::Method)?).ok()
+}
+
+/// `tag` parameter of From or To header
+pub fn tag<'a>(headers: &SipHeaders<'a>, header: SipRFCHeader) -> Option<&'a str> {
+    let hdr = headers.get_rfc_s(header)?;
+    let tag = *hdr.params()?.get("tag")?;
+    tag
+}
+
+/// URI of name-addr or addr-spec. Ex: `sip:p1.example.com;lr` of `<sip:p1.example.com;lr>`
+pub(crate) fn uri(value: &str) -> &str {
+    match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        // parameters of addr-spec belong to header
+        _ => value.split(';').next().unwrap_or(value).trim(),
+    }
+}
+
+/// Response to request. [rfc3261 section-8.2.6](https://tools.ietf.org/html/rfc3261#section-8.2.6)
+///
+/// Via, From, Call-ID and CSeq are copied from request. `to_tag` is added to To
+/// if request has no To tag. Timestamp is copied into 100 (Trying)
+/// ```rust
+/// use sipcore::message::response;
+/// use sipcore::SipRequest;
+///
+/// let (_, request) = SipRequest::parse(
+///     "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+///      Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\
+///      To: <sip:carol@chicago.com>\r\n\
+///      From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+///      Call-ID: a84b4c76e66710\r\n\
+///      CSeq: 63104 OPTIONS\r\n\r\n"
+///         .as_bytes(),
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     response(&request, 200, "OK", Some("93810874")),
+///     "SIP/2.0 200 OK\r\n\
+///      Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\
+///      From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+///      To: <sip:carol@chicago.com>;tag=93810874\r\n\
+///      Call-ID: a84b4c76e66710\r\n\
+///      CSeq: 63104 OPTIONS\r\n\
+///      Content-Length: 0\r\n\r\n"
+///         .as_bytes()
+/// );
+/// ```
+pub fn response(
+    request: &SipRequest,
+    status_code: u16,
+    reason: &str,
+    to_tag: Option<&str>,
+) -> Vec<u8> {
+    let headers = &request.headers;
+    let mut response = String::with_capacity(512);
+    let _ = write!(response, "SIP/2.0 {:03} {}\r\n", status_code, reason);
+    push_all(&mut response, headers, SipRFCHeader::Via);
+    push_all(&mut response, headers, SipRFCHeader::From);
+    if let Some(to) = headers.get_rfc_s(SipRFCHeader::To) {
+        push_header(&mut response, SipRFCHeader::To, to);
+        match to_tag {
+            Some(to_tag) if tag(headers, SipRFCHeader::To).is_none() => {
+                // replace CRLF of To header
+                response.truncate(response.len() - 2);
+                let _ = write!(response, ";tag={}\r\n", to_tag);
+            }
+            _ => {}
+        }
+    }
+    push_all(&mut response, headers, SipRFCHeader::CallID);
+    push_all(&mut response, headers, SipRFCHeader::CSeq);
+    if status_code == 100 {
+        push_all(&mut response, headers, SipRFCHeader::Timestamp);
+    }
+    response.push_str("Content-Length: 0\r\n\r\n");
+    response.into_bytes()
+}
+
+/// ACK for non-2xx final response to INVITE.
+/// [rfc3261 section-17.1.1.3](https://tools.ietf.org/html/rfc3261#section-17.1.1.3)
+pub(crate) fn ack(invite: &SipRequest, response: &SipResponse) -> Vec<u8> {
+    let headers = &invite.headers;
+    let mut ack = String::with_capacity(512);
+    let _ = write!(ack, "ACK {} SIP/2.0\r\n", request_uri(invite));
+    if let Some(via) = headers.get_rfc(SipRFCHeader::Via).and_then(|v| v.front()) {
+        push_header(&mut ack, SipRFCHeader::Via, via);
+    }
+    push_all(&mut ack, headers, SipRFCHeader::Route);
+    push_all(&mut ack, headers, SipRFCHeader::From);
+    push_all(&mut ack, &response.headers, SipRFCHeader::To);
+    push_all(&mut ack, headers, SipRFCHeader::CallID);
+    let _ = write!(
+        ack,
+        "CSeq: {} ACK\r\nMax-Forwards: 70\r\nContent-Length: 0\r\n\r\n",
+        cseq_number(headers).unwrap_or(0)
+    );
+    ack.into_bytes()
+}
+
+fn push_all(buf: &mut String, headers: &SipHeaders, header: SipRFCHeader) {
+    if let Some(hdrs) = headers.get_rfc(header) {
+        for hdr in hdrs {
+            push_header(buf, header, hdr);
+        }
+    }
+}
+
+fn push_header(buf: &mut String, header: SipRFCHeader, hdr: &SipHeader) {
+    let _ = write!(
+        buf,
+        "{}: {}\r\n",
+        header.as_str(),
+        String::from_utf8_lossy(hdr.raw_value_param)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Via: SIP/2.0/UDP proxy.atlanta.com;branch=z9hG4bK1\r\n\
+Route: <sip:proxy.biloxi.com;lr>\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Timestamp: 54\r\n\
+Content-Length: 0\r\n\r\n";
+
+    #[test]
+    fn uri_test() {
+        assert_eq!(uri("<sip:p1.example.com;lr>"), "sip:p1.example.com;lr");
+        assert_eq!(
+            uri("Bob <sip:bob@192.0.2.4;transport=tcp>;expires=60"),
+            "sip:bob@192.0.2.4;transport=tcp"
+        );
+        assert_eq!(uri("sip:bob@192.0.2.4;expires=60"), "sip:bob@192.0.2.4");
+    }
+
+    #[test]
+    fn response_test() {
+        let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+        let trying = response(&invite, 100, "Trying", None);
+        let (_, trying) = SipResponse::parse(&trying).unwrap();
+        assert_eq!(status_code(&trying), 100);
+        assert_eq!(trying.headers.get_rfc(SipRFCHeader::Via).unwrap().len(), 2);
+        assert_eq!(tag(&trying.headers, SipRFCHeader::To), None);
+        assert_eq!(
+            trying
+                .headers
+                .get_rfc_s(SipRFCHeader::Timestamp)
+                .unwrap()
+                .value
+                .vstr,
+            "54"
+        );
+
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        let (_, busy) = SipResponse::parse(&busy).unwrap();
+        assert_eq!(status_code(&busy), 486);
+        assert_eq!(tag(&busy.headers, SipRFCHeader::To), Some("a6c85cf"));
+        assert_eq!(cseq_number(&busy.headers), Some(314159));
+        assert_eq!(cseq_method(&busy.headers), Some("INVITE"));
+        assert!(busy.headers.get_rfc(SipRFCHeader::Timestamp).is_none());
+    }
+
+    #[test]
+    fn ack_test() {
+        let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        let (_, busy) = SipResponse::parse(&busy).unwrap();
+        let ack = ack(&invite, &busy);
+        assert_eq!(
+            ack,
+            "ACK sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Route: <sip:proxy.biloxi.com;lr>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 ACK\r\n\
+Max-Forwards: 70\r\n\
+Content-Length: 0\r\n\r\n"
+                .as_bytes()
+        );
+        assert_eq!(request_uri(&invite), "sip:bob@biloxi.com");
+    }
+
+    #[test]
+    fn status_code_test() {
+        assert_eq!(
+            status_code_of(b"SIP/2.0 183 Session Progress\r\n"),
+            Some(183)
+        );
+        assert_eq!(status_code_of(b"SIP/2.0 499 Whatever\r\n"), Some(499));
+        assert_eq!(status_code_of(b"SIP/2.0 99 Short\r\n"), None);
+        assert_eq!(
+            status_code_of(b"INVITE sip:bob@biloxi.com SIP/2.0\r\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! In-memory network for tests of several agents without sockets.
+//!
+//! `Network` holds datagrams in flight. Every link can delay, lose, reorder
+//! and duplicate datagrams of unreliable transports. Datagrams of reliable
+//! transports are only delayed and keep their order, like in TCP connection.
+//! Randomness is seeded, so every run of test is the same.
+//!
+//! `Simulation` wires `Engine`s of user agents, proxies and registrars to
+//! network and moves `ManualClock` from one delivery or timer to the next one,
+//! so call flows with timers of 32 seconds take milliseconds. Application logic
+//! stays in test: it takes events of node and answers through its engine.
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::clock::ManualClock;
+//! use sipcore::engine::Event;
+//! use sipcore::network::{LinkSettings, Network, Simulation};
+//! use sipcore::transaction::TimerSettings;
+//! use sipcore::transport::TransportKind;
+//! use std::time::Duration;
+//!
+//! let options = "OPTIONS sip:carol@192.0.2.4 SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP 192.0.2.1;branch=z9hG4bKhjhs8ass877\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! To: <sip:carol@chicago.com>\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 63104 OPTIONS\r\n\r\n";
+//!
+//! let clock = ManualClock::new();
+//! let settings = LinkSettings {
+//!     latency: Duration::from_millis(40),
+//!     loss: 0.3,
+//!     ..LinkSettings::default()
+//! };
+//! let mut simulation = Simulation::new(&clock, Network::new(settings, 7));
+//! let alice = "192.0.2.1:5060".parse().unwrap();
+//! let carol = "192.0.2.4:5060".parse().unwrap();
+//! simulation.add_node(alice, TimerSettings::default());
+//! simulation.add_node(carol, TimerSettings::default());
+//!
+//! simulation
+//!     .engine(alice)
+//!     .send_request(options.as_bytes().to_vec(), carol, TransportKind::Udp)
+//!     .unwrap();
+//! simulation.run_for(Duration::from_secs(4));
+//! let events = simulation.take_events(carol);
+//! assert!(matches!(&events[..], [Event::Request { .. }]));
+//! ```
+
+use crate::clock::{Clock, ManualClock};
+use crate::engine::{Datagram, Engine, Event, Incoming, Output};
+use crate::transaction::TimerSettings;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Properties of link in one direction. Default is perfect link without delay
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct LinkSettings {
+    /// Delay of every datagram
+    pub latency: Duration,
+    /// Random additional delay up to this value.
+    /// Datagrams of unreliable transports that are sent within it are reordered
+    pub jitter: Duration,
+    /// Probability that datagram of unreliable transport is lost, from 0 to 1
+    pub loss: f64,
+    /// Probability that datagram of unreliable transport is delivered twice, from 0 to 1
+    pub duplication: f64,
+}
+
+/// Counters of datagrams
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct NetworkStats {
+    pub sent: usize,
+    pub lost: usize,
+    pub duplicated: usize,
+    pub delivered: usize,
+}
+
+/// Datagram that reached destination
+#[derive(Clone, PartialEq, Debug)]
+pub struct Delivery {
+    pub source: SocketAddr,
+    pub datagram: Datagram,
+}
+
+struct Packet {
+    at: Instant,
+    /// Keeps order of datagrams that arrive at the same time
+    sequence: u64,
+    delivery: Delivery,
+}
+
+pub struct Network {
+    settings: LinkSettings,
+    links: HashMap<(SocketAddr, SocketAddr), LinkSettings>,
+    /// Sorted by arrival time
+    queue: Vec<Packet>,
+    /// Arrival of last datagram of reliable transport on link
+    last_reliable: HashMap<(SocketAddr, SocketAddr), Instant>,
+    random: u64,
+    sequence: u64,
+    stats: NetworkStats,
+}
+
+impl Network {
+    /// `settings` are used by links that are not configured by `set_link`
+    pub fn new(settings: LinkSettings, seed: u64) -> Network {
+        Network {
+            settings,
+            links: HashMap::new(),
+            queue: vec![],
+            last_reliable: HashMap::new(),
+            // xorshift state must not be zero
+            random: splitmix64(seed) | 1,
+            sequence: 0,
+            stats: NetworkStats::default(),
+        }
+    }
+
+    /// Settings of datagrams from `source` to `destination`
+    pub fn set_link(
+        &mut self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        settings: LinkSettings,
+    ) {
+        self.links.insert((source, destination), settings);
+    }
+
+    pub fn link(&self, source: SocketAddr, destination: SocketAddr) -> LinkSettings {
+        self.links
+            .get(&(source, destination))
+            .copied()
+            .unwrap_or(self.settings)
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.stats
+    }
+
+    /// Number of datagrams that are not delivered yet
+    pub fn in_flight(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Arrival time of next datagram
+    pub fn next_delivery(&self) -> Option<Instant> {
+        self.queue.first().map(|packet| packet.at)
+    }
+
+    /// Puts datagram that is sent by `source` to network
+    pub fn send(&mut self, source: SocketAddr, datagram: Datagram, now: Instant) {
+        let link = (source, datagram.destination);
+        let settings = self.link(link.0, link.1);
+        self.stats.sent += 1;
+
+        if datagram.transport.is_reliable() {
+            let at = now + settings.latency + self.jitter(&settings);
+            // stream keeps order of data
+            let at = match self.last_reliable.get(&link) {
+                Some(last) if *last > at => *last,
+                _ => at,
+            };
+            self.last_reliable.insert(link, at);
+            self.enqueue(at, Delivery { source, datagram });
+            return;
+        }
+
+        if self.chance(settings.loss) {
+            self.stats.lost += 1;
+            return;
+        }
+        let copies = if self.chance(settings.duplication) {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let at = now + settings.latency + self.jitter(&settings);
+            let delivery = Delivery {
+                source,
+                datagram: datagram.clone(),
+            };
+            self.enqueue(at, delivery);
+        }
+    }
+
+    /// Takes datagrams that arrive at `now` or earlier, in order of arrival
+    pub fn deliver(&mut self, now: Instant) -> Vec<Delivery> {
+        let arrived = self.queue.partition_point(|packet| packet.at <= now);
+        self.stats.delivered += arrived;
+        self.queue
+            .drain(..arrived)
+            .map(|packet| packet.delivery)
+            .collect()
+    }
+
+    fn enqueue(&mut self, at: Instant, delivery: Delivery) {
+        let packet = Packet {
+            at,
+            sequence: self.sequence,
+            delivery,
+        };
+        self.sequence += 1;
+        let index = self
+            .queue
+            .partition_point(|queued| (queued.at, queued.sequence) <= (at, packet.sequence));
+        self.queue.insert(index, packet);
+    }
+
+    fn jitter(&mut self, settings: &LinkSettings) -> Duration {
+        settings.jitter.mul_f64(self.next_f64())
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+
+    /// xorshift64* in `0..1`
+    fn next_f64(&mut self) -> f64 {
+        self.random ^= self.random >> 12;
+        self.random ^= self.random << 25;
+        self.random ^= self.random >> 27;
+        let value = self.random.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Scrambles seed, so close seeds give unrelated sequences
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+struct Node<'c> {
+    addr: SocketAddr,
+    engine: Engine<&'c ManualClock>,
+    events: Vec<Event>,
+}
+
+/// Engines that are connected by `Network` and share `ManualClock`
+pub struct Simulation<'c> {
+    clock: &'c ManualClock,
+    network: Network,
+    nodes: Vec<Node<'c>>,
+}
+
+impl<'c> Simulation<'c> {
+    pub fn new(clock: &'c ManualClock, network: Network) -> Simulation<'c> {
+        Simulation {
+            clock,
+            network,
+            nodes: vec![],
+        }
+    }
+
+    /// Adds engine that receives datagrams sent to `addr`
+    pub fn add_node(&mut self, addr: SocketAddr, settings: TimerSettings) {
+        self.nodes.retain(|node| node.addr != addr);
+        self.nodes.push(Node {
+            addr,
+            engine: Engine::new(self.clock, settings),
+            events: vec![],
+        });
+    }
+
+    /// Engine of node. Panics if node isn't added
+    pub fn engine(&mut self, addr: SocketAddr) -> &mut Engine<&'c ManualClock> {
+        &mut self.node(addr).engine
+    }
+
+    pub fn network(&mut self) -> &mut Network {
+        &mut self.network
+    }
+
+    /// Events of node since previous call. Panics if node isn't added
+    pub fn take_events(&mut self, addr: SocketAddr) -> Vec<Event> {
+        std::mem::take(&mut self.node(addr).events)
+    }
+
+    /// Delivers datagrams and fires timers until `deadline`, then sets clock to it
+    pub fn run_until(&mut self, deadline: Instant) {
+        loop {
+            self.flush();
+            let now = self.clock.now();
+            let next = self
+                .nodes
+                .iter()
+                .filter_map(|node| node.engine.next_deadline())
+                .chain(self.network.next_delivery())
+                .min();
+            match next {
+                Some(at) if at <= deadline => {
+                    if at > now {
+                        self.clock.advance(at - now);
+                    }
+                    self.step();
+                }
+                _ => {
+                    if deadline > now {
+                        self.clock.advance(deadline - now);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn run_for(&mut self, duration: Duration) {
+        self.run_until(self.clock.now() + duration);
+    }
+
+    fn node(&mut self, addr: SocketAddr) -> &mut Node<'c> {
+        self.nodes
+            .iter_mut()
+            .find(|node| node.addr == addr)
+            .unwrap_or_else(|| panic!("node {} isn't added", addr))
+    }
+
+    /// Handles arrived datagrams and expired timers
+    fn step(&mut self) {
+        let now = self.clock.now();
+        for delivery in self.network.deliver(now) {
+            let destination = delivery.datagram.destination;
+            // datagrams to unknown addresses disappear
+            let node = match self.nodes.iter_mut().find(|node| node.addr == destination) {
+                Some(node) => node,
+                None => continue,
+            };
+            let datagram = &delivery.datagram;
+            if let Ok(incoming) =
+                Incoming::parse(&datagram.data, delivery.source, datagram.transport)
+            {
+                node.engine.handle_incoming(&incoming);
+            }
+        }
+        for node in &mut self.nodes {
+            node.engine.handle_timers();
+        }
+    }
+
+    /// Moves outputs of engines to network and events
+    fn flush(&mut self) {
+        let now = self.clock.now();
+        for node in &mut self.nodes {
+            while let Some(output) = node.engine.poll_output() {
+                match output {
+                    Output::Send(datagram) => self.network.send(node.addr, datagram, now),
+                    Output::Event(event) => node.events.push(event),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::TransportKind;
+
+    fn datagram(data: &[u8], transport: TransportKind) -> Datagram {
+        Datagram {
+            data: data.to_vec(),
+            destination: "192.0.2.4:5060".parse().unwrap(),
+            transport,
+            transaction: None,
+        }
+    }
+
+    #[test]
+    fn network_test() {
+        let source = "192.0.2.1:5060".parse().unwrap();
+        let settings = LinkSettings {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(100),
+            loss: 0.2,
+            duplication: 0.1,
+        };
+        let mut network = Network::new(settings, 1);
+        let now = Instant::now();
+        for i in 0..1000u32 {
+            network.send(source, datagram(&i.to_be_bytes(), TransportKind::Udp), now);
+        }
+        assert_eq!(network.next_delivery(), network.queue.first().map(|p| p.at));
+        assert!(network.deliver(now + Duration::from_millis(49)).is_empty());
+        let delivered = network.deliver(now + Duration::from_millis(150));
+        let stats = network.stats();
+        assert_eq!(stats.sent, 1000);
+        assert!(stats.lost > 150 && stats.lost < 250, "{:?}", stats);
+        assert!(
+            stats.duplicated > 50 && stats.duplicated < 130,
+            "{:?}",
+            stats
+        );
+        assert_eq!(stats.delivered, 1000 - stats.lost + stats.duplicated);
+        assert_eq!(delivered.len(), stats.delivered);
+        assert_eq!(network.in_flight(), 0);
+        let reordered = delivered
+            .windows(2)
+            .filter(|pair| pair[0].datagram.data > pair[1].datagram.data)
+            .count();
+        assert!(reordered > 100, "{}", reordered);
+
+        // reliable transport keeps order and doesn't lose data
+        for i in 0..100u32 {
+            network.send(source, datagram(&i.to_be_bytes(), TransportKind::Tcp), now);
+        }
+        let delivered = network.deliver(now + Duration::from_millis(150));
+        assert_eq!(delivered.len(), 100);
+        assert!(delivered
+            .windows(2)
+            .all(|pair| pair[0].datagram.data < pair[1].datagram.data));
+
+        // the same seed gives the same network
+        let arrivals = |seed| {
+            let mut network = Network::new(settings, seed);
+            for i in 0..10u32 {
+                network.send(source, datagram(&i.to_be_bytes(), TransportKind::Udp), now);
+            }
+            network
+                .deliver(now + Duration::from_secs(1))
+                .into_iter()
+                .map(|delivery| delivery.datagram.data)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(arrivals(5), arrivals(5));
+        assert_ne!(arrivals(5), arrivals(6));
+        // splitmix64 of this seed is zero, that would stop xorshift
+        assert_eq!(splitmix64(0x61c8_8646_80b5_83eb), 0);
+        assert!(!arrivals(0x61c8_8646_80b5_83eb).is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+//! Registration of UA. [rfc3261 section-10.2](https://tools.ietf.org/html/rfc3261#section-10.2)
+//!
+//! `Registration` binds Contact URI to address-of-record at registrar and refreshes
+//! binding before it expires. Like dialog, it doesn't send anything: `Registration::request`
+//! returns bytes of REGISTER and final responses are passed to `on_response`.
+//! `Engine::register` does it and fires refreshes by `next_refresh`.
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::registration::{Registration, RegistrationState};
+//! use sipcore::SipResponse;
+//! use std::time::{Duration, Instant};
+//!
+//! let mut registration = Registration::new(
+//!     "sip:registrar.biloxi.com",
+//!     "sip:bob@biloxi.com",
+//!     "sip:bob@192.0.2.4",
+//!     "SIP/2.0/UDP 192.0.2.4:5060",
+//!     "843817637684230@998sdasdh09",
+//!     "456248",
+//!     3600,
+//! );
+//! let register = registration.request();
+//! assert!(register.starts_with(b"REGISTER sip:registrar.biloxi.com SIP/2.0\r\n"));
+//!
+//! let ok = "SIP/2.0 200 OK\r\n\
+//! Via: SIP/2.0/UDP 192.0.2.4:5060;branch=z9hG4bKnashds7\r\n\
+//! To: <sip:bob@biloxi.com>;tag=2493k59kd\r\n\
+//! From: <sip:bob@biloxi.com>;tag=456248\r\n\
+//! Call-ID: 843817637684230@998sdasdh09\r\n\
+//! CSeq: 1 REGISTER\r\n\
+//! Contact: <sip:bob@192.0.2.4>;expires=1800\r\n\
+//! Content-Length: 0\r\n\r\n";
+//! let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+//! let now = Instant::now();
+//! registration.on_response(&ok, now).unwrap();
+//! assert_eq!(registration.state(), RegistrationState::Registered);
+//! assert_eq!(registration.expires(), 1800);
+//! assert_eq!(registration.next_refresh(), Some(now + Duration::from_secs(900)));
+//! ```
+
+use crate::message::{cseq_method, cseq_number, status_code, tag, uri};
+use crate::transaction::BRANCH_MAGIC_COOKIE;
+use crate::{SipHeaders, SipMethod, SipRFCHeader, SipResponse};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RegistrationState {
+    /// REGISTER is sent, binding isn't confirmed yet
+    Registering,
+    /// Registrar accepted binding
+    Registered,
+    /// REGISTER with Expires 0 is sent
+    Unregistering,
+    /// Binding is removed
+    Unregistered,
+    /// Registrar rejected binding or REGISTER failed
+    Failed,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Response doesn't belong to the last REGISTER
+    InvalidMessage,
+    /// Binding is removed or removal is in progress
+    InvalidState,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMessage => f.write_str("Invalid message for registration"),
+            Error::InvalidState => f.write_str("Registration is removed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Binding of Contact URI to address-of-record
+#[derive(Clone, PartialEq, Debug)]
+pub struct Registration {
+    /// Request-URI of REGISTER
+    registrar: String,
+    /// URI of To and From
+    aor: String,
+    contact: String,
+    /// Via without branch
+    via: String,
+    call_id: String,
+    from_tag: String,
+    cseq: u32,
+    /// Requested duration of binding in seconds
+    requested: u32,
+    /// Duration of binding that is granted by registrar
+    expires: u32,
+    state: RegistrationState,
+    refresh_at: Option<Instant>,
+}
+
+impl Registration {
+    /// `registrar` is Request-URI, ex: `sip:registrar.biloxi.com`. `aor` is URI of To and
+    /// From, `contact` is URI that is bound to it. `via` is Via value without branch, ex:
+    /// `SIP/2.0/UDP 192.0.2.4:5060`. Every REGISTER has the same `call_id` and `from_tag`.
+    /// `expires` is requested duration of binding in seconds
+    pub fn new(
+        registrar: &str,
+        aor: &str,
+        contact: &str,
+        via: &str,
+        call_id: &str,
+        from_tag: &str,
+        expires: u32,
+    ) -> Registration {
+        Registration {
+            registrar: registrar.to_string(),
+            aor: aor.to_string(),
+            contact: contact.to_string(),
+            via: via.to_string(),
+            call_id: call_id.to_string(),
+            from_tag: from_tag.to_string(),
+            cseq: 0,
+            requested: expires,
+            expires: 0,
+            state: RegistrationState::Registering,
+            refresh_at: None,
+        }
+    }
+
+    pub fn call_id(&self) -> &str {
+        &self.call_id
+    }
+
+    pub fn state(&self) -> RegistrationState {
+        self.state
+    }
+
+    /// Duration of binding in seconds that is granted by the last 2xx
+    pub fn expires(&self) -> u32 {
+        self.expires
+    }
+
+    /// Time when binding should be refreshed by `request`.
+    /// It is half of granted duration, or now after 423 (Interval Too Brief)
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.refresh_at
+    }
+
+    /// REGISTER that adds or refreshes binding. CSeq is incremented, branch is new.
+    /// Refreshed binding stays `Registered`
+    pub fn request(&mut self) -> Vec<u8> {
+        if self.state != RegistrationState::Registered {
+            self.state = RegistrationState::Registering;
+        }
+        self.refresh_at = None;
+        self.build(self.requested)
+    }
+
+    /// REGISTER with Expires 0 that removes binding
+    pub fn unregister(&mut self) -> Result<Vec<u8>, Error> {
+        if self.state == RegistrationState::Unregistered {
+            return Err(Error::InvalidState);
+        }
+        self.state = RegistrationState::Unregistering;
+        self.refresh_at = None;
+        Ok(self.build(0))
+    }
+
+    /// Updates binding by final response to the last REGISTER. Provisional responses are ignored.
+    ///
+    /// 2xx takes expires of our Contact or Expires header. 423 (Interval Too Brief) takes
+    /// Min-Expires and asks for refresh now. Other final responses fail registration
+    pub fn on_response(&mut self, response: &SipResponse, now: Instant) -> Result<(), Error> {
+        let headers = &response.headers;
+        let matches = call_id(headers) == Some(self.call_id.as_str())
+            && tag(headers, SipRFCHeader::From) == Some(self.from_tag.as_str())
+            && cseq_number(headers) == Some(self.cseq)
+            && cseq_method(headers)
+                .is_some_and(|method| method.eq_ignore_ascii_case(SipMethod::REGISTER.as_str()));
+        if !matches {
+            return Err(Error::InvalidMessage);
+        }
+        match (status_code(response), self.state) {
+            (100..=199, _) => {}
+            (200..=299, RegistrationState::Unregistering) => {
+                self.state = RegistrationState::Unregistered;
+            }
+            (200..=299, RegistrationState::Registering)
+            | (200..=299, RegistrationState::Registered) => {
+                let expires = self.granted(headers);
+                self.expires = expires;
+                if expires == 0 {
+                    self.state = RegistrationState::Unregistered;
+                } else {
+                    self.state = RegistrationState::Registered;
+                    self.refresh_at = Some(now + Duration::from_secs(u64::from(expires) / 2));
+                }
+            }
+            (423, RegistrationState::Registering) | (423, RegistrationState::Registered) => {
+                match headers.min_expires() {
+                    Ok(Some(min_expires)) if min_expires > self.requested => {
+                        self.requested = min_expires;
+                        self.refresh_at = Some(now);
+                    }
+                    _ => self.fail(),
+                }
+            }
+            (200..=299, _) | (423, _) => {}
+            _ => self.fail(),
+        }
+        Ok(())
+    }
+
+    /// REGISTER transaction timed out or transport failed
+    pub fn on_failure(&mut self) {
+        self.fail();
+    }
+
+    fn fail(&mut self) {
+        self.state = RegistrationState::Failed;
+        self.refresh_at = None;
+    }
+
+    /// expires parameter of our Contact, Expires header or requested duration
+    fn granted(&self, headers: &SipHeaders) -> u32 {
+        let contact = headers
+            .get_rfc(SipRFCHeader::Contact)
+            .into_iter()
+            .flatten()
+            .find(|contact| uri(contact.value.vstr) == self.contact);
+        let param = contact
+            .and_then(|contact| *contact.params()?.get("expires")?)
+            .and_then(|expires| expires.parse::<u32>().ok());
+        match (param, headers.expires()) {
+            (Some(expires), _) | (None, Ok(Some(expires))) => expires,
+            _ => self.requested,
+        }
+    }
+
+    fn build(&mut self, expires: u32) -> Vec<u8> {
+        self.cseq += 1;
+        let mut request = String::with_capacity(512);
+        let _ = write!(
+            request,
+            "REGISTER {} SIP/2.0\r\nVia: {};branch={}\r\nMax-Forwards: 70\r\n",
+            self.registrar,
+            self.via,
+            self.branch()
+        );
+        let _ = write!(
+            request,
+            "To: <{aor}>\r\nFrom: <{aor}>;tag={}\r\nCall-ID: {}\r\nCSeq: {} REGISTER\r\n",
+            self.from_tag,
+            self.call_id,
+            self.cseq,
+            aor = self.aor
+        );
+        let _ = write!(
+            request,
+            "Contact: <{}>\r\nExpires: {}\r\nContent-Length: 0\r\n\r\n",
+            self.contact, expires
+        );
+        request.into_bytes()
+    }
+
+    /// Branch is unique for Call-ID, From tag and CSeq
+    fn branch(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        (&self.call_id, &self.from_tag).hash(&mut hasher);
+        format!("{}{:x}.{}", BRANCH_MAGIC_COOKIE, hasher.finish(), self.cseq)
+    }
+}
+
+fn call_id<'a>(headers: &SipHeaders<'a>) -> Option<&'a str> {
+    Some(headers.get_rfc_s(SipRFCHeader::CallID)?.value.vstr)
+}
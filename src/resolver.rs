@@ -0,0 +1,528 @@
+//! Locating SIP servers. [rfc3263 section-4](https://tools.ietf.org/html/rfc3263#section-4)
+//!
+//! Host of URI is resolved to ordered list of `Target`s:
+//! 1. Numeric IP is used as it is, with explicit or default port
+//! 2. Host with explicit port is resolved by A/AAAA records
+//! 3. Otherwise NAPTR records select transport and SRV names. If there are no
+//!    NAPTR records, SRV records of supported transports are queried.
+//!    SRV records are ordered by priority and weight of
+//!    [rfc2782](https://tools.ietf.org/html/rfc2782)
+//! 4. If there are no SRV records, A/AAAA records of host are used with default port
+//!
+//! Queries are made by `DnsResolver`, so tests can use `StaticZone` instead of DNS.
+//!
+//! When request to target fails with transport error, timeout or 503 (Service Unavailable),
+//! it is sent to next target ([rfc3263 section-4.3](https://tools.ietf.org/html/rfc3263#section-4.3)).
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::resolver::{Resolver, SrvRecord, StaticZone, Target};
+//! use sipcore::transport::TransportKind;
+//! use sipcore::SipUri;
+//!
+//! let mut zone = StaticZone::new();
+//! zone.add_srv("_sip._udp.biloxi.com", SrvRecord::new(0, 0, 5060, "sip1.biloxi.com"))
+//!     .add_srv("_sip._udp.biloxi.com", SrvRecord::new(1, 0, 5060, "sip2.biloxi.com"))
+//!     .add_ip("sip1.biloxi.com", "192.0.2.1".parse().unwrap())
+//!     .add_ip("sip2.biloxi.com", "192.0.2.2".parse().unwrap());
+//! let resolver = Resolver::new(zone, &[TransportKind::Udp]);
+//!
+//! let (_, uri) = SipUri::parse(b"sip:bob@biloxi.com").unwrap();
+//! let mut targets = resolver.resolve(&uri).unwrap();
+//! assert_eq!(targets.transports(), vec![TransportKind::Udp]);
+//! assert_eq!(
+//!     targets.next(),
+//!     Some(Target::new("192.0.2.1:5060".parse().unwrap(), TransportKind::Udp))
+//! );
+//! // sip1 responded with 503
+//! assert_eq!(
+//!     targets.next(),
+//!     Some(Target::new("192.0.2.2:5060".parse().unwrap(), TransportKind::Udp))
+//! );
+//! assert_eq!(targets.next(), None);
+//! ```
+
+use crate::common::hostport::HostPort;
+use crate::transport::selector::secure_variant;
+use crate::transport::{via, TransportKind};
+use crate::{SipRequestUriScheme, SipUri};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NaptrRecord {
+    pub order: u16,
+    pub preference: u16,
+    /// Only `s` is used by SIP
+    pub flags: String,
+    /// Ex: `SIPS+D2T`
+    pub service: String,
+    /// Name of SRV record
+    pub replacement: String,
+}
+
+impl NaptrRecord {
+    pub fn new(
+        order: u16,
+        preference: u16,
+        flags: &str,
+        service: &str,
+        replacement: &str,
+    ) -> NaptrRecord {
+        NaptrRecord {
+            order,
+            preference,
+            flags: flags.to_owned(),
+            service: service.to_owned(),
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    /// Transport of service field.
+    /// [rfc3263 section-4.1](https://tools.ietf.org/html/rfc3263#section-4.1),
+    /// [rfc7118 section-6](https://tools.ietf.org/html/rfc7118#section-6)
+    pub fn transport(&self) -> Option<TransportKind> {
+        let kind = match self.service.to_ascii_uppercase().as_str() {
+            "SIP+D2U" => TransportKind::Udp,
+            "SIP+D2T" => TransportKind::Tcp,
+            "SIPS+D2T" => TransportKind::Tls,
+            "SIP+D2S" => TransportKind::Sctp,
+            "SIP+D2W" => TransportKind::Ws,
+            "SIPS+D2W" => TransportKind::Wss,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// `.` means that service is not available
+    pub target: String,
+}
+
+impl SrvRecord {
+    pub fn new(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_owned(),
+        }
+    }
+}
+
+/// DNS queries that are needed for locating of SIP servers.
+/// Name without records is not an error, result is empty
+pub trait DnsResolver {
+    fn naptr(&self, domain: &str) -> io::Result<Vec<NaptrRecord>>;
+
+    fn srv(&self, name: &str) -> io::Result<Vec<SrvRecord>>;
+
+    /// A and AAAA records
+    fn lookup_ip(&self, host: &str) -> io::Result<Vec<IpAddr>>;
+}
+
+/// Resolver of operating system. Std has no NAPTR and SRV queries,
+/// so only A/AAAA records are used
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SystemResolver;
+
+impl DnsResolver for SystemResolver {
+    fn naptr(&self, _domain: &str) -> io::Result<Vec<NaptrRecord>> {
+        Ok(vec![])
+    }
+
+    fn srv(&self, _name: &str) -> io::Result<Vec<SrvRecord>> {
+        Ok(vec![])
+    }
+
+    fn lookup_ip(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        Ok((host, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// Records that are kept in memory. Names are case insensitive
+#[derive(Clone, Default, Debug)]
+pub struct StaticZone {
+    naptr: HashMap<String, Vec<NaptrRecord>>,
+    srv: HashMap<String, Vec<SrvRecord>>,
+    ip: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticZone {
+    pub fn new() -> StaticZone {
+        StaticZone::default()
+    }
+
+    pub fn add_naptr(&mut self, domain: &str, record: NaptrRecord) -> &mut StaticZone {
+        self.naptr.entry(name_key(domain)).or_default().push(record);
+        self
+    }
+
+    pub fn add_srv(&mut self, name: &str, record: SrvRecord) -> &mut StaticZone {
+        self.srv.entry(name_key(name)).or_default().push(record);
+        self
+    }
+
+    pub fn add_ip(&mut self, host: &str, ip: IpAddr) -> &mut StaticZone {
+        self.ip.entry(name_key(host)).or_default().push(ip);
+        self
+    }
+}
+
+impl DnsResolver for StaticZone {
+    fn naptr(&self, domain: &str) -> io::Result<Vec<NaptrRecord>> {
+        Ok(self
+            .naptr
+            .get(&name_key(domain))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn srv(&self, name: &str) -> io::Result<Vec<SrvRecord>> {
+        Ok(self.srv.get(&name_key(name)).cloned().unwrap_or_default())
+    }
+
+    fn lookup_ip(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        Ok(self.ip.get(&name_key(host)).cloned().unwrap_or_default())
+    }
+}
+
+fn name_key(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Address and transport where request can be sent
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Target {
+    pub addr: SocketAddr,
+    pub transport: TransportKind,
+}
+
+impl Target {
+    pub fn new(addr: SocketAddr, transport: TransportKind) -> Target {
+        Target { addr, transport }
+    }
+}
+
+/// Targets in order of preference. Iterator returns next target after failure
+#[derive(Clone, Default, Debug)]
+pub struct Targets {
+    targets: VecDeque<Target>,
+}
+
+impl Targets {
+    /// Number of targets that are left
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Transports of targets that are left, in order of preference.
+    /// Can be passed to `TransportSelector::select`
+    pub fn transports(&self) -> Vec<TransportKind> {
+        let mut transports = vec![];
+        for target in &self.targets {
+            if !transports.contains(&target.transport) {
+                transports.push(target.transport);
+            }
+        }
+        transports
+    }
+
+    fn push(&mut self, target: Target) {
+        if !self.targets.contains(&target) {
+            self.targets.push_back(target);
+        }
+    }
+}
+
+impl Iterator for Targets {
+    type Item = Target;
+
+    fn next(&mut self) -> Option<Target> {
+        self.targets.pop_front()
+    }
+}
+
+pub struct Resolver<R> {
+    dns: R,
+    transports: Vec<TransportKind>,
+}
+
+impl<R: DnsResolver> Resolver<R> {
+    /// `transports` are supported by client, in order of preference.
+    /// They are used when there are no NAPTR records
+    pub fn new(dns: R, transports: &[TransportKind]) -> Resolver<R> {
+        Resolver {
+            dns,
+            transports: transports.to_vec(),
+        }
+    }
+
+    pub fn dns(&self) -> &R {
+        &self.dns
+    }
+
+    /// Targets of request to `uri`. Scheme and `transport` parameter are taken into account.
+    /// Error is returned if transport parameter is unknown or DNS query fails
+    pub fn resolve(&self, uri: &SipUri) -> io::Result<Targets> {
+        let transport = match uri
+            .params()
+            .and_then(|params| params.get("transport"))
+            .and_then(|value| *value)
+        {
+            Some(param) => Some(TransportKind::parse(param).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "unknown transport parameter")
+            })?),
+            None => None,
+        };
+        self.resolve_hostport(
+            &uri.hostport,
+            uri.scheme == SipRequestUriScheme::SIPS,
+            transport,
+        )
+    }
+
+    /// Targets of host and port of URI. `secure` is true for SIPS URI,
+    /// `transport` is value of `transport` parameter
+    pub fn resolve_hostport(
+        &self,
+        hostport: &HostPort,
+        secure: bool,
+        transport: Option<TransportKind>,
+    ) -> io::Result<Targets> {
+        let transport = match transport {
+            Some(kind) if secure => Some(secure_variant(kind).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "transport can't be secured")
+            })?),
+            transport => transport,
+        };
+        let default_transport = transport.unwrap_or(if secure {
+            TransportKind::Tls
+        } else {
+            TransportKind::Udp
+        });
+        let mut targets = Targets::default();
+
+        if let Some(ip) = via::parse_ip(hostport.host) {
+            let port = hostport
+                .port
+                .unwrap_or_else(|| default_transport.default_port());
+            targets.push(Target::new(SocketAddr::new(ip, port), default_transport));
+            return Ok(targets);
+        }
+        if let Some(port) = hostport.port {
+            self.add_host(&mut targets, hostport.host, port, default_transport)?;
+            return Ok(targets);
+        }
+
+        match transport {
+            Some(kind) => self.add_srv(&mut targets, &srv_name(kind, hostport.host), kind)?,
+            None => {
+                self.add_naptr(&mut targets, hostport.host, secure)?;
+                if targets.is_empty() {
+                    for kind in &self.transports {
+                        if secure && !kind.is_secure() {
+                            continue;
+                        }
+                        self.add_srv(&mut targets, &srv_name(*kind, hostport.host), *kind)?;
+                    }
+                }
+            }
+        }
+        if targets.is_empty() {
+            let port = default_transport.default_port();
+            self.add_host(&mut targets, hostport.host, port, default_transport)?;
+        }
+        Ok(targets)
+    }
+
+    fn add_naptr(&self, targets: &mut Targets, domain: &str, secure: bool) -> io::Result<()> {
+        let mut records: Vec<(NaptrRecord, TransportKind)> = self
+            .dns
+            .naptr(domain)?
+            .into_iter()
+            .filter(|record| record.flags.eq_ignore_ascii_case("s"))
+            .filter_map(|record| {
+                let kind = record.transport()?;
+                Some((record, kind))
+            })
+            .filter(|(_, kind)| self.transports.contains(kind) && (!secure || kind.is_secure()))
+            .collect();
+        records.sort_by_key(|(record, _)| (record.order, record.preference));
+        for (record, kind) in records {
+            self.add_srv(targets, &record.replacement, kind)?;
+        }
+        Ok(())
+    }
+
+    fn add_srv(&self, targets: &mut Targets, name: &str, kind: TransportKind) -> io::Result<()> {
+        for record in order_srv(&self.dns.srv(name)?, random) {
+            self.add_host(targets, &record.target, record.port, kind)?;
+        }
+        Ok(())
+    }
+
+    fn add_host(
+        &self,
+        targets: &mut Targets,
+        host: &str,
+        port: u16,
+        kind: TransportKind,
+    ) -> io::Result<()> {
+        for ip in self.dns.lookup_ip(host)? {
+            targets.push(Target::new(SocketAddr::new(ip, port), kind));
+        }
+        Ok(())
+    }
+}
+
+/// Name of SRV record of transport. Ex: `_sips._tcp.biloxi.com`
+fn srv_name(kind: TransportKind, domain: &str) -> String {
+    let prefix = match kind {
+        TransportKind::Udp => "_sip._udp",
+        TransportKind::Tcp => "_sip._tcp",
+        TransportKind::Tls => "_sips._tcp",
+        TransportKind::Sctp => "_sip._sctp",
+        TransportKind::Ws => "_sip._ws",
+        TransportKind::Wss => "_sips._ws",
+    };
+    format!("{}.{}", prefix, domain)
+}
+
+/// Orders SRV records by priority, records of the same priority are ordered
+/// by weighted random selection of [rfc2782](https://tools.ietf.org/html/rfc2782).
+/// `random(n)` must return random number in `0..=n`.
+/// Records with `.` target are removed
+pub fn order_srv<F: FnMut(u32) -> u32>(records: &[SrvRecord], mut random: F) -> Vec<SrvRecord> {
+    let mut records: Vec<SrvRecord> = records
+        .iter()
+        .filter(|record| record.target != ".")
+        .cloned()
+        .collect();
+    // records with zero weight are placed at the beginning of group
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+    let mut ordered = Vec::with_capacity(records.len());
+    while let Some(priority) = records.first().map(|record| record.priority) {
+        let len = records
+            .iter()
+            .take_while(|record| record.priority == priority)
+            .count();
+        let mut group: Vec<SrvRecord> = records.drain(..len).collect();
+        while !group.is_empty() {
+            let total = group.iter().map(|record| record.weight as u32).sum();
+            let selected = random(total);
+            let mut sum = 0;
+            let index = group
+                .iter()
+                .position(|record| {
+                    sum += record.weight as u32;
+                    sum >= selected
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+/// Random number in `0..=max`
+fn random(max: u32) -> u32 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(max);
+    (hasher.finish() % (max as u64 + 1)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(records: &[SrvRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record.target.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn order_srv_test() {
+        let records = [
+            SrvRecord::new(20, 0, 5060, "backup.biloxi.com"),
+            SrvRecord::new(10, 60, 5060, "a.biloxi.com"),
+            SrvRecord::new(10, 0, 5060, "zero.biloxi.com"),
+            SrvRecord::new(10, 40, 5060, "b.biloxi.com"),
+            SrvRecord::new(5, 0, 5060, "."),
+        ];
+        // running sums are 0, 60, 100
+        assert_eq!(
+            targets(&order_srv(&records, |_| 0)),
+            vec![
+                "zero.biloxi.com",
+                "a.biloxi.com",
+                "b.biloxi.com",
+                "backup.biloxi.com"
+            ]
+        );
+        assert_eq!(
+            targets(&order_srv(&records, |total| total)),
+            vec![
+                "b.biloxi.com",
+                "a.biloxi.com",
+                "zero.biloxi.com",
+                "backup.biloxi.com"
+            ]
+        );
+        assert_eq!(
+            targets(&order_srv(&records, |total| total.min(61))),
+            vec![
+                "b.biloxi.com",
+                "a.biloxi.com",
+                "zero.biloxi.com",
+                "backup.biloxi.com"
+            ]
+        );
+        assert_eq!(
+            targets(&order_srv(&records, |total| total.min(60))),
+            vec![
+                "a.biloxi.com",
+                "b.biloxi.com",
+                "zero.biloxi.com",
+                "backup.biloxi.com"
+            ]
+        );
+
+        let mut first = 0;
+        for _ in 0..1000 {
+            if order_srv(&records, random)[0].target == "a.biloxi.com" {
+                first += 1;
+            }
+        }
+        assert!(first > 450 && first < 750, "{}", first);
+    }
+
+    #[test]
+    fn naptr_transport_test() {
+        let record = NaptrRecord::new(50, 50, "s", "sips+d2t", "_sips._tcp.example.com");
+        assert_eq!(record.transport(), Some(TransportKind::Tls));
+        assert_eq!(
+            NaptrRecord::new(90, 50, "s", "SIP+D2W", "_sip._ws.example.com").transport(),
+            Some(TransportKind::Ws)
+        );
+        assert_eq!(
+            NaptrRecord::new(90, 50, "u", "E2U+sip", "!^.*$!sip:info@example.com!").transport(),
+            None
+        );
+        assert_eq!(random(0), 0);
+    }
+}
@@ -0,0 +1,527 @@
+use super::{
+    timers::{Timer, TimerSettings, Timers},
+    Action, Error, State, TransactionKey,
+};
+use crate::message::{ack, status_code};
+use crate::{SipMethod, SipRequest, SipResponse};
+use std::time::{Duration, Instant};
+
+/// Parses request that is sent by client transaction
+fn client_key(request: &[u8], invite: bool) -> Result<TransactionKey, Error> {
+    let (_, request) = SipRequest::parse(request).map_err(|_| Error::InvalidMessage)?;
+    match request.rl.method {
+        SipMethod::INVITE if invite => {}
+        SipMethod::INVITE | SipMethod::ACK => return Err(Error::InvalidMessage),
+        _ if invite => return Err(Error::InvalidMessage),
+        _ => {}
+    }
+    TransactionKey::client(&request).ok_or(Error::InvalidMessage)
+}
+
+/// [rfc3261 section-17.1.1](https://tools.ietf.org/html/rfc3261#section-17.1.1)
+///
+/// 2xx response terminates transaction, TU sends ACK for it.
+/// ACK for non-2xx response is sent by transaction
+#[derive(Debug)]
+pub struct InviteClientTransaction {
+    key: TransactionKey,
+    request: Vec<u8>,
+    reliable: bool,
+    settings: TimerSettings,
+    state: State,
+    timers: Timers,
+    retransmit_interval: Duration,
+    ack: Option<Vec<u8>>,
+}
+
+impl InviteClientTransaction {
+    /// Returns transaction in Calling state and action that sends request.
+    /// `reliable` is true for TCP, TLS and other reliable transports
+    pub fn new(
+        request: Vec<u8>,
+        reliable: bool,
+        settings: TimerSettings,
+        now: Instant,
+    ) -> Result<(InviteClientTransaction, Vec<Action>), Error> {
+        let key = client_key(&request, true)?;
+        let mut timers = Timers::default();
+        if !reliable {
+            timers.start(Timer::A, now + settings.t1);
+        }
+        timers.start(Timer::B, now + settings.timeout());
+        if let Some(timer_c) = settings.timer_c {
+            timers.start(Timer::C, now + timer_c);
+        }
+        let actions = vec![Action::Send(request.clone())];
+        Ok((
+            InviteClientTransaction {
+                key,
+                request,
+                reliable,
+                settings,
+                state: State::Calling,
+                timers,
+                retransmit_interval: settings.t1,
+                ack: None,
+            },
+            actions,
+        ))
+    }
+
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Request that was sent by transaction
+    pub fn request(&self) -> &[u8] {
+        &self.request
+    }
+
+    /// Time when `on_timer` should be called
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// Response must match `key`
+    pub fn on_response(&mut self, response: &SipResponse, now: Instant) -> Vec<Action> {
+        match (self.state, status_code(response)) {
+            (State::Calling, 100..=199) | (State::Proceeding, 100..=199) => {
+                self.state = State::Proceeding;
+                self.timers.cancel(Timer::A);
+                self.timers.cancel(Timer::B);
+                if let Some(timer_c) = self.settings.timer_c {
+                    self.timers.start(Timer::C, now + timer_c);
+                }
+                vec![Action::PassToTu]
+            }
+            (State::Calling, 200..=299) | (State::Proceeding, 200..=299) => {
+                self.terminate();
+                vec![Action::PassToTu, Action::Terminated]
+            }
+            (State::Calling, 300..=699) | (State::Proceeding, 300..=699) => {
+                let ack = match SipRequest::parse(&self.request) {
+                    Ok((_, request)) => ack(&request, response),
+                    Err(_) => return vec![],
+                };
+                self.ack = Some(ack.clone());
+                self.state = State::Completed;
+                self.timers.clear();
+                let mut actions = vec![Action::PassToTu, Action::Send(ack)];
+                if self.reliable {
+                    self.terminate();
+                    actions.push(Action::Terminated);
+                } else {
+                    self.timers.start(Timer::D, now + self.settings.timer_d);
+                }
+                actions
+            }
+            // retransmission of final response
+            (State::Completed, 300..=699) => self.ack.iter().cloned().map(Action::Send).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Fires timers that expired before `now`
+    pub fn on_timer(&mut self, now: Instant) -> Vec<Action> {
+        let mut actions = vec![];
+        while let Some(timer) = self.timers.pop_expired(now) {
+            match (timer, self.state) {
+                (Timer::A, State::Calling) => {
+                    actions.push(Action::Send(self.request.clone()));
+                    self.retransmit_interval *= 2;
+                    self.timers.start(Timer::A, now + self.retransmit_interval);
+                }
+                (Timer::B, State::Calling) | (Timer::C, State::Calling) => {
+                    self.terminate();
+                    actions.push(Action::Timeout);
+                    actions.push(Action::Terminated);
+                }
+                (Timer::C, State::Proceeding) => actions.push(Action::Cancel),
+                (Timer::D, _) => {
+                    self.terminate();
+                    actions.push(Action::Terminated);
+                }
+                _ => {}
+            }
+        }
+        actions
+    }
+
+    /// Transport layer failed to send request or ACK
+    pub fn on_transport_error(&mut self) -> Vec<Action> {
+        if self.state == State::Terminated {
+            return vec![];
+        }
+        self.terminate();
+        vec![Action::TransportError, Action::Terminated]
+    }
+
+    fn terminate(&mut self) {
+        self.state = State::Terminated;
+        self.timers.clear();
+    }
+}
+
+/// [rfc3261 section-17.1.2](https://tools.ietf.org/html/rfc3261#section-17.1.2)
+#[derive(Debug)]
+pub struct NonInviteClientTransaction {
+    key: TransactionKey,
+    request: Vec<u8>,
+    reliable: bool,
+    settings: TimerSettings,
+    state: State,
+    timers: Timers,
+    retransmit_interval: Duration,
+}
+
+impl NonInviteClientTransaction {
+    /// Returns transaction in Trying state and action that sends request.
+    /// Request must not be INVITE or ACK
+    pub fn new(
+        request: Vec<u8>,
+        reliable: bool,
+        settings: TimerSettings,
+        now: Instant,
+    ) -> Result<(NonInviteClientTransaction, Vec<Action>), Error> {
+        let key = client_key(&request, false)?;
+        let mut timers = Timers::default();
+        if !reliable {
+            timers.start(Timer::E, now + settings.t1);
+        }
+        timers.start(Timer::F, now + settings.timeout());
+        let actions = vec![Action::Send(request.clone())];
+        Ok((
+            NonInviteClientTransaction {
+                key,
+                request,
+                reliable,
+                settings,
+                state: State::Trying,
+                timers,
+                retransmit_interval: settings.t1,
+            },
+            actions,
+        ))
+    }
+
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Request that was sent by transaction
+    pub fn request(&self) -> &[u8] {
+        &self.request
+    }
+
+    /// Time when `on_timer` should be called
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// Response must match `key`
+    pub fn on_response(&mut self, response: &SipResponse, now: Instant) -> Vec<Action> {
+        match (self.state, status_code(response)) {
+            (State::Trying, 100..=199) | (State::Proceeding, 100..=199) => {
+                self.state = State::Proceeding;
+                vec![Action::PassToTu]
+            }
+            (State::Trying, 200..=699) | (State::Proceeding, 200..=699) => {
+                self.state = State::Completed;
+                self.timers.clear();
+                if self.reliable {
+                    self.terminate();
+                    return vec![Action::PassToTu, Action::Terminated];
+                }
+                self.timers.start(Timer::K, now + self.settings.t4);
+                vec![Action::PassToTu]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Fires timers that expired before `now`
+    pub fn on_timer(&mut self, now: Instant) -> Vec<Action> {
+        let mut actions = vec![];
+        while let Some(timer) = self.timers.pop_expired(now) {
+            match (timer, self.state) {
+                (Timer::E, State::Trying) => {
+                    actions.push(Action::Send(self.request.clone()));
+                    self.retransmit_interval = (self.retransmit_interval * 2).min(self.settings.t2);
+                    self.timers.start(Timer::E, now + self.retransmit_interval);
+                }
+                (Timer::E, State::Proceeding) => {
+                    actions.push(Action::Send(self.request.clone()));
+                    self.timers.start(Timer::E, now + self.settings.t2);
+                }
+                (Timer::F, State::Trying) | (Timer::F, State::Proceeding) => {
+                    self.terminate();
+                    actions.push(Action::Timeout);
+                    actions.push(Action::Terminated);
+                }
+                (Timer::K, _) => {
+                    self.terminate();
+                    actions.push(Action::Terminated);
+                }
+                _ => {}
+            }
+        }
+        actions
+    }
+
+    /// Transport layer failed to send request
+    pub fn on_transport_error(&mut self) -> Vec<Action> {
+        if self.state == State::Terminated {
+            return vec![];
+        }
+        self.terminate();
+        vec![Action::TransportError, Action::Terminated]
+    }
+
+    fn terminate(&mut self) {
+        self.state = State::Terminated;
+        self.timers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Content-Length: 0\r\n\r\n";
+
+    fn response(code: u16, method: &str) -> String {
+        format!(
+            "SIP/2.0 {} Reason\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 {}\r\n\r\n",
+            code, method
+        )
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn invite_retransmissions_and_timeout() {
+        let now = Instant::now();
+        let settings = TimerSettings::default();
+        let (mut transaction, actions) =
+            InviteClientTransaction::new(INVITE.as_bytes().to_vec(), false, settings, now).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(transaction.state(), State::Calling);
+        assert_eq!(transaction.next_deadline(), Some(now + ms(500)));
+
+        // Timer A: 0.5s, 1s, 2s, 4s, 8s, 16s
+        let mut sent = 0;
+        for at in &[500, 1500, 3500, 7500, 15500, 31500] {
+            let actions = transaction.on_timer(now + ms(*at));
+            assert_eq!(actions, vec![Action::Send(INVITE.as_bytes().to_vec())]);
+            sent += 1;
+        }
+        assert_eq!(sent, 6);
+        assert_eq!(
+            transaction.on_timer(now + ms(32000)),
+            vec![Action::Timeout, Action::Terminated]
+        );
+        assert_eq!(transaction.state(), State::Terminated);
+        assert_eq!(transaction.next_deadline(), None);
+    }
+
+    #[test]
+    fn invite_non_2xx_ack() {
+        let now = Instant::now();
+        let (mut transaction, _) = InviteClientTransaction::new(
+            INVITE.as_bytes().to_vec(),
+            false,
+            TimerSettings::default(),
+            now,
+        )
+        .unwrap();
+        let ringing = response(180, "INVITE");
+        let (_, ringing) = SipResponse::parse(ringing.as_bytes()).unwrap();
+        assert_eq!(
+            transaction.on_response(&ringing, now),
+            vec![Action::PassToTu]
+        );
+        assert_eq!(transaction.state(), State::Proceeding);
+        // no retransmissions in Proceeding
+        assert_eq!(transaction.next_deadline(), None);
+
+        let busy = response(486, "INVITE");
+        let (_, busy) = SipResponse::parse(busy.as_bytes()).unwrap();
+        let actions = transaction.on_response(&busy, now);
+        assert_eq!(actions[0], Action::PassToTu);
+        let ack = match &actions[1] {
+            Action::Send(ack) => ack.clone(),
+            _ => panic!("ACK is expected"),
+        };
+        let (_, parsed) = SipRequest::parse(&ack).unwrap();
+        assert_eq!(parsed.rl.method, SipMethod::ACK);
+        assert_eq!(transaction.state(), State::Completed);
+
+        // retransmitted 486 is absorbed, ACK is sent again
+        assert_eq!(transaction.on_response(&busy, now), vec![Action::Send(ack)]);
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![Action::Terminated]
+        );
+    }
+
+    #[test]
+    fn invite_2xx_and_reliable_transport() {
+        let now = Instant::now();
+        let (mut transaction, _) = InviteClientTransaction::new(
+            INVITE.as_bytes().to_vec(),
+            true,
+            TimerSettings::default(),
+            now,
+        )
+        .unwrap();
+        // Timer A is not started for reliable transport
+        assert_eq!(
+            transaction.next_deadline(),
+            Some(now + Duration::from_secs(32))
+        );
+        let ok = response(200, "INVITE");
+        let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+        assert_eq!(
+            transaction.on_response(&ok, now),
+            vec![Action::PassToTu, Action::Terminated]
+        );
+
+        let (mut transaction, _) = InviteClientTransaction::new(
+            INVITE.as_bytes().to_vec(),
+            true,
+            TimerSettings::default(),
+            now,
+        )
+        .unwrap();
+        let busy = response(486, "INVITE");
+        let (_, busy) = SipResponse::parse(busy.as_bytes()).unwrap();
+        let actions = transaction.on_response(&busy, now);
+        assert_eq!(actions.len(), 3);
+        assert_eq!(actions[2], Action::Terminated);
+        assert_eq!(transaction.on_transport_error(), vec![]);
+    }
+
+    #[test]
+    fn invite_timer_c() {
+        let now = Instant::now();
+        let settings = TimerSettings {
+            timer_c: Some(Duration::from_secs(180)),
+            ..TimerSettings::default()
+        };
+        let (mut transaction, _) =
+            InviteClientTransaction::new(INVITE.as_bytes().to_vec(), true, settings, now).unwrap();
+        let ringing = response(180, "INVITE");
+        let (_, ringing) = SipResponse::parse(ringing.as_bytes()).unwrap();
+        transaction.on_response(&ringing, now + Duration::from_secs(10));
+        assert_eq!(
+            transaction.next_deadline(),
+            Some(now + Duration::from_secs(190))
+        );
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(190)),
+            vec![Action::Cancel]
+        );
+    }
+
+    #[test]
+    fn non_invite_transaction() {
+        let now = Instant::now();
+        let request = INVITE.replace("INVITE", "OPTIONS");
+        assert_eq!(
+            InviteClientTransaction::new(
+                request.as_bytes().to_vec(),
+                false,
+                TimerSettings::default(),
+                now
+            )
+            .unwrap_err(),
+            Error::InvalidMessage
+        );
+        assert!(NonInviteClientTransaction::new(
+            INVITE.as_bytes().to_vec(),
+            false,
+            TimerSettings::default(),
+            now
+        )
+        .is_err());
+
+        let (mut transaction, _) = NonInviteClientTransaction::new(
+            request.as_bytes().to_vec(),
+            false,
+            TimerSettings::default(),
+            now,
+        )
+        .unwrap();
+        assert_eq!(transaction.state(), State::Trying);
+        // Timer E: 0.5s, 1s, 2s, 4s, 4s
+        let mut at = 0;
+        for interval in &[500, 1000, 2000, 4000, 4000] {
+            at += interval;
+            assert_eq!(transaction.on_timer(now + ms(at)).len(), 1);
+        }
+        let trying = response(100, "OPTIONS");
+        let (_, trying) = SipResponse::parse(trying.as_bytes()).unwrap();
+        assert_eq!(
+            transaction.on_response(&trying, now + ms(at)),
+            vec![Action::PassToTu]
+        );
+        assert_eq!(transaction.state(), State::Proceeding);
+        assert_eq!(transaction.next_deadline(), Some(now + ms(at + 4000)));
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![
+                Action::Send(request.as_bytes().to_vec()),
+                Action::Timeout,
+                Action::Terminated
+            ]
+        );
+    }
+
+    #[test]
+    fn non_invite_timer_k() {
+        let now = Instant::now();
+        let request = INVITE.replace("INVITE", "BYE");
+        let (mut transaction, _) = NonInviteClientTransaction::new(
+            request.as_bytes().to_vec(),
+            false,
+            TimerSettings::default(),
+            now,
+        )
+        .unwrap();
+        let ok = response(200, "BYE");
+        let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+        assert_eq!(transaction.on_response(&ok, now), vec![Action::PassToTu]);
+        assert_eq!(transaction.state(), State::Completed);
+        assert_eq!(transaction.on_response(&ok, now), vec![]);
+        assert_eq!(
+            transaction.next_deadline(),
+            Some(now + Duration::from_secs(5))
+        );
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(5)),
+            vec![Action::Terminated]
+        );
+    }
+}
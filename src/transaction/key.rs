@@ -0,0 +1,176 @@
+use crate::message::{cseq_method, cseq_number, request_uri, tag};
+use crate::{
+    SipHeader, SipHeaderTagType, SipHeaders, SipMethod, SipRFCHeader, SipRequest, SipResponse,
+};
+use std::str;
+
+/// Branch of rfc3261 compliant Via starts with magic cookie
+pub const BRANCH_MAGIC_COOKIE: &str = "z9hG4bK";
+
+/// Identifies transaction of message.
+/// [rfc3261 section-17.1.3](https://tools.ietf.org/html/rfc3261#section-17.1.3),
+/// [rfc3261 section-17.2.3](https://tools.ietf.org/html/rfc3261#section-17.2.3)
+///
+/// ACK for non-2xx response matches INVITE server transaction.
+/// ACK for 2xx has own branch and doesn't match any transaction
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TransactionKey {
+    /// Branch of top Via and method of CSeq. Response matches client transaction by it
+    Client { branch: String, method: String },
+    /// Branch and sent-by of top Via and method of request
+    Server {
+        branch: String,
+        sent_by: String,
+        method: String,
+    },
+    /// Request from rfc2543 element which branch doesn't start with magic cookie
+    Rfc2543 {
+        request_uri: String,
+        from_tag: Option<String>,
+        call_id: String,
+        cseq: u32,
+        via: String,
+        method: String,
+    },
+}
+
+impl TransactionKey {
+    /// Key of request that is sent by client transaction
+    pub fn client(request: &SipRequest) -> Option<TransactionKey> {
+        Some(TransactionKey::Client {
+            branch: branch(&request.headers)?.to_string(),
+            method: cseq_method(&request.headers)?.to_string(),
+        })
+    }
+
+    /// Key of client transaction that should receive response
+    pub fn from_response(response: &SipResponse) -> Option<TransactionKey> {
+        Some(TransactionKey::Client {
+            branch: branch(&response.headers)?.to_string(),
+            method: cseq_method(&response.headers)?.to_string(),
+        })
+    }
+
+    /// Key of server transaction that should receive request
+    pub fn server(request: &SipRequest) -> Option<TransactionKey> {
+        let headers = &request.headers;
+        let method = match request.rl.method {
+            SipMethod::ACK => SipMethod::INVITE.as_str(),
+            _ => request.rl.method_str(),
+        }
+        .to_string();
+        let via = top_via(headers)?;
+        match branch(headers) {
+            Some(branch) if branch.starts_with(BRANCH_MAGIC_COOKIE) => {
+                Some(TransactionKey::Server {
+                    branch: branch.to_string(),
+                    sent_by: sent_by(via)?,
+                    method,
+                })
+            }
+            _ => Some(TransactionKey::Rfc2543 {
+                request_uri: request_uri(request).to_string(),
+                from_tag: tag(headers, SipRFCHeader::From).map(|tag| tag.to_string()),
+                call_id: headers
+                    .get_rfc_s(SipRFCHeader::CallID)?
+                    .value
+                    .vstr
+                    .to_string(),
+                cseq: cseq_number(headers)?,
+                via: String::from_utf8_lossy(via.raw_value_param).into_owned(),
+                method,
+            }),
+        }
+    }
+}
+
+fn top_via<'a, 'b>(headers: &'b SipHeaders<'a>) -> Option<&'b SipHeader<'a>> {
+    headers.get_rfc(SipRFCHeader::Via)?.front()
+}
+
+fn branch<'a>(headers: &SipHeaders<'a>) -> Option<&'a str> {
+    let branch = *top_via(headers)?.params()?.get("branch")?;
+    branch
+}
+
+/// host[:port] of Via
+fn sent_by(via: &SipHeader) -> Option<String> {
+    let tags = via.value.tags()?;
+    let host = str::from_utf8(tags.get(&SipHeaderTagType::Host)?).ok()?;
+    match tags.get(&SipHeaderTagType::Port) {
+        Some(port) => Some(format!("{}:{}", host, str::from_utf8(port).ok()?)),
+        None => Some(host.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, branch: &str) -> String {
+        format!(
+            "{} sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com:5060;branch={}\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 314159 {}\r\n\r\n",
+            method, branch, method
+        )
+    }
+
+    fn server_key(buf: &str) -> Option<TransactionKey> {
+        let (_, request) = SipRequest::parse(buf.as_bytes()).unwrap();
+        TransactionKey::server(&request)
+    }
+
+    #[test]
+    fn server_key_test() {
+        let invite = server_key(&request("INVITE", "z9hG4bK776asdhds")).unwrap();
+        assert_eq!(
+            invite,
+            TransactionKey::Server {
+                branch: "z9hG4bK776asdhds".to_string(),
+                sent_by: "pc33.atlanta.com:5060".to_string(),
+                method: "INVITE".to_string()
+            }
+        );
+        assert_eq!(
+            server_key(&request("ACK", "z9hG4bK776asdhds")).unwrap(),
+            invite
+        );
+        assert_ne!(
+            server_key(&request("CANCEL", "z9hG4bK776asdhds")).unwrap(),
+            invite
+        );
+        assert_ne!(
+            server_key(&request("ACK", "z9hG4bK776asdhdt")).unwrap(),
+            invite
+        );
+
+        // rfc2543 fallback
+        let invite = server_key(&request("INVITE", "1")).unwrap();
+        match &invite {
+            TransactionKey::Rfc2543 { cseq, from_tag, .. } => {
+                assert_eq!(*cseq, 314159);
+                assert_eq!(from_tag.as_deref(), Some("1928301774"));
+            }
+            _ => panic!("rfc2543 key is expected"),
+        }
+        assert_eq!(server_key(&request("ACK", "1")).unwrap(), invite);
+    }
+
+    #[test]
+    fn client_key_test() {
+        let buf = request("INVITE", "z9hG4bK776asdhds");
+        let (_, invite) = SipRequest::parse(buf.as_bytes()).unwrap();
+        let response = "SIP/2.0 180 Ringing\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com:5060;branch=z9hG4bK776asdhds\r\n\
+CSeq: 314159 INVITE\r\n\r\n";
+        let (_, response) = SipResponse::parse(response.as_bytes()).unwrap();
+        assert_eq!(
+            TransactionKey::client(&invite),
+            TransactionKey::from_response(&response)
+        );
+    }
+}
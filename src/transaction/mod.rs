@@ -0,0 +1,118 @@
+//! Transaction layer. [rfc3261 section-17](https://tools.ietf.org/html/rfc3261#section-17)
+//!
+//! State machines don't own sockets and don't read time. Each input is a parsed
+//! message or timer tick with `now`, and result is a list of `Action`s: bytes that
+//! should be sent, message that should be passed to transaction user (TU), timeout etc.
+//! `next_deadline` returns time when `on_timer` should be called next.
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::clock::{Clock, ManualClock};
+//! use sipcore::transaction::{Action, NonInviteClientTransaction, State, TimerSettings};
+//! use sipcore::SipResponse;
+//! use std::time::Duration;
+//!
+//! let options = "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! To: <sip:carol@chicago.com>\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 63104 OPTIONS\r\n\r\n";
+//!
+//! let clock = ManualClock::new();
+//! let (mut transaction, actions) = NonInviteClientTransaction::new(
+//!     options.as_bytes().to_vec(),
+//!     false,
+//!     TimerSettings::default(),
+//!     clock.now(),
+//! )
+//! .unwrap();
+//! assert_eq!(actions, vec![Action::Send(options.as_bytes().to_vec())]);
+//!
+//! // Timer E retransmits request over UDP
+//! clock.advance(Duration::from_millis(500));
+//! assert_eq!(transaction.on_timer(clock.now()), actions);
+//!
+//! let ok = "SIP/2.0 200 OK\r\n\
+//! Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\
+//! From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//! To: <sip:carol@chicago.com>;tag=93810874\r\n\
+//! Call-ID: a84b4c76e66710\r\n\
+//! CSeq: 63104 OPTIONS\r\n\r\n";
+//! let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+//! assert_eq!(transaction.on_response(&ok, clock.now()), vec![Action::PassToTu]);
+//! assert_eq!(transaction.state(), State::Completed);
+//!
+//! // Timer K absorbs response retransmissions
+//! clock.advance(Duration::from_secs(5));
+//! assert_eq!(transaction.on_timer(clock.now()), vec![Action::Terminated]);
+//! ```
+
+mod client;
+mod key;
+mod server;
+mod timers;
+
+pub use client::{InviteClientTransaction, NonInviteClientTransaction};
+pub use key::{TransactionKey, BRANCH_MAGIC_COOKIE};
+pub use server::{InviteServerTransaction, NonInviteServerTransaction};
+pub use timers::{Timer, TimerSettings};
+
+use std::fmt;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum State {
+    /// INVITE client transaction waits for response
+    Calling,
+    /// Non-INVITE transaction waits for response or TU
+    Trying,
+    Proceeding,
+    Completed,
+    /// INVITE server transaction sent 2xx response.
+    /// [rfc6026 section-7.1](https://tools.ietf.org/html/rfc6026#section-7.1)
+    Accepted,
+    /// INVITE server transaction received ACK
+    Confirmed,
+    Terminated,
+}
+
+/// What driver of transaction must do after input
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    /// Send message to transport
+    Send(Vec<u8>),
+    /// Pass received message to TU
+    PassToTu,
+    /// Timer B, F or H fired. TU must treat it as 408 (Request Timeout).
+    /// If server transaction retransmits 2xx, it is Timer L that fired without ACK:
+    /// TU should end session by BYE
+    Timeout,
+    /// Timer C fired after provisional response. Proxy must CANCEL the request
+    Cancel,
+    /// Transport failed to send message. TU must treat it as 503 (Service Unavailable)
+    TransportError,
+    /// Transaction is terminated and must be destroyed
+    Terminated,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Message can't be parsed or has no headers that identify transaction
+    InvalidMessage,
+    /// Message can't be sent in current state of transaction
+    InvalidState,
+    /// Request with SIPS Request-URI can be sent over TLS only
+    InsecureTransport,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMessage => f.write_str("Invalid message for transaction"),
+            Error::InvalidState => f.write_str("Invalid state of transaction"),
+            Error::InsecureTransport => f.write_str("SIPS request over insecure transport"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,570 @@
+use super::{
+    timers::{Timer, TimerSettings, Timers},
+    Action, Error, State, TransactionKey,
+};
+use crate::message::{response, status_code_of};
+use crate::{SipMethod, SipRequest};
+use std::time::{Duration, Instant};
+
+/// [rfc3261 section-17.2.1](https://tools.ietf.org/html/rfc3261#section-17.2.1)
+///
+/// 100 (Trying) is sent as soon as transaction is created.
+/// After 2xx response transaction is Accepted
+/// ([rfc6026 section-7.1](https://tools.ietf.org/html/rfc6026#section-7.1)):
+/// it absorbs INVITE retransmissions until Timer L fires.
+/// TU retransmits 2xx until ACK is received, unless it is delegated by `set_2xx_retransmission`
+#[derive(Debug)]
+pub struct InviteServerTransaction {
+    key: TransactionKey,
+    reliable: bool,
+    settings: TimerSettings,
+    state: State,
+    timers: Timers,
+    retransmit_interval: Duration,
+    retransmit_2xx: bool,
+    last_response: Vec<u8>,
+}
+
+impl InviteServerTransaction {
+    /// Returns transaction in Proceeding state, action that sends 100 (Trying)
+    /// and action that passes request to TU
+    pub fn new(
+        request: &SipRequest,
+        reliable: bool,
+        settings: TimerSettings,
+    ) -> Result<(InviteServerTransaction, Vec<Action>), Error> {
+        if request.rl.method != SipMethod::INVITE {
+            return Err(Error::InvalidMessage);
+        }
+        let key = TransactionKey::server(request).ok_or(Error::InvalidMessage)?;
+        let trying = response(request, 100, "Trying", None);
+        let actions = vec![Action::Send(trying.clone()), Action::PassToTu];
+        Ok((
+            InviteServerTransaction {
+                key,
+                reliable,
+                settings,
+                state: State::Proceeding,
+                timers: Timers::default(),
+                retransmit_interval: settings.t1,
+                retransmit_2xx: false,
+                last_response: trying,
+            },
+            actions,
+        ))
+    }
+
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// 100 (Trying) or response that is sent by TU
+    pub fn last_response(&self) -> &[u8] {
+        &self.last_response
+    }
+
+    /// Time when `on_timer` should be called
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// 2xx response is retransmitted by Timer G until ACK is received,
+    /// as [rfc3261 section-13.3.1.4](https://tools.ietf.org/html/rfc3261#section-13.3.1.4)
+    /// requires from UAS core. Timer L without ACK is reported as `Timeout`.
+    /// Must be set before 2xx is sent, disabled by default
+    pub fn set_2xx_retransmission(&mut self, enabled: bool) {
+        self.retransmit_2xx = enabled;
+    }
+
+    /// Retransmission of INVITE or ACK for final response. Request must match `key`,
+    /// ACK for 2xx has own branch and must match dialog of 2xx
+    pub fn on_request(&mut self, request: &SipRequest, now: Instant) -> Vec<Action> {
+        match (request.rl.method, self.state) {
+            (SipMethod::ACK, State::Accepted) => {
+                // INVITE retransmissions are absorbed until Timer L
+                self.state = State::Confirmed;
+                self.timers.cancel(Timer::G);
+                vec![]
+            }
+            (SipMethod::ACK, State::Completed) => {
+                self.state = State::Confirmed;
+                self.timers.clear();
+                if self.reliable {
+                    self.terminate();
+                    return vec![Action::Terminated];
+                }
+                self.timers.start(Timer::I, now + self.settings.t4);
+                vec![]
+            }
+            (SipMethod::INVITE, State::Proceeding) | (SipMethod::INVITE, State::Completed) => {
+                vec![Action::Send(self.last_response.clone())]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Sends response of TU. Response must start with Status-Line
+    pub fn send_response(&mut self, response: Vec<u8>, now: Instant) -> Result<Vec<Action>, Error> {
+        let code = status_code_of(&response).ok_or(Error::InvalidMessage)?;
+        if self.state == State::Accepted && !self.retransmit_2xx && (200..=299).contains(&code) {
+            // retransmission of 2xx by TU
+            return Ok(vec![Action::Send(response)]);
+        }
+        if self.state != State::Proceeding {
+            return Err(Error::InvalidState);
+        }
+        if !(100..=699).contains(&code) {
+            return Err(Error::InvalidMessage);
+        }
+        self.last_response = response.clone();
+        match code {
+            100..=199 => Ok(vec![Action::Send(response)]),
+            200..=299 => {
+                self.state = State::Accepted;
+                if self.retransmit_2xx {
+                    // 2xx is retransmitted over any transport, it may be lost behind proxy
+                    self.retransmit_interval = self.settings.t1;
+                    self.timers.start(Timer::G, now + self.retransmit_interval);
+                }
+                self.timers.start(Timer::L, now + self.settings.timeout());
+                Ok(vec![Action::Send(response)])
+            }
+            _ => {
+                self.state = State::Completed;
+                if !self.reliable {
+                    self.timers.start(Timer::G, now + self.retransmit_interval);
+                }
+                self.timers.start(Timer::H, now + self.settings.timeout());
+                Ok(vec![Action::Send(response)])
+            }
+        }
+    }
+
+    /// Fires timers that expired before `now`
+    pub fn on_timer(&mut self, now: Instant) -> Vec<Action> {
+        let mut actions = vec![];
+        while let Some(timer) = self.timers.pop_expired(now) {
+            match (timer, self.state) {
+                (Timer::G, State::Completed) | (Timer::G, State::Accepted) => {
+                    actions.push(Action::Send(self.last_response.clone()));
+                    self.retransmit_interval = (self.retransmit_interval * 2).min(self.settings.t2);
+                    self.timers.start(Timer::G, now + self.retransmit_interval);
+                }
+                (Timer::H, State::Completed) => {
+                    self.terminate();
+                    actions.push(Action::Timeout);
+                    actions.push(Action::Terminated);
+                }
+                (Timer::L, State::Accepted) if self.retransmit_2xx => {
+                    self.terminate();
+                    actions.push(Action::Timeout);
+                    actions.push(Action::Terminated);
+                }
+                (Timer::I, _) | (Timer::L, _) => {
+                    self.terminate();
+                    actions.push(Action::Terminated);
+                }
+                _ => {}
+            }
+        }
+        actions
+    }
+
+    /// Transport layer failed to send response
+    pub fn on_transport_error(&mut self) -> Vec<Action> {
+        if self.state == State::Terminated {
+            return vec![];
+        }
+        self.terminate();
+        vec![Action::TransportError, Action::Terminated]
+    }
+
+    fn terminate(&mut self) {
+        self.state = State::Terminated;
+        self.timers.clear();
+    }
+}
+
+/// [rfc3261 section-17.2.2](https://tools.ietf.org/html/rfc3261#section-17.2.2)
+#[derive(Debug)]
+pub struct NonInviteServerTransaction {
+    key: TransactionKey,
+    reliable: bool,
+    settings: TimerSettings,
+    state: State,
+    timers: Timers,
+    last_response: Option<Vec<u8>>,
+}
+
+impl NonInviteServerTransaction {
+    /// Returns transaction in Trying state and action that passes request to TU.
+    /// Request must not be INVITE or ACK
+    pub fn new(
+        request: &SipRequest,
+        reliable: bool,
+        settings: TimerSettings,
+    ) -> Result<(NonInviteServerTransaction, Vec<Action>), Error> {
+        if request.rl.method == SipMethod::INVITE || request.rl.method == SipMethod::ACK {
+            return Err(Error::InvalidMessage);
+        }
+        let key = TransactionKey::server(request).ok_or(Error::InvalidMessage)?;
+        Ok((
+            NonInviteServerTransaction {
+                key,
+                reliable,
+                settings,
+                state: State::Trying,
+                timers: Timers::default(),
+                last_response: None,
+            },
+            vec![Action::PassToTu],
+        ))
+    }
+
+    pub fn key(&self) -> &TransactionKey {
+        &self.key
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Response that is sent by TU
+    pub fn last_response(&self) -> Option<&[u8]> {
+        self.last_response.as_deref()
+    }
+
+    /// Time when `on_timer` should be called
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// Retransmission of request. Request must match `key`
+    pub fn on_request(&mut self, _request: &SipRequest, _now: Instant) -> Vec<Action> {
+        match self.state {
+            State::Proceeding | State::Completed => self
+                .last_response
+                .iter()
+                .cloned()
+                .map(Action::Send)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Sends response of TU. Response must start with Status-Line
+    pub fn send_response(&mut self, response: Vec<u8>, now: Instant) -> Result<Vec<Action>, Error> {
+        let code = status_code_of(&response).ok_or(Error::InvalidMessage)?;
+        if self.state != State::Trying && self.state != State::Proceeding {
+            return Err(Error::InvalidState);
+        }
+        if !(100..=699).contains(&code) {
+            return Err(Error::InvalidMessage);
+        }
+        self.last_response = Some(response.clone());
+        match code {
+            100..=199 => {
+                self.state = State::Proceeding;
+                Ok(vec![Action::Send(response)])
+            }
+            _ => {
+                if self.reliable {
+                    self.terminate();
+                    return Ok(vec![Action::Send(response), Action::Terminated]);
+                }
+                self.state = State::Completed;
+                self.timers.start(Timer::J, now + self.settings.timeout());
+                Ok(vec![Action::Send(response)])
+            }
+        }
+    }
+
+    /// Fires timers that expired before `now`
+    pub fn on_timer(&mut self, now: Instant) -> Vec<Action> {
+        match self.timers.pop_expired(now) {
+            Some(Timer::J) => {
+                self.terminate();
+                vec![Action::Terminated]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Transport layer failed to send response
+    pub fn on_transport_error(&mut self) -> Vec<Action> {
+        if self.state == State::Terminated {
+            return vec![];
+        }
+        self.terminate();
+        vec![Action::TransportError, Action::Terminated]
+    }
+
+    fn terminate(&mut self) {
+        self.state = State::Terminated;
+        self.timers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SipResponse, SipResponseStatusCode};
+
+    fn request(method: &str) -> String {
+        format!(
+            "{} sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 {}\r\n\
+Content-Length: 0\r\n\r\n",
+            method, method
+        )
+    }
+
+    #[test]
+    fn invite_server_non_2xx() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, actions) =
+            InviteServerTransaction::new(&invite, false, TimerSettings::default()).unwrap();
+        assert_eq!(actions.len(), 2);
+        let trying = match &actions[0] {
+            Action::Send(trying) => trying.clone(),
+            _ => panic!("100 Trying is expected"),
+        };
+        let (_, parsed) = SipResponse::parse(&trying).unwrap();
+        assert_eq!(parsed.sl.status_code, SipResponseStatusCode::Trying);
+        assert_eq!(actions[1], Action::PassToTu);
+
+        // retransmitted INVITE gets last provisional response
+        assert_eq!(
+            transaction.on_request(&invite, now),
+            vec![Action::Send(trying)]
+        );
+
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        assert_eq!(
+            transaction.send_response(busy.clone(), now),
+            Ok(vec![Action::Send(busy.clone())])
+        );
+        assert_eq!(transaction.state(), State::Completed);
+        assert_eq!(
+            transaction.send_response(busy.clone(), now),
+            Err(Error::InvalidState)
+        );
+
+        // Timer G: 0.5s, 1s, 2s, 4s, 4s
+        let mut at = Duration::from_millis(0);
+        for interval in &[500, 1000, 2000, 4000, 4000] {
+            at += Duration::from_millis(*interval);
+            assert_eq!(
+                transaction.on_timer(now + at),
+                vec![Action::Send(busy.clone())]
+            );
+        }
+
+        let ack = request("ACK");
+        let (_, ack) = SipRequest::parse(ack.as_bytes()).unwrap();
+        assert_eq!(transaction.on_request(&ack, now + at), vec![]);
+        assert_eq!(transaction.state(), State::Confirmed);
+        assert_eq!(transaction.on_request(&ack, now + at), vec![]);
+        assert_eq!(
+            transaction.on_timer(now + at + Duration::from_secs(5)),
+            vec![Action::Terminated]
+        );
+    }
+
+    #[test]
+    fn invite_server_timer_h() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            InviteServerTransaction::new(&invite, true, TimerSettings::default()).unwrap();
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        transaction.send_response(busy, now).unwrap();
+        assert_eq!(
+            transaction.next_deadline(),
+            Some(now + Duration::from_secs(32))
+        );
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![Action::Timeout, Action::Terminated]
+        );
+    }
+
+    #[test]
+    fn invite_server_2xx() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            InviteServerTransaction::new(&invite, false, TimerSettings::default()).unwrap();
+        assert_eq!(
+            transaction.send_response(b"INVITE".to_vec(), now),
+            Err(Error::InvalidMessage)
+        );
+        let ok = response(&invite, 200, "OK", Some("a6c85cf"));
+        assert_eq!(
+            transaction.send_response(ok.clone(), now),
+            Ok(vec![Action::Send(ok.clone())])
+        );
+        assert_eq!(transaction.state(), State::Accepted);
+        // TU retransmits 2xx, retransmitted INVITE is absorbed
+        assert_eq!(
+            transaction.send_response(ok.clone(), now + Duration::from_millis(500)),
+            Ok(vec![Action::Send(ok.clone())])
+        );
+        assert_eq!(transaction.on_request(&invite, now), vec![]);
+        let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+        assert_eq!(
+            transaction.send_response(busy, now),
+            Err(Error::InvalidState)
+        );
+        assert_eq!(
+            transaction.next_deadline(),
+            Some(now + Duration::from_secs(32))
+        );
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![Action::Terminated]
+        );
+    }
+
+    #[test]
+    fn invite_server_2xx_retransmission() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            InviteServerTransaction::new(&invite, true, TimerSettings::default()).unwrap();
+        transaction.set_2xx_retransmission(true);
+        let ok = response(&invite, 200, "OK", Some("a6c85cf"));
+        transaction.send_response(ok.clone(), now).unwrap();
+        // transaction retransmits 2xx instead of TU
+        assert_eq!(
+            transaction.send_response(ok.clone(), now),
+            Err(Error::InvalidState)
+        );
+
+        // Timer G over reliable transport too
+        let mut at = Duration::from_millis(0);
+        for interval in &[500, 1000, 2000] {
+            at += Duration::from_millis(*interval);
+            assert_eq!(
+                transaction.on_timer(now + at),
+                vec![Action::Send(ok.clone())]
+            );
+        }
+
+        let ack = request("ACK").replace("z9hG4bK776asdhds", "z9hG4bK776asdhdt");
+        let (_, ack) = SipRequest::parse(ack.as_bytes()).unwrap();
+        assert_eq!(transaction.on_request(&ack, now + at), vec![]);
+        assert_eq!(transaction.state(), State::Confirmed);
+        assert_eq!(transaction.on_request(&invite, now + at), vec![]);
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![Action::Terminated]
+        );
+    }
+
+    #[test]
+    fn invite_server_2xx_without_ack() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            InviteServerTransaction::new(&invite, false, TimerSettings::default()).unwrap();
+        transaction.set_2xx_retransmission(true);
+        let ok = response(&invite, 200, "OK", Some("a6c85cf"));
+        transaction.send_response(ok.clone(), now).unwrap();
+
+        // Timer G: 0.5s, 1s, 2s, 4s, 4s... until Timer L
+        let mut sent = 0;
+        let at = loop {
+            let at = transaction.next_deadline().unwrap();
+            let actions = transaction.on_timer(at);
+            if actions != vec![Action::Send(ok.clone())] {
+                assert_eq!(actions, vec![Action::Timeout, Action::Terminated]);
+                break at;
+            }
+            sent += 1;
+        };
+        assert_eq!(at, now + Duration::from_secs(32));
+        // 0.5+1+2+4*7 = 31.5
+        assert_eq!(sent, 10);
+    }
+
+    #[test]
+    fn invalid_response() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            InviteServerTransaction::new(&invite, false, TimerSettings::default()).unwrap();
+        let ringing = response(&invite, 180, "Ringing", Some("a6c85cf"));
+        transaction.send_response(ringing.clone(), now).unwrap();
+        let invalid = response(&invite, 999, "Invalid", Some("a6c85cf"));
+        assert_eq!(
+            transaction.send_response(invalid.clone(), now),
+            Err(Error::InvalidMessage)
+        );
+        assert_eq!(
+            transaction.on_request(&invite, now),
+            vec![Action::Send(ringing)]
+        );
+        assert_eq!(transaction.state(), State::Proceeding);
+
+        let bye = request("BYE");
+        let (_, bye) = SipRequest::parse(bye.as_bytes()).unwrap();
+        let (mut transaction, _) =
+            NonInviteServerTransaction::new(&bye, false, TimerSettings::default()).unwrap();
+        let trying = response(&bye, 100, "Trying", None);
+        transaction.send_response(trying.clone(), now).unwrap();
+        assert_eq!(
+            transaction.send_response(invalid, now),
+            Err(Error::InvalidMessage)
+        );
+        assert_eq!(
+            transaction.on_request(&bye, now),
+            vec![Action::Send(trying)]
+        );
+        assert_eq!(transaction.state(), State::Proceeding);
+    }
+
+    #[test]
+    fn non_invite_server() {
+        let now = Instant::now();
+        let invite = request("INVITE");
+        let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+        assert!(NonInviteServerTransaction::new(&invite, false, TimerSettings::default()).is_err());
+
+        let bye = request("BYE");
+        let (_, bye) = SipRequest::parse(bye.as_bytes()).unwrap();
+        let (mut transaction, actions) =
+            NonInviteServerTransaction::new(&bye, false, TimerSettings::default()).unwrap();
+        assert_eq!(actions, vec![Action::PassToTu]);
+        // retransmission in Trying is discarded
+        assert_eq!(transaction.on_request(&bye, now), vec![]);
+
+        let ok = response(&bye, 200, "OK", None);
+        assert_eq!(
+            transaction.send_response(ok.clone(), now),
+            Ok(vec![Action::Send(ok.clone())])
+        );
+        assert_eq!(transaction.on_request(&bye, now), vec![Action::Send(ok)]);
+        assert_eq!(
+            transaction.on_timer(now + Duration::from_secs(32)),
+            vec![Action::Terminated]
+        );
+        assert_eq!(transaction.state(), State::Terminated);
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+/// Timers of [rfc3261 section-17](https://tools.ietf.org/html/rfc3261#page-265)
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Timer {
+    /// INVITE request retransmit interval, for UDP only
+    A,
+    /// INVITE transaction timeout
+    B,
+    /// Proxy INVITE transaction timeout
+    C,
+    /// Wait time for response retransmits
+    D,
+    /// non-INVITE request retransmit interval, UDP only
+    E,
+    /// non-INVITE transaction timeout
+    F,
+    /// INVITE response retransmit interval
+    G,
+    /// Wait time for ACK receipt
+    H,
+    /// Wait time for ACK retransmits
+    I,
+    /// Wait time for non-INVITE request retransmits
+    J,
+    /// Wait time for response retransmits
+    K,
+    /// Wait time for accepted INVITE request retransmits.
+    /// [rfc6026 section-8.7](https://tools.ietf.org/html/rfc6026#section-8.7)
+    L,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TimerSettings {
+    /// RTT Estimate. 500ms default
+    pub t1: Duration,
+    /// The maximum retransmit interval for non-INVITE requests and INVITE responses. 4s default
+    pub t2: Duration,
+    /// Maximum duration a message will remain in the network. 5s default
+    pub t4: Duration,
+    /// Timer D for unreliable transports. 32s default
+    pub timer_d: Duration,
+    /// Timer C of proxy. Disabled by default, rfc3261 recommends 3min
+    pub timer_c: Option<Duration>,
+}
+
+impl TimerSettings {
+    /// 64*T1. Timers B, F, H, J and L
+    pub fn timeout(&self) -> Duration {
+        self.t1 * 64
+    }
+}
+
+impl Default for TimerSettings {
+    fn default() -> TimerSettings {
+        TimerSettings {
+            t1: Duration::from_millis(500),
+            t2: Duration::from_secs(4),
+            t4: Duration::from_secs(5),
+            timer_d: Duration::from_secs(32),
+            timer_c: None,
+        }
+    }
+}
+
+/// Started timers of transaction
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    deadlines: Vec<(Timer, Instant)>,
+}
+
+impl Timers {
+    /// Restarts timer if it is already started
+    pub(crate) fn start(&mut self, timer: Timer, deadline: Instant) {
+        self.cancel(timer);
+        self.deadlines.push((timer, deadline));
+    }
+
+    pub(crate) fn cancel(&mut self, timer: Timer) {
+        self.deadlines.retain(|(started, _)| *started != timer);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.deadlines.clear();
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.iter().map(|(_, deadline)| *deadline).min()
+    }
+
+    /// Removes and returns timer that fired first
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<Timer> {
+        let (index, _) = self
+            .deadlines
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .min_by_key(|(_, (_, deadline))| *deadline)?;
+        Some(self.deadlines.remove(index).0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timers_test() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        assert_eq!(timers.next_deadline(), None);
+        timers.start(Timer::B, now + Duration::from_secs(32));
+        timers.start(Timer::A, now + Duration::from_secs(1));
+        timers.start(Timer::A, now + Duration::from_millis(500));
+        assert_eq!(
+            timers.next_deadline(),
+            Some(now + Duration::from_millis(500))
+        );
+        assert_eq!(timers.pop_expired(now), None);
+
+        let later = now + Duration::from_secs(40);
+        assert_eq!(timers.pop_expired(later), Some(Timer::A));
+        assert_eq!(timers.pop_expired(later), Some(Timer::B));
+        assert_eq!(timers.pop_expired(later), None);
+        assert_eq!(TimerSettings::default().timeout(), Duration::from_secs(32));
+    }
+}
@@ -0,0 +1,147 @@
+//! Transport protocols of [rfc3261 section-18](https://tools.ietf.org/html/rfc3261#section-18)
+
+use crate::ParseLimits;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+pub mod selector;
+pub mod stream;
+pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod udp;
+pub mod via;
+pub mod ws;
+
+pub use selector::{SelectError, TransportSelector};
+pub use stream::{StreamEvent, StreamSettings};
+pub use tcp::TcpTransport;
+#[cfg(feature = "tls")]
+pub use tls::{ClientAuth, TlsConfig, TlsTransport};
+pub use udp::UdpTransport;
+pub use ws::WsTransport;
+
+/// Limits of messages that are received from network. Messages of peers are untrusted input,
+/// so engine and transports use them unless other limits are configured
+pub fn network_limits() -> ParseLimits {
+    ParseLimits {
+        max_headers: Some(128),
+        max_header_line_len: Some(8192),
+        max_via: Some(70),
+        max_values_per_header: Some(128),
+        max_uri_len: Some(2048),
+        max_body_len: Some(64 * 1024),
+    }
+}
+
+/// transport of Via sent-protocol and `transport` URI parameter
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TransportKind {
+    Udp,
+    Tcp,
+    Tls,
+    Sctp,
+    /// [rfc7118](https://tools.ietf.org/html/rfc7118)
+    Ws,
+    Wss,
+}
+
+impl TransportKind {
+    /// Case insensitive. Ex: `UDP`, `tcp`
+    pub fn parse(s: &str) -> Option<TransportKind> {
+        let kind = match s.to_ascii_uppercase().as_str() {
+            "UDP" => TransportKind::Udp,
+            "TCP" => TransportKind::Tcp,
+            "TLS" => TransportKind::Tls,
+            "SCTP" => TransportKind::Sctp,
+            "WS" => TransportKind::Ws,
+            "WSS" => TransportKind::Wss,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// As it is written in Via. Ex: `UDP`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Udp => "UDP",
+            TransportKind::Tcp => "TCP",
+            TransportKind::Tls => "TLS",
+            TransportKind::Sctp => "SCTP",
+            TransportKind::Ws => "WS",
+            TransportKind::Wss => "WSS",
+        }
+    }
+
+    /// Reliable transports don't need retransmissions of transaction layer
+    pub fn is_reliable(&self) -> bool {
+        *self != TransportKind::Udp
+    }
+
+    pub fn is_secure(&self) -> bool {
+        *self == TransportKind::Tls || *self == TransportKind::Wss
+    }
+
+    /// Port that is used when URI or Via has no port
+    pub fn default_port(&self) -> u16 {
+        if self.is_secure() {
+            5061
+        } else {
+            5060
+        }
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Sending side of transport, so transports can be used interchangeably
+/// by `TransportSelector`. Receiving is specific to transport.
+pub trait Transport {
+    fn kind(&self) -> TransportKind;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Sends message to `destination`. `domain` is SIP domain that must be
+    /// validated by certificate of TLS server or `Host` of WebSocket handshake.
+    /// It is not used by UDP and TCP
+    fn send_message(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        domain: &str,
+        now: Instant,
+    ) -> io::Result<()>;
+
+    /// Maximum size of message that can be sent
+    fn max_message_size(&self) -> usize;
+
+    fn is_reliable(&self) -> bool {
+        self.kind().is_reliable()
+    }
+
+    fn is_secure(&self) -> bool {
+        self.kind().is_secure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport_kind_test() {
+        assert_eq!(TransportKind::parse("udp"), Some(TransportKind::Udp));
+        assert_eq!(TransportKind::parse("WSS"), Some(TransportKind::Wss));
+        assert_eq!(TransportKind::parse("quic"), None);
+        assert!(!TransportKind::Udp.is_reliable());
+        assert!(TransportKind::Ws.is_reliable());
+        assert!(TransportKind::Tls.is_secure());
+        assert_eq!(TransportKind::Sctp.to_string(), "SCTP");
+    }
+}
@@ -0,0 +1,339 @@
+//! Choice of transport for outgoing request.
+//! [rfc3263 section-4.1](https://tools.ietf.org/html/rfc3263#section-4.1)
+//!
+//! Order of rules:
+//! 1. `transport` parameter of Request-URI. For SIPS URI it means transport
+//!    below TLS, so `tcp` is `TLS` and `ws` is `WSS`
+//! 2. Transports found by resolver (NAPTR/SRV) in order of preference,
+//!    unless host is numeric IP or port is explicit
+//! 3. `UDP` for SIP URI, `TLS` for SIPS URI
+//!
+//! Request that is bigger than `udp_size_limit` is sent over TCP instead of UDP
+//! ([rfc3261 section-18.1.1](https://tools.ietf.org/html/rfc3261#section-18.1.1)).
+
+use super::{via, Transport, TransportKind};
+use crate::engine::Datagram;
+use crate::{SipRequestUriScheme, SipUri};
+use std::fmt;
+use std::io;
+use std::time::Instant;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SelectError {
+    /// `transport` parameter of URI is unknown
+    UnknownTransport,
+    /// SIPS URI with transport that can't be secured. Ex: `transport=udp`
+    InsecureTransport,
+    /// Transport isn't added to selector
+    Unavailable(TransportKind),
+    /// Message is bigger than maximum size of transport
+    TooLarge,
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectError::UnknownTransport => f.write_str("Unknown transport parameter"),
+            SelectError::InsecureTransport => f.write_str("SIPS URI requires secure transport"),
+            SelectError::Unavailable(kind) => write!(f, "Transport {} is unavailable", kind),
+            SelectError::TooLarge => f.write_str("Message is too large for transport"),
+        }
+    }
+}
+
+impl std::error::Error for SelectError {}
+
+/// Set of transports of endpoint, one per kind
+pub struct TransportSelector {
+    transports: Vec<Box<dyn Transport>>,
+    udp_size_limit: usize,
+}
+
+impl Default for TransportSelector {
+    fn default() -> TransportSelector {
+        TransportSelector::new()
+    }
+}
+
+impl TransportSelector {
+    pub fn new() -> TransportSelector {
+        TransportSelector {
+            transports: vec![],
+            // path MTU is unknown
+            udp_size_limit: 1300,
+        }
+    }
+
+    /// Requests bigger than `limit` are sent over TCP if it is available.
+    /// 1300 bytes default, should be 200 bytes less than path MTU if it is known
+    pub fn with_udp_size_limit(mut self, limit: usize) -> TransportSelector {
+        self.udp_size_limit = limit;
+        self
+    }
+
+    /// Adds transport. Replaces transport of the same kind
+    pub fn add<T: Transport + 'static>(&mut self, transport: T) {
+        let kind = transport.kind();
+        self.transports.retain(|t| t.kind() != kind);
+        self.transports.push(Box::new(transport));
+    }
+
+    pub fn get(&self, kind: TransportKind) -> Option<&dyn Transport> {
+        self.transports
+            .iter()
+            .find(|t| t.kind() == kind)
+            .map(|t| t.as_ref())
+    }
+
+    pub fn get_mut(&mut self, kind: TransportKind) -> Option<&mut (dyn Transport + 'static)> {
+        self.transports
+            .iter_mut()
+            .find(|t| t.kind() == kind)
+            .map(|t| t.as_mut())
+    }
+
+    /// Transport for request to `uri` of `message_size` bytes.
+    /// `resolved` are transports of NAPTR/SRV records in order of preference
+    pub fn select(
+        &self,
+        uri: &SipUri,
+        message_size: usize,
+        resolved: &[TransportKind],
+    ) -> Result<TransportKind, SelectError> {
+        let secure = uri.scheme == SipRequestUriScheme::SIPS;
+        let param = uri
+            .params()
+            .and_then(|params| params.get("transport"))
+            .and_then(|value| *value);
+        let kind = if let Some(param) = param {
+            let kind = TransportKind::parse(param).ok_or(SelectError::UnknownTransport)?;
+            if secure {
+                secure_variant(kind).ok_or(SelectError::InsecureTransport)?
+            } else {
+                kind
+            }
+        } else if resolved.is_empty()
+            || via::parse_ip(uri.hostport.host).is_some()
+            || uri.hostport.port.is_some()
+        {
+            if secure {
+                TransportKind::Tls
+            } else {
+                TransportKind::Udp
+            }
+        } else {
+            let mut candidates = resolved
+                .iter()
+                .copied()
+                .filter(|kind| !secure || kind.is_secure())
+                .peekable();
+            let first = *candidates.peek().ok_or(SelectError::InsecureTransport)?;
+            candidates
+                .find(|kind| self.get(*kind).is_some())
+                .unwrap_or(first)
+        };
+
+        let kind = if kind == TransportKind::Udp
+            && message_size > self.udp_size_limit
+            && self.get(TransportKind::Tcp).is_some()
+        {
+            TransportKind::Tcp
+        } else {
+            kind
+        };
+        let transport = self.get(kind).ok_or(SelectError::Unavailable(kind))?;
+        if message_size > transport.max_message_size() {
+            return Err(SelectError::TooLarge);
+        }
+        Ok(kind)
+    }
+
+    /// Sends output of `Engine` by transport of datagram.
+    /// `domain` is passed to `Transport::send_message`
+    pub fn send(&mut self, datagram: &Datagram, domain: &str, now: Instant) -> io::Result<()> {
+        let transport = self.get_mut(datagram.transport).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                SelectError::Unavailable(datagram.transport),
+            )
+        })?;
+        transport.send_message(&datagram.data, datagram.destination, domain, now)
+    }
+}
+
+/// Transport that is secured by TLS for SIPS URI
+pub(crate) fn secure_variant(kind: TransportKind) -> Option<TransportKind> {
+    match kind {
+        TransportKind::Tcp | TransportKind::Tls => Some(TransportKind::Tls),
+        TransportKind::Ws | TransportKind::Wss => Some(TransportKind::Wss),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::net::SocketAddr;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<(TransportKind, SocketAddr, String)>>>;
+
+    struct Recorder {
+        kind: TransportKind,
+        sent: Log,
+    }
+
+    impl Transport for Recorder {
+        fn kind(&self) -> TransportKind {
+            self.kind
+        }
+
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            Ok("127.0.0.1:5060".parse().unwrap())
+        }
+
+        fn send_message(
+            &mut self,
+            _message: &[u8],
+            destination: SocketAddr,
+            domain: &str,
+            _now: Instant,
+        ) -> io::Result<()> {
+            self.sent
+                .borrow_mut()
+                .push((self.kind, destination, domain.to_owned()));
+            Ok(())
+        }
+
+        fn max_message_size(&self) -> usize {
+            if self.kind == TransportKind::Udp {
+                65535
+            } else {
+                4096
+            }
+        }
+    }
+
+    fn selector(kinds: &[TransportKind]) -> TransportSelector {
+        logged_selector(kinds, &Log::default())
+    }
+
+    fn logged_selector(kinds: &[TransportKind], log: &Log) -> TransportSelector {
+        let mut selector = TransportSelector::new();
+        for kind in kinds {
+            selector.add(Recorder {
+                kind: *kind,
+                sent: log.clone(),
+            });
+        }
+        selector
+    }
+
+    fn select(
+        selector: &TransportSelector,
+        uri: &str,
+        size: usize,
+        resolved: &[TransportKind],
+    ) -> Result<TransportKind, SelectError> {
+        let (_, uri) = SipUri::parse(uri.as_bytes()).unwrap();
+        selector.select(&uri, size, resolved)
+    }
+
+    #[test]
+    fn select_test() {
+        use TransportKind::*;
+        let all = selector(&[Udp, Tcp, Tls, Ws, Wss]);
+        assert_eq!(select(&all, "sip:bob@biloxi.com", 500, &[]), Ok(Udp));
+        assert_eq!(select(&all, "sips:bob@biloxi.com", 500, &[]), Ok(Tls));
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com;transport=tcp", 500, &[]),
+            Ok(Tcp)
+        );
+        assert_eq!(
+            select(&all, "sips:bob@biloxi.com;transport=tcp", 500, &[]),
+            Ok(Tls)
+        );
+        assert_eq!(
+            select(&all, "sips:bob@biloxi.com;transport=ws", 500, &[]),
+            Ok(Wss)
+        );
+        assert_eq!(
+            select(&all, "sips:bob@biloxi.com;transport=udp", 500, &[]),
+            Err(SelectError::InsecureTransport)
+        );
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com;transport=xyz", 500, &[]),
+            Err(SelectError::UnknownTransport)
+        );
+
+        // resolver results are not used for numeric IP and explicit port
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com", 500, &[Tcp, Udp]),
+            Ok(Tcp)
+        );
+        assert_eq!(select(&all, "sip:bob@192.0.2.4", 500, &[Tcp, Udp]), Ok(Udp));
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com:5080", 500, &[Tcp, Udp]),
+            Ok(Udp)
+        );
+        assert_eq!(
+            select(&all, "sips:bob@biloxi.com", 500, &[Tcp, Wss, Tls]),
+            Ok(Wss)
+        );
+        assert_eq!(
+            select(&all, "sips:bob@biloxi.com", 500, &[Udp]),
+            Err(SelectError::InsecureTransport)
+        );
+        let udp_tcp = selector(&[Udp, Tcp]);
+        assert_eq!(
+            select(&udp_tcp, "sip:bob@biloxi.com", 500, &[Sctp, Udp]),
+            Ok(Udp)
+        );
+        assert_eq!(
+            select(&udp_tcp, "sips:bob@biloxi.com", 500, &[]),
+            Err(SelectError::Unavailable(Tls))
+        );
+
+        // large requests
+        assert_eq!(select(&all, "sip:bob@biloxi.com", 1301, &[]), Ok(Tcp));
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com;transport=udp", 1301, &[]),
+            Ok(Tcp)
+        );
+        let all = all.with_udp_size_limit(1000);
+        assert_eq!(select(&all, "sip:bob@biloxi.com", 1001, &[]), Ok(Tcp));
+        assert_eq!(
+            select(&all, "sip:bob@biloxi.com", 5000, &[]),
+            Err(SelectError::TooLarge)
+        );
+        let udp = selector(&[Udp]);
+        assert_eq!(select(&udp, "sip:bob@biloxi.com", 5000, &[]), Ok(Udp));
+    }
+
+    #[test]
+    fn send_test() {
+        let log = Log::default();
+        let mut selector = logged_selector(&[TransportKind::Udp, TransportKind::Tls], &log);
+        let destination: SocketAddr = "192.0.2.4:5061".parse().unwrap();
+        let mut datagram = Datagram {
+            data: b"OPTIONS".to_vec(),
+            destination,
+            transport: TransportKind::Tls,
+            transaction: None,
+        };
+        selector
+            .send(&datagram, "biloxi.com", Instant::now())
+            .unwrap();
+        assert_eq!(
+            *log.borrow(),
+            vec![(TransportKind::Tls, destination, "biloxi.com".to_owned())]
+        );
+        assert!(selector.get(TransportKind::Tls).unwrap().is_secure());
+        datagram.transport = TransportKind::Tcp;
+        let error = selector
+            .send(&datagram, "biloxi.com", Instant::now())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
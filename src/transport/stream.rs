@@ -0,0 +1,586 @@
+//! Common parts of connection-oriented transports.
+//!
+//! Messages are framed by Content-Length
+//! [rfc3261 section-18.3](https://tools.ietf.org/html/rfc3261#section-18.3),
+//! WebSocket uses own `Codec` with frames.
+//! Connections are non-blocking and driven by `poll` of transport,
+//! outbound connections are established in background too
+
+use super::ws::is_invalid_host;
+use super::{network_limits, via, TransportKind};
+use crate::{ParseOptions, SipHeaderTagType, SipMessage, SipRFCHeader, SipRequest};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::str;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StreamSettings {
+    /// Connection without traffic is closed after this time. 5min default
+    pub idle_timeout: Duration,
+    /// Maximum size of message. Connection is closed if peer sends bigger message. 64KiB default
+    pub max_message_size: usize,
+    /// Timeout of establishing of outbound connection. 10s default
+    pub connect_timeout: Duration,
+    /// Parsing of received messages. Messages that can't be parsed or exceed limits
+    /// are dropped. Strict mode with `network_limits` default
+    pub parse_options: ParseOptions,
+}
+
+impl Default for StreamSettings {
+    fn default() -> StreamSettings {
+        StreamSettings {
+            idle_timeout: Duration::from_secs(300),
+            max_message_size: 65535,
+            connect_timeout: Duration::from_secs(10),
+            parse_options: ParseOptions::strict().with_limits(network_limits()),
+        }
+    }
+}
+
+/// Result of `poll` of connection-oriented transport
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// Received message. Top Via of request has `received` parameter
+    Message { data: Vec<u8>, source: SocketAddr },
+    /// Connection was closed by peer or failed. Error `TimedOut` means that
+    /// connection wasn't established in time or was idle.
+    /// Should be passed to `Engine::handle_connection_failure`
+    Closed {
+        peer: SocketAddr,
+        error: Option<io::Error>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FramingError {
+    /// Message is bigger than `max_message_size`
+    TooLarge,
+    /// Content-Length is mandatory for stream transports
+    MissingContentLength,
+    BadContentLength,
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            FramingError::TooLarge => "message is too large",
+            FramingError::MissingContentLength => "message has no Content-Length",
+            FramingError::BadContentLength => "Content-Length is invalid",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for FramingError {}
+
+impl From<FramingError> for io::Error {
+    fn from(err: FramingError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Splits byte stream into messages
+/// ```rust
+/// use sipcore::transport::stream::StreamFramer;
+///
+/// let mut framer = StreamFramer::new(1024);
+/// framer.push(b"\r\n\r\nOPTIONS sip:carol@chicago.com SIP/2.0\r\nl: 4\r\n\r\nbo");
+/// assert_eq!(framer.next_message(), Ok(None));
+/// framer.push(b"dyBYE");
+/// assert_eq!(
+///     framer.next_message(),
+///     Ok(Some(b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nl: 4\r\n\r\nbody".to_vec()))
+/// );
+/// assert_eq!(framer.next_message(), Ok(None));
+/// ```
+#[derive(Debug)]
+pub struct StreamFramer {
+    buf: Vec<u8>,
+    max_message_size: usize,
+}
+
+impl StreamFramer {
+    pub fn new(max_message_size: usize) -> StreamFramer {
+        StreamFramer {
+            buf: Vec::new(),
+            max_message_size,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Takes next complete message. CRLF keep-alives between messages are skipped
+    pub fn next_message(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        let keepalive = self
+            .buf
+            .iter()
+            .take_while(|c| **c == b'\r' || **c == b'\n')
+            .count();
+        self.buf.drain(..keepalive);
+
+        let headers_end = match find(&self.buf, b"\r\n\r\n") {
+            Some(pos) => pos + 4,
+            None if self.buf.len() > self.max_message_size => return Err(FramingError::TooLarge),
+            None => return Ok(None),
+        };
+        let body_len = content_length(&self.buf[..headers_end])?;
+        let message_len = headers_end
+            .checked_add(body_len)
+            .ok_or(FramingError::TooLarge)?;
+        if message_len > self.max_message_size {
+            return Err(FramingError::TooLarge);
+        }
+        if self.buf.len() < message_len {
+            return Ok(None);
+        }
+        let rest = self.buf.split_off(message_len);
+        Ok(Some(std::mem::replace(&mut self.buf, rest)))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Value of Content-Length header or its compact form `l`
+fn content_length(head: &[u8]) -> Result<usize, FramingError> {
+    for line in head.split(|c| *c == b'\n').skip(1) {
+        let colon = match line.iter().position(|c| *c == b':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+        let name = str::from_utf8(&line[..colon]).unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("Content-Length") || name.eq_ignore_ascii_case("l") {
+            return str::from_utf8(&line[colon + 1..])
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .ok_or(FramingError::BadContentLength);
+        }
+    }
+    Err(FramingError::MissingContentLength)
+}
+
+/// Starts non-blocking connect. Messages can be sent at once, they are
+/// written when connection is established. Refused connection is reported
+/// by `poll` of transport
+pub(crate) fn connect(destination: SocketAddr) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(destination),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.set_nonblocking(true)?;
+    socket.set_nodelay(true)?;
+    match socket.connect(&destination.into()) {
+        Ok(()) => {}
+        #[cfg(unix)]
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+        Err(e) => return Err(e),
+    }
+    Ok(socket.into())
+}
+
+/// Byte stream of connection
+pub(crate) trait Stream: Read + Write {
+    fn tcp(&self) -> &TcpStream;
+
+    /// Certificate of peer is validated. Only such peers can register aliases
+    /// [rfc5923 section-10](https://tools.ietf.org/html/rfc5923#section-10)
+    fn is_authenticated(&self) -> bool {
+        false
+    }
+}
+
+impl Stream for TcpStream {
+    fn tcp(&self) -> &TcpStream {
+        self
+    }
+}
+
+/// Converts messages to bytes of stream and back
+pub(crate) trait Codec {
+    /// Called once when connection is created. Ex: handshake of WebSocket client
+    fn start(&mut self, _outbound: &mut Vec<u8>) {}
+
+    /// Writes message to `outbound`
+    fn encode(&mut self, message: &[u8], outbound: &mut Vec<u8>);
+
+    /// Takes received bytes and returns complete messages.
+    /// Answers to control data are written to `outbound`
+    fn decode(&mut self, data: &[u8], outbound: &mut Vec<u8>) -> io::Result<Vec<Vec<u8>>>;
+
+    /// Connection doesn't read more at once
+    fn max_message_size(&self) -> usize;
+}
+
+impl Codec for StreamFramer {
+    fn encode(&mut self, message: &[u8], outbound: &mut Vec<u8>) {
+        outbound.extend_from_slice(message);
+    }
+
+    fn decode(&mut self, data: &[u8], _outbound: &mut Vec<u8>) -> io::Result<Vec<Vec<u8>>> {
+        self.push(data);
+        let mut messages = vec![];
+        while let Some(message) = self.next_message()? {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+}
+
+pub(crate) struct Connection<S, C = StreamFramer> {
+    stream: S,
+    codec: C,
+    outbound: Vec<u8>,
+    last_activity: Instant,
+    /// Outbound connection is closed if it isn't established till this time
+    connect_deadline: Option<Instant>,
+}
+
+impl<S: Stream, C: Codec> Connection<S, C> {
+    pub(crate) fn new(stream: S, mut codec: C, now: Instant) -> Connection<S, C> {
+        let mut outbound = Vec::new();
+        codec.start(&mut outbound);
+        Connection {
+            stream,
+            codec,
+            outbound,
+            last_activity: now,
+            connect_deadline: None,
+        }
+    }
+
+    /// Connection that is opened by `connect` and must be established in `connect_timeout`
+    pub(crate) fn outbound(
+        stream: S,
+        codec: C,
+        now: Instant,
+        connect_timeout: Duration,
+    ) -> Connection<S, C> {
+        let mut connection = Connection::new(stream, codec, now);
+        connection.connect_deadline = Some(now + connect_timeout);
+        connection
+    }
+
+    pub(crate) fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Queues message and writes as much as socket accepts
+    pub(crate) fn send(&mut self, message: &[u8], now: Instant) -> io::Result<()> {
+        self.codec.encode(message, &mut self.outbound);
+        self.last_activity = now;
+        self.flush()
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        while !self.outbound.is_empty() {
+            match self.stream.write(&self.outbound) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outbound.drain(..written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match self.stream.flush() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Checks that outbound connection is established. Error `TimedOut` after `connect_timeout`
+    fn check_connected(&mut self, now: Instant) -> io::Result<()> {
+        let deadline = match self.connect_deadline {
+            Some(deadline) => deadline,
+            None => return Ok(()),
+        };
+        if self.stream.tcp().peer_addr().is_ok() {
+            self.connect_deadline = None;
+            return Ok(());
+        }
+        if let Some(error) = self.stream.tcp().take_error()? {
+            return Err(error);
+        }
+        if deadline <= now {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        Ok(())
+    }
+
+    /// Reads what is available, but not much more than `max_message_size` of codec,
+    /// the rest is read by next call. Returns complete messages.
+    /// Error `UnexpectedEof` means that peer closed connection
+    pub(crate) fn read(&mut self, now: Instant) -> io::Result<Vec<Vec<u8>>> {
+        let mut data = vec![];
+        let mut buf = [0; 4096];
+        let closed = loop {
+            if data.len() > self.codec.max_message_size() {
+                break false;
+            }
+            match self.stream.read(&mut buf) {
+                Ok(0) => break true,
+                Ok(len) => {
+                    data.extend_from_slice(&buf[..len]);
+                    self.last_activity = now;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        let decoded = self.codec.decode(&data, &mut self.outbound);
+        // answers and error responses are sent before connection is closed
+        let flushed = self.flush();
+        let messages = decoded?;
+        flushed?;
+        if closed {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(messages)
+    }
+}
+
+/// Connections keyed by remote address. Aliases of
+/// [rfc5923](https://tools.ietf.org/html/rfc5923) point to existing connections
+pub(crate) struct ConnectionTable<S, C = StreamFramer> {
+    transport: TransportKind,
+    connections: HashMap<SocketAddr, Connection<S, C>>,
+    aliases: HashMap<SocketAddr, SocketAddr>,
+    /// `.invalid` hosts of Contact of WebSocket clients
+    /// [rfc7118 section-5.2](https://tools.ietf.org/html/rfc7118#section-5.2)
+    host_aliases: HashMap<String, SocketAddr>,
+}
+
+impl<S: Stream, C: Codec> ConnectionTable<S, C> {
+    pub(crate) fn new(transport: TransportKind) -> ConnectionTable<S, C> {
+        ConnectionTable {
+            transport,
+            connections: HashMap::new(),
+            aliases: HashMap::new(),
+            host_aliases: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub(crate) fn insert(&mut self, peer: SocketAddr, connection: Connection<S, C>) {
+        self.connections.insert(peer, connection);
+    }
+
+    /// Connection to address or to its alias
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub(crate) fn get(&self, addr: SocketAddr) -> Option<&Connection<S, C>> {
+        self.connections.get(&self.peer(addr)?)
+    }
+
+    /// Remote address of connection that can be used for sending to `addr`
+    pub(crate) fn peer(&self, addr: SocketAddr) -> Option<SocketAddr> {
+        if self.connections.contains_key(&addr) {
+            return Some(addr);
+        }
+        self.aliases
+            .get(&addr)
+            .copied()
+            .filter(|peer| self.connections.contains_key(peer))
+    }
+
+    /// Connection of client that uses `.invalid` host in Contact
+    pub(crate) fn peer_for_host(&self, host: &str) -> Option<SocketAddr> {
+        self.host_aliases.get(&host.to_ascii_lowercase()).copied()
+    }
+
+    pub(crate) fn remove(&mut self, peer: SocketAddr) -> Option<Connection<S, C>> {
+        self.aliases.retain(|_, aliased| *aliased != peer);
+        self.host_aliases.retain(|_, aliased| *aliased != peer);
+        self.connections.remove(&peer)
+    }
+
+    pub(crate) fn peers(&self) -> Vec<SocketAddr> {
+        self.connections.keys().copied().collect()
+    }
+
+    /// Sends message over existing connection. Failed connection is removed
+    pub(crate) fn send(
+        &mut self,
+        message: &[u8],
+        addr: SocketAddr,
+        now: Instant,
+    ) -> io::Result<()> {
+        let peer = self.peer(addr).ok_or(io::ErrorKind::NotConnected)?;
+        let result = match self.connections.get_mut(&peer) {
+            Some(connection) => connection.send(message, now),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        };
+        if result.is_err() {
+            self.remove(peer);
+        }
+        result
+    }
+
+    /// Flushes, reads all connections and closes idle ones and ones that weren't established
+    pub(crate) fn poll(&mut self, settings: &StreamSettings, now: Instant) -> Vec<StreamEvent> {
+        let mut events = vec![];
+        for peer in self.peers() {
+            let connection = match self.connections.get_mut(&peer) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            let result = connection
+                .check_connected(now)
+                .and_then(|_| connection.flush())
+                .and_then(|_| connection.read(now))
+                .map(|messages| {
+                    let idle = connection.outbound.is_empty()
+                        && connection.last_activity + settings.idle_timeout <= now;
+                    (messages, idle)
+                });
+            match result {
+                Ok((messages, idle)) => {
+                    for message in messages {
+                        let options = &settings.parse_options;
+                        if let Some(data) = self.accept_message(message, peer, options) {
+                            events.push(StreamEvent::Message { data, source: peer });
+                        }
+                    }
+                    if idle {
+                        self.remove(peer);
+                        events.push(StreamEvent::Closed {
+                            peer,
+                            error: Some(io::ErrorKind::TimedOut.into()),
+                        });
+                    }
+                }
+                Err(e) => {
+                    self.remove(peer);
+                    let error = match e.kind() {
+                        io::ErrorKind::UnexpectedEof => None,
+                        _ => Some(e),
+                    };
+                    events.push(StreamEvent::Closed { peer, error });
+                }
+            }
+        }
+        events
+    }
+
+    /// Adds `received` to request and registers `alias` of its top Via if peer is
+    /// authenticated. Existing alias isn't replaced. `.invalid` host of Contact
+    /// of WebSocket client is registered too. Messages that can't be parsed
+    /// with `options` are dropped
+    fn accept_message(
+        &mut self,
+        message: Vec<u8>,
+        peer: SocketAddr,
+        options: &ParseOptions,
+    ) -> Option<Vec<u8>> {
+        let request = match SipMessage::parse_ext(&message, options).ok()? {
+            (_, SipMessage::Request(request)) => request,
+            (_, SipMessage::Response(_)) => return Some(message),
+        };
+        let authenticated = self
+            .connections
+            .get(&peer)
+            .is_some_and(|connection| connection.stream.is_authenticated());
+        if let Some(alias) = alias(&request, self.transport).filter(|_| authenticated) {
+            self.aliases.entry(alias).or_insert(peer);
+        }
+        if self.transport == TransportKind::Ws || self.transport == TransportKind::Wss {
+            if let Some(host) = invalid_contact_host(&request) {
+                self.host_aliases.insert(host, peer);
+            }
+        }
+        via::add_received_parsed(&message, &request, peer)
+    }
+}
+
+/// `sent-by` of top Via with `alias` parameter
+fn alias(request: &SipRequest, transport: TransportKind) -> Option<SocketAddr> {
+    let via = request.headers.get_rfc(SipRFCHeader::Via)?.front()?;
+    via.params()?.get("alias")?;
+    let tags = via.value.tags()?;
+    let host = str::from_utf8(tags.get(&SipHeaderTagType::Host)?).ok()?;
+    let ip = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok()?;
+    let port = match tags.get(&SipHeaderTagType::Port) {
+        Some(port) => str::from_utf8(port).ok()?.parse::<u16>().ok()?,
+        None => transport.default_port(),
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Host of Contact that ends with `.invalid`, in lower case
+fn invalid_contact_host(request: &SipRequest) -> Option<String> {
+    let contact = request.headers.get_rfc_s(SipRFCHeader::Contact)?;
+    let host = contact.value.sip_uri()?.hostport.host;
+    if is_invalid_host(host) {
+        Some(host.trim_end_matches('.').to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framer_test() {
+        let message = "SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let mut framer = StreamFramer::new(64);
+        framer.push(message.as_bytes());
+        framer.push(message.as_bytes());
+        assert_eq!(framer.next_message(), Ok(Some(message.as_bytes().to_vec())));
+        assert_eq!(framer.next_message(), Ok(Some(message.as_bytes().to_vec())));
+        assert_eq!(framer.next_message(), Ok(None));
+
+        framer.push(b"SIP/2.0 200 OK\r\nContent-Length: 100\r\n\r\n");
+        assert_eq!(framer.next_message(), Err(FramingError::TooLarge));
+
+        let mut framer = StreamFramer::new(64);
+        framer.push(b"SIP/2.0 200 OK\r\nCSeq: 1 BYE\r\n\r\n");
+        assert_eq!(
+            framer.next_message(),
+            Err(FramingError::MissingContentLength)
+        );
+
+        let mut framer = StreamFramer::new(64);
+        framer.push(&[b'a'; 65]);
+        assert_eq!(framer.next_message(), Err(FramingError::TooLarge));
+    }
+
+    #[test]
+    fn alias_test() {
+        let request = "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+Via: SIP/2.0/TCP 192.0.2.1:5062;alias;branch=z9hG4bK1\r\n\
+Content-Length: 0\r\n\r\n";
+        let (_, parsed) = SipRequest::parse(request.as_bytes()).unwrap();
+        assert_eq!(
+            alias(&parsed, TransportKind::Tcp),
+            "192.0.2.1:5062".parse().ok()
+        );
+        let request = request.replace(";alias", "");
+        let (_, parsed) = SipRequest::parse(request.as_bytes()).unwrap();
+        assert_eq!(alias(&parsed, TransportKind::Tcp), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! TCP transport of [rfc3261 section-18](https://tools.ietf.org/html/rfc3261#section-18)
+//!
+//! Responses are sent back over connection where request was received.
+//! New connection is opened only if there is no suitable one. Peers of TCP
+//! aren't authenticated, so `alias` parameter of Via
+//! ([rfc5923](https://tools.ietf.org/html/rfc5923)) isn't accepted from them.
+//!
+//! Transport is non-blocking. `poll` should be called periodically,
+//! it accepts connections, reads messages and closes idle connections.
+
+use super::stream::{
+    connect, Connection, ConnectionTable, StreamEvent, StreamFramer, StreamSettings,
+};
+use super::{Transport, TransportKind};
+use crate::engine::Datagram;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Instant;
+
+pub struct TcpTransport {
+    listener: TcpListener,
+    settings: StreamSettings,
+    connections: ConnectionTable<TcpStream>,
+}
+
+impl TcpTransport {
+    pub fn bind<A: ToSocketAddrs>(addr: A, settings: StreamSettings) -> io::Result<TcpTransport> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpTransport {
+            listener,
+            settings,
+            connections: ConnectionTable::new(TransportKind::Tcp),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn settings(&self) -> &StreamSettings {
+        &self.settings
+    }
+
+    /// Number of open connections
+    pub fn connections_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Remote address of connection that would be used for sending to `destination`
+    pub fn connection_for(&self, destination: SocketAddr) -> Option<SocketAddr> {
+        self.connections.peer(destination)
+    }
+
+    /// Sends message over existing connection or opens new one.
+    /// Error means transport error for transaction of message. Connection that
+    /// can't be established is reported by `poll` as closed connection
+    pub fn send_to(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        now: Instant,
+    ) -> io::Result<()> {
+        if self.connections.peer(destination).is_none() {
+            let connection = Connection::outbound(
+                connect(destination)?,
+                StreamFramer::new(self.settings.max_message_size),
+                now,
+                self.settings.connect_timeout,
+            );
+            self.connections.insert(destination, connection);
+        }
+        self.connections.send(message, destination, now)
+    }
+
+    /// Sends output of `Engine`
+    pub fn send(&mut self, datagram: &Datagram, now: Instant) -> io::Result<()> {
+        if datagram.transport != TransportKind::Tcp {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram is not for TCP transport",
+            ));
+        }
+        self.send_to(&datagram.data, datagram.destination, now)
+    }
+
+    /// Closes connection
+    pub fn close(&mut self, peer: SocketAddr) {
+        if let Some(connection) = self.connections.remove(peer) {
+            let _ = connection.stream().shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Accepts new connections, reads received messages and closes idle connections
+    pub fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_nonblocking(true)?;
+                    let connection = Connection::new(
+                        stream,
+                        StreamFramer::new(self.settings.max_message_size),
+                        now,
+                    );
+                    self.connections.insert(peer, connection);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.connections.poll(&self.settings, now))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Tcp
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpTransport::local_addr(self)
+    }
+
+    fn send_message(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        _domain: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        self.send_to(message, destination, now)
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.settings.max_message_size
+    }
+}
@@ -0,0 +1,569 @@
+//! TLS transport of [rfc3261 section-26.2](https://tools.ietf.org/html/rfc3261#section-26.2).
+//! Requires `tls` feature.
+//!
+//! Certificate of server is validated against SIP domain by rules of
+//! [rfc5922 section-7](https://tools.ietf.org/html/rfc5922#section-7):
+//! SIP URIs without user part of subjectAltName are compared with domain,
+//! dNSNames only if there are no URIs. Wildcards and Common Name are not used.
+//!
+//! Connections are managed like in `TcpTransport`, SIP domain is used only
+//! when new connection is opened.
+
+use super::stream::{
+    connect, Connection, ConnectionTable, Stream, StreamEvent, StreamFramer, StreamSettings,
+};
+use super::{Transport, TransportKind};
+use crate::engine::Datagram;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore,
+    ServerConfig, ServerConnection, SignatureScheme, StreamOwned,
+};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Certificates of clients that are requested by server
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ClientAuth {
+    Disabled,
+    /// Clients without certificate are accepted
+    Optional,
+    Required,
+}
+
+/// Configuration of client and server sides of TLS transport
+#[derive(Clone)]
+pub struct TlsConfig {
+    client: Arc<ClientConfig>,
+    server: Option<Arc<ServerConfig>>,
+}
+
+impl TlsConfig {
+    /// `roots` are CAs that are trusted for peers. `cert_chain` with `key`
+    /// identify this endpoint as server and as client
+    pub fn new(
+        roots: RootCertStore,
+        cert_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        client_auth: ClientAuth,
+    ) -> Result<TlsConfig, rustls::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let roots = Arc::new(roots);
+        let client = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(SipDomainVerifier::new(roots.clone(), &provider)?)
+            .with_client_auth_cert(cert_chain.clone(), key.clone_key())?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match client_auth {
+            ClientAuth::Disabled => builder.with_no_client_auth(),
+            _ => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots, provider.clone());
+                let verifier = match client_auth {
+                    ClientAuth::Optional => verifier.allow_unauthenticated(),
+                    _ => verifier,
+                };
+                let verifier = verifier
+                    .build()
+                    .map_err(|e| rustls::Error::General(e.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let server = builder.with_single_cert(cert_chain, key)?;
+        Ok(TlsConfig {
+            client: Arc::new(client),
+            server: Some(Arc::new(server)),
+        })
+    }
+
+    /// Endpoint without certificate. Inbound connections are not accepted
+    pub fn client_only(roots: RootCertStore) -> Result<TlsConfig, rustls::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(SipDomainVerifier::new(Arc::new(roots), &provider)?)
+            .with_no_client_auth();
+        Ok(TlsConfig {
+            client: Arc::new(client),
+            server: None,
+        })
+    }
+
+    /// Client side of connection, certificate of server must be valid for SIP `domain`
+    pub(crate) fn connect(&self, domain: &str, stream: TcpStream) -> io::Result<TlsStream> {
+        let server_name = ServerName::try_from(domain.to_owned())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let tls = ClientConnection::new(self.client.clone(), server_name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(TlsStream::Client(StreamOwned::new(tls, stream)))
+    }
+
+    /// Server side of accepted connection. None if server identity isn't configured
+    pub(crate) fn accept(&self, stream: TcpStream) -> io::Result<Option<TlsStream>> {
+        let server = match &self.server {
+            Some(server) => server.clone(),
+            None => return Ok(None),
+        };
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        let tls = ServerConnection::new(server).map_err(io::Error::other)?;
+        Ok(Some(TlsStream::Server(StreamOwned::new(tls, stream))))
+    }
+}
+
+/// Chain is validated by webpki, name is validated by rfc5922 rules
+#[derive(Debug)]
+struct SipDomainVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl SipDomainVerifier {
+    fn new(
+        roots: Arc<RootCertStore>,
+        provider: &Arc<rustls::crypto::CryptoProvider>,
+    ) -> Result<Arc<SipDomainVerifier>, rustls::Error> {
+        let inner = WebPkiServerVerifier::builder_with_provider(roots, provider.clone())
+            .build()
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        Ok(Arc::new(SipDomainVerifier { inner }))
+    }
+}
+
+impl ServerCertVerifier for SipDomainVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        );
+        let domain = match server_name {
+            ServerName::DnsName(domain) => domain.as_ref(),
+            _ => return result,
+        };
+        match result {
+            Ok(_)
+            | Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName))
+            | Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForNameContext {
+                ..
+            })) => {}
+            Err(e) => return Err(e),
+        }
+        if is_valid_for_domain(end_entity, domain) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Checks that certificate identifies SIP domain. dNSNames are used only
+/// if there are no URIs in subjectAltName.
+/// [rfc5922 section-7.1](https://tools.ietf.org/html/rfc5922#section-7.1)
+pub fn is_valid_for_domain(certificate: &CertificateDer, domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+    let domain = domain
+        .strip_prefix('[')
+        .and_then(|domain| domain.strip_suffix(']'))
+        .unwrap_or(domain);
+    let names = subject_alt_names(certificate.as_ref()).unwrap_or_default();
+    let has_uri = names
+        .iter()
+        .any(|name| matches!(name, SubjectAltName::Uri(_)));
+    names.iter().any(|name| match name {
+        SubjectAltName::Dns(name) => {
+            !has_uri && name.trim_end_matches('.').eq_ignore_ascii_case(domain)
+        }
+        SubjectAltName::Uri(uri) => {
+            sip_uri_domain(uri).is_some_and(|host| host.eq_ignore_ascii_case(domain))
+        }
+    })
+}
+
+/// Host of `sip:` URI without user part, port and parameters.
+/// IPv6 reference is returned without brackets
+fn sip_uri_domain(uri: &str) -> Option<&str> {
+    let rest = match uri.get(..4) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("sip:") => &uri[4..],
+        _ => return None,
+    };
+    if rest.contains('@') {
+        return None;
+    }
+    if let Some(reference) = rest.strip_prefix('[') {
+        return reference.split_once(']').map(|(host, _)| host);
+    }
+    rest.split(&[';', '?', ':'][..]).next()
+}
+
+#[derive(PartialEq, Debug)]
+enum SubjectAltName<'a> {
+    Dns(&'a str),
+    Uri(&'a str),
+}
+
+/// OID 2.5.29.17
+const SUBJECT_ALT_NAME_OID: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// dNSName and uniformResourceIdentifier of subjectAltName extension
+fn subject_alt_names(certificate: &[u8]) -> Option<Vec<SubjectAltName<'_>>> {
+    let (_, certificate, _) = der_take(certificate)?;
+    let (_, mut tbs, _) = der_take(certificate)?;
+    let mut extensions = None;
+    while !tbs.is_empty() {
+        let (tag, value, rest) = der_take(tbs)?;
+        // extensions [3] EXPLICIT
+        if tag == 0xa3 {
+            extensions = Some(der_take(value)?.1);
+        }
+        tbs = rest;
+    }
+
+    let mut extensions = extensions?;
+    let mut names = vec![];
+    while !extensions.is_empty() {
+        let (_, extension, rest) = der_take(extensions)?;
+        extensions = rest;
+        let (_, oid, mut extension) = der_take(extension)?;
+        if oid != SUBJECT_ALT_NAME_OID {
+            continue;
+        }
+        let (mut tag, mut value, _) = der_take(extension)?;
+        // critical BOOLEAN DEFAULT FALSE
+        if tag == 0x01 {
+            extension = der_take(extension)?.2;
+            let (next_tag, next_value, _) = der_take(extension)?;
+            tag = next_tag;
+            value = next_value;
+        }
+        if tag != 0x04 {
+            return None;
+        }
+        let (_, mut general_names, _) = der_take(value)?;
+        while !general_names.is_empty() {
+            let (tag, value, rest) = der_take(general_names)?;
+            general_names = rest;
+            let value = match std::str::from_utf8(value) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            match tag {
+                0x82 => names.push(SubjectAltName::Dns(value)),
+                0x86 => names.push(SubjectAltName::Uri(value)),
+                _ => {}
+            }
+        }
+    }
+    Some(names)
+}
+
+/// Takes DER element. Returns tag, value and rest of input
+fn der_take(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *input.first()?;
+    let first = *input.get(1)? as usize;
+    let (len, header_len) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let len_bytes = first & 0x7f;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + len_bytes)?;
+        let len = bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, 2 + len_bytes)
+    };
+    let end = header_len.checked_add(len)?;
+    let value = input.get(header_len..end)?;
+    Some((tag, value, &input[end..]))
+}
+
+pub(crate) enum TlsStream {
+    Client(StreamOwned<ClientConnection, TcpStream>),
+    Server(StreamOwned<ServerConnection, TcpStream>),
+}
+
+impl Stream for TlsStream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            TlsStream::Client(stream) => &stream.sock,
+            TlsStream::Server(stream) => &stream.sock,
+        }
+    }
+
+    /// Certificate of server is always validated, client may be anonymous
+    /// if `ClientAuth` isn't `Required`
+    fn is_authenticated(&self) -> bool {
+        match self {
+            TlsStream::Client(stream) => !stream.conn.is_handshaking(),
+            TlsStream::Server(stream) => {
+                !stream.conn.is_handshaking() && stream.conn.peer_certificates().is_some()
+            }
+        }
+    }
+}
+
+impl TlsStream {
+    fn peer_certificates(&self) -> Option<&[CertificateDer<'static>]> {
+        match self {
+            TlsStream::Client(stream) => stream.conn.peer_certificates(),
+            TlsStream::Server(stream) => stream.conn.peer_certificates(),
+        }
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            TlsStream::Client(stream) => stream.read(buf),
+            TlsStream::Server(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            TlsStream::Client(stream) => stream.write(buf),
+            TlsStream::Server(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            TlsStream::Client(stream) => stream.flush(),
+            TlsStream::Server(stream) => stream.flush(),
+        }
+    }
+}
+
+pub struct TlsTransport {
+    listener: TcpListener,
+    settings: StreamSettings,
+    config: TlsConfig,
+    connections: ConnectionTable<TlsStream>,
+}
+
+impl TlsTransport {
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        settings: StreamSettings,
+        config: TlsConfig,
+    ) -> io::Result<TlsTransport> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TlsTransport {
+            listener,
+            settings,
+            config,
+            connections: ConnectionTable::new(TransportKind::Tls),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn settings(&self) -> &StreamSettings {
+        &self.settings
+    }
+
+    /// Number of open connections
+    pub fn connections_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Remote address of connection that would be used for sending to `destination`
+    pub fn connection_for(&self, destination: SocketAddr) -> Option<SocketAddr> {
+        self.connections.peer(destination)
+    }
+
+    /// Certificates that peer presented in handshake
+    pub fn peer_certificates(&self, peer: SocketAddr) -> Option<Vec<CertificateDer<'static>>> {
+        let connection = self.connections.get(peer)?;
+        Some(connection.stream().peer_certificates()?.to_vec())
+    }
+
+    /// Sends message over existing connection or opens new one, where
+    /// certificate of server must be valid for SIP `domain`.
+    /// Failed handshake is reported by `poll` as closed connection
+    pub fn send_to(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        domain: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        if self.connections.peer(destination).is_none() {
+            let stream = self.config.connect(domain, connect(destination)?)?;
+            let connection = Connection::outbound(
+                stream,
+                StreamFramer::new(self.settings.max_message_size),
+                now,
+                self.settings.connect_timeout,
+            );
+            self.connections.insert(destination, connection);
+        }
+        self.connections.send(message, destination, now)
+    }
+
+    /// Sends output of `Engine`
+    pub fn send(&mut self, datagram: &Datagram, domain: &str, now: Instant) -> io::Result<()> {
+        if datagram.transport != TransportKind::Tls {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram is not for TLS transport",
+            ));
+        }
+        self.send_to(&datagram.data, datagram.destination, domain, now)
+    }
+
+    /// Closes connection
+    pub fn close(&mut self, peer: SocketAddr) {
+        if let Some(connection) = self.connections.remove(peer) {
+            let _ = connection.stream().tcp().shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Accepts new connections, reads received messages and closes idle connections
+    pub fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        loop {
+            let (stream, peer) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            let stream = match self.config.accept(stream)? {
+                Some(stream) => stream,
+                None => continue,
+            };
+            let connection = Connection::new(
+                stream,
+                StreamFramer::new(self.settings.max_message_size),
+                now,
+            );
+            self.connections.insert(peer, connection);
+        }
+        Ok(self.connections.poll(&self.settings, now))
+    }
+}
+
+impl Transport for TlsTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Tls
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TlsTransport::local_addr(self)
+    }
+
+    fn send_message(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        domain: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        self.send_to(message, destination, domain, now)
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.settings.max_message_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::pem::PemObject;
+
+    #[test]
+    fn subject_alt_names_test() {
+        let biloxi =
+            CertificateDer::from_pem_slice(include_bytes!("../../tests/certs/biloxi.pem")).unwrap();
+        assert_eq!(
+            subject_alt_names(&biloxi),
+            Some(vec![
+                SubjectAltName::Uri("sip:biloxi.example.com"),
+                SubjectAltName::Uri("sip:admin@other.example.com"),
+                SubjectAltName::Dns("*.biloxi.example.com"),
+            ])
+        );
+        assert!(is_valid_for_domain(&biloxi, "BILOXI.example.com"));
+        assert!(!is_valid_for_domain(&biloxi, "other.example.com"));
+        assert!(!is_valid_for_domain(&biloxi, "proxy.biloxi.example.com"));
+
+        // dNSName is ignored when URI is present
+        let chicago =
+            CertificateDer::from_pem_slice(include_bytes!("../../tests/certs/chicago.pem"))
+                .unwrap();
+        assert_eq!(
+            subject_alt_names(&chicago),
+            Some(vec![
+                SubjectAltName::Uri("sip:chicago.example.com"),
+                SubjectAltName::Dns("proxy.example.com"),
+            ])
+        );
+        assert!(is_valid_for_domain(&chicago, "chicago.example.com"));
+        assert!(!is_valid_for_domain(&chicago, "proxy.example.com"));
+        let atlanta =
+            CertificateDer::from_pem_slice(include_bytes!("../../tests/certs/atlanta.pem"))
+                .unwrap();
+        assert!(is_valid_for_domain(&atlanta, "atlanta.example.com"));
+
+        let ca =
+            CertificateDer::from_pem_slice(include_bytes!("../../tests/certs/ca.pem")).unwrap();
+        assert_eq!(subject_alt_names(&ca), Some(vec![]));
+        assert_eq!(
+            sip_uri_domain("sip:example.com;transport=tls"),
+            Some("example.com")
+        );
+        assert_eq!(sip_uri_domain("sips:example.com"), None);
+        assert_eq!(
+            sip_uri_domain("sip:[2001:db8::1]:5061;transport=tls"),
+            Some("2001:db8::1")
+        );
+        assert_eq!(sip_uri_domain("sip:[2001:db8::1]"), Some("2001:db8::1"));
+        assert_eq!(sip_uri_domain("sip:[2001:db8::1"), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! UDP transport of [rfc3261 section-18](https://tools.ietf.org/html/rfc3261#section-18)
+//!
+//! ## Example
+//! ```rust
+//! use sipcore::transport::UdpTransport;
+//!
+//! let alice = UdpTransport::bind("127.0.0.1:0").unwrap();
+//! let bob = UdpTransport::bind("127.0.0.1:0").unwrap();
+//! let options = "OPTIONS sip:bob@127.0.0.1 SIP/2.0\r\n\
+//! Via: SIP/2.0/UDP client.atlanta.com;rport;branch=z9hG4bKhjhs8ass877\r\n\
+//! CSeq: 1 OPTIONS\r\n\r\n";
+//! alice
+//!     .send_to(options.as_bytes(), bob.local_addr().unwrap())
+//!     .unwrap();
+//!
+//! let (request, source) = bob.recv().unwrap();
+//! assert_eq!(source, alice.local_addr().unwrap());
+//! assert!(String::from_utf8(request).unwrap().contains(&format!(
+//!     ";rport={};branch=z9hG4bKhjhs8ass877;received=127.0.0.1\r\n",
+//!     source.port()
+//! )));
+//! ```
+
+use super::{network_limits, via, Transport, TransportKind};
+use crate::engine::Datagram;
+use crate::{ParseOptions, SipMessage, SipResponse};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Instant;
+
+/// Maximum size of UDP datagram
+pub const MAX_DATAGRAM_SIZE: usize = 65535;
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    parse_options: ParseOptions,
+}
+
+impl UdpTransport {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpTransport> {
+        Ok(UdpTransport::from_socket(UdpSocket::bind(addr)?))
+    }
+
+    pub fn from_socket(socket: UdpSocket) -> UdpTransport {
+        UdpTransport {
+            socket,
+            parse_options: ParseOptions::strict().with_limits(network_limits()),
+        }
+    }
+
+    /// Options of parsing of received datagrams. Default is strict mode with `network_limits`
+    pub fn with_parse_options(mut self, options: ParseOptions) -> UdpTransport {
+        self.parse_options = options;
+        self
+    }
+
+    /// Socket for setting of timeouts or non-blocking mode
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn send_to(&self, message: &[u8], destination: SocketAddr) -> io::Result<()> {
+        let sent = self.socket.send_to(message, destination)?;
+        if sent != message.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "datagram was truncated",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sends output of `Engine`
+    pub fn send(&self, datagram: &Datagram) -> io::Result<()> {
+        if datagram.transport != TransportKind::Udp {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram is not for UDP transport",
+            ));
+        }
+        self.send_to(&datagram.data, datagram.destination)
+    }
+
+    /// Sends response to address from top Via. Returns destination
+    pub fn send_response(&self, response: &[u8]) -> io::Result<SocketAddr> {
+        let destination = SipResponse::parse(response)
+            .ok()
+            .and_then(|(_, parsed)| via::response_destination(&parsed.headers, TransportKind::Udp))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "response has no Via with IP address",
+                )
+            })?;
+        self.send_to(response, destination)?;
+        Ok(destination)
+    }
+
+    /// Waits for next SIP message. Datagrams that aren't SIP messages or exceed
+    /// parse limits are dropped. Top Via of request gets `received` and `rport` parameters
+    pub fn recv(&self) -> io::Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, source) = self.socket.recv_from(&mut buf)?;
+            if let Some(message) = accept_datagram(&buf[..len], source, &self.parse_options) {
+                return Ok((message, source));
+            }
+        }
+    }
+}
+
+/// Validates received datagram. Returns message that is passed to `Engine`
+fn accept_datagram(datagram: &[u8], source: SocketAddr, options: &ParseOptions) -> Option<Vec<u8>> {
+    match SipMessage::parse_ext(datagram, options).ok()? {
+        (_, SipMessage::Request(request)) => via::add_received_parsed(datagram, &request, source),
+        (_, SipMessage::Response(_)) => Some(datagram.to_vec()),
+    }
+}
+
+impl Transport for UdpTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Udp
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpTransport::local_addr(self)
+    }
+
+    fn send_message(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        _domain: &str,
+        _now: Instant,
+    ) -> io::Result<()> {
+        self.send_to(message, destination)
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_DATAGRAM_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_datagram_test() {
+        let source = "192.0.2.1:5060".parse().unwrap();
+        let options = ParseOptions::strict().with_limits(network_limits());
+        assert_eq!(accept_datagram(b"\r\n\r\n", source, &options), None);
+        assert_eq!(
+            accept_datagram(
+                b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\r\n",
+                source,
+                &options
+            ),
+            None
+        );
+        let response = "SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP a.com\r\n\r\n";
+        assert_eq!(
+            accept_datagram(response.as_bytes(), source, &options),
+            Some(response.as_bytes().to_vec())
+        );
+
+        // extension method is passed to engine
+        let publish = "PUBLISH sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP 192.0.2.1;branch=z9hG4bK1\r\nCSeq: 1 PUBLISH\r\n\r\n";
+        assert_eq!(
+            accept_datagram(publish.as_bytes(), source, &options),
+            Some(publish.as_bytes().to_vec())
+        );
+        let custom = publish.replace("PUBLISH", "FOOBAR");
+        assert_eq!(
+            accept_datagram(custom.as_bytes(), source, &options),
+            Some(custom.into_bytes())
+        );
+
+        let long_uri = publish.replacen("bob@", &format!("{}@", "b".repeat(4096)), 1);
+        assert_eq!(accept_datagram(long_uri.as_bytes(), source, &options), None);
+        assert!(accept_datagram(long_uri.as_bytes(), source, &ParseOptions::strict()).is_some());
+    }
+}
@@ -0,0 +1,198 @@
+//! Top Via handling of [rfc3261 section-18.2](https://tools.ietf.org/html/rfc3261#section-18.2)
+//! and [rfc3581](https://tools.ietf.org/html/rfc3581)
+
+use super::TransportKind;
+use crate::{SipHeader, SipHeaderTagType, SipHeaders, SipRFCHeader, SipRequest};
+use std::net::{IpAddr, SocketAddr};
+use std::str;
+
+/// Adds `received` to top Via of request when `sent-by` differs from source address
+/// and fills empty `rport` parameter with source port.
+/// Returns `None` if request can't be parsed or has no Via
+/// ```rust
+/// use sipcore::transport::via::add_received;
+///
+/// let request = add_received(
+///     "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+///      Via: SIP/2.0/UDP 10.1.1.1:4540;rport;branch=z9hG4bKkjshdyff\r\n\r\n"
+///         .as_bytes(),
+///     "192.0.2.1:9988".parse().unwrap(),
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     request,
+///     "OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+///      Via: SIP/2.0/UDP 10.1.1.1:4540;rport=9988;branch=z9hG4bKkjshdyff;received=192.0.2.1\r\n\r\n"
+///         .as_bytes()
+/// );
+/// ```
+pub fn add_received(request: &[u8], source: SocketAddr) -> Option<Vec<u8>> {
+    let (_, parsed) = SipRequest::parse(request).ok()?;
+    add_received_parsed(request, &parsed, source)
+}
+
+/// `add_received` for `request` that is already parsed into `parsed`
+pub(crate) fn add_received_parsed(
+    request: &[u8],
+    parsed: &SipRequest,
+    source: SocketAddr,
+) -> Option<Vec<u8>> {
+    let via = parsed.headers.get_rfc(SipRFCHeader::Via)?.front()?;
+    let raw = via.raw_value_param;
+    let start = raw.as_ptr() as usize - request.as_ptr() as usize;
+
+    let has_rport = via.params().and_then(|p| p.get("rport")).is_some();
+    if !has_rport && sent_by_ip(via) == Some(source.ip()) {
+        return Some(request.to_vec());
+    }
+    let mut value = rewrite_params(&String::from_utf8_lossy(raw), |name, param| {
+        if name.eq_ignore_ascii_case("received") {
+            None
+        } else if name.eq_ignore_ascii_case("rport") {
+            Some(format!("rport={}", source.port()))
+        } else {
+            Some(param.to_owned())
+        }
+    });
+    value.push_str(";received=");
+    value.push_str(&source.ip().to_string());
+
+    let mut result = Vec::with_capacity(request.len() + 32);
+    result.extend_from_slice(&request[..start]);
+    result.extend_from_slice(value.as_bytes());
+    result.extend_from_slice(&request[start + raw.len()..]);
+    Some(result)
+}
+
+/// Address where response is sent over unreliable transport.
+/// [rfc3261 section-18.2.2](https://tools.ietf.org/html/rfc3261#section-18.2.2)
+///
+/// Order is `maddr`, then `received` with `rport`, then `sent-by`.
+/// Returns `None` if address is a domain name that must be resolved
+pub fn response_destination(headers: &SipHeaders, transport: TransportKind) -> Option<SocketAddr> {
+    let via = headers.get_rfc(SipRFCHeader::Via)?.front()?;
+    let params = via.params();
+    let param = |name: &'static str| params.and_then(|p| p.get(name)).and_then(|v| *v);
+    let tags = via.value.tags()?;
+    let sent_by_port = match tags.get(&SipHeaderTagType::Port) {
+        Some(port) => str::from_utf8(port).ok()?.parse::<u16>().ok()?,
+        None => transport.default_port(),
+    };
+
+    if let Some(maddr) = param("maddr") {
+        return Some(SocketAddr::new(parse_ip(maddr)?, sent_by_port));
+    }
+    if let Some(received) = param("received") {
+        let port = match param("rport") {
+            Some(rport) => rport.parse::<u16>().ok()?,
+            None => sent_by_port,
+        };
+        return Some(SocketAddr::new(parse_ip(received)?, port));
+    }
+    Some(SocketAddr::new(sent_by_ip(via)?, sent_by_port))
+}
+
+fn sent_by_ip(via: &SipHeader) -> Option<IpAddr> {
+    let host = via.value.tags()?.get(&SipHeaderTagType::Host)?;
+    parse_ip(str::from_utf8(host).ok()?)
+}
+
+/// IPv4address, IPv6address or IPv6reference
+pub(crate) fn parse_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok()
+}
+
+/// Calls `f` with name and text of every parameter of header value.
+/// Parameter is removed if `f` returns `None`
+fn rewrite_params<F>(value: &str, f: F) -> String
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    let mut parts = value.split(';');
+    let mut result = parts.next().unwrap_or("").to_owned();
+    for param in parts {
+        let name = param.split('=').next().unwrap_or("").trim();
+        if let Some(param) = f(name, param) {
+            result.push(';');
+            result.push_str(&param);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(via: &str) -> String {
+        format!(
+            "OPTIONS sip:carol@chicago.com SIP/2.0\r\nVia: {}\r\nCSeq: 1 OPTIONS\r\n\r\n",
+            via
+        )
+    }
+
+    fn destination(via: &str) -> Option<SocketAddr> {
+        let request = request(via);
+        let (_, request) = SipRequest::parse(request.as_bytes()).unwrap();
+        response_destination(&request.headers, TransportKind::Udp)
+    }
+
+    #[test]
+    fn add_received_test() {
+        let source = "192.0.2.1:5060".parse().unwrap();
+        let same = request("SIP/2.0/UDP 192.0.2.1;branch=z9hG4bK1");
+        assert_eq!(
+            add_received(same.as_bytes(), source).unwrap(),
+            same.as_bytes()
+        );
+
+        let domain = request("SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK1, SIP/2.0/UDP b.com");
+        assert_eq!(
+            add_received(domain.as_bytes(), source).unwrap(),
+            request(
+                "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK1;received=192.0.2.1, SIP/2.0/UDP b.com"
+            )
+            .as_bytes()
+        );
+
+        let replaced = request("SIP/2.0/UDP 10.0.0.1;received=10.0.0.1;branch=z9hG4bK1");
+        assert_eq!(
+            add_received(replaced.as_bytes(), source).unwrap(),
+            request("SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1;received=192.0.2.1").as_bytes()
+        );
+
+        let ipv6 = "[2001:db8::9]:5070".parse().unwrap();
+        assert_eq!(
+            add_received(request("SIP/2.0/UDP h.com;rport").as_bytes(), ipv6).unwrap(),
+            request("SIP/2.0/UDP h.com;rport=5070;received=2001:db8::9").as_bytes()
+        );
+        assert_eq!(
+            add_received(b"OPTIONS sip:a@b.com SIP/2.0\r\n\r\n", source),
+            None
+        );
+    }
+
+    #[test]
+    fn response_destination_test() {
+        assert_eq!(
+            destination("SIP/2.0/UDP 10.1.1.1:4540;received=192.0.2.1;rport=9988"),
+            "192.0.2.1:9988".parse().ok()
+        );
+        assert_eq!(
+            destination("SIP/2.0/UDP 10.1.1.1:4540;received=192.0.2.1"),
+            "192.0.2.1:4540".parse().ok()
+        );
+        assert_eq!(
+            destination("SIP/2.0/UDP a.com;maddr=224.0.1.75;received=192.0.2.1"),
+            "224.0.1.75:5060".parse().ok()
+        );
+        assert_eq!(
+            destination("SIP/2.0/UDP [2001:db8::9]:5070"),
+            "[2001:db8::9]:5070".parse().ok()
+        );
+        assert_eq!(destination("SIP/2.0/UDP pc33.atlanta.com"), None);
+    }
+}
@@ -0,0 +1,828 @@
+//! WebSocket transport of [rfc7118](https://tools.ietf.org/html/rfc7118).
+//! `WSS` requires `tls` feature.
+//!
+//! Connection starts with HTTP upgrade that negotiates `sip` subprotocol,
+//! after that each text or binary frame carries exactly one SIP message
+//! ([rfc7118 section-5](https://tools.ietf.org/html/rfc7118#section-5)).
+//!
+//! Browsers can't accept connections, so they put random `.invalid` host to
+//! Contact and Via. Server remembers such hosts and requests to them are sent
+//! over connection where they were registered, see `connection_for_host`.
+//!
+//! Connections are managed like in `TcpTransport`.
+
+use super::stream::{
+    connect, Codec, Connection, ConnectionTable, FramingError, Stream, StreamEvent, StreamSettings,
+};
+#[cfg(feature = "tls")]
+use super::tls::{TlsConfig, TlsStream};
+use super::{Transport, TransportKind};
+use crate::engine::Datagram;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str;
+use std::time::Instant;
+
+/// [rfc6455 section-1.3](https://tools.ietf.org/html/rfc6455#section-1.3)
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Limit of HTTP part of handshake
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+const BAD_REQUEST: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+/// Host of Contact or Via that is generated by client which can't accept
+/// connections. [rfc7118 section-5.2](https://tools.ietf.org/html/rfc7118#section-5.2)
+pub fn is_invalid_host(host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    host.len() > ".invalid".len()
+        && host
+            .get(host.len() - ".invalid".len()..)
+            .map(|tld| tld.eq_ignore_ascii_case(".invalid"))
+            .unwrap_or(false)
+}
+
+/// `Sec-WebSocket-Accept` for `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+fn invalid(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+enum Role {
+    Client {
+        /// Handshake request
+        request: Vec<u8>,
+        /// Expected `Sec-WebSocket-Accept`
+        accept: String,
+    },
+    Server,
+}
+
+/// HTTP upgrade and framing of [rfc6455](https://tools.ietf.org/html/rfc6455)
+pub(crate) struct WsCodec {
+    role: Role,
+    /// Handshake is completed
+    open: bool,
+    /// Messages that were sent before handshake is completed
+    pending: Vec<Vec<u8>>,
+    buf: Vec<u8>,
+    /// Payload of fragmented message
+    fragments: Option<Vec<u8>>,
+    max_message_size: usize,
+    /// Close frame is received
+    closed: bool,
+}
+
+impl WsCodec {
+    pub(crate) fn client(host: &str, path: &str, max_message_size: usize) -> WsCodec {
+        let key = base64(&random_bytes(16));
+        let request = format!(
+            "GET {} HTTP/1.1\r\n\
+Host: {}\r\n\
+Upgrade: websocket\r\n\
+Connection: Upgrade\r\n\
+Sec-WebSocket-Key: {}\r\n\
+Sec-WebSocket-Version: 13\r\n\
+Sec-WebSocket-Protocol: sip\r\n\r\n",
+            path, host, key
+        );
+        WsCodec::new(
+            Role::Client {
+                request: request.into_bytes(),
+                accept: accept_key(&key),
+            },
+            max_message_size,
+        )
+    }
+
+    pub(crate) fn server(max_message_size: usize) -> WsCodec {
+        WsCodec::new(Role::Server, max_message_size)
+    }
+
+    fn new(role: Role, max_message_size: usize) -> WsCodec {
+        WsCodec {
+            role,
+            open: false,
+            pending: vec![],
+            buf: vec![],
+            fragments: None,
+            max_message_size,
+            closed: false,
+        }
+    }
+
+    fn is_server(&self) -> bool {
+        matches!(self.role, Role::Server)
+    }
+
+    /// Validates handshake request or response, writes response of server
+    fn handshake(&mut self, head: &str, outbound: &mut Vec<u8>) -> io::Result<()> {
+        match &self.role {
+            Role::Server => {
+                let key = match upgrade_request_key(head) {
+                    Some(key) => key,
+                    None => {
+                        outbound.extend_from_slice(BAD_REQUEST);
+                        return Err(invalid("bad WebSocket upgrade request"));
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 101 Switching Protocols\r\n\
+Upgrade: websocket\r\n\
+Connection: Upgrade\r\n\
+Sec-WebSocket-Accept: {}\r\n\
+Sec-WebSocket-Protocol: sip\r\n\r\n",
+                    accept_key(key)
+                );
+                outbound.extend_from_slice(response.as_bytes());
+                Ok(())
+            }
+            Role::Client { accept, .. } => {
+                let switched = head.starts_with("HTTP/1.1 101 ")
+                    && header(head, "Sec-WebSocket-Accept") == Some(accept.as_str())
+                    && header(head, "Sec-WebSocket-Protocol")
+                        .map(|protocol| protocol.eq_ignore_ascii_case("sip"))
+                        .unwrap_or(false);
+                if switched {
+                    Ok(())
+                } else {
+                    Err(invalid("WebSocket upgrade is rejected"))
+                }
+            }
+        }
+    }
+
+    fn write_frame(&self, opcode: u8, payload: &[u8], outbound: &mut Vec<u8>) {
+        // client masks frames, server doesn't
+        let mask_bit = if self.is_server() { 0 } else { 0x80 };
+        outbound.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => outbound.push(mask_bit | len as u8),
+            len if len <= 0xffff => {
+                outbound.push(mask_bit | 126);
+                outbound.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                outbound.push(mask_bit | 127);
+                outbound.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        if self.is_server() {
+            outbound.extend_from_slice(payload);
+        } else {
+            let mask = random_bytes(4);
+            outbound.extend_from_slice(&mask);
+            outbound.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+    }
+
+    /// Takes complete frame from buffer. Returns FIN flag, opcode and unmasked payload
+    fn next_frame(&mut self) -> io::Result<Option<(bool, u8, Vec<u8>)>> {
+        let buf = &self.buf;
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        let fin = buf[0] & 0x80 != 0;
+        let opcode = buf[0] & 0x0f;
+        if buf[0] & 0x70 != 0 {
+            return Err(invalid("reserved bits of WebSocket frame are set"));
+        }
+        // [rfc6455 section-5.1](https://tools.ietf.org/html/rfc6455#section-5.1)
+        let masked = buf[1] & 0x80 != 0;
+        if masked != self.is_server() {
+            return Err(invalid("wrong masking of WebSocket frame"));
+        }
+        let (len, mut offset) = match buf[1] & 0x7f {
+            126 if buf.len() < 4 => return Ok(None),
+            126 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+            127 if buf.len() < 10 => return Ok(None),
+            127 => {
+                let mut len = [0; 8];
+                len.copy_from_slice(&buf[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            len => (len as u64, 2),
+        };
+        if opcode & 0x08 != 0 {
+            if !fin || len > 125 {
+                return Err(invalid("bad WebSocket control frame"));
+            }
+        } else {
+            let assembled = self.fragments.as_ref().map(Vec::len).unwrap_or(0);
+            if len > self.max_message_size.saturating_sub(assembled) as u64 {
+                return Err(FramingError::TooLarge.into());
+            }
+        }
+        let len = len as usize;
+        let mut mask = [0; 4];
+        if masked {
+            if buf.len() < offset + 4 {
+                return Ok(None);
+            }
+            mask.copy_from_slice(&buf[offset..offset + 4]);
+            offset += 4;
+        }
+        if buf.len() < offset + len {
+            return Ok(None);
+        }
+        let mut payload: Vec<u8> = self.buf.drain(..offset + len).skip(offset).collect();
+        if masked {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        Ok(Some((fin, opcode, payload)))
+    }
+}
+
+impl Codec for WsCodec {
+    fn start(&mut self, outbound: &mut Vec<u8>) {
+        if let Role::Client { request, .. } = &mut self.role {
+            outbound.append(request);
+        }
+    }
+
+    fn encode(&mut self, message: &[u8], outbound: &mut Vec<u8>) {
+        if !self.open {
+            self.pending.push(message.to_vec());
+            return;
+        }
+        let opcode = if str::from_utf8(message).is_ok() {
+            OP_TEXT
+        } else {
+            OP_BINARY
+        };
+        self.write_frame(opcode, message, outbound);
+    }
+
+    fn decode(&mut self, data: &[u8], outbound: &mut Vec<u8>) -> io::Result<Vec<Vec<u8>>> {
+        if self.closed {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.buf.extend_from_slice(data);
+        if !self.open {
+            let end = match self.buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                Some(position) => position + 4,
+                None if self.buf.len() > MAX_HANDSHAKE_SIZE => {
+                    return Err(invalid("WebSocket handshake is too large"))
+                }
+                None => return Ok(vec![]),
+            };
+            let head: Vec<u8> = self.buf.drain(..end).collect();
+            let head = str::from_utf8(&head).map_err(|_| invalid("bad WebSocket handshake"))?;
+            self.handshake(head, outbound)?;
+            self.open = true;
+            for message in mem::take(&mut self.pending) {
+                self.encode(&message, outbound);
+            }
+        }
+
+        let mut messages = vec![];
+        while let Some((fin, opcode, payload)) = self.next_frame()? {
+            let message = match opcode {
+                OP_CONTINUATION => {
+                    let fragments = self
+                        .fragments
+                        .as_mut()
+                        .ok_or_else(|| invalid("unexpected WebSocket continuation frame"))?;
+                    fragments.extend_from_slice(&payload);
+                    if !fin {
+                        continue;
+                    }
+                    self.fragments.take()
+                }
+                OP_TEXT | OP_BINARY => {
+                    if self.fragments.is_some() {
+                        return Err(invalid("WebSocket message is not finished"));
+                    }
+                    if !fin {
+                        self.fragments = Some(payload);
+                        continue;
+                    }
+                    Some(payload)
+                }
+                OP_CLOSE => {
+                    // echo status code and wait when peer closes TCP connection
+                    self.write_frame(OP_CLOSE, &payload[..payload.len().min(2)], outbound);
+                    self.closed = true;
+                    break;
+                }
+                OP_PING => {
+                    self.write_frame(OP_PONG, &payload, outbound);
+                    None
+                }
+                OP_PONG => None,
+                _ => return Err(invalid("unknown WebSocket opcode")),
+            };
+            messages.extend(message);
+        }
+        if self.closed && messages.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(messages)
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+}
+
+/// Value of `Sec-WebSocket-Key` of valid upgrade request that offers `sip` subprotocol
+fn upgrade_request_key(head: &str) -> Option<&str> {
+    let has_token = |name: &str, token: &str| {
+        header(head, name)
+            .map(|value| {
+                value
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(token))
+            })
+            .unwrap_or(false)
+    };
+    let valid = head.starts_with("GET ")
+        && head.lines().next().unwrap_or("").ends_with(" HTTP/1.1")
+        && has_token("Upgrade", "websocket")
+        && has_token("Connection", "upgrade")
+        && has_token("Sec-WebSocket-Protocol", "sip")
+        && header(head, "Sec-WebSocket-Version") == Some("13");
+    if valid {
+        header(head, "Sec-WebSocket-Key")
+    } else {
+        None
+    }
+}
+
+/// Value of HTTP header, `head` includes request or status line
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (header_name, value) = line.split_once(':')?;
+        if header_name.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Masks and keys need to be unpredictable for proxies only, not cryptographically strong
+fn random_bytes(len: usize) -> Vec<u8> {
+    let state = RandomState::new();
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(bytes.len());
+        bytes.extend_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// SHA-1 of [rfc3174](https://tools.ietf.org/html/rfc3174), used by handshake only
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, state) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+pub(crate) enum WsStream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Secure(Box<TlsStream>),
+}
+
+impl Stream for WsStream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            WsStream::Plain(stream) => stream,
+            #[cfg(feature = "tls")]
+            WsStream::Secure(stream) => stream.tcp(),
+        }
+    }
+
+    fn is_authenticated(&self) -> bool {
+        match self {
+            WsStream::Plain(_) => false,
+            #[cfg(feature = "tls")]
+            WsStream::Secure(stream) => stream.is_authenticated(),
+        }
+    }
+}
+
+impl Read for WsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            WsStream::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            WsStream::Secure(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for WsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            WsStream::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            WsStream::Secure(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            WsStream::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            WsStream::Secure(stream) => stream.flush(),
+        }
+    }
+}
+
+pub struct WsTransport {
+    listener: TcpListener,
+    settings: StreamSettings,
+    /// Request-URI of handshake of outbound connections
+    path: String,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+    connections: ConnectionTable<WsStream, WsCodec>,
+}
+
+impl WsTransport {
+    /// Plain `WS` transport
+    pub fn bind<A: ToSocketAddrs>(addr: A, settings: StreamSettings) -> io::Result<WsTransport> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(WsTransport {
+            listener,
+            settings,
+            path: "/".to_owned(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            connections: ConnectionTable::new(TransportKind::Ws),
+        })
+    }
+
+    /// `WSS` transport, WebSocket over TLS
+    #[cfg(feature = "tls")]
+    pub fn bind_secure<A: ToSocketAddrs>(
+        addr: A,
+        settings: StreamSettings,
+        config: TlsConfig,
+    ) -> io::Result<WsTransport> {
+        let mut transport = WsTransport::bind(addr, settings)?;
+        transport.tls = Some(config);
+        transport.connections = ConnectionTable::new(TransportKind::Wss);
+        Ok(transport)
+    }
+
+    /// Sets path of handshake request of outbound connections. `/` default
+    pub fn with_path(mut self, path: &str) -> WsTransport {
+        self.path = path.to_owned();
+        self
+    }
+
+    /// `Ws` or `Wss`
+    pub fn kind(&self) -> TransportKind {
+        #[cfg(feature = "tls")]
+        {
+            if self.tls.is_some() {
+                return TransportKind::Wss;
+            }
+        }
+        TransportKind::Ws
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn settings(&self) -> &StreamSettings {
+        &self.settings
+    }
+
+    /// Number of open connections
+    pub fn connections_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Remote address of connection that would be used for sending to `destination`
+    pub fn connection_for(&self, destination: SocketAddr) -> Option<SocketAddr> {
+        self.connections.peer(destination)
+    }
+
+    /// Remote address of connection of client that uses `.invalid` `host` in Contact
+    pub fn connection_for_host(&self, host: &str) -> Option<SocketAddr> {
+        self.connections.peer_for_host(host.trim_end_matches('.'))
+    }
+
+    /// Sends message over existing connection or opens new one.
+    /// `host` is used for `Host` header of handshake and as SIP domain of `WSS` server.
+    /// Rejected handshake is reported by `poll` as closed connection
+    pub fn send_to(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        host: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        if self.connections.peer(destination).is_none() {
+            if is_invalid_host(host) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "connection to .invalid host can't be opened",
+                ));
+            }
+            let stream = self.client_stream(host, connect(destination)?)?;
+            let codec = WsCodec::client(host, &self.path, self.settings.max_message_size);
+            let connection =
+                Connection::outbound(stream, codec, now, self.settings.connect_timeout);
+            self.connections.insert(destination, connection);
+        }
+        self.connections.send(message, destination, now)
+    }
+
+    /// Sends output of `Engine`
+    pub fn send(&mut self, datagram: &Datagram, host: &str, now: Instant) -> io::Result<()> {
+        if datagram.transport != self.kind() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram is not for WebSocket transport",
+            ));
+        }
+        self.send_to(&datagram.data, datagram.destination, host, now)
+    }
+
+    /// Closes connection
+    pub fn close(&mut self, peer: SocketAddr) {
+        if let Some(connection) = self.connections.remove(peer) {
+            let _ = connection.stream().tcp().shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Accepts new connections, reads received messages and closes idle connections
+    pub fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        loop {
+            let (stream, peer) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            let stream = match self.server_stream(stream)? {
+                Some(stream) => stream,
+                None => continue,
+            };
+            let codec = WsCodec::server(self.settings.max_message_size);
+            self.connections
+                .insert(peer, Connection::new(stream, codec, now));
+        }
+        Ok(self.connections.poll(&self.settings, now))
+    }
+
+    fn client_stream(&self, _host: &str, stream: TcpStream) -> io::Result<WsStream> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(config) = &self.tls {
+                return Ok(WsStream::Secure(Box::new(config.connect(_host, stream)?)));
+            }
+        }
+        Ok(WsStream::Plain(stream))
+    }
+
+    /// Server side of accepted connection. None if `WSS` has no server identity
+    fn server_stream(&self, stream: TcpStream) -> io::Result<Option<WsStream>> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(config) = &self.tls {
+                return Ok(config
+                    .accept(stream)?
+                    .map(|stream| WsStream::Secure(Box::new(stream))));
+            }
+        }
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Some(WsStream::Plain(stream)))
+    }
+}
+
+impl Transport for WsTransport {
+    fn kind(&self) -> TransportKind {
+        WsTransport::kind(self)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        WsTransport::local_addr(self)
+    }
+
+    fn send_message(
+        &mut self,
+        message: &[u8],
+        destination: SocketAddr,
+        domain: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        self.send_to(message, destination, domain, now)
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.settings.max_message_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write as _;
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        })
+    }
+
+    /// Client and server codecs after handshake
+    fn open() -> (WsCodec, WsCodec) {
+        let mut client = WsCodec::client("biloxi.example.com", "/", 1024);
+        let mut server = WsCodec::server(1024);
+        let mut request = vec![];
+        client.start(&mut request);
+        let mut response = vec![];
+        assert!(server.decode(&request, &mut response).unwrap().is_empty());
+        assert!(response.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(client.decode(&response, &mut vec![]).unwrap().is_empty());
+        (client, server)
+    }
+
+    #[test]
+    fn handshake_test() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        // rfc6455 section-1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let request = "GET / HTTP/1.1\r\n\
+Host: biloxi.example.com\r\n\
+Upgrade: websocket\r\n\
+Connection: keep-alive, Upgrade\r\n\
+Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+Sec-WebSocket-Version: 13\r\n\
+Sec-WebSocket-Protocol: xmpp, sip\r\n\r\n";
+        assert_eq!(
+            upgrade_request_key(request),
+            Some("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        let mut response = vec![];
+        let xmpp = request.replace("xmpp, sip", "xmpp");
+        assert!(WsCodec::server(1024)
+            .decode(xmpp.as_bytes(), &mut response)
+            .is_err());
+        assert_eq!(response, BAD_REQUEST);
+
+        assert!(is_invalid_host("df7jal23ls0d.INVALID."));
+        assert!(!is_invalid_host("invalid"));
+        assert!(!is_invalid_host("biloxi.example.com"));
+    }
+
+    #[test]
+    fn frames_test() {
+        let (mut client, mut server) = open();
+        let mut data = vec![];
+        client.encode(b"OPTIONS", &mut data);
+        assert_eq!(data[0], 0x80 | OP_TEXT);
+        assert_eq!(data[1], 0x80 | 7);
+        assert_eq!(server.decode(&data, &mut vec![]).unwrap(), vec![b"OPTIONS"]);
+
+        // 16 bit length, binary frame, partial reads
+        let big = [0xff; 300];
+        let mut data = vec![];
+        server.encode(&big, &mut data);
+        assert_eq!(&data[..4], &[0x80 | OP_BINARY, 126, 1, 44]);
+        assert!(client.decode(&data[..100], &mut vec![]).unwrap().is_empty());
+        assert_eq!(client.decode(&data[100..], &mut vec![]).unwrap(), vec![big]);
+
+        // fragmented message and ping in the middle
+        let mut data = vec![OP_TEXT, 3];
+        data.extend_from_slice(b"REG");
+        data.extend_from_slice(&[0x80 | OP_PING, 1, b'!']);
+        data.extend_from_slice(&[0x80 | OP_CONTINUATION, 5]);
+        data.extend_from_slice(b"ISTER");
+        let mut outbound = vec![];
+        assert_eq!(
+            client.decode(&data, &mut outbound).unwrap(),
+            vec![b"REGISTER"]
+        );
+        assert_eq!(outbound[0], 0x80 | OP_PONG);
+        assert_eq!(server.decode(&outbound, &mut vec![]).unwrap().len(), 0);
+
+        // client must mask frames
+        assert!(server
+            .decode(&[0x80 | OP_TEXT, 1, b'A'], &mut vec![])
+            .is_err());
+
+        let (_, mut server) = open();
+        assert!(server
+            .decode(
+                &[0x80 | OP_BINARY, 0x80 | 127, 0, 0, 0, 0, 0, 1, 0, 0],
+                &mut vec![]
+            )
+            .is_err());
+
+        // close is echoed
+        let (mut client, _) = open();
+        let mut outbound = vec![];
+        let error = client
+            .decode(&[0x80 | OP_CLOSE, 2, 0x03, 0xe8], &mut outbound)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(outbound[0], 0x80 | OP_CLOSE);
+    }
+}
@@ -0,0 +1,26 @@
+#!/bin/sh
+# Regenerates test CA and certificates of tests/tls.rs
+set -e
+cd "$(dirname "$0")"
+
+openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out ca.key
+openssl req -x509 -new -key ca.key -days 36500 -subj "/CN=sipcore test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" \
+    -addext "keyUsage=critical,keyCertSign,cRLSign" -out ca.pem
+
+# name san
+issue() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1.key"
+    openssl req -new -key "$1.key" -subj "/CN=$1" -out "$1.csr"
+    printf "basicConstraints=critical,CA:FALSE\nkeyUsage=critical,digitalSignature\nextendedKeyUsage=serverAuth,clientAuth\nsubjectAltName=%s\n" "$2" > "$1.ext"
+    openssl x509 -req -in "$1.csr" -CA ca.pem -CAkey ca.key -CAcreateserial \
+        -days 36500 -extfile "$1.ext" -out "$1.pem"
+    rm "$1.csr" "$1.ext"
+}
+
+# SIP URI with user part and wildcard dNSName must not be accepted (rfc5922)
+issue biloxi "URI:sip:biloxi.example.com,URI:sip:admin@other.example.com,DNS:*.biloxi.example.com"
+issue atlanta "DNS:atlanta.example.com"
+# dNSName is ignored when SIP URI is present
+issue chicago "URI:sip:chicago.example.com,DNS:proxy.example.com"
+rm -f ca.srl
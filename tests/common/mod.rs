@@ -0,0 +1,87 @@
+//! Helpers that are shared by integration tests
+#![allow(dead_code)]
+
+use sipcore::clock::Clock;
+use sipcore::engine::{Datagram, Engine, Event, Output};
+#[cfg(feature = "tls")]
+use sipcore::transport::TlsTransport;
+use sipcore::transport::{StreamEvent, TcpTransport, WsTransport};
+use std::io;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Transport that is driven by `poll`
+pub trait Poll {
+    fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>>;
+}
+
+impl Poll for TcpTransport {
+    fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        TcpTransport::poll(self, now)
+    }
+}
+
+impl Poll for WsTransport {
+    fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        WsTransport::poll(self, now)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Poll for TlsTransport {
+    fn poll(&mut self, now: Instant) -> io::Result<Vec<StreamEvent>> {
+        TlsTransport::poll(self, now)
+    }
+}
+
+/// Polls both transports until one of them returns events
+pub fn poll<A: Poll, B: Poll>(
+    first: &mut A,
+    second: &mut B,
+) -> (Vec<StreamEvent>, Vec<StreamEvent>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let events = (
+            first.poll(Instant::now()).unwrap(),
+            second.poll(Instant::now()).unwrap(),
+        );
+        if !events.0.is_empty() || !events.1.is_empty() {
+            return events;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("no events")
+}
+
+/// Data and source of the first event that must be a message
+pub fn message(events: Vec<StreamEvent>) -> (Vec<u8>, SocketAddr) {
+    match events.into_iter().next() {
+        Some(StreamEvent::Message { data, source }) => (data, source),
+        event => panic!("message is expected, got {:?}", event),
+    }
+}
+
+pub fn addr(addr: &str) -> SocketAddr {
+    addr.parse().unwrap()
+}
+
+/// Takes all outputs of engine
+pub fn drain<C: Clock>(engine: &mut Engine<C>) -> (Vec<Datagram>, Vec<Event>) {
+    let (mut datagrams, mut events) = (vec![], vec![]);
+    while let Some(output) = engine.poll_output() {
+        match output {
+            Output::Send(datagram) => datagrams.push(datagram),
+            Output::Event(event) => events.push(event),
+        }
+    }
+    (datagrams, events)
+}
+
+/// Passes datagram that is sent from `source` to engine
+pub fn deliver<C: Clock>(engine: &mut Engine<C>, datagram: &Datagram, source: SocketAddr) {
+    let incoming = engine
+        .parse_incoming(&datagram.data, source, datagram.transport)
+        .unwrap();
+    engine.handle_incoming(&incoming);
+}
@@ -0,0 +1,465 @@
+mod common;
+
+use common::addr;
+use sipcore::clock::ManualClock;
+use sipcore::dialog::*;
+use sipcore::engine::Event;
+use sipcore::message::response;
+use sipcore::network::{LinkSettings, Network, Simulation};
+use sipcore::transaction::TimerSettings;
+use sipcore::transport::TransportKind;
+use sipcore::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// INVITE of Alice that passed proxies of atlanta.com and biloxi.com
+const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP p2.biloxi.com;branch=z9hG4bK2d4790.1\r\n\
+Via: SIP/2.0/UDP p1.atlanta.com;branch=z9hG4bK77ef4c2312983.1\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8\r\n\
+Record-Route: <sip:p2.biloxi.com;lr>\r\n\
+Record-Route: <sip:p1.atlanta.com;lr>\r\n\
+Max-Forwards: 68\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Contact: <sip:alice@pc33.atlanta.com>\r\n\
+Content-Length: 0\r\n\r\n";
+
+/// Response of Bob with `contact` and Record-Route of INVITE
+fn response_of_bob(code: u16, reason: &str, contact: &str) -> String {
+    format!(
+        "SIP/2.0 {} {}\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8\r\n\
+Record-Route: <sip:p2.biloxi.com;lr>, <sip:p1.atlanta.com;lr>\r\n\
+To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Contact: <{}>\r\n\
+Content-Length: 0\r\n\r\n",
+        code, reason, contact
+    )
+}
+
+fn request_line(request: &[u8]) -> &str {
+    let request = std::str::from_utf8(request).unwrap();
+    request.split("\r\n").next().unwrap()
+}
+
+fn headers<'a>(request: &'a [u8], name: &str) -> Vec<&'a str> {
+    let request = std::str::from_utf8(request).unwrap();
+    request
+        .split("\r\n")
+        .filter_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+        .collect()
+}
+
+#[test]
+fn uac_dialog() {
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    let ringing = response_of_bob(180, "Ringing", "sip:bob@192.0.2.4");
+    let (_, ringing) = SipResponse::parse(ringing.as_bytes()).unwrap();
+
+    let mut dialog = Dialog::uac(&invite, &ringing).unwrap();
+    assert_eq!(dialog.state(), DialogState::Early);
+    assert_eq!(
+        dialog.id(),
+        &DialogId {
+            call_id: "a84b4c76e66710@pc33.atlanta.com".to_string(),
+            local_tag: "1928301774".to_string(),
+            remote_tag: "a6c85cf".to_string(),
+        }
+    );
+    // route set of UAC is reversed Record-Route
+    assert_eq!(
+        dialog.route_set(),
+        &["<sip:p1.atlanta.com;lr>", "<sip:p2.biloxi.com;lr>"]
+    );
+    assert_eq!(dialog.remote_target(), "sip:bob@192.0.2.4");
+    assert_eq!(dialog.local_cseq(), Some(314159));
+    assert_eq!(dialog.remote_cseq(), None);
+    assert!(!dialog.is_secure());
+
+    // 2xx confirms dialog and replaces remote target
+    let ok = response_of_bob(200, "OK", "sip:bob@192.0.2.4:5070;transport=tcp");
+    let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+    dialog.on_response(&ok).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+    assert_eq!(
+        dialog.remote_target(),
+        "sip:bob@192.0.2.4:5070;transport=tcp"
+    );
+    assert_eq!(dialog.next_hop(), "sip:p1.atlanta.com;lr");
+
+    // ACK has CSeq of INVITE
+    let ack = dialog.ack(
+        314159,
+        "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds9",
+        "",
+        b"",
+    );
+    assert_eq!(
+        request_line(&ack),
+        "ACK sip:bob@192.0.2.4:5070;transport=tcp SIP/2.0"
+    );
+    assert_eq!(headers(&ack, "CSeq"), vec!["314159 ACK"]);
+    assert_eq!(dialog.local_cseq(), Some(314159));
+
+    // re-INVITE with new offer
+    let sdp = b"v=0\r\n";
+    let reinvite = dialog
+        .request(
+            SipMethod::INVITE,
+            "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashdsa",
+            "Content-Type: application/sdp\r\n",
+            sdp,
+        )
+        .unwrap();
+    assert_eq!(
+        request_line(&reinvite),
+        "INVITE sip:bob@192.0.2.4:5070;transport=tcp SIP/2.0"
+    );
+    assert_eq!(
+        headers(&reinvite, "Route"),
+        vec!["<sip:p1.atlanta.com;lr>", "<sip:p2.biloxi.com;lr>"]
+    );
+    assert_eq!(
+        headers(&reinvite, "From"),
+        vec!["Alice <sip:alice@atlanta.com>;tag=1928301774"]
+    );
+    assert_eq!(
+        headers(&reinvite, "To"),
+        vec!["Bob <sip:bob@biloxi.com>;tag=a6c85cf"]
+    );
+    assert_eq!(headers(&reinvite, "CSeq"), vec!["314160 INVITE"]);
+    assert_eq!(
+        headers(&reinvite, "Contact"),
+        vec!["<sip:alice@pc33.atlanta.com>"]
+    );
+    assert_eq!(headers(&reinvite, "Content-Type"), vec!["application/sdp"]);
+    assert_eq!(headers(&reinvite, "Content-Length"), vec!["5"]);
+    assert!(reinvite.ends_with(sdp));
+    let (_, parsed) = SipRequest::parse(&reinvite).unwrap();
+    assert_eq!(parsed.rl.method, SipMethod::INVITE);
+
+    // UPDATE with new local target, INFO without Contact
+    dialog.set_local_target("sip:alice@192.0.2.1:5080");
+    let update = dialog
+        .request(
+            SipMethod::UPDATE,
+            "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashdsb",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(headers(&update, "CSeq"), vec!["314161 UPDATE"]);
+    assert_eq!(
+        headers(&update, "Contact"),
+        vec!["<sip:alice@192.0.2.1:5080>"]
+    );
+    let info = dialog
+        .request(
+            SipMethod::INFO,
+            "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashdsc",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(headers(&info, "CSeq"), vec!["314162 INFO"]);
+    assert!(headers(&info, "Contact").is_empty());
+    assert_eq!(
+        dialog.request(SipMethod::CANCEL, "SIP/2.0/UDP pc33.atlanta.com", "", b""),
+        Err(Error::InvalidMessage)
+    );
+
+    let bye = dialog
+        .request(
+            SipMethod::BYE,
+            "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashdsd",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(headers(&bye, "CSeq"), vec!["314163 BYE"]);
+    assert_eq!(dialog.state(), DialogState::Terminated);
+    assert_eq!(
+        dialog.request(SipMethod::INFO, "SIP/2.0/UDP pc33.atlanta.com", "", b""),
+        Err(Error::InvalidState)
+    );
+}
+
+#[test]
+fn uas_dialog() {
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    let ringing = response_of_bob(180, "Ringing", "sip:bob@192.0.2.4");
+    let (_, ringing) = SipResponse::parse(ringing.as_bytes()).unwrap();
+
+    let mut dialog = Dialog::uas(&invite, &ringing).unwrap();
+    assert_eq!(dialog.state(), DialogState::Early);
+    assert_eq!(dialog.id().local_tag, "a6c85cf");
+    assert_eq!(dialog.id().remote_tag, "1928301774");
+    // route set of UAS is Record-Route in order
+    assert_eq!(
+        dialog.route_set(),
+        &["<sip:p2.biloxi.com;lr>", "<sip:p1.atlanta.com;lr>"]
+    );
+    assert_eq!(dialog.remote_target(), "sip:alice@pc33.atlanta.com");
+    assert_eq!(dialog.local_cseq(), None);
+    assert_eq!(dialog.remote_cseq(), Some(314159));
+
+    let ok = response_of_bob(200, "OK", "sip:bob@192.0.2.4");
+    let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+    dialog.on_response(&ok).unwrap();
+    assert_eq!(dialog.state(), DialogState::Confirmed);
+
+    // re-INVITE of Alice from new address
+    let reinvite = INVITE
+        .replace(
+            "To: Bob <sip:bob@biloxi.com>",
+            "To: Bob <sip:bob@biloxi.com>;tag=a6c85cf",
+        )
+        .replace("314159 INVITE", "314160 INVITE")
+        .replace("<sip:alice@pc33.atlanta.com>", "<sip:alice@192.0.2.1>");
+    let (_, reinvite) = SipRequest::parse(reinvite.as_bytes()).unwrap();
+    assert_eq!(
+        DialogId::from_request(&reinvite).as_ref(),
+        Some(dialog.id())
+    );
+    dialog.on_request(&reinvite).unwrap();
+    assert_eq!(dialog.remote_cseq(), Some(314160));
+    assert_eq!(dialog.remote_target(), "sip:alice@192.0.2.1");
+
+    // request that is older than last one
+    let info = INVITE.replace("INVITE", "INFO").replace(
+        "To: Bob <sip:bob@biloxi.com>",
+        "To: Bob <sip:bob@biloxi.com>;tag=a6c85cf",
+    );
+    let (_, info) = SipRequest::parse(info.as_bytes()).unwrap();
+    assert_eq!(dialog.on_request(&info), Err(Error::OutOfOrder));
+    assert_eq!(dialog.remote_cseq(), Some(314160));
+
+    // Bob hangs up: From is local side, To is remote side
+    let bye = dialog
+        .request(
+            SipMethod::BYE,
+            "SIP/2.0/UDP 192.0.2.4;branch=z9hG4bK4b43c2ff8.1",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(request_line(&bye), "BYE sip:alice@192.0.2.1 SIP/2.0");
+    assert_eq!(
+        headers(&bye, "Route"),
+        vec!["<sip:p2.biloxi.com;lr>", "<sip:p1.atlanta.com;lr>"]
+    );
+    assert_eq!(
+        headers(&bye, "From"),
+        vec!["Bob <sip:bob@biloxi.com>;tag=a6c85cf"]
+    );
+    assert_eq!(
+        headers(&bye, "To"),
+        vec!["Alice <sip:alice@atlanta.com>;tag=1928301774"]
+    );
+    assert_eq!(headers(&bye, "CSeq"), vec!["1 BYE"]);
+    assert_eq!(dialog.state(), DialogState::Terminated);
+
+    // request of another dialog
+    let (_, other) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    assert_eq!(dialog.on_request(&other), Err(Error::InvalidMessage));
+}
+
+#[test]
+fn strict_routing() {
+    let invite = INVITE.replace(
+        "<sip:p2.biloxi.com;lr>",
+        "<sip:p2.biloxi.com;transport=tcp>",
+    );
+    let (_, invite) = SipRequest::parse(invite.as_bytes()).unwrap();
+    let ok = response_of_bob(200, "OK", "sip:bob@192.0.2.4")
+        .replace("<sip:p1.atlanta.com;lr>", "<sip:p1.atlanta.com>");
+    let (_, ok) = SipResponse::parse(ok.as_bytes()).unwrap();
+
+    // strict router is the first one: it becomes Request-URI and remote target is the last Route
+    let mut dialog = Dialog::uac(&invite, &ok).unwrap();
+    assert_eq!(dialog.next_hop(), "sip:p1.atlanta.com");
+    let bye = dialog
+        .request(
+            SipMethod::BYE,
+            "SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashdsd",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(request_line(&bye), "BYE sip:p1.atlanta.com SIP/2.0");
+    assert_eq!(
+        headers(&bye, "Route"),
+        vec!["<sip:p2.biloxi.com;lr>", "<sip:bob@192.0.2.4>"]
+    );
+
+    // loose router on top
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    let mut dialog = Dialog::uas(&invite, &ok).unwrap();
+    let bye = dialog
+        .request(
+            SipMethod::BYE,
+            "SIP/2.0/UDP 192.0.2.4;branch=z9hG4bK1",
+            "",
+            b"",
+        )
+        .unwrap();
+    assert_eq!(request_line(&bye), "BYE sip:alice@pc33.atlanta.com SIP/2.0");
+    assert_eq!(
+        headers(&bye, "Route"),
+        vec!["<sip:p2.biloxi.com;lr>", "<sip:p1.atlanta.com;lr>"]
+    );
+}
+
+#[test]
+fn dialog_termination() {
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    let of_bob =
+        |code: u16, reason: &str| response_of_bob(code, reason, "sip:bob@192.0.2.4").into_bytes();
+
+    // no dialog without To tag or by final non-2xx response
+    let trying = of_bob(100, "Trying");
+    let trying = String::from_utf8(trying)
+        .unwrap()
+        .replace(";tag=a6c85cf", "");
+    let (_, trying) = SipResponse::parse(trying.as_bytes()).unwrap();
+    assert_eq!(Dialog::uac(&invite, &trying), Err(Error::InvalidMessage));
+    let busy = of_bob(486, "Busy Here");
+    let (_, busy) = SipResponse::parse(&busy).unwrap();
+    assert_eq!(Dialog::uac(&invite, &busy), Err(Error::InvalidMessage));
+
+    // early dialog is terminated by non-2xx response
+    let ringing = of_bob(180, "Ringing");
+    let (_, ringing) = SipResponse::parse(&ringing).unwrap();
+    let mut dialog = Dialog::uac(&invite, &ringing).unwrap();
+    dialog.on_response(&busy).unwrap();
+    assert_eq!(dialog.state(), DialogState::Terminated);
+    assert_eq!(dialog.on_response(&busy), Err(Error::InvalidState));
+
+    // response of another early dialog of forked INVITE
+    let mut dialog = Dialog::uac(&invite, &ringing).unwrap();
+    let other = String::from_utf8(of_bob(200, "OK"))
+        .unwrap()
+        .replace("a6c85cf", "314159");
+    let (_, other) = SipResponse::parse(other.as_bytes()).unwrap();
+    assert_eq!(dialog.on_response(&other), Err(Error::InvalidMessage));
+    assert_eq!(dialog.state(), DialogState::Early);
+
+    // confirmed dialog is terminated by 481 to request within it
+    let ok = of_bob(200, "OK");
+    let (_, ok) = SipResponse::parse(&ok).unwrap();
+    dialog.on_response(&ok).unwrap();
+    let info = String::from_utf8(of_bob(481, "Call/Transaction Does Not Exist"))
+        .unwrap()
+        .replace("314159 INVITE", "314160 INFO");
+    let (_, info) = SipResponse::parse(info.as_bytes()).unwrap();
+    dialog.on_response(&info).unwrap();
+    assert_eq!(dialog.state(), DialogState::Terminated);
+}
+
+fn take_message(simulation: &mut Simulation, node: SocketAddr) -> Vec<u8> {
+    match simulation.take_events(node).into_iter().last() {
+        Some(Event::Request { message, .. }) | Some(Event::Response { message, .. }) => message,
+        event => panic!("message is expected, got {:?}", event),
+    }
+}
+
+/// Call where ACK and BYE are built by dialogs. Alice uses dialog that her engine keeps
+#[test]
+fn call_over_network() {
+    let (alice, bob) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(40),
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 1));
+    simulation.add_node(alice, TimerSettings::default());
+    simulation.add_node(bob, TimerSettings::default());
+
+    let invite = "INVITE sip:bob@192.0.2.4 SIP/2.0\r\n\
+Via: SIP/2.0/UDP 192.0.2.1:5060;branch=z9hG4bKnashds8\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 1 INVITE\r\n\
+Contact: <sip:alice@192.0.2.1>\r\n\
+Content-Length: 0\r\n\r\n";
+    simulation
+        .engine(alice)
+        .send_request(invite.as_bytes().to_vec(), bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+
+    // Bob answers
+    let (transaction, received) = match simulation.take_events(bob).remove(0) {
+        Event::Request {
+            transaction,
+            message,
+            ..
+        } => (transaction.unwrap(), message),
+        event => panic!("INVITE is expected, got {:?}", event),
+    };
+    let (_, request) = SipRequest::parse(&received).unwrap();
+    let ok = response(&request, 200, "OK", Some("a6c85cf"));
+    let ok = String::from_utf8(ok).unwrap().replace(
+        "Content-Length",
+        "Contact: <sip:bob@192.0.2.4>\r\nContent-Length",
+    );
+    let (_, parsed) = SipResponse::parse(ok.as_bytes()).unwrap();
+    let mut bob_dialog = Dialog::uas(&request, &parsed).unwrap();
+    simulation
+        .engine(bob)
+        .send_response(&transaction, ok.into_bytes())
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+
+    // Alice confirms by ACK and hangs up
+    let received = take_message(&mut simulation, alice);
+    let (_, ok) = SipResponse::parse(&received).unwrap();
+    let id = DialogId::from_response(&ok).unwrap();
+    let alice_dialog = simulation.engine(alice).dialog_mut(&id).unwrap();
+    assert_eq!(alice_dialog.state(), DialogState::Confirmed);
+    assert_eq!(alice_dialog.next_hop(), "sip:bob@192.0.2.4");
+    let ack = alice_dialog.ack(
+        1,
+        "SIP/2.0/UDP 192.0.2.1:5060;branch=z9hG4bKnashds9",
+        "",
+        b"",
+    );
+    let bye = alice_dialog
+        .request(
+            SipMethod::BYE,
+            "SIP/2.0/UDP 192.0.2.1:5060;branch=z9hG4bKnashdsa",
+            "",
+            b"",
+        )
+        .unwrap();
+    let engine = simulation.engine(alice);
+    engine.send_request(ack, bob, TransportKind::Udp).unwrap();
+    engine.send_request(bye, bob, TransportKind::Udp).unwrap();
+    assert_eq!(engine.dialogs_count(), 0);
+    simulation.run_for(Duration::from_millis(100));
+
+    for event in simulation.take_events(bob) {
+        let message = match event {
+            Event::Request { message, .. } => message,
+            event => panic!("request is expected, got {:?}", event),
+        };
+        let (_, request) = SipRequest::parse(&message).unwrap();
+        assert_eq!(
+            DialogId::from_request(&request).as_ref(),
+            Some(bob_dialog.id())
+        );
+        bob_dialog.on_request(&request).unwrap();
+    }
+    assert_eq!(bob_dialog.state(), DialogState::Terminated);
+    assert_eq!(bob_dialog.remote_cseq(), Some(2));
+    assert_eq!(simulation.engine(bob).dialogs_count(), 0);
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use common::{addr, deliver, drain};
+use sipcore::clock::{Clock, ManualClock};
+use sipcore::engine::*;
+use sipcore::message::response;
+use sipcore::transaction::TimerSettings;
+use sipcore::transport::TransportKind;
+use sipcore::*;
+use std::time::Duration;
+
+const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Contact: <sip:alice@pc33.atlanta.com>\r\n\
+Content-Length: 0\r\n\r\n";
+
+#[test]
+fn invite_over_lossy_network() {
+    let clock = ManualClock::new();
+    let (alice_addr, bob_addr) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let mut alice = Engine::new(&clock, TimerSettings::default());
+    let mut bob = Engine::new(&clock, TimerSettings::default());
+
+    let transaction = alice
+        .send_request(INVITE.as_bytes().to_vec(), bob_addr, TransportKind::Udp)
+        .unwrap()
+        .unwrap();
+    // first INVITE is lost
+    let (sent, _) = drain(&mut alice);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].destination, bob_addr);
+
+    clock.advance(Duration::from_millis(500));
+    alice.handle_timers();
+    let (sent, _) = drain(&mut alice);
+    deliver(&mut bob, &sent[0], alice_addr);
+    let (trying, events) = drain(&mut bob);
+    let server_transaction = match &events[..] {
+        [Event::Request {
+            transaction: Some(transaction),
+            ..
+        }] => transaction.clone(),
+        _ => panic!("INVITE is expected, got {:?}", events),
+    };
+
+    // 100 Trying stops retransmissions of INVITE
+    deliver(&mut alice, &trying[0], bob_addr);
+    let (_, events) = drain(&mut alice);
+    assert_eq!(events.len(), 1);
+    clock.advance(Duration::from_secs(1));
+    alice.handle_timers();
+    assert_eq!(drain(&mut alice), (vec![], vec![]));
+
+    // first 486 is lost, Timer G retransmits it
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    let busy = response(&invite, 486, "Busy Here", Some("a6c85cf"));
+    bob.send_response(&server_transaction, busy.clone())
+        .unwrap();
+    drain(&mut bob);
+    clock.advance(Duration::from_millis(500));
+    bob.handle_timers();
+    let (sent, _) = drain(&mut bob);
+    assert_eq!(sent[0].data, busy);
+
+    deliver(&mut alice, &sent[0], bob_addr);
+    let (ack, events) = drain(&mut alice);
+    assert_eq!(
+        events,
+        vec![Event::Response {
+            transaction: Some(transaction),
+            message: busy,
+            source: bob_addr,
+            transport: TransportKind::Udp
+        }]
+    );
+    deliver(&mut bob, &ack[0], alice_addr);
+    assert_eq!(drain(&mut bob), (vec![], vec![]));
+
+    // Timer I and Timer D terminate transactions
+    clock.advance(Duration::from_secs(32));
+    alice.handle_timers();
+    bob.handle_timers();
+    assert_eq!(alice.transactions_count(), 0);
+    assert_eq!(bob.transactions_count(), 0);
+    assert_eq!(alice.next_deadline(), None);
+}
+
+#[test]
+fn request_timeout() {
+    let clock = ManualClock::new();
+    let mut alice = Engine::new(&clock, TimerSettings::default());
+    let bye = INVITE.replace("INVITE", "BYE");
+    let bob_addr = addr("192.0.2.4:5060");
+    let transaction = alice
+        .send_request(bye.into_bytes(), bob_addr, TransportKind::Udp)
+        .unwrap()
+        .unwrap();
+
+    let mut retransmissions = 0;
+    while let Some(deadline) = alice.next_deadline() {
+        clock.advance(deadline - clock.now());
+        alice.handle_timers();
+        let (sent, events) = drain(&mut alice);
+        retransmissions += sent.len();
+        if !events.is_empty() {
+            assert_eq!(events, vec![Event::Timeout { transaction }]);
+            break;
+        }
+    }
+    // Timer E: 0.5s, 1s, 2s, 4s, 4s...
+    assert_eq!(retransmissions, 11);
+    assert_eq!(alice.transactions_count(), 0);
+}
+
+/// Engines create dialogs from INVITE and its responses, and remove them after BYE
+#[test]
+fn dialogs_of_engine() {
+    let clock = ManualClock::new();
+    let (alice_addr, bob_addr) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let mut alice = Engine::new(&clock, TimerSettings::default());
+    let mut bob = Engine::new(&clock, TimerSettings::default());
+
+    alice
+        .send_request(INVITE.as_bytes().to_vec(), bob_addr, TransportKind::Udp)
+        .unwrap();
+    let (sent, _) = drain(&mut alice);
+    deliver(&mut bob, &sent[0], alice_addr);
+    let (_, events) = drain(&mut bob);
+    let transaction = match &events[..] {
+        [Event::Request {
+            transaction: Some(transaction),
+            ..
+        }] => transaction.clone(),
+        _ => panic!("INVITE is expected, got {:?}", events),
+    };
+    let (_, invite) = SipRequest::parse(INVITE.as_bytes()).unwrap();
+    for (code, reason) in [(180, "Ringing"), (200, "OK")].iter() {
+        let answer = response(&invite, *code, reason, Some("a6c85cf"));
+        let answer = String::from_utf8(answer).unwrap().replace(
+            "Content-Length",
+            "Contact: <sip:bob@192.0.2.4>\r\nContent-Length",
+        );
+        bob.send_response(&transaction, answer.into_bytes()).unwrap();
+        let (sent, _) = drain(&mut bob);
+        deliver(&mut alice, &sent[0], bob_addr);
+        drain(&mut alice);
+    }
+
+    let alice_id = dialog::DialogId {
+        call_id: "a84b4c76e66710@pc33.atlanta.com".to_string(),
+        local_tag: "1928301774".to_string(),
+        remote_tag: "a6c85cf".to_string(),
+    };
+    let bob_id = dialog::DialogId {
+        call_id: "a84b4c76e66710@pc33.atlanta.com".to_string(),
+        local_tag: "a6c85cf".to_string(),
+        remote_tag: "1928301774".to_string(),
+    };
+    let dialog = alice.dialog(&alice_id).unwrap();
+    assert_eq!(dialog.state(), dialog::DialogState::Confirmed);
+    assert_eq!(dialog.local_cseq(), Some(314159));
+    let dialog = bob.dialog(&bob_id).unwrap();
+    assert_eq!(dialog.state(), dialog::DialogState::Confirmed);
+    assert_eq!(dialog.remote_cseq(), Some(314159));
+
+    let within = |method: &str, cseq: u32, branch: &str| {
+        INVITE
+            .replacen("INVITE sip", &format!("{} sip", method), 1)
+            .replace("CSeq: 314159 INVITE", &format!("CSeq: {} {}", cseq, method))
+            .replace("z9hG4bK776asdhds", branch)
+            .replace(
+                "Bob <sip:bob@biloxi.com>",
+                "Bob <sip:bob@biloxi.com>;tag=a6c85cf",
+            )
+            .into_bytes()
+    };
+    let ack = within("ACK", 314159, "z9hG4bK776asdhdt");
+    alice
+        .send_request(ack, bob_addr, TransportKind::Udp)
+        .unwrap();
+    let (sent, _) = drain(&mut alice);
+    deliver(&mut bob, &sent[0], alice_addr);
+    drain(&mut bob);
+
+    // request with CSeq lower than remote CSeq is rejected by engine of Bob
+    let info = within("INFO", 314161, "z9hG4bK776asdhdu");
+    let stale = within("INFO", 314160, "z9hG4bK776asdhdv");
+    alice
+        .send_request(info, bob_addr, TransportKind::Udp)
+        .unwrap();
+    alice
+        .send_request(stale, bob_addr, TransportKind::Udp)
+        .unwrap();
+    let (sent, _) = drain(&mut alice);
+    deliver(&mut bob, &sent[0], alice_addr);
+    deliver(&mut bob, &sent[1], alice_addr);
+    let (sent, events) = drain(&mut bob);
+    assert_eq!(events.len(), 1);
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0]
+        .data
+        .starts_with(b"SIP/2.0 500 Server Internal Error\r\n"));
+    assert_eq!(bob.dialog(&bob_id).unwrap().remote_cseq(), Some(314161));
+
+    // BYE removes dialogs
+    let bye = within("BYE", 314162, "z9hG4bK776asdhdw");
+    alice
+        .send_request(bye, bob_addr, TransportKind::Udp)
+        .unwrap();
+    assert_eq!(alice.dialogs_count(), 0);
+    let (sent, _) = drain(&mut alice);
+    deliver(&mut bob, &sent[0], alice_addr);
+    assert_eq!(bob.dialogs_count(), 0);
+}
+
+#[test]
+fn oversized_message_is_rejected() {
+    let clock = ManualClock::new();
+    let alice_addr = addr("192.0.2.1:5060");
+    let engine = Engine::new(&clock, TimerSettings::default());
+
+    // 71 Via entries
+    let via = "Via: SIP/2.0/UDP p1.example.com;branch=z9hG4bK1\r\n";
+    let many_vias = INVITE.replacen(
+        "Max-Forwards",
+        &format!("{}Max-Forwards", via.repeat(70)),
+        1,
+    );
+    assert!(engine
+        .parse_incoming(many_vias.as_bytes(), alice_addr, TransportKind::Udp)
+        .is_err());
+    let long_uri = INVITE.replacen("bob@", &format!("{}@", "b".repeat(4096)), 1);
+    assert!(Incoming::parse(long_uri.as_bytes(), alice_addr, TransportKind::Udp).is_err());
+
+    // limits are configurable
+    let engine = engine.with_parse_options(ParseOptions::strict());
+    assert!(engine
+        .parse_incoming(many_vias.as_bytes(), alice_addr, TransportKind::Udp)
+        .is_ok());
+    assert!(engine
+        .parse_incoming(long_uri.as_bytes(), alice_addr, TransportKind::Udp)
+        .is_ok());
+}
+
+#[test]
+fn response_after_connection_is_gone() {
+    let clock = ManualClock::new();
+    let mut bob = Engine::new(&clock, TimerSettings::default());
+    let source = addr("192.0.2.1:49152");
+    let invite = INVITE.replace(
+        "UDP pc33.atlanta.com",
+        "TCP pc33.atlanta.com:5062;received=192.0.2.1",
+    );
+    let incoming = Incoming::parse(invite.as_bytes(), source, TransportKind::Tcp).unwrap();
+    bob.handle_incoming(&incoming);
+    let transaction = match drain(&mut bob).1.remove(0) {
+        Event::Request {
+            transaction: Some(transaction),
+            ..
+        } => transaction,
+        event => panic!("request is expected, got {:?}", event),
+    };
+    let (_, request) = SipRequest::parse(invite.as_bytes()).unwrap();
+    let busy = response(&request, 486, "Busy Here", Some("93810874"));
+    bob.send_response(&transaction, busy.clone()).unwrap();
+    let (sent, _) = drain(&mut bob);
+    assert_eq!(sent[0].destination, source);
+
+    // response is sent again to received address and port of sent-by
+    bob.handle_connection_failure(source, TransportKind::Tcp);
+    let (sent, events) = drain(&mut bob);
+    assert!(events.is_empty());
+    assert_eq!(sent[0].data, busy);
+    assert_eq!(sent[0].destination, addr("192.0.2.1:5062"));
+
+    bob.handle_transport_error(&transaction);
+    assert_eq!(
+        drain(&mut bob).1,
+        vec![Event::TransportError { transaction }]
+    );
+    assert_eq!(bob.transactions_count(), 0);
+}
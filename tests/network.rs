@@ -0,0 +1,560 @@
+mod common;
+
+use common::addr;
+use sipcore::clock::ManualClock;
+use sipcore::engine::*;
+use sipcore::message::{response, status_code, tag};
+use sipcore::network::{LinkSettings, Network, Simulation};
+use sipcore::transaction::{Error, TimerSettings, TransactionKey};
+use sipcore::transport::TransportKind;
+use sipcore::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Request of Alice in call a84b4c76e66710
+fn request(method: &str, uri: &str, branch: &str, to_tag: Option<&str>, cseq: u32) -> Vec<u8> {
+    let to_tag = to_tag
+        .map(|tag| format!(";tag={}", tag))
+        .unwrap_or_default();
+    format!(
+        "{method} {uri} SIP/2.0\r\n\
+Via: SIP/2.0/UDP 192.0.2.1:5060;branch={branch}\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>{to_tag}\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: {cseq} {method}\r\n\
+Content-Length: 0\r\n\r\n",
+        method = method,
+        uri = uri,
+        branch = branch,
+        to_tag = to_tag,
+        cseq = cseq
+    )
+    .into_bytes()
+}
+
+type Received = Vec<(Option<TransactionKey>, Vec<u8>)>;
+
+/// Requests and responses of events
+fn split(events: Vec<Event>) -> (Received, Received) {
+    let (mut requests, mut responses) = (vec![], vec![]);
+    for event in events {
+        match event {
+            Event::Request {
+                transaction,
+                message,
+                ..
+            } => requests.push((transaction, message)),
+            Event::Response {
+                transaction,
+                message,
+                ..
+            } => responses.push((transaction, message)),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    (requests, responses)
+}
+
+fn method(message: &[u8]) -> SipMethod {
+    SipRequest::parse(message).unwrap().1.rl.method
+}
+
+fn status(message: &[u8]) -> u16 {
+    status_code(&SipResponse::parse(message).unwrap().1)
+}
+
+fn to_tag(message: &[u8]) -> String {
+    let (_, response) = SipResponse::parse(message).unwrap();
+    tag(&response.headers, SipRFCHeader::To).unwrap().to_owned()
+}
+
+/// Answers request of server transaction
+fn answer(
+    simulation: &mut Simulation,
+    node: SocketAddr,
+    (transaction, message): &(Option<TransactionKey>, Vec<u8>),
+    code: u16,
+    reason: &str,
+    to_tag: &str,
+) {
+    let (_, request) = SipRequest::parse(message).unwrap();
+    let response = response(&request, code, reason, Some(to_tag));
+    simulation
+        .engine(node)
+        .send_response(transaction.as_ref().unwrap(), response)
+        .unwrap();
+}
+
+fn assert_idle(simulation: &mut Simulation, nodes: &[SocketAddr]) {
+    simulation.run_for(Duration::from_secs(64));
+    for node in nodes {
+        assert_eq!(simulation.engine(*node).transactions_count(), 0);
+    }
+    assert_eq!(simulation.network().in_flight(), 0);
+}
+
+#[test]
+fn call_flow() {
+    let (alice, bob) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(30),
+        jitter: Duration::from_millis(20),
+        duplication: 0.5,
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 3));
+    simulation.add_node(alice, TimerSettings::default());
+    simulation.add_node(bob, TimerSettings::default());
+
+    let invite = request("INVITE", "sip:bob@192.0.2.4", "z9hG4bK74bf9", None, 1);
+    let transaction = simulation
+        .engine(alice)
+        .send_request(invite, bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(200));
+
+    // duplicates are absorbed by server transaction, 100 (Trying) is sent by engine
+    let (requests, _) = split(simulation.take_events(bob));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(method(&requests[0].1), SipMethod::INVITE);
+    answer(
+        &mut simulation,
+        bob,
+        &requests[0],
+        180,
+        "Ringing",
+        "8321234356",
+    );
+    simulation.run_for(Duration::from_millis(200));
+    answer(&mut simulation, bob, &requests[0], 200, "OK", "8321234356");
+    simulation.run_for(Duration::from_millis(200));
+
+    let (_, responses) = split(simulation.take_events(alice));
+    let mut statuses: Vec<u16> = responses
+        .iter()
+        .filter(|(key, _)| *key == transaction)
+        .map(|(_, message)| status(message))
+        .collect();
+    statuses.dedup();
+    assert_eq!(statuses, vec![100, 180, 200]);
+    // copies of 200 arrive after transaction is terminated, they are ACKed too
+    let oks = responses
+        .iter()
+        .filter(|(_, message)| status(message) == 200)
+        .count();
+    assert!(responses
+        .iter()
+        .filter(|(key, _)| key.is_none())
+        .all(|(_, message)| status(message) == 200));
+    for _ in 0..oks {
+        let ack = request(
+            "ACK",
+            "sip:bob@192.0.2.4",
+            "z9hG4bK74bfa",
+            Some("8321234356"),
+            1,
+        );
+        let key = simulation
+            .engine(alice)
+            .send_request(ack, bob, TransportKind::Udp)
+            .unwrap();
+        assert_eq!(key, None);
+    }
+    simulation.run_for(Duration::from_millis(200));
+    let (requests, _) = split(simulation.take_events(bob));
+    assert!(requests.len() >= oks);
+    assert!(requests
+        .iter()
+        .all(|(key, message)| key.is_none() && method(message) == SipMethod::ACK));
+
+    // Alice hangs up
+    let bye = request(
+        "BYE",
+        "sip:bob@192.0.2.4",
+        "z9hG4bK74bfb",
+        Some("8321234356"),
+        2,
+    );
+    let transaction = simulation
+        .engine(alice)
+        .send_request(bye, bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(200));
+    let (requests, _) = split(simulation.take_events(bob));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(method(&requests[0].1), SipMethod::BYE);
+    answer(&mut simulation, bob, &requests[0], 200, "OK", "8321234356");
+    simulation.run_for(Duration::from_millis(200));
+    let (_, responses) = split(simulation.take_events(alice));
+    assert_eq!(responses[0].0, transaction);
+    assert_eq!(status(&responses[0].1), 200);
+
+    assert!(simulation.network().stats().duplicated > 0);
+    assert_idle(&mut simulation, &[alice, bob]);
+}
+
+#[test]
+fn retransmissions_over_lossy_link() {
+    let (alice, bob) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(50),
+        loss: 0.4,
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 11));
+    simulation.add_node(alice, TimerSettings::default());
+    simulation.add_node(bob, TimerSettings::default());
+
+    for cseq in 1..=10 {
+        let branch = format!("z9hG4bKmsg{}", cseq);
+        let message = request("MESSAGE", "sip:bob@192.0.2.4", &branch, None, cseq);
+        let transaction = simulation
+            .engine(alice)
+            .send_request(message, bob, TransportKind::Udp)
+            .unwrap();
+
+        // Timer E retransmits request until it arrives
+        let mut requests = vec![];
+        while requests.is_empty() {
+            simulation.run_for(Duration::from_millis(100));
+            requests = split(simulation.take_events(bob)).0;
+        }
+        assert_eq!(requests.len(), 1);
+        answer(&mut simulation, bob, &requests[0], 200, "OK", "a6c85cf");
+
+        // server transaction retransmits 200 for every copy of request
+        let mut responses = vec![];
+        while responses.is_empty() {
+            simulation.run_for(Duration::from_millis(100));
+            responses = split(simulation.take_events(alice)).1;
+        }
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].0, transaction);
+        assert_eq!(status(&responses[0].1), 200);
+    }
+
+    let stats = simulation.network().stats();
+    assert!(stats.lost > 0);
+    assert!(stats.sent > 20);
+    assert_idle(&mut simulation, &[alice, bob]);
+}
+
+/// 200 (OK) of Bob and 100 (Trying) are lost until link recovers
+#[test]
+fn lost_2xx() {
+    let (alice, bob) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(50),
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 13));
+    simulation.add_node(alice, TimerSettings::default());
+    simulation.add_node(bob, TimerSettings::default());
+    let lossy = LinkSettings {
+        loss: 1.0,
+        ..settings
+    };
+    simulation.network().set_link(bob, alice, lossy);
+
+    let invite = request("INVITE", "sip:bob@192.0.2.4", "z9hG4bK776asdhds", None, 1);
+    let transaction = simulation
+        .engine(alice)
+        .send_request(invite, bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (requests, _) = split(simulation.take_events(bob));
+    assert_eq!(requests.len(), 1);
+    answer(&mut simulation, bob, &requests[0], 200, "OK", "a6c85cf");
+
+    // Timer A retransmits INVITE, accepted transaction absorbs it
+    simulation.run_for(Duration::from_secs(2));
+    assert!(simulation.take_events(bob).is_empty());
+    assert!(simulation.take_events(alice).is_empty());
+
+    // Bob retransmits 200 until it arrives
+    simulation.network().set_link(bob, alice, settings);
+    simulation.run_for(Duration::from_secs(2));
+    let (_, responses) = split(simulation.take_events(alice));
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].0, transaction);
+    assert_eq!(status(&responses[0].1), 200);
+
+    // ACK stops retransmissions of 200
+    let ack = request(
+        "ACK",
+        "sip:bob@192.0.2.4",
+        "z9hG4bK776asdhdt",
+        Some("a6c85cf"),
+        1,
+    );
+    simulation
+        .engine(alice)
+        .send_request(ack, bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (requests, _) = split(simulation.take_events(bob));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, None);
+    assert_eq!(method(&requests[0].1), SipMethod::ACK);
+    simulation.run_for(Duration::from_secs(10));
+    assert!(simulation.take_events(alice).is_empty());
+
+    let stats = simulation.network().stats();
+    assert!(stats.lost >= 4);
+    assert_idle(&mut simulation, &[alice, bob]);
+    assert!(simulation.take_events(alice).is_empty());
+    assert!(simulation.take_events(bob).is_empty());
+}
+
+/// Proxy 192.0.2.10 forks INVITE of Alice to two phones of Bob.
+/// First phone answers, proxy cancels the other one
+#[test]
+fn forking() {
+    let (alice, proxy) = (addr("192.0.2.1:5060"), addr("192.0.2.10:5060"));
+    let phones = [addr("192.0.2.4:5060"), addr("192.0.2.5:5060")];
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(20),
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 5));
+    for node in [alice, proxy, phones[0], phones[1]].iter() {
+        simulation.add_node(*node, TimerSettings::default());
+    }
+
+    let invite = request("INVITE", "sip:bob@biloxi.com", "z9hG4bKnashds8", None, 1);
+    simulation
+        .engine(alice)
+        .send_request(invite, proxy, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (mut requests, _) = split(simulation.take_events(proxy));
+    let (server, invite) = requests.remove(0);
+    let server = server.unwrap();
+
+    // proxy adds own Via and retargets request to every phone
+    let invite = String::from_utf8(invite).unwrap();
+    let (_, rest) = invite.split_once("\r\n").unwrap();
+    let mut forks = vec![];
+    let mut forwarded = vec![];
+    for (i, phone) in phones.iter().enumerate() {
+        let fork = format!(
+            "INVITE sip:bob@{} SIP/2.0\r\nVia: SIP/2.0/UDP 192.0.2.10:5060;branch=z9hG4bKfork{}\r\n{}",
+            phone.ip(),
+            i,
+            rest
+        );
+        let key = simulation
+            .engine(proxy)
+            .send_request(fork.clone().into_bytes(), *phone, TransportKind::Udp)
+            .unwrap();
+        forks.push(key);
+        forwarded.push(fork);
+    }
+    simulation.run_for(Duration::from_millis(100));
+    let mut invites = vec![];
+    for (phone, tag) in phones.iter().zip(&["bob1", "bob2"]) {
+        let (mut requests, _) = split(simulation.take_events(*phone));
+        assert_eq!(requests.len(), 1);
+        answer(&mut simulation, *phone, &requests[0], 180, "Ringing", tag);
+        invites.push(requests.remove(0));
+    }
+    simulation.run_for(Duration::from_millis(100));
+    answer(&mut simulation, phones[0], &invites[0], 200, "OK", "bob1");
+
+    // proxy forwards responses without own Via and cancels second fork after 200
+    let mut cancelled = false;
+    for _ in 0..10 {
+        simulation.run_for(Duration::from_millis(20));
+        let (_, responses) = split(simulation.take_events(proxy));
+        for (key, message) in responses {
+            let code = status(&message);
+            if code == 100 || code == 487 {
+                continue;
+            }
+            let message = String::from_utf8(message).unwrap();
+            let (status_line, rest) = message.split_once("\r\n").unwrap();
+            let (_, rest) = rest.split_once("\r\n").unwrap();
+            let forwarded_response = format!("{}\r\n{}", status_line, rest);
+            simulation
+                .engine(proxy)
+                .send_response(&server, forwarded_response.into_bytes())
+                .unwrap();
+            if code == 200 && key == forks[0] && !cancelled {
+                let cancel = forwarded[1]
+                    .replacen("INVITE", "CANCEL", 1)
+                    .replace("CSeq: 1 INVITE", "CSeq: 1 CANCEL");
+                simulation
+                    .engine(proxy)
+                    .send_request(cancel.into_bytes(), phones[1], TransportKind::Udp)
+                    .unwrap();
+                cancelled = true;
+            }
+        }
+    }
+    assert!(cancelled);
+
+    // second phone accepts CANCEL and terminates INVITE by 487 (Request Terminated)
+    let (requests, _) = split(simulation.take_events(phones[1]));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(method(&requests[0].1), SipMethod::CANCEL);
+    answer(&mut simulation, phones[1], &requests[0], 200, "OK", "bob2");
+    answer(
+        &mut simulation,
+        phones[1],
+        &invites[1],
+        487,
+        "Request Terminated",
+        "bob2",
+    );
+    simulation.run_for(Duration::from_millis(200));
+    let (_, responses) = split(simulation.take_events(proxy));
+    let mut codes: Vec<u16> = responses
+        .iter()
+        .map(|(_, message)| status(message))
+        .collect();
+    codes.sort_unstable();
+    assert_eq!(codes, vec![200, 487]);
+
+    // Alice sees early dialogs of both phones and is connected to the first one
+    let (_, responses) = split(simulation.take_events(alice));
+    let early: Vec<String> = responses
+        .iter()
+        .filter(|(_, message)| status(message) == 180)
+        .map(|(_, message)| to_tag(message))
+        .collect();
+    assert_eq!(early, vec!["bob1", "bob2"]);
+    let answered: Vec<String> = responses
+        .iter()
+        .filter(|(_, message)| status(message) == 200)
+        .map(|(_, message)| to_tag(message))
+        .collect();
+    assert_eq!(answered, vec!["bob1"]);
+
+    let ack = request(
+        "ACK",
+        "sip:bob@192.0.2.4",
+        "z9hG4bKnashds9",
+        Some("bob1"),
+        1,
+    );
+    simulation
+        .engine(alice)
+        .send_request(ack, phones[0], TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (requests, _) = split(simulation.take_events(phones[0]));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, None);
+    assert_eq!(method(&requests[0].1), SipMethod::ACK);
+
+    assert_idle(&mut simulation, &[alice, proxy, phones[0], phones[1]]);
+    assert!(simulation.take_events(phones[1]).is_empty());
+}
+
+/// CANCEL of Alice crosses 200 (OK) of Bob
+#[test]
+fn cancel_race() {
+    let (alice, bob) = (addr("192.0.2.1:5060"), addr("192.0.2.4:5060"));
+    let clock = ManualClock::new();
+    let settings = LinkSettings {
+        latency: Duration::from_millis(50),
+        ..LinkSettings::default()
+    };
+    let mut simulation = Simulation::new(&clock, Network::new(settings, 9));
+    simulation.add_node(alice, TimerSettings::default());
+    simulation.add_node(bob, TimerSettings::default());
+
+    let invite = request("INVITE", "sip:bob@192.0.2.4", "z9hG4bK776asdhds", None, 1);
+    let transaction = simulation
+        .engine(alice)
+        .send_request(invite.clone(), bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (mut requests, _) = split(simulation.take_events(bob));
+    let invite_request = requests.remove(0);
+    answer(
+        &mut simulation,
+        bob,
+        &invite_request,
+        180,
+        "Ringing",
+        "a6c85cf",
+    );
+    simulation.run_for(Duration::from_millis(100));
+
+    // both are in flight at the same time
+    answer(&mut simulation, bob, &invite_request, 200, "OK", "a6c85cf");
+    let cancel = String::from_utf8(invite)
+        .unwrap()
+        .replacen("INVITE", "CANCEL", 1)
+        .replace("CSeq: 1 INVITE", "CSeq: 1 CANCEL");
+    let cancel_transaction = simulation
+        .engine(alice)
+        .send_request(cancel.into_bytes(), bob, TransportKind::Udp)
+        .unwrap();
+    simulation.run_for(Duration::from_millis(100));
+
+    // call is already answered, CANCEL has no effect on it
+    let (requests, _) = split(simulation.take_events(bob));
+    assert_eq!(requests.len(), 1);
+    assert_eq!(method(&requests[0].1), SipMethod::CANCEL);
+    answer(&mut simulation, bob, &requests[0], 200, "OK", "a6c85cf");
+    let (_, invite) = SipRequest::parse(&invite_request.1).unwrap();
+    let terminated = response(&invite, 487, "Request Terminated", Some("a6c85cf"));
+    assert_eq!(
+        simulation
+            .engine(bob)
+            .send_response(invite_request.0.as_ref().unwrap(), terminated),
+        Err(Error::InvalidState)
+    );
+    simulation.run_for(Duration::from_millis(100));
+
+    let (_, responses) = split(simulation.take_events(alice));
+    let codes: Vec<(bool, u16)> = responses
+        .iter()
+        .map(|(key, message)| (*key == cancel_transaction, status(message)))
+        .collect();
+    assert_eq!(
+        codes,
+        vec![(false, 100), (false, 180), (false, 200), (true, 200)]
+    );
+    assert_eq!(responses[2].0, transaction);
+
+    // Alice confirms and ends the call
+    let ack = request(
+        "ACK",
+        "sip:bob@192.0.2.4",
+        "z9hG4bK776asdhdt",
+        Some("a6c85cf"),
+        1,
+    );
+    let bye = request(
+        "BYE",
+        "sip:bob@192.0.2.4",
+        "z9hG4bK776asdhdu",
+        Some("a6c85cf"),
+        2,
+    );
+    let engine = simulation.engine(alice);
+    engine.send_request(ack, bob, TransportKind::Udp).unwrap();
+    engine.send_request(bye, bob, TransportKind::Udp).unwrap();
+    simulation.run_for(Duration::from_millis(100));
+    let (requests, _) = split(simulation.take_events(bob));
+    let methods: Vec<SipMethod> = requests
+        .iter()
+        .map(|(_, message)| method(message))
+        .collect();
+    assert_eq!(methods, vec![SipMethod::ACK, SipMethod::BYE]);
+    answer(&mut simulation, bob, &requests[1], 200, "OK", "a6c85cf");
+    simulation.run_for(Duration::from_millis(100));
+    let (_, responses) = split(simulation.take_events(alice));
+    assert_eq!(status(&responses[0].1), 200);
+
+    assert_idle(&mut simulation, &[alice, bob]);
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use common::{addr, deliver, drain};
+use sipcore::clock::{Clock, ManualClock};
+use sipcore::engine::{Engine, Event};
+use sipcore::message::response;
+use sipcore::registration::{Registration, RegistrationState};
+use sipcore::transaction::{Error, TimerSettings, TransactionKey};
+use sipcore::transport::TransportKind;
+use sipcore::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const CALL_ID: &str = "843817637684230@998sdasdh09";
+
+fn registration(expires: u32) -> Registration {
+    Registration::new(
+        "sip:registrar.biloxi.com",
+        "sip:bob@biloxi.com",
+        "sip:bob@192.0.2.4",
+        "SIP/2.0/UDP 192.0.2.4:5060",
+        CALL_ID,
+        "456248",
+        expires,
+    )
+}
+
+/// Delivers datagrams of `from` to `to` and returns events of `from`
+fn transfer<C: Clock>(from: &mut Engine<C>, source: SocketAddr, to: &mut Engine<C>) -> Vec<Event> {
+    let (datagrams, events) = drain(from);
+    for datagram in &datagrams {
+        deliver(to, datagram, source);
+    }
+    events
+}
+
+/// REGISTER requests that are received by registrar
+fn take_registers<C: Clock>(registrar: &mut Engine<C>) -> Vec<(TransactionKey, Vec<u8>)> {
+    let (_, events) = drain(registrar);
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Request {
+                transaction,
+                message,
+                ..
+            } => (transaction.unwrap(), message),
+            event => panic!("REGISTER is expected, got {:?}", event),
+        })
+        .collect()
+}
+
+fn header<'a>(message: &'a [u8], name: &str) -> Option<&'a str> {
+    let message = std::str::from_utf8(message).unwrap();
+    message
+        .split("\r\n")
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+}
+
+/// Registrar answers REGISTER with `code` and `headers`
+fn answer<C: Clock>(
+    registrar: &mut Engine<C>,
+    (transaction, message): &(TransactionKey, Vec<u8>),
+    code: u16,
+    reason: &str,
+    headers: &str,
+) {
+    let (_, request) = SipRequest::parse(message).unwrap();
+    let answer = response(&request, code, reason, Some("2493k59kd"));
+    let answer = String::from_utf8(answer)
+        .unwrap()
+        .replace("Content-Length", &format!("{}Content-Length", headers));
+    registrar
+        .send_response(transaction, answer.into_bytes())
+        .unwrap();
+}
+
+/// Registration states of events
+fn states(events: Vec<Event>) -> Vec<RegistrationState> {
+    events
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Registration { call_id, state } => {
+                assert_eq!(call_id, CALL_ID);
+                Some(state)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn register_refresh_unregister() {
+    let (bob_addr, registrar_addr) = (addr("192.0.2.4:5060"), addr("192.0.2.10:5060"));
+    let clock = ManualClock::new();
+    let mut bob = Engine::new(&clock, TimerSettings::default());
+    let mut registrar = Engine::new(&clock, TimerSettings::default());
+
+    bob.register(registration(60), registrar_addr, TransportKind::Udp)
+        .unwrap();
+    transfer(&mut bob, bob_addr, &mut registrar);
+    let registers = take_registers(&mut registrar);
+    assert_eq!(registers.len(), 1);
+    assert_eq!(header(&registers[0].1, "Expires"), Some("60"));
+
+    // too brief interval is raised to Min-Expires and REGISTER is sent again
+    answer(
+        &mut registrar,
+        &registers[0],
+        423,
+        "Interval Too Brief",
+        "Min-Expires: 1800\r\n",
+    );
+    transfer(&mut registrar, registrar_addr, &mut bob);
+    assert_eq!(bob.next_deadline(), Some(clock.now()));
+    bob.handle_timers();
+    transfer(&mut bob, bob_addr, &mut registrar);
+    let registers = take_registers(&mut registrar);
+    assert_eq!(registers.len(), 1);
+    assert_eq!(header(&registers[0].1, "Expires"), Some("1800"));
+    assert_eq!(header(&registers[0].1, "CSeq"), Some("2 REGISTER"));
+    answer(
+        &mut registrar,
+        &registers[0],
+        200,
+        "OK",
+        "Contact: <sip:bob@192.0.2.4>;expires=1200\r\n",
+    );
+    transfer(&mut registrar, registrar_addr, &mut bob);
+    let events = transfer(&mut bob, bob_addr, &mut registrar);
+    assert_eq!(states(events), vec![RegistrationState::Registered]);
+    assert_eq!(bob.registration(CALL_ID).unwrap().expires(), 1200);
+
+    // binding is refreshed at half of granted duration
+    clock.advance(Duration::from_secs(599));
+    bob.handle_timers();
+    transfer(&mut bob, bob_addr, &mut registrar);
+    assert!(take_registers(&mut registrar).is_empty());
+    clock.advance(Duration::from_secs(1));
+    bob.handle_timers();
+    transfer(&mut bob, bob_addr, &mut registrar);
+    let registers = take_registers(&mut registrar);
+    assert_eq!(registers.len(), 1);
+    assert_eq!(header(&registers[0].1, "CSeq"), Some("3 REGISTER"));
+    answer(
+        &mut registrar,
+        &registers[0],
+        200,
+        "OK",
+        "Expires: 1800\r\n",
+    );
+    transfer(&mut registrar, registrar_addr, &mut bob);
+    let events = transfer(&mut bob, bob_addr, &mut registrar);
+    assert!(states(events).is_empty());
+    let registration = bob.registration(CALL_ID).unwrap();
+    assert_eq!(registration.state(), RegistrationState::Registered);
+    assert_eq!(registration.expires(), 1800);
+
+    bob.unregister(CALL_ID).unwrap();
+    transfer(&mut bob, bob_addr, &mut registrar);
+    let registers = take_registers(&mut registrar);
+    assert_eq!(header(&registers[0].1, "Expires"), Some("0"));
+    answer(&mut registrar, &registers[0], 200, "OK", "");
+    transfer(&mut registrar, registrar_addr, &mut bob);
+    let events = transfer(&mut bob, bob_addr, &mut registrar);
+    assert_eq!(states(events), vec![RegistrationState::Unregistered]);
+    assert!(bob.registration(CALL_ID).is_none());
+    assert_eq!(bob.unregister(CALL_ID), Err(Error::InvalidState));
+
+    clock.advance(Duration::from_secs(64));
+    bob.handle_timers();
+    assert_eq!(bob.next_deadline(), None);
+}
+
+#[test]
+fn registrar_is_unreachable() {
+    let clock = ManualClock::new();
+    let mut bob = Engine::new(&clock, TimerSettings::default());
+
+    bob.register(
+        registration(3600),
+        addr("192.0.2.10:5060"),
+        TransportKind::Udp,
+    )
+    .unwrap();
+    // every REGISTER is lost
+    let mut events = vec![];
+    while let Some(deadline) = bob.next_deadline() {
+        clock.advance(deadline - clock.now());
+        bob.handle_timers();
+        events.extend(drain(&mut bob).1);
+    }
+    assert!(matches!(events[0], Event::Timeout { .. }));
+    assert_eq!(
+        events[1],
+        Event::Registration {
+            call_id: CALL_ID.to_string(),
+            state: RegistrationState::Failed
+        }
+    );
+    assert!(bob.registration(CALL_ID).is_none());
+    assert_eq!(bob.transactions_count(), 0);
+}
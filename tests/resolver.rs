@@ -0,0 +1,189 @@
+use sipcore::resolver::*;
+use sipcore::transport::{TransportKind, TransportSelector, UdpTransport};
+use sipcore::SipUri;
+use std::io;
+use std::net::SocketAddr;
+
+use TransportKind::*;
+
+/// Zone of example of rfc3263 section-4.1
+fn zone() -> StaticZone {
+    let mut zone = StaticZone::new();
+    zone.add_naptr(
+        "example.com",
+        NaptrRecord::new(90, 50, "s", "SIP+D2T", "_sip._tcp.example.com."),
+    )
+    .add_naptr(
+        "example.com",
+        NaptrRecord::new(100, 50, "s", "SIP+D2U", "_sip._udp.example.com."),
+    )
+    .add_naptr(
+        "example.com",
+        NaptrRecord::new(50, 50, "s", "SIPS+D2T", "_sips._tcp.example.com."),
+    )
+    .add_srv(
+        "_sip._tcp.example.com",
+        SrvRecord::new(0, 0, 5060, "server1.example.com"),
+    )
+    .add_srv(
+        "_sip._udp.example.com",
+        SrvRecord::new(0, 0, 5060, "server1.example.com"),
+    )
+    .add_srv(
+        "_sip._udp.example.com",
+        SrvRecord::new(1, 0, 5060, "server2.example.com"),
+    )
+    .add_srv(
+        "_sips._tcp.example.com",
+        SrvRecord::new(0, 0, 5061, "server1.example.com"),
+    )
+    .add_ip("server1.example.com", "192.0.2.1".parse().unwrap())
+    .add_ip("server2.example.com", "192.0.2.2".parse().unwrap())
+    .add_ip("server2.example.com", "2001:db8::2".parse().unwrap())
+    .add_ip("example.com", "192.0.2.100".parse().unwrap())
+    // no NAPTR and SRV
+    .add_ip("atlanta.com", "192.0.2.10".parse().unwrap())
+    // SRV without NAPTR
+    .add_srv(
+        "_sip._tcp.biloxi.com",
+        SrvRecord::new(0, 0, 5070, "sip.biloxi.com"),
+    )
+    .add_ip("sip.biloxi.com", "192.0.2.20".parse().unwrap());
+    zone
+}
+
+fn resolve(transports: &[TransportKind], uri: &str) -> Vec<(String, TransportKind)> {
+    let resolver = Resolver::new(zone(), transports);
+    let (_, uri) = SipUri::parse(uri.as_bytes()).unwrap();
+    resolver
+        .resolve(&uri)
+        .unwrap()
+        .map(|target| (target.addr.to_string(), target.transport))
+        .collect()
+}
+
+fn target(addr: &str, transport: TransportKind) -> (String, TransportKind) {
+    (addr.to_owned(), transport)
+}
+
+#[test]
+fn naptr_srv_a() {
+    // TLS has the lowest order
+    assert_eq!(
+        resolve(&[Udp, Tcp, Tls], "sip:bob@example.com"),
+        vec![
+            target("192.0.2.1:5061", Tls),
+            target("192.0.2.1:5060", Tcp),
+            target("192.0.2.1:5060", Udp),
+            target("192.0.2.2:5060", Udp),
+            target("[2001:db8::2]:5060", Udp),
+        ]
+    );
+    // records of unsupported transports are ignored
+    assert_eq!(
+        resolve(&[Udp], "sip:bob@EXAMPLE.com"),
+        vec![
+            target("192.0.2.1:5060", Udp),
+            target("192.0.2.2:5060", Udp),
+            target("[2001:db8::2]:5060", Udp),
+        ]
+    );
+    assert_eq!(
+        resolve(&[Udp, Tcp, Tls], "sips:bob@example.com"),
+        vec![target("192.0.2.1:5061", Tls)]
+    );
+
+    // SRV of supported transports without NAPTR
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:bob@biloxi.com"),
+        vec![target("192.0.2.20:5070", Tcp)]
+    );
+    // A record without NAPTR and SRV
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:alice@atlanta.com"),
+        vec![target("192.0.2.10:5060", Udp)]
+    );
+    assert_eq!(
+        resolve(&[Udp, Tcp, Tls], "sips:alice@atlanta.com"),
+        vec![target("192.0.2.10:5061", Tls)]
+    );
+    assert!(resolve(&[Udp], "sip:carol@chicago.com").is_empty());
+}
+
+#[test]
+fn transport_parameter() {
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:bob@example.com;transport=tcp"),
+        vec![target("192.0.2.1:5060", Tcp)]
+    );
+    assert_eq!(
+        resolve(&[Udp, Tcp, Tls], "sips:bob@example.com;transport=tcp"),
+        vec![target("192.0.2.1:5061", Tls)]
+    );
+    // no SRV of TCP
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:alice@atlanta.com;transport=TCP"),
+        vec![target("192.0.2.10:5060", Tcp)]
+    );
+
+    let resolver = Resolver::new(zone(), &[Udp]);
+    for uri in &[
+        "sip:bob@example.com;transport=xyz",
+        "sips:bob@example.com;transport=udp",
+    ] {
+        let (_, uri) = SipUri::parse(uri.as_bytes()).unwrap();
+        assert_eq!(
+            resolver.resolve(&uri).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
+
+#[test]
+fn numeric_ip_and_explicit_port() {
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:bob@192.0.2.4"),
+        vec![target("192.0.2.4:5060", Udp)]
+    );
+    assert_eq!(
+        resolve(&[Udp, Tcp, Tls], "sips:bob@192.0.2.4"),
+        vec![target("192.0.2.4:5061", Tls)]
+    );
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:bob@[2001:db8::4]:5080;transport=tcp"),
+        vec![target("[2001:db8::4]:5080", Tcp)]
+    );
+    // NAPTR and SRV are not used with explicit port
+    assert_eq!(
+        resolve(&[Udp, Tcp], "sip:bob@example.com:5080"),
+        vec![target("192.0.2.100:5080", Udp)]
+    );
+}
+
+#[test]
+fn failover_and_transport_selection() {
+    let mut selector = TransportSelector::new();
+    selector.add(UdpTransport::bind("127.0.0.1:0").unwrap());
+    let resolver = Resolver::new(zone(), &[Tcp, Udp]);
+    let (_, uri) = SipUri::parse(b"sip:bob@example.com").unwrap();
+    let mut targets = resolver.resolve(&uri).unwrap();
+    assert_eq!(targets.transports(), vec![Tcp, Udp]);
+
+    // TCP isn't added to selector
+    assert_eq!(selector.select(&uri, 500, &targets.transports()), Ok(Udp));
+    let mut tried = vec![];
+    for target in targets.by_ref() {
+        if target.transport != Udp {
+            continue;
+        }
+        tried.push(target.addr);
+        // server responded with 503 (Service Unavailable), next one is tried
+    }
+    let expected: Vec<SocketAddr> = vec![
+        "192.0.2.1:5060".parse().unwrap(),
+        "192.0.2.2:5060".parse().unwrap(),
+        "[2001:db8::2]:5060".parse().unwrap(),
+    ];
+    assert_eq!(tried, expected);
+    assert!(targets.is_empty());
+}
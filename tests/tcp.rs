@@ -0,0 +1,227 @@
+mod common;
+
+use common::{message, poll};
+use sipcore::clock::SystemClock;
+use sipcore::engine::*;
+use sipcore::message::response;
+use sipcore::transaction::TimerSettings;
+use sipcore::transport::{StreamEvent, StreamSettings, TcpTransport, TransportKind};
+use sipcore::*;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn bind(settings: StreamSettings) -> TcpTransport {
+    TcpTransport::bind("127.0.0.1:0", settings).unwrap()
+}
+
+fn options(via: &str) -> String {
+    format!(
+        "OPTIONS sip:bob@127.0.0.1 SIP/2.0\r\n\
+Via: SIP/2.0/TCP {};branch=z9hG4bKnashds7\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+To: <sip:bob@biloxi.com>\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 63104 OPTIONS\r\n\
+Content-Length: 0\r\n\r\n",
+        via
+    )
+}
+
+#[test]
+fn connection_reuse() {
+    let mut alice = bind(StreamSettings::default());
+    let mut bob = bind(StreamSettings::default());
+    let alice_addr = alice.local_addr().unwrap();
+    let bob_addr = bob.local_addr().unwrap();
+
+    let request = options(&format!("{};alias", alice_addr));
+    alice
+        .send_to(request.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    let (data, source) = message(poll(&mut bob, &mut alice).0);
+    assert_ne!(source, alice_addr);
+    assert!(String::from_utf8(data.clone())
+        .unwrap()
+        .contains(";alias;branch=z9hG4bKnashds7\r\n"));
+
+    // response goes back over inbound connection
+    let (_, request) = SipRequest::parse(&data).unwrap();
+    let ok = response(&request, 200, "OK", Some("93810874"));
+    bob.send_to(&ok, source, Instant::now()).unwrap();
+    assert_eq!(message(poll(&mut alice, &mut bob).0), (ok, bob_addr));
+
+    // alias isn't accepted from unauthenticated peer, request opens new connection
+    assert_eq!(bob.connection_for(alice_addr), None);
+    let bye = options(&format!("{}", bob_addr)).replace("OPTIONS", "BYE");
+    bob.send_to(bye.as_bytes(), alice_addr, Instant::now())
+        .unwrap();
+    let (data, _) = message(poll(&mut alice, &mut bob).0);
+    assert!(data.starts_with(b"BYE "));
+    assert_eq!(alice.connections_count(), 2);
+    assert_eq!(bob.connections_count(), 2);
+}
+
+#[test]
+fn limits_and_idle_timeout() {
+    let settings = StreamSettings {
+        max_message_size: 512,
+        ..StreamSettings::default()
+    };
+    let mut alice = bind(StreamSettings::default());
+    let mut bob = bind(settings.clone());
+    let bob_addr = bob.local_addr().unwrap();
+
+    let request = options("127.0.0.1");
+    alice
+        .send_to(request.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    let (_, source) = message(poll(&mut bob, &mut alice).0);
+    assert_eq!(bob.connections_count(), 1);
+    let later = Instant::now() + settings.idle_timeout;
+    match bob.poll(later).unwrap().remove(0) {
+        StreamEvent::Closed {
+            peer,
+            error: Some(error),
+        } => {
+            assert_eq!(peer, source);
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        }
+        event => panic!("idle connection must be closed, got {:?}", event),
+    }
+    assert_eq!(bob.connections_count(), 0);
+    match poll(&mut alice, &mut bob).0.remove(0) {
+        StreamEvent::Closed { peer, error: None } => assert_eq!(peer, bob_addr),
+        event => panic!("connection must be closed, got {:?}", event),
+    }
+
+    let big = request.replace("Content-Length: 0", "Content-Length: 1000");
+    alice
+        .send_to(big.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    match poll(&mut bob, &mut alice).0.remove(0) {
+        StreamEvent::Closed {
+            error: Some(error), ..
+        } => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+        event => panic!("connection must be closed, got {:?}", event),
+    }
+}
+
+#[test]
+fn connection_failure_is_transport_error() {
+    let mut alice = bind(StreamSettings::default());
+    let mut bob = bind(StreamSettings::default());
+    let bob_addr = bob.local_addr().unwrap();
+    let mut engine = Engine::new(SystemClock, TimerSettings::default());
+
+    let transaction = engine
+        .send_request(
+            options("127.0.0.1").into_bytes(),
+            bob_addr,
+            TransportKind::Tcp,
+        )
+        .unwrap()
+        .unwrap();
+    match engine.poll_output() {
+        Some(Output::Send(datagram)) => alice.send(&datagram, Instant::now()).unwrap(),
+        output => panic!("request is expected, got {:?}", output),
+    }
+    let (_, source) = message(poll(&mut bob, &mut alice).0);
+    bob.close(source);
+
+    match poll(&mut alice, &mut bob).0.remove(0) {
+        StreamEvent::Closed { peer, .. } => {
+            assert_eq!(peer, bob_addr);
+            engine.handle_connection_failure(peer, TransportKind::Tcp);
+        }
+        event => panic!("connection must be closed, got {:?}", event),
+    }
+    let mut events = vec![];
+    while let Some(output) = engine.poll_output() {
+        events.push(output);
+    }
+    assert_eq!(
+        events,
+        vec![Output::Event(Event::TransportError { transaction })]
+    );
+    assert_eq!(engine.transactions_count(), 0);
+
+    // nobody listens, refusal is seen by send or later by poll
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let error = match alice.send_to(b"OPTIONS", closed, Instant::now()) {
+        Err(error) => error,
+        Ok(()) => match poll(&mut alice, &mut bob).0.remove(0) {
+            StreamEvent::Closed {
+                peer,
+                error: Some(error),
+            } if peer == closed => error,
+            event => panic!("connection must fail, got {:?}", event),
+        },
+    };
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    assert_eq!(alice.connections_count(), 0);
+}
+
+#[test]
+fn read_is_limited() {
+    let settings = StreamSettings {
+        max_message_size: 512,
+        ..StreamSettings::default()
+    };
+    let mut alice = bind(StreamSettings::default());
+    let mut bob = bind(settings);
+    let bob_addr = bob.local_addr().unwrap();
+
+    let request = options("127.0.0.1");
+    alice
+        .send_to(request.repeat(20).as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let mut received = poll(&mut bob, &mut alice).0.len();
+    assert!(received < 20);
+    while received < 20 {
+        received += poll(&mut bob, &mut alice).0.len();
+    }
+    assert_eq!(received, 20);
+    assert_eq!(bob.connections_count(), 1);
+}
+
+#[test]
+fn message_over_parse_limits_is_dropped() {
+    let mut alice = bind(StreamSettings::default());
+    let mut bob = bind(StreamSettings::default());
+    let bob_addr = bob.local_addr().unwrap();
+
+    // 71 Via entries
+    let request = options("127.0.0.1");
+    let via = "Via: SIP/2.0/TCP p1.example.com;branch=z9hG4bK1\r\n";
+    let many_vias = request.replacen("From", &format!("{}From", via.repeat(70)), 1);
+    alice
+        .send_to(many_vias.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    alice
+        .send_to(request.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    let (data, _) = message(poll(&mut bob, &mut alice).0);
+    assert!(String::from_utf8(data)
+        .unwrap()
+        .contains("From: Alice <sip:alice@atlanta.com>"));
+    assert_eq!(bob.connections_count(), 1);
+
+    // limits are configurable
+    let settings = StreamSettings {
+        parse_options: ParseOptions::strict(),
+        ..StreamSettings::default()
+    };
+    let mut bob = bind(settings);
+    let bob_addr = bob.local_addr().unwrap();
+    alice
+        .send_to(many_vias.as_bytes(), bob_addr, Instant::now())
+        .unwrap();
+    let (data, _) = message(poll(&mut bob, &mut alice).0);
+    assert_eq!(data, many_vias.as_bytes());
+}
@@ -0,0 +1,228 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use common::{message, poll};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::RootCertStore;
+use sipcore::message::response;
+use sipcore::transport::tls::is_valid_for_domain;
+use sipcore::transport::{
+    ClientAuth, StreamEvent, StreamSettings, TlsConfig, TlsTransport, TransportKind, WsTransport,
+};
+use sipcore::*;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+const CERTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/");
+
+fn certificate(name: &str) -> CertificateDer<'static> {
+    CertificateDer::from_pem_file(format!("{}{}.pem", CERTS, name)).unwrap()
+}
+
+fn roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate("ca")).unwrap();
+    roots
+}
+
+fn config(name: &str, client_auth: ClientAuth) -> TlsConfig {
+    let key = PrivateKeyDer::from_pem_file(format!("{}{}.key", CERTS, name)).unwrap();
+    TlsConfig::new(roots(), vec![certificate(name)], key, client_auth).unwrap()
+}
+
+fn bind(config: TlsConfig) -> TlsTransport {
+    TlsTransport::bind("127.0.0.1:0", StreamSettings::default(), config).unwrap()
+}
+
+fn options() -> &'static str {
+    "OPTIONS sips:bob@biloxi.example.com SIP/2.0\r\n\
+Via: SIP/2.0/TLS 127.0.0.1;branch=z9hG4bKnashds7\r\n\
+From: Alice <sips:alice@atlanta.example.com>;tag=1928301774\r\n\
+To: <sips:bob@biloxi.example.com>\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 63104 OPTIONS\r\n\
+Content-Length: 0\r\n\r\n"
+}
+
+fn closed_with_certificate_error(events: Vec<StreamEvent>, peer: SocketAddr) {
+    match events.into_iter().next() {
+        Some(StreamEvent::Closed {
+            peer: closed,
+            error: Some(error),
+        }) => {
+            assert_eq!(closed, peer);
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        event => panic!("connection must fail, got {:?}", event),
+    }
+}
+
+#[test]
+fn mutual_authentication() {
+    let mut atlanta = bind(config("atlanta", ClientAuth::Disabled));
+    let mut biloxi = bind(config("biloxi", ClientAuth::Required));
+    let biloxi_addr = biloxi.local_addr().unwrap();
+
+    atlanta
+        .send_to(
+            options().as_bytes(),
+            biloxi_addr,
+            "biloxi.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (data, source) = message(poll(&mut biloxi, &mut atlanta).0);
+    let client_certificates = biloxi.peer_certificates(source).unwrap();
+    assert!(is_valid_for_domain(
+        &client_certificates[0],
+        "atlanta.example.com"
+    ));
+
+    let (_, request) = SipRequest::parse(&data).unwrap();
+    let ok = response(&request, 200, "OK", Some("93810874"));
+    biloxi.send_to(&ok, source, "", Instant::now()).unwrap();
+    assert_eq!(
+        message(poll(&mut atlanta, &mut biloxi).0),
+        (ok, biloxi_addr)
+    );
+}
+
+#[test]
+fn sip_domain_validation() {
+    let mut atlanta = bind(config("atlanta", ClientAuth::Disabled));
+    let mut biloxi = bind(config("biloxi", ClientAuth::Disabled));
+    let biloxi_addr = biloxi.local_addr().unwrap();
+
+    // wildcard dNSName and SIP URI with user part don't identify domain
+    for domain in &["proxy.biloxi.example.com", "other.example.com"] {
+        atlanta
+            .send_to(options().as_bytes(), biloxi_addr, domain, Instant::now())
+            .unwrap();
+        let (atlanta_events, _) = poll(&mut atlanta, &mut biloxi);
+        closed_with_certificate_error(atlanta_events, biloxi_addr);
+    }
+    assert_eq!(atlanta.connections_count(), 0);
+
+    // dNSName doesn't count when certificate has SIP URI
+    let mut chicago = bind(config("chicago", ClientAuth::Disabled));
+    let chicago_addr = chicago.local_addr().unwrap();
+    atlanta
+        .send_to(
+            options().as_bytes(),
+            chicago_addr,
+            "proxy.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (atlanta_events, _) = poll(&mut atlanta, &mut chicago);
+    closed_with_certificate_error(atlanta_events, chicago_addr);
+
+    // CA isn't trusted
+    let mut untrusted = RootCertStore::empty();
+    untrusted.add(certificate("atlanta")).unwrap();
+    let mut mallory = bind(TlsConfig::client_only(untrusted).unwrap());
+    mallory
+        .send_to(
+            options().as_bytes(),
+            biloxi_addr,
+            "biloxi.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (mallory_events, _) = poll(&mut mallory, &mut biloxi);
+    closed_with_certificate_error(mallory_events, biloxi_addr);
+}
+
+#[test]
+fn client_certificate_is_required() {
+    let mut biloxi = bind(config("biloxi", ClientAuth::Required));
+    let mut anonymous = bind(TlsConfig::client_only(roots()).unwrap());
+    let biloxi_addr = biloxi.local_addr().unwrap();
+
+    anonymous
+        .send_to(
+            options().as_bytes(),
+            biloxi_addr,
+            "biloxi.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (biloxi_events, _) = poll(&mut biloxi, &mut anonymous);
+    match biloxi_events.into_iter().next() {
+        Some(StreamEvent::Closed { error: Some(_), .. }) => {}
+        event => panic!("connection must fail, got {:?}", event),
+    }
+}
+
+/// Sends request with `alias` of Via from `client` to `server`. Returns source of request
+fn send_with_alias(
+    server: &mut TlsTransport,
+    client: &mut TlsTransport,
+    alias: SocketAddr,
+) -> SocketAddr {
+    let request = options().replace("TLS 127.0.0.1;", &format!("TLS {};alias;", alias));
+    client
+        .send_to(
+            request.as_bytes(),
+            server.local_addr().unwrap(),
+            "biloxi.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (data, source) = message(poll(server, client).0);
+    assert!(data.starts_with(b"OPTIONS "));
+    source
+}
+
+#[test]
+fn alias_of_authenticated_client() {
+    let mut biloxi = bind(config("biloxi", ClientAuth::Optional));
+    let mut atlanta = bind(config("atlanta", ClientAuth::Disabled));
+    let mut anonymous = bind(TlsConfig::client_only(roots()).unwrap());
+    let mut mallory = bind(config("atlanta", ClientAuth::Disabled));
+    let atlanta_addr = atlanta.local_addr().unwrap();
+    let anonymous_addr = anonymous.local_addr().unwrap();
+
+    let source = send_with_alias(&mut biloxi, &mut atlanta, atlanta_addr);
+    assert_eq!(biloxi.connection_for(atlanta_addr), Some(source));
+
+    // anonymous client can't set alias, alias of other connection isn't replaced
+    send_with_alias(&mut biloxi, &mut anonymous, anonymous_addr);
+    send_with_alias(&mut biloxi, &mut mallory, atlanta_addr);
+    assert_eq!(biloxi.connection_for(anonymous_addr), None);
+    assert_eq!(biloxi.connection_for(atlanta_addr), Some(source));
+    assert_eq!(biloxi.connections_count(), 3);
+}
+
+#[test]
+fn secure_websocket() {
+    let mut atlanta = WsTransport::bind_secure(
+        "127.0.0.1:0",
+        StreamSettings::default(),
+        config("atlanta", ClientAuth::Disabled),
+    )
+    .unwrap();
+    let mut biloxi = WsTransport::bind_secure(
+        "127.0.0.1:0",
+        StreamSettings::default(),
+        config("biloxi", ClientAuth::Disabled),
+    )
+    .unwrap();
+    assert_eq!(biloxi.kind(), TransportKind::Wss);
+    let biloxi_addr = biloxi.local_addr().unwrap();
+
+    let request = options().replace("SIP/2.0/TLS", "SIP/2.0/WSS");
+    atlanta
+        .send_to(
+            request.as_bytes(),
+            biloxi_addr,
+            "biloxi.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (data, _) = message(poll(&mut biloxi, &mut atlanta).0);
+    assert!(data.starts_with(b"OPTIONS "));
+}
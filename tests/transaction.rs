@@ -0,0 +1,96 @@
+use sipcore::clock::{Clock, ManualClock};
+use sipcore::message::response;
+use sipcore::transaction::*;
+use sipcore::*;
+use std::time::Duration;
+
+const INVITE: &str = "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+Max-Forwards: 70\r\n\
+To: Bob <sip:bob@biloxi.com>\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+CSeq: 314159 INVITE\r\n\
+Content-Length: 0\r\n\r\n";
+
+fn sent(actions: &[Action]) -> Vec<Vec<u8>> {
+    actions
+        .iter()
+        .filter_map(|action| match action {
+            Action::Send(message) => Some(message.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn invite_rejected_over_lossy_udp() {
+    let clock = ManualClock::new();
+    let settings = TimerSettings::default();
+    let (mut client, actions) =
+        InviteClientTransaction::new(INVITE.as_bytes().to_vec(), false, settings, clock.now())
+            .unwrap();
+    // first INVITE is lost, Timer A retransmits it
+    assert_eq!(sent(&actions).len(), 1);
+    clock.advance(Duration::from_millis(500));
+    let invite = sent(&client.on_timer(clock.now())).remove(0);
+
+    let (_, request) = SipRequest::parse(&invite).unwrap();
+    let (mut server, actions) = InviteServerTransaction::new(&request, false, settings).unwrap();
+    assert_eq!(server.key(), &TransactionKey::server(&request).unwrap());
+    let trying = sent(&actions).remove(0);
+    let (_, trying) = SipResponse::parse(&trying).unwrap();
+    assert_eq!(
+        TransactionKey::from_response(&trying).as_ref(),
+        Some(client.key())
+    );
+    assert_eq!(
+        client.on_response(&trying, clock.now()),
+        vec![Action::PassToTu]
+    );
+    assert_eq!(client.state(), State::Proceeding);
+
+    // TU of server rejects call. First 486 is lost, Timer G retransmits it
+    let busy = response(&request, 486, "Busy Here", Some("a6c85cf"));
+    server.send_response(busy, clock.now()).unwrap();
+    clock.advance(Duration::from_millis(500));
+    let busy = sent(&server.on_timer(clock.now())).remove(0);
+    let (_, busy) = SipResponse::parse(&busy).unwrap();
+
+    let actions = client.on_response(&busy, clock.now());
+    assert_eq!(actions[0], Action::PassToTu);
+    let ack = sent(&actions).remove(0);
+    let (_, ack) = SipRequest::parse(&ack).unwrap();
+    assert_eq!(TransactionKey::server(&ack).as_ref(), Some(server.key()));
+    assert_eq!(server.on_request(&ack, clock.now()), vec![]);
+    assert_eq!(server.state(), State::Confirmed);
+
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(server.on_timer(clock.now()), vec![Action::Terminated]);
+    clock.advance(Duration::from_secs(27));
+    assert_eq!(client.on_timer(clock.now()), vec![Action::Terminated]);
+}
+
+#[test]
+fn non_invite_over_tcp() {
+    let clock = ManualClock::new();
+    let settings = TimerSettings::default();
+    let bye = INVITE.replace("INVITE", "BYE");
+    let (mut client, actions) =
+        NonInviteClientTransaction::new(bye.into_bytes(), true, settings, clock.now()).unwrap();
+    let bye = sent(&actions).remove(0);
+    let (_, request) = SipRequest::parse(&bye).unwrap();
+    let (mut server, _) = NonInviteServerTransaction::new(&request, true, settings).unwrap();
+
+    let ok = response(&request, 200, "OK", None);
+    assert_eq!(
+        server.send_response(ok.clone(), clock.now()),
+        Ok(vec![Action::Send(ok.clone()), Action::Terminated])
+    );
+    let (_, ok) = SipResponse::parse(&ok).unwrap();
+    assert_eq!(
+        client.on_response(&ok, clock.now()),
+        vec![Action::PassToTu, Action::Terminated]
+    );
+    assert_eq!(client.next_deadline(), None);
+}
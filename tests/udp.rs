@@ -0,0 +1,134 @@
+use sipcore::clock::SystemClock;
+use sipcore::engine::*;
+use sipcore::message::response;
+use sipcore::transaction::TimerSettings;
+use sipcore::transport::{TransportKind, UdpTransport};
+use sipcore::*;
+use std::time::Duration;
+
+fn bind() -> UdpTransport {
+    let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+    transport
+        .socket()
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    transport
+}
+
+fn send_all(engine: &mut Engine<SystemClock>, transport: &UdpTransport) -> Vec<Event> {
+    let mut events = vec![];
+    while let Some(output) = engine.poll_output() {
+        match output {
+            Output::Send(datagram) => transport.send(&datagram).unwrap(),
+            Output::Event(event) => events.push(event),
+        }
+    }
+    events
+}
+
+#[test]
+fn options_over_loopback() {
+    let (alice_udp, bob_udp) = (bind(), bind());
+    let mut alice = Engine::new(SystemClock, TimerSettings::default());
+    let mut bob = Engine::new(SystemClock, TimerSettings::default());
+
+    // sent-by can't be used for response, it is answered by received and rport
+    let options = "OPTIONS sip:bob@127.0.0.1 SIP/2.0\r\n\
+Via: SIP/2.0/UDP alice.invalid;rport;branch=z9hG4bKnashds7\r\n\
+From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+To: <sip:bob@biloxi.com>\r\n\
+Call-ID: a84b4c76e66710\r\n\
+CSeq: 63104 OPTIONS\r\n\
+Content-Length: 0\r\n\r\n";
+    let transaction = alice
+        .send_request(
+            options.as_bytes().to_vec(),
+            bob_udp.local_addr().unwrap(),
+            TransportKind::Udp,
+        )
+        .unwrap();
+    assert!(send_all(&mut alice, &alice_udp).is_empty());
+
+    // garbage is dropped by transport
+    alice_udp
+        .send_to(b"\r\n\r\n", bob_udp.local_addr().unwrap())
+        .unwrap();
+    let (data, source) = bob_udp.recv().unwrap();
+    assert_eq!(source, alice_udp.local_addr().unwrap());
+    let incoming = Incoming::parse(&data, source, TransportKind::Udp).unwrap();
+    let request = incoming.message.request().unwrap();
+    let via = request.headers.get_rfc_s(SipRFCHeader::Via).unwrap();
+    assert_eq!(
+        via.params().unwrap().get("received"),
+        Some(&Some("127.0.0.1"))
+    );
+    bob.handle_incoming(&incoming);
+    let server_transaction = match &send_all(&mut bob, &bob_udp)[..] {
+        [Event::Request {
+            transaction: Some(transaction),
+            ..
+        }] => transaction.clone(),
+        events => panic!("OPTIONS is expected, got {:?}", events),
+    };
+
+    let ok = response(request, 200, "OK", Some("93810874"));
+    bob.send_response(&server_transaction, ok.clone()).unwrap();
+    assert!(send_all(&mut bob, &bob_udp).is_empty());
+
+    let (data, source) = alice_udp.recv().unwrap();
+    assert_eq!(data, ok);
+    let incoming = Incoming::parse(&data, source, TransportKind::Udp).unwrap();
+    alice.handle_incoming(&incoming);
+    assert_eq!(
+        send_all(&mut alice, &alice_udp),
+        vec![Event::Response {
+            transaction,
+            message: ok,
+            source,
+            transport: TransportKind::Udp
+        }]
+    );
+}
+
+#[test]
+fn response_to_via() {
+    let (alice_udp, bob_udp) = (bind(), bind());
+    let response = format!(
+        "SIP/2.0 200 OK\r\n\
+Via: SIP/2.0/UDP alice.invalid;received=127.0.0.1;rport={}\r\n\
+CSeq: 1 OPTIONS\r\n\r\n",
+        alice_udp.local_addr().unwrap().port()
+    );
+    assert_eq!(
+        bob_udp.send_response(response.as_bytes()).unwrap(),
+        alice_udp.local_addr().unwrap()
+    );
+    let (data, source) = alice_udp.recv().unwrap();
+    assert_eq!(data, response.as_bytes());
+    assert_eq!(source, bob_udp.local_addr().unwrap());
+
+    let unresolved = "SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP alice.invalid\r\nCSeq: 1 OPTIONS\r\n\r\n";
+    assert!(bob_udp.send_response(unresolved.as_bytes()).is_err());
+}
+
+#[test]
+fn oversized_datagram_is_dropped() {
+    let (alice_udp, bob_udp) = (bind(), bind());
+    let bob_addr = bob_udp.local_addr().unwrap();
+    let options = "OPTIONS sip:bob@127.0.0.1 SIP/2.0\r\n\
+Via: SIP/2.0/UDP 127.0.0.1;branch=z9hG4bKnashds7\r\n\
+CSeq: 1 OPTIONS\r\n\r\n";
+    let via = "Via: SIP/2.0/UDP p1.example.com;branch=z9hG4bK1\r\n";
+    let many_vias = options.replacen("CSeq", &format!("{}CSeq", via.repeat(70)), 1);
+    alice_udp.send_to(many_vias.as_bytes(), bob_addr).unwrap();
+    alice_udp.send_to(options.as_bytes(), bob_addr).unwrap();
+    let (data, _) = bob_udp.recv().unwrap();
+    assert_eq!(data, options.as_bytes());
+
+    // limits are configurable
+    let bob_udp = bind().with_parse_options(ParseOptions::strict());
+    let bob_addr = bob_udp.local_addr().unwrap();
+    alice_udp.send_to(many_vias.as_bytes(), bob_addr).unwrap();
+    let (data, _) = bob_udp.recv().unwrap();
+    assert_eq!(data, many_vias.as_bytes());
+}
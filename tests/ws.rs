@@ -0,0 +1,137 @@
+mod common;
+
+use common::{message, poll};
+use sipcore::message::response;
+use sipcore::transport::{StreamEvent, StreamSettings, TransportKind, WsTransport};
+use sipcore::*;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn bind() -> WsTransport {
+    WsTransport::bind("127.0.0.1:0", StreamSettings::default()).unwrap()
+}
+
+/// REGISTER of browser, example of rfc7118 section-8.1
+fn register() -> &'static str {
+    "REGISTER sip:proxy.example.com SIP/2.0\r\n\
+Via: SIP/2.0/WS df7jal23ls0d.invalid;branch=z9hG4bKasudf\r\n\
+From: sip:alice@example.com;tag=65bnmj.34asd\r\n\
+To: sip:alice@example.com\r\n\
+Call-ID: aiuy7k9njasd\r\n\
+CSeq: 1 REGISTER\r\n\
+Max-Forwards: 70\r\n\
+Supported: path, outbound, gruu\r\n\
+Contact: <sip:alice@df7jal23ls0d.invalid;transport=ws>;expires=300\r\n\
+Content-Length: 0\r\n\r\n"
+}
+
+#[test]
+fn register_from_browser() {
+    let mut alice = bind();
+    let mut proxy = bind();
+    let proxy_addr = proxy.local_addr().unwrap();
+    assert_eq!(proxy.kind(), TransportKind::Ws);
+
+    alice
+        .send_to(
+            register().as_bytes(),
+            proxy_addr,
+            "proxy.example.com",
+            Instant::now(),
+        )
+        .unwrap();
+    let (data, source) = message(poll(&mut proxy, &mut alice).0);
+    assert!(String::from_utf8(data.clone()).unwrap().contains(
+        "Via: SIP/2.0/WS df7jal23ls0d.invalid;branch=z9hG4bKasudf;received=127.0.0.1\r\n"
+    ));
+
+    let (_, request) = SipRequest::parse(&data).unwrap();
+    let ok = response(&request, 200, "OK", Some("1234"));
+    proxy.send_to(&ok, source, "", Instant::now()).unwrap();
+    assert_eq!(message(poll(&mut alice, &mut proxy).0), (ok, proxy_addr));
+
+    // request to registered contact goes over connection of browser,
+    // body doesn't fit into 7 bit length of frame
+    assert_eq!(
+        proxy.connection_for_host("DF7JAL23LS0D.invalid"),
+        Some(source)
+    );
+    let text = "x".repeat(1000);
+    let text_message = format!(
+        "MESSAGE sip:alice@df7jal23ls0d.invalid;transport=ws SIP/2.0\r\n\
+Via: SIP/2.0/WS proxy.example.com;branch=z9hG4bK56sdasks\r\n\
+From: sip:bob@example.com;tag=93810874\r\n\
+To: sip:alice@example.com\r\n\
+Call-ID: asidkj3ss\r\n\
+CSeq: 1 MESSAGE\r\n\
+Content-Type: text/plain\r\n\
+Content-Length: {}\r\n\r\n{}",
+        text.len(),
+        text
+    );
+    proxy
+        .send_to(
+            text_message.as_bytes(),
+            source,
+            "df7jal23ls0d.invalid",
+            Instant::now(),
+        )
+        .unwrap();
+    let (data, _) = message(poll(&mut alice, &mut proxy).0);
+    assert!(data.ends_with(text.as_bytes()));
+    assert_eq!(alice.connections_count(), 1);
+    assert_eq!(proxy.connections_count(), 1);
+
+    // browser is gone
+    alice.close(proxy_addr);
+    match poll(&mut proxy, &mut alice).0.remove(0) {
+        StreamEvent::Closed { peer, error: None } => assert_eq!(peer, source),
+        event => panic!("connection must be closed, got {:?}", event),
+    }
+    assert_eq!(proxy.connection_for_host("df7jal23ls0d.invalid"), None);
+    let error = proxy
+        .send_to(
+            text_message.as_bytes(),
+            source,
+            "df7jal23ls0d.invalid",
+            Instant::now(),
+        )
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn sip_subprotocol_is_required() {
+    let mut proxy = bind();
+    let mut client = TcpStream::connect(proxy.local_addr().unwrap()).unwrap();
+    client
+        .write_all(
+            b"GET / HTTP/1.1\r\n\
+Host: proxy.example.com\r\n\
+Upgrade: websocket\r\n\
+Connection: Upgrade\r\n\
+Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+Sec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let events = loop {
+        let events = proxy.poll(Instant::now()).unwrap();
+        if !events.is_empty() || Instant::now() > deadline {
+            break events;
+        }
+        thread::sleep(Duration::from_millis(1));
+    };
+    match events.into_iter().next() {
+        Some(StreamEvent::Closed {
+            error: Some(error), ..
+        }) => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+        event => panic!("connection must fail, got {:?}", event),
+    }
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+}